@@ -0,0 +1,148 @@
+//! Compares the latency of the hot queries prepared on every call with the prepared statements the
+//! pooled connections cache. A plain `query` with statement text makes tokio-postgres prepare an
+//! unnamed statement first, one more round trip, and Postgres plans it again each time. Both sides
+//! run the statements of `fetch_fills_from` and `fetch_candles_page`.
+//!
+//! Needs a database with fills and candles for one market, configured through `.env` like the
+//! binaries:
+//!
+//! ```text
+//! BENCH_MARKET_NAME=SOL/USDC BENCH_MARKET_ADDRESS=8BnEgHoWFysVcuFFX7QztDmzuH8r5ZFvyP3sYwn1XTh6 \
+//!     cargo bench --bench prepared_statements
+//! ```
+//!
+//! `BENCH_TASKS` (default 32) tasks run `BENCH_ITERATIONS` (default 200) queries each at the same
+//! time to put the pool under load.
+
+use std::{future::Future, time::Instant};
+
+use chrono::{DateTime, Duration, Utc};
+use deadpool_postgres::Pool;
+use openbook_candles::{
+    database::{
+        fetch::{fetch_candles_from, fetch_fills_from, CANDLES_PAGE_ASC, FILLS_FROM},
+        initialize::connect_to_database,
+    },
+    structs::resolution::Resolution,
+};
+
+#[derive(Clone)]
+struct Target {
+    market_name: String,
+    market_address: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let tasks: usize = env_or("BENCH_TASKS", 32)?;
+    let iterations: usize = env_or("BENCH_ITERATIONS", 200)?;
+    let end = Utc::now();
+    let target = Target {
+        market_name: dotenv::var("BENCH_MARKET_NAME")?,
+        market_address: dotenv::var("BENCH_MARKET_ADDRESS")?,
+        start: end - Duration::hours(6),
+        end,
+    };
+    let pool = connect_to_database().await?;
+    println!(
+        "{} tasks x {} queries over the last 6 hours of {}",
+        tasks, iterations, target.market_name
+    );
+
+    let samples = measure(&pool, &target, tasks, iterations, |pool, t| async move {
+        let client = pool.get().await?;
+        client
+            .query(FILLS_FROM, &[&t.market_address, &t.start, &t.end])
+            .await?;
+        Ok(())
+    })
+    .await?;
+    report("fills, per call", samples);
+    let samples = measure(&pool, &target, tasks, iterations, |pool, t| async move {
+        fetch_fills_from(&pool, &t.market_address, t.start, t.end).await?;
+        Ok(())
+    })
+    .await?;
+    report("fills, cached", samples);
+
+    let samples = measure(&pool, &target, tasks, iterations, |pool, t| async move {
+        let client = pool.get().await?;
+        let resolution = Resolution::R1m.to_string();
+        let (limit, offset): (Option<i64>, i64) = (None, 0);
+        client
+            .query(
+                CANDLES_PAGE_ASC,
+                &[
+                    &t.market_name,
+                    &resolution,
+                    &t.start,
+                    &t.end,
+                    &limit,
+                    &offset,
+                ],
+            )
+            .await?;
+        Ok(())
+    })
+    .await?;
+    report("candles, per call", samples);
+    let samples = measure(&pool, &target, tasks, iterations, |pool, t| async move {
+        fetch_candles_from(&pool, &t.market_name, Resolution::R1m, t.start, t.end).await?;
+        Ok(())
+    })
+    .await?;
+    report("candles, cached", samples);
+    Ok(())
+}
+
+/// Runs `query` `iterations` times in each of `tasks` concurrent tasks and returns the latency of
+/// every call in milliseconds.
+async fn measure<F, Fut>(
+    pool: &Pool,
+    target: &Target,
+    tasks: usize,
+    iterations: usize,
+    query: F,
+) -> anyhow::Result<Vec<f64>>
+where
+    F: Fn(Pool, Target) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send,
+{
+    let handles = (0..tasks).map(|_| {
+        let (pool, target, query) = (pool.clone(), target.clone(), query.clone());
+        tokio::spawn(async move {
+            let mut samples = Vec::with_capacity(iterations);
+            for _ in 0..iterations {
+                let started = Instant::now();
+                query(pool.clone(), target.clone()).await?;
+                samples.push(started.elapsed().as_secs_f64() * 1000.0);
+            }
+            anyhow::Ok(samples)
+        })
+    });
+    let mut samples = vec![];
+    for result in futures::future::join_all(handles).await {
+        samples.extend(result??);
+    }
+    Ok(samples)
+}
+
+fn report(name: &str, mut samples: Vec<f64>) {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let percentile = |p: usize| samples[(samples.len() * p / 100).min(samples.len() - 1)];
+    println!(
+        "{:<18} p50 {:>8.2}ms  p99 {:>8.2}ms",
+        name,
+        percentile(50),
+        percentile(99)
+    );
+}
+
+fn env_or(name: &str, default: usize) -> anyhow::Result<usize> {
+    match dotenv::var(name) {
+        Ok(v) => Ok(v.parse()?),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use openbook_candles::{
+    database::{initialize::connect_to_database_as, roles::DbRole},
+    worker::archive::{archive_candles, archive_fills, ArchiveDestination},
+};
+
+use crate::SharedConfig;
+
+/// Dates are YYYY-MM-DD (UTC, `to` exclusive), destination is a directory or s3://bucket/prefix.
+pub async fn run(
+    shared: SharedConfig,
+    table: &str,
+    from: &str,
+    to: &str,
+    destination: &str,
+) -> anyhow::Result<()> {
+    let from = parse_date(from)?;
+    let to = parse_date(to)?;
+    let destination = ArchiveDestination::parse(destination)?;
+    let market_infos = shared.markets;
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+
+    for market in market_infos.iter() {
+        let written = match table {
+            "fills" => archive_fills(&pool, &destination, market, from, to).await?,
+            "candles" => archive_candles(&pool, &destination, market, from, to).await?,
+            _ => anyhow::bail!("unknown table {}, expected fills or candles", table),
+        };
+        println!("{}: archived {} {}", market.name, written, table);
+    }
+    Ok(())
+}
+
+fn parse_date(s: &str) -> anyhow::Result<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")?;
+    Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
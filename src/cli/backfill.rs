@@ -0,0 +1,37 @@
+use openbook_candles::{
+    database::{initialize::connect_to_database_as, roles::DbRole},
+    worker::candle_batching::{
+        higher_order_candles::backfill_batch_higher_order_candles,
+        minute_candles::backfill_batch_1m_candles, OutlierConfig,
+    },
+};
+use tracing::info;
+
+use crate::SharedConfig;
+
+/// Rebuilds the candles of every market from its stored fills.
+pub async fn run(shared: SharedConfig) -> anyhow::Result<()> {
+    let market_infos = shared.markets;
+    info!(
+        "Backfilling candles for {:?}",
+        market_infos.iter().map(|m| &m.name).collect::<Vec<_>>()
+    );
+
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+    let outlier_filter = OutlierConfig::from_env()?.outlier_filter();
+    backfill_batch_1m_candles(&pool, market_infos.clone(), outlier_filter).await?;
+
+    let mut handles = vec![];
+    let mi = market_infos.clone();
+    for market in mi.into_iter() {
+        let pc = pool.clone();
+        handles.push(tokio::spawn(async move {
+            backfill_batch_higher_order_candles(&pc, &market)
+                .await
+                .unwrap();
+        }));
+    }
+
+    futures::future::join_all(handles).await;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use openbook_candles::{
+    database::{initialize::connect_to_database_as, roles::DbRole},
+    worker::compaction::compact_candles,
+};
+
+use crate::SharedConfig;
+
+/// Without `apply` only reports what it would repair.
+pub async fn run(shared: SharedConfig, apply: bool) -> anyhow::Result<()> {
+    let market_infos = shared.markets;
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+
+    let reports = compact_candles(&pool, &market_infos, apply).await?;
+    if reports.is_empty() {
+        println!("No duplicate or overlapping candles found");
+    }
+    for r in reports.iter() {
+        println!(
+            "{} {}: {} duplicate groups, {} misaligned rows, {} rows deleted, {} unverified",
+            r.market_name,
+            r.resolution,
+            r.duplicate_groups,
+            r.misaligned_rows,
+            r.deleted_rows,
+            r.unverified
+        );
+    }
+    if !apply {
+        println!("Dry run, rerun with --apply to delete redundant rows");
+    }
+    Ok(())
+}
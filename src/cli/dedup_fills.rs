@@ -0,0 +1,44 @@
+use chrono::{Duration, DurationRound, Utc};
+use openbook_candles::database::{
+    fill_dedup::{create_fill_key_index, dedup_fills},
+    initialize::connect_to_database_as,
+    lifecycle::fetch_first_fill_time,
+    roles::DbRole,
+};
+
+use crate::SharedConfig;
+
+/// Deletes fills stored more than once a day at a time, then builds the unique index that keeps
+/// new duplicates out. Without `apply` only reports how many there are.
+pub async fn run(shared: SharedConfig, apply: bool) -> anyhow::Result<()> {
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+    let now = Utc::now();
+
+    let mut total = 0;
+    for market in shared.markets.iter() {
+        let first_fill_at = match fetch_first_fill_time(&pool, &market.address).await? {
+            Some(t) => t.duration_trunc(Duration::days(1))?,
+            None => continue,
+        };
+        let mut duplicates = 0;
+        let mut start_time = first_fill_at;
+        while start_time < now {
+            let end_time = start_time + Duration::days(1);
+            duplicates += dedup_fills(&pool, &market.address, start_time, end_time, apply).await?;
+            start_time = end_time;
+        }
+        println!("{}: {} duplicate fills", market.name, duplicates);
+        total += duplicates;
+    }
+    if !apply {
+        println!("Dry run, rerun with --apply to delete them and add the unique index");
+        return Ok(());
+    }
+    if create_fill_key_index(&pool).await? {
+        println!("Added the unique index on market, sequence number and maker");
+    }
+    if total > 0 {
+        println!("Run backfill-candles to rebuild the candles that counted them");
+    }
+    Ok(())
+}
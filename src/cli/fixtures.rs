@@ -0,0 +1,65 @@
+use openbook_candles::{
+    database::{
+        fill_import::copy_fills,
+        initialize::{connect_to_database, setup_database},
+        lifecycle::record_fills_seen,
+    },
+    structs::fixtures::{fixture_markets, fixture_range, generate_fixture_fills, FixtureConfig},
+    utils::settings::CommonConfig,
+    worker::candle_batching::{
+        higher_order_candles::backfill_batch_higher_order_candles,
+        minute_candles::backfill_batch_1m_candles,
+    },
+};
+use tracing::info;
+
+use crate::SharedConfig;
+
+/// Points at a local validator, only the order book endpoints use it and fail without one
+const FIXTURE_RPC_URL: &str = "http://127.0.0.1:8899";
+
+/// Sets up the schema, stores synthetic fills of the fixture markets and builds their candles.
+/// Fills stored by an earlier run are kept, so the history only grows.
+pub async fn seed() -> anyhow::Result<SharedConfig> {
+    let config = FixtureConfig::from_env()?;
+    let markets = fixture_markets();
+    let pool = connect_to_database().await?;
+    setup_database(&pool).await?;
+
+    let (from, to) = fixture_range(&config);
+    for market in markets.iter() {
+        let fills = generate_fixture_fills(&config, market, from, to);
+        let mut inserted = 0;
+        for chunk in fills.chunks(50_000) {
+            inserted += copy_fills(&pool, chunk).await?;
+        }
+        if let (Some(first), Some(last)) = (fills.first(), fills.last()) {
+            record_fills_seen(
+                &pool,
+                &market.address,
+                first.block_datetime,
+                last.block_datetime,
+            )
+            .await?;
+        }
+        info!(
+            "Seeded {} of {} fixture fills for {}",
+            inserted,
+            fills.len(),
+            market.name
+        );
+    }
+
+    backfill_batch_1m_candles(&pool, markets.clone(), None).await?;
+    for market in markets.iter() {
+        backfill_batch_higher_order_candles(&pool, market).await?;
+    }
+
+    Ok(SharedConfig {
+        rpc_url: CommonConfig::from_env()?
+            .rpc_url
+            .filter(|u| !u.is_empty())
+            .unwrap_or_else(|| FIXTURE_RPC_URL.to_string()),
+        markets,
+    })
+}
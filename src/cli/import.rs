@@ -0,0 +1,48 @@
+use openbook_candles::{
+    database::{
+        initialize::connect_to_database_as, insert::build_imported_candles_insert_statement,
+        roles::DbRole,
+    },
+    structs::{candle_import::parse_candle_csv, resolution::Resolution},
+    utils::AnyhowWrap,
+};
+use tracing::info;
+
+/// Candles per insert statement
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// Loads candles that predate fill ingestion, tagged with `source`.
+pub async fn run(
+    market_name: &str,
+    resolution: &str,
+    csv_path: &str,
+    source: &str,
+) -> anyhow::Result<()> {
+    let resolution = Resolution::from_str(resolution)
+        .map_err(|_| anyhow::anyhow!("unknown resolution {}", resolution))?;
+    let contents = std::fs::read_to_string(csv_path)?;
+    if source == "fills"
+        || !source
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        anyhow::bail!("source must be made of letters, digits, _ and - and not be 'fills'");
+    }
+
+    let candles = parse_candle_csv(&contents, market_name, resolution)?;
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+    let client = pool.get().await?;
+    let mut inserted = 0;
+    for batch in candles.chunks(IMPORT_BATCH_SIZE) {
+        let stmt = build_imported_candles_insert_statement(&batch.to_vec(), source);
+        inserted += client.execute(&stmt, &[]).await.map_err_anyhow()?;
+    }
+    info!(
+        "Imported {} of {} {} candles for {}, the rest already existed",
+        inserted,
+        candles.len(),
+        resolution,
+        market_name
+    );
+    Ok(())
+}
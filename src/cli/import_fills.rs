@@ -0,0 +1,121 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    time::Instant,
+};
+
+use chrono::{DateTime, Utc};
+use openbook_candles::{
+    database::{
+        fill_import::copy_fills, initialize::connect_to_database_as, lifecycle::record_fills_seen,
+        roles::DbRole,
+    },
+    structs::{
+        fill_import::{parse_fill_json, FillCsv},
+        openbook::OpenBookFill,
+    },
+};
+use tracing::info;
+
+/// Fills per `COPY`, each chunk is committed on its own so an interrupted import can be rerun
+const IMPORT_CHUNK_SIZE: usize = 50_000;
+
+type Fills = Box<dyn Iterator<Item = anyhow::Result<OpenBookFill>>>;
+
+/// Loads a dump of fills, `format` defaults to the file's extension. Fills already stored are
+/// skipped.
+pub async fn run(path: &str, format: Option<&str>) -> anyhow::Result<()> {
+    let format = match format {
+        Some(f) => f.to_string(),
+        None => Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .ok_or_else(|| anyhow::anyhow!("can't tell the format of {}, pass --format", path))?,
+    };
+    let fills = open_fills(path, &format)?;
+
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+    let started = Instant::now();
+    let mut read = 0;
+    let mut inserted = 0;
+    let mut seen: HashMap<String, (DateTime<Utc>, DateTime<Utc>)> = HashMap::new();
+    let mut chunk = Vec::with_capacity(IMPORT_CHUNK_SIZE);
+    let mut fills = fills.peekable();
+    while let Some(fill) = fills.next() {
+        let fill = fill?;
+        let range = seen
+            .entry(fill.market.clone())
+            .or_insert((fill.block_datetime, fill.block_datetime));
+        range.0 = range.0.min(fill.block_datetime);
+        range.1 = range.1.max(fill.block_datetime);
+        chunk.push(fill);
+
+        if chunk.len() == IMPORT_CHUNK_SIZE || fills.peek().is_none() {
+            inserted += copy_fills(&pool, &chunk).await?;
+            read += chunk.len();
+            chunk.clear();
+            info!(
+                "Read {} fills, {} new, {:.0} fills/s",
+                read,
+                inserted,
+                read as f64 / started.elapsed().as_secs_f64()
+            );
+        }
+    }
+    // the batcher starts a market without candles from its first fill
+    for (market, (first, last)) in seen.iter() {
+        record_fills_seen(&pool, market, *first, *last).await?;
+    }
+    println!(
+        "Imported {} of {} fills for {} markets in {}s, the rest already existed",
+        inserted,
+        read,
+        seen.len(),
+        started.elapsed().as_secs()
+    );
+    Ok(())
+}
+
+fn open_fills(path: &str, format: &str) -> anyhow::Result<Fills> {
+    match format {
+        "csv" => {
+            let mut lines = BufReader::new(File::open(path)?).lines();
+            let header = lines
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("{} is empty", path))??;
+            let csv = FillCsv::from_header(&header)?;
+            Ok(numbered(lines, 2, move |line| csv.parse_line(line)))
+        }
+        "json" | "jsonl" | "ndjson" => {
+            let lines = BufReader::new(File::open(path)?).lines();
+            Ok(numbered(lines, 1, parse_fill_json))
+        }
+        #[cfg(feature = "archive")]
+        "parquet" => Ok(Box::new(
+            openbook_candles::worker::archive::read_archived_fills(path)?,
+        )),
+        #[cfg(not(feature = "archive"))]
+        "parquet" => anyhow::bail!("reading Parquet needs the archive feature"),
+        _ => anyhow::bail!("unknown format {}, expected csv, json or parquet", format),
+    }
+}
+
+/// Parses non-empty lines, prefixing errors with their line number
+fn numbered(
+    lines: impl Iterator<Item = std::io::Result<String>> + 'static,
+    first_line: usize,
+    parse: impl Fn(&str) -> anyhow::Result<OpenBookFill> + 'static,
+) -> Fills {
+    Box::new(
+        lines
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(move |(i, line)| {
+                let line = line?;
+                parse(&line).map_err(|e| e.context(format!("line {}", i + first_line)))
+            }),
+    )
+}
@@ -0,0 +1,242 @@
+use actix_web::rt::System;
+use clap::{CommandFactory, Parser, Subcommand};
+use openbook_candles::{
+    structs::markets::{fetch_market_infos, load_markets, MarketInfo},
+    utils::{
+        logging::init_logging,
+        settings::{Config, CONFIG_FILE_VAR},
+    },
+};
+use serde::Serialize;
+
+#[cfg(feature = "archive")]
+mod archive;
+mod backfill;
+mod compact;
+mod dedup_fills;
+mod fixtures;
+mod import;
+mod import_fills;
+mod rescale;
+mod server;
+mod verify;
+mod worker;
+
+/// OpenBook trade scraper, candle batcher and web API
+#[derive(Parser)]
+#[command(name = "openbook-candles", version)]
+struct Cli {
+    /// Settings file that environment variables override, instead of `CONFIG_FILE`
+    #[arg(long, global = true)]
+    config: Option<String>,
+    /// Print the effective settings, with secrets redacted, and exit
+    #[arg(long)]
+    print_config: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Ingest fills and batch them into candles
+    Worker { markets_json_path: String },
+    /// Serve the web API
+    Server {
+        #[arg(required_unless_present = "fixtures")]
+        markets_json_path: Option<String>,
+        /// Seed synthetic fills for made up markets and serve those, no RPC needed
+        #[arg(long, conflicts_with = "markets_json_path")]
+        fixtures: bool,
+    },
+    /// Store synthetic fills and candles of made up markets for local development
+    SeedFixtures,
+    /// Rebuild every market's candles from its stored fills
+    BackfillCandles { markets_json_path: String },
+    /// Report duplicate and misaligned candles
+    CompactCandles {
+        markets_json_path: String,
+        /// Delete the redundant rows instead of only reporting them
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Re-derive fill prices and sizes from their native quantities
+    RescaleFills {
+        markets_json_path: String,
+        /// Rewrite the mismatched fills instead of only counting them
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Delete fills stored more than once and add the unique index that keeps them out
+    DedupFills {
+        markets_json_path: String,
+        /// Delete the duplicates and build the index instead of only counting them
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Recompute sampled candles from their fills and report mismatches
+    Verify {
+        markets_json_path: String,
+        /// Candles sampled per market and resolution
+        #[arg(long, default_value_t = 100)]
+        samples: i64,
+        /// Only sample candles from the last this many days
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+    },
+    /// Import candles that predate fill ingestion from a CSV file
+    ImportCandles {
+        market_name: String,
+        resolution: String,
+        csv_path: String,
+        /// Tag stored in the candles' `source` column
+        #[arg(default_value = "import")]
+        source: String,
+    },
+    /// Bulk load historical fills from a CSV, JSON lines or Parquet dump
+    ImportFills {
+        path: String,
+        /// `csv`, `json` or `parquet`, taken from the file extension when left out
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Export fills or candles to Parquet
+    #[cfg(feature = "archive")]
+    Archive {
+        /// `fills` or `candles`
+        table: String,
+        markets_json_path: String,
+        /// First day to export, YYYY-MM-DD
+        from: String,
+        /// Day to stop before, YYYY-MM-DD
+        to: String,
+        /// A local directory or s3://bucket/prefix
+        destination: String,
+    },
+}
+
+/// Settings shared by the subcommands that work on the configured markets.
+pub struct SharedConfig {
+    pub rpc_url: String,
+    pub markets: Vec<MarketInfo>,
+}
+
+impl SharedConfig {
+    async fn load(path_to_markets_json: &str) -> anyhow::Result<Self> {
+        let rpc_url = Config::load()?
+            .common
+            .rpc_url
+            .filter(|u| !u.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("RPC_URL: required to look up the markets"))?;
+        let markets = load_markets(path_to_markets_json);
+        let market_infos = fetch_market_infos(&rpc_url, markets).await?;
+        Ok(SharedConfig {
+            rpc_url,
+            markets: market_infos,
+        })
+    }
+}
+
+/// Everything `--print-config` shows, keyed like the config file
+#[derive(Serialize)]
+struct PrintedConfig {
+    #[serde(flatten)]
+    config: Config,
+    #[serde(flatten)]
+    server: server::ServerConfig,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    dotenv::dotenv().ok();
+    if let Some(path) = &cli.config {
+        std::env::set_var(CONFIG_FILE_VAR, path);
+    }
+    init_logging();
+
+    // every setting is checked up front, so a typo fails here rather than when it's first used
+    let config = Config::load()?;
+    if cli.print_config {
+        let printed = PrintedConfig {
+            config,
+            server: server::ServerConfig::from_env()?,
+        };
+        println!("{}", serde_json::to_string_pretty(&printed)?);
+        return Ok(());
+    }
+    let command = match cli.command {
+        Some(command) => command,
+        None => {
+            Cli::command().print_help()?;
+            std::process::exit(2);
+        }
+    };
+
+    // actix wants its own system, everything else runs on a multi-threaded tokio runtime
+    if let Command::Server {
+        markets_json_path,
+        fixtures,
+    } = &command
+    {
+        return System::new().block_on(async {
+            let shared = match markets_json_path {
+                Some(path) if !fixtures => SharedConfig::load(path).await?,
+                _ => fixtures::seed().await?,
+            };
+            server::run(shared).await
+        });
+    }
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(10)
+        .enable_all()
+        .build()?
+        .block_on(run(command))
+}
+
+async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Worker { markets_json_path } => {
+            let shared = SharedConfig::load(&markets_json_path).await?;
+            worker::run(shared, markets_json_path).await
+        }
+        Command::Server { .. } => unreachable!("the server runs on an actix system"),
+        Command::SeedFixtures => fixtures::seed().await.map(|_| ()),
+        Command::BackfillCandles { markets_json_path } => {
+            backfill::run(SharedConfig::load(&markets_json_path).await?).await
+        }
+        Command::CompactCandles {
+            markets_json_path,
+            apply,
+        } => compact::run(SharedConfig::load(&markets_json_path).await?, apply).await,
+        Command::RescaleFills {
+            markets_json_path,
+            apply,
+        } => rescale::run(SharedConfig::load(&markets_json_path).await?, apply).await,
+        Command::DedupFills {
+            markets_json_path,
+            apply,
+        } => dedup_fills::run(SharedConfig::load(&markets_json_path).await?, apply).await,
+        Command::Verify {
+            markets_json_path,
+            samples,
+            days,
+        } => verify::run(SharedConfig::load(&markets_json_path).await?, samples, days).await,
+        Command::ImportCandles {
+            market_name,
+            resolution,
+            csv_path,
+            source,
+        } => import::run(&market_name, &resolution, &csv_path, &source).await,
+        Command::ImportFills { path, format } => import_fills::run(&path, format.as_deref()).await,
+        #[cfg(feature = "archive")]
+        Command::Archive {
+            table,
+            markets_json_path,
+            from,
+            to,
+            destination,
+        } => {
+            let shared = SharedConfig::load(&markets_json_path).await?;
+            archive::run(shared, &table, &from, &to, &destination).await
+        }
+    }
+}
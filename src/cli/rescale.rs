@@ -0,0 +1,46 @@
+use chrono::{Duration, DurationRound, Utc};
+use openbook_candles::database::{
+    initialize::connect_to_database_as, lifecycle::fetch_first_fill_time, rescale::rescale_fills,
+    roles::DbRole,
+};
+
+use crate::SharedConfig;
+
+/// Re-derives the UI price and size of every stored fill from its native quantities, a day at a
+/// time. Without `apply` only reports how many fills are off.
+pub async fn run(shared: SharedConfig, apply: bool) -> anyhow::Result<()> {
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+    let now = Utc::now();
+
+    let mut total = 0;
+    for market in shared.markets.iter() {
+        let first_fill_at = match fetch_first_fill_time(&pool, &market.address).await? {
+            Some(t) => t.duration_trunc(Duration::days(1))?,
+            None => continue,
+        };
+        let mut rescaled = 0;
+        let mut start_time = first_fill_at;
+        while start_time < now {
+            let end_time = start_time + Duration::days(1);
+            rescaled += rescale_fills(
+                &pool,
+                &market.address,
+                market.base_decimals,
+                market.quote_decimals,
+                start_time,
+                end_time,
+                apply,
+            )
+            .await?;
+            start_time = end_time;
+        }
+        println!("{}: {} fills with off price or size", market.name, rescaled);
+        total += rescaled;
+    }
+    if !apply {
+        println!("Dry run, rerun with --apply to rewrite them");
+    } else if total > 0 {
+        println!("Run backfill-candles to rebuild the candles of the rescaled fills");
+    }
+    Ok(())
+}
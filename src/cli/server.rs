@@ -0,0 +1,371 @@
+use actix_web::{
+    dev::Service,
+    http::StatusCode,
+    middleware::{Compress, Condition},
+    rt::System,
+    web::{self, Data},
+    App, HttpServer,
+};
+use actix_web_prom::PrometheusMetricsBuilder;
+use api::{
+    admin::{self, AdminConfig},
+    candle_cache_warmer::warm_candle_cache,
+    candles::{get_batch_candles, get_candles, get_recent_candles, get_rolling_candle},
+    changes::get_changes,
+    coingecko::{self, CoinGeckoConfig},
+    cors::CorsConfig,
+    depth_history::get_depth_history,
+    divergence::get_divergence,
+    etag::{tag_response, Conditional},
+    freshness::refresh_freshness,
+    health::{self, HealthConfig},
+    indicators::get_indicator,
+    key_case::{convert_response_keys, KeyCase},
+    markets::{get_market_summaries, get_markets},
+    openapi,
+    oracle::get_oracle_prices,
+    orderbook_snapshots::refresh_orderbook_snapshots,
+    patterns::get_patterns,
+    price_change::get_price_change,
+    rate::get_rate,
+    rate_limit::{limit_request, reload_rate_limit, sync_api_keys},
+    returns::get_returns,
+    revisions::get_revisions,
+    session::{get_session_stats, refresh_session_stats},
+    spread_history::get_spread_history,
+    sse,
+    startup::{StartupConfig, StartupGate},
+    status::get_market_statuses,
+    traders::{
+        get_maker_uptime, get_top_traders_by_base_volume, get_top_traders_by_quote_volume,
+        get_trader_leaderboard,
+    },
+    trades::get_trades,
+    validation::ValidationConfig,
+    volume_profile::get_volume_profile,
+};
+use openbook_candles::{
+    database::{
+        initialize::{connect_to_database_as, connect_to_read_replica_as},
+        replica::{monitor_read_replica, ReadPool},
+        roles::DbRole,
+        telemetry::{monitor_pools, register_database_metrics},
+    },
+    structs::{
+        cache_backend::cache_backend_from_env,
+        candle_cache::CandleCache,
+        composite::composite_markets,
+        embargo::{Embargo, EmbargoConfig},
+        markets::MarketResolver,
+        privacy::{Anonymizer, PrivacyConfig},
+        rate_limit::{RateLimitConfig, RateLimiter},
+        session::SessionConfig,
+    },
+    utils::{settings::CommonConfig, singleflight::SingleFlight, WebContext},
+};
+use prometheus::Registry;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::thread;
+use tokio::sync::{broadcast, RwLock};
+use tracing::info;
+use tracing_actix_web::TracingLogger;
+
+use crate::SharedConfig;
+
+#[path = "../server/mod.rs"]
+mod api;
+
+/// Candle updates buffered per streaming client before it starts missing some
+const CANDLE_UPDATES_CAPACITY: usize = 1024;
+
+/// Settings only the server reads, printed along with the library's
+#[derive(Serialize)]
+pub struct ServerConfig {
+    #[serde(flatten)]
+    admin: AdminConfig,
+    #[serde(flatten)]
+    cors: CorsConfig,
+    #[serde(flatten)]
+    health: HealthConfig,
+    #[serde(flatten)]
+    startup: StartupConfig,
+    #[serde(flatten)]
+    validation: ValidationConfig,
+    #[serde(flatten)]
+    coingecko: CoinGeckoConfig,
+}
+
+impl ServerConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(ServerConfig {
+            admin: AdminConfig::from_env()?,
+            cors: CorsConfig::from_env()?,
+            health: HealthConfig::from_env()?,
+            startup: StartupConfig::from_env()?,
+            validation: ValidationConfig::from_env()?,
+            coingecko: CoinGeckoConfig::from_env()?,
+        })
+    }
+}
+
+/// Serves the web API, and privately its metrics, until the process is stopped. Runs on an actix
+/// system rather than a plain tokio runtime.
+pub async fn run(shared: SharedConfig) -> anyhow::Result<()> {
+    let SharedConfig {
+        rpc_url,
+        markets: market_infos,
+    } = shared;
+    let bind_addr = CommonConfig::from_env()?
+        .server_bind_addr
+        .expect("reading bind addr from config");
+
+    let pool = connect_to_database_as(DbRole::ApiReader).await.unwrap();
+    let replica = connect_to_read_replica_as(DbRole::ApiReader).await.unwrap();
+    let read_pool = ReadPool::new(pool.clone(), replica);
+    let admin_config = AdminConfig::from_env().unwrap();
+    // only the admin endpoints need the admin role, leave it out of servers that don't serve them
+    let admin_pool = if admin_config.is_enabled() {
+        Some(connect_to_database_as(DbRole::Admin).await.unwrap())
+    } else {
+        None
+    };
+
+    let registry = Registry::new();
+    register_database_metrics(&registry, "openbook_candles_server").unwrap();
+    // For serving metrics on a private port
+    let private_metrics = PrometheusMetricsBuilder::new("openbook_candles_server_private")
+        .registry(registry.clone())
+        .exclude("/metrics")
+        .exclude_status(StatusCode::NOT_FOUND)
+        .endpoint("/metrics")
+        .build()
+        .unwrap();
+    // For collecting metrics on the public api, excluding 404s
+    let public_metrics = PrometheusMetricsBuilder::new("openbook_candles_server")
+        .registry(registry)
+        .exclude_status(StatusCode::NOT_FOUND)
+        .build()
+        .unwrap();
+
+    let cache = cache_backend_from_env().await.unwrap();
+    let candle_cache = if cache.is_shared() {
+        CandleCache::with_shared_backend(cache.clone())
+    } else {
+        CandleCache::default()
+    };
+
+    let rate_limit_config = RateLimitConfig::from_env().unwrap();
+    let coingecko_config = CoinGeckoConfig::from_env().unwrap();
+    let embargo = Embargo::from_config(&EmbargoConfig::from_env().unwrap()).unwrap();
+    let session_config = SessionConfig::from_env().unwrap();
+    let anonymizer = Anonymizer::from_config(&PrivacyConfig::from_env().unwrap());
+    let validation_config = ValidationConfig::from_env().unwrap();
+    let composites: Vec<_> = composite_markets(&market_infos)
+        .into_iter()
+        .map(|c| c.info)
+        .collect();
+
+    let context = Data::new(WebContext {
+        rpc_url,
+        pool,
+        read_pool,
+        admin_pool,
+        market_resolver: MarketResolver::new(&market_infos),
+        markets: market_infos,
+        composite_resolver: MarketResolver::new(&composites),
+        composite_markets: composites,
+        orderbook_snapshots: RwLock::new(HashMap::new()),
+        candle_cache,
+        cache,
+        rate_limiter: RateLimiter::new(&rate_limit_config),
+        candle_requests: SingleFlight::default(),
+        ticker_requests: SingleFlight::default(),
+        coingecko_max_markets_per_query: coingecko_config.coingecko_max_markets_per_query,
+        max_range_candles: validation_config.max_range_candles,
+        embargo,
+        freshness: RwLock::new(HashMap::new()),
+        candle_updates: broadcast::channel(CANDLE_UPDATES_CAPACITY).0,
+        session_stats: RwLock::new(HashMap::new()),
+        anonymizer,
+    });
+
+    // Thread to keep order book snapshots fresh
+    let snapshot_context = context.clone();
+    let snapshot_refresher = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(refresh_orderbook_snapshots(snapshot_context));
+    });
+
+    // Thread to refresh hot candle windows as new batches land
+    let cache_context = context.clone();
+    let cache_warmer = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(warm_candle_cache(cache_context));
+    });
+
+    // Thread to poll the last fill time and data version of every market
+    let freshness_context = context.clone();
+    let freshness_refresher = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(refresh_freshness(freshness_context));
+    });
+
+    // Thread to keep the current session stats of every market
+    let session_context = context.clone();
+    let session_refresher = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(refresh_session_stats(session_context, session_config));
+    });
+
+    // Thread to move reads off the read replica while it's down
+    let replica_context = context.clone();
+    let replica_monitor = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(monitor_read_replica(&replica_context.read_pool));
+    });
+
+    // Thread to sample the connection pools for the private metrics
+    let mut pools_to_monitor = vec![("primary", context.pool.clone())];
+    if let Some(replica) = context.read_pool.replica() {
+        pools_to_monitor.push(("replica", replica.clone()));
+    }
+    if let Some(admin_pool) = &context.admin_pool {
+        pools_to_monitor.push(("admin", admin_pool.clone()));
+    }
+    let pool_monitor = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(monitor_pools(pools_to_monitor));
+    });
+
+    // Thread to reload API keys and record their usage
+    let api_key_context = context.clone();
+    let api_key_sync = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(sync_api_keys(api_key_context));
+    });
+
+    // Thread to apply a changed anonymous rate limit on SIGHUP
+    let reload_context = context.clone();
+    let rate_limit_reloader = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(reload_rate_limit(reload_context));
+    });
+
+    let key_case = KeyCase::from_env();
+    let cors_config = CorsConfig::from_env().unwrap();
+    let health_config = HealthConfig::from_env().unwrap();
+
+    let startup_gate = Data::new(StartupGate::new(StartupConfig::from_env().unwrap()));
+    if startup_gate.config.startup_wait_before_bind {
+        info!("Waiting for startup data before binding");
+        startup_gate.wait(&context).await;
+    }
+    // Thread to pass the startup gate when the server binds before it has
+    let startup_context = context.clone();
+    let startup_waiter = startup_gate.clone();
+    let startup_checker = thread::spawn(move || {
+        let sys = System::new();
+        sys.block_on(async move { startup_waiter.wait(&startup_context).await });
+    });
+
+    info!("Starting server");
+    // Thread to serve public API
+    let public_server = thread::spawn(move || {
+        let sys = System::new();
+        let srv = HttpServer::new(move || {
+            let limit_context = context.clone();
+            App::new()
+                .wrap_fn(move |req, srv| limit_request(&limit_context, req, srv))
+                .wrap(TracingLogger::default())
+                .wrap(public_metrics.clone())
+                .wrap_fn(move |req, srv| {
+                    let case = KeyCase::for_request(&req, key_case);
+                    let fut = srv.call(req);
+                    async move { convert_response_keys(fut.await?, case).await }
+                })
+                // tags the body clients see, before it's compressed
+                .wrap_fn(|req, srv| {
+                    let conditional = Conditional::for_request(&req);
+                    let fut = srv.call(req);
+                    async move { tag_response(fut.await?, conditional).await }
+                })
+                .wrap(Compress::default())
+                .app_data(context.clone())
+                .service(
+                    web::scope("/api")
+                        .wrap(Condition::new(
+                            cors_config.public_enabled(),
+                            cors_config.public(),
+                        ))
+                        .service(get_batch_candles)
+                        .service(get_candles)
+                        .service(get_recent_candles)
+                        .service(get_rolling_candle)
+                        .service(get_top_traders_by_base_volume)
+                        .service(get_top_traders_by_quote_volume)
+                        .service(get_trader_leaderboard)
+                        .service(get_maker_uptime)
+                        .service(get_market_summaries)
+                        .service(get_markets)
+                        .service(get_divergence)
+                        .service(get_rate)
+                        .service(get_changes)
+                        .service(get_revisions)
+                        .service(get_trades)
+                        .service(get_patterns)
+                        .service(get_market_statuses)
+                        .service(get_session_stats)
+                        .service(get_returns)
+                        .service(get_indicator)
+                        .service(get_price_change)
+                        .service(get_oracle_prices)
+                        .service(get_volume_profile)
+                        .service(get_depth_history)
+                        .service(get_spread_history)
+                        .service(coingecko::service()),
+                )
+                .service(admin::service(admin_config.clone()).wrap(Condition::new(
+                    cors_config.admin_enabled(),
+                    cors_config.admin(),
+                )))
+                .service(
+                    health::service(health_config.clone(), startup_gate.clone()).wrap(
+                        Condition::new(cors_config.public_enabled(), cors_config.public()),
+                    ),
+                )
+                .service(sse::service().wrap(Condition::new(
+                    cors_config.public_enabled(),
+                    cors_config.public(),
+                )))
+                .service(openapi::service())
+        })
+        .bind(&bind_addr)
+        .unwrap()
+        .run();
+        sys.block_on(srv).unwrap();
+    });
+
+    // Thread to serve metrics endpoint privately
+    let private_server = thread::spawn(move || {
+        let sys = System::new();
+        let srv = HttpServer::new(move || App::new().wrap(private_metrics.clone()))
+            .bind("0.0.0.0:9091")
+            .unwrap()
+            .run();
+        sys.block_on(srv).unwrap();
+    });
+
+    private_server.join().unwrap();
+    public_server.join().unwrap();
+    snapshot_refresher.join().unwrap();
+    cache_warmer.join().unwrap();
+    freshness_refresher.join().unwrap();
+    session_refresher.join().unwrap();
+    api_key_sync.join().unwrap();
+    rate_limit_reloader.join().unwrap();
+    replica_monitor.join().unwrap();
+    pool_monitor.join().unwrap();
+    startup_checker.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use chrono::{Duration, Utc};
+use openbook_candles::{
+    database::{initialize::connect_to_database_as, roles::DbRole},
+    worker::verification::verify_candles,
+};
+
+use crate::SharedConfig;
+
+/// Fails when any sampled candle disagrees with its fills so it can gate a backfill or deploy.
+pub async fn run(shared: SharedConfig, samples: i64, days: i64) -> anyhow::Result<()> {
+    let market_infos = shared.markets;
+    let pool = connect_to_database_as(DbRole::Admin).await?;
+
+    let since = Utc::now() - Duration::days(days);
+    let reports = verify_candles(&pool, &market_infos, since, samples).await?;
+    let mut mismatched = 0;
+    for r in reports.iter() {
+        println!(
+            "{} {}: {} sampled, {} mismatched, {} unverified",
+            r.market_name, r.resolution, r.sampled, r.mismatched, r.unverified
+        );
+        for m in r.mismatches.iter() {
+            println!(
+                "  {} {}: stored {}, fills give {}",
+                m.start_time.to_rfc3339(),
+                m.field,
+                m.stored,
+                m.expected
+            );
+        }
+        mismatched += r.mismatched;
+    }
+    if mismatched > 0 {
+        anyhow::bail!("{} candles don't match their fills", mismatched);
+    }
+    println!("All sampled candles match their fills");
+    Ok(())
+}
@@ -0,0 +1,627 @@
+use deadpool_postgres::Pool;
+use openbook_candles::structs::cache_backend::cache_backend_from_env;
+use openbook_candles::structs::candle_cache::CandleCache;
+use openbook_candles::structs::composite::composite_markets;
+use openbook_candles::structs::markets::{LiveMarkets, MarketInfo};
+use openbook_candles::structs::oracle::OracleConfig;
+use openbook_candles::structs::resolution::Resolution;
+use openbook_candles::structs::spread::SpreadConfig;
+use openbook_candles::structs::uptime::UptimeConfig;
+use openbook_candles::structs::venue::Venue;
+use openbook_candles::utils::reload::{listen_for_reload, reload_env_file};
+use openbook_candles::worker::alerts::{watch_for_alerts, AlertConfig};
+use openbook_candles::worker::cluster::{
+    join_cluster, maintain_membership, ClusterConfig, MarketAssignment,
+};
+use openbook_candles::worker::comparator::{run_comparator, ComparatorConfig};
+use openbook_candles::worker::composite::batch_composite_candles;
+use openbook_candles::worker::depth_stats::record_depth_stats;
+use openbook_candles::worker::ingestion::{
+    ingest_fills,
+    phoenix::{PhoenixConfig, PhoenixFillSource},
+    websocket::{WebsocketConfig, WebsocketFillSource},
+    IngestionConfig, TradeSource,
+};
+use openbook_candles::worker::jobs::{run_jobs, JobConfig, JobContext};
+use openbook_candles::worker::leaderboard::materialize_leaderboards;
+use openbook_candles::worker::metrics::{
+    serve_metrics, METRIC_DB_POOL_AVAILABLE, METRIC_DB_POOL_SIZE,
+};
+use openbook_candles::worker::oracle::record_oracle_prices;
+use openbook_candles::worker::patterns::{post_pattern_detections, PatternWebhookConfig};
+use openbook_candles::worker::reconciliation::{reconcile_fills, ReconciliationConfig};
+use openbook_candles::worker::retention::{schedule_pruning, RetentionConfig};
+use openbook_candles::worker::shutdown::{listen_for_shutdown, Shutdown, StopHandle};
+use openbook_candles::worker::spread_history::record_spread_samples;
+use openbook_candles::worker::uptime::record_maker_uptime;
+use openbook_candles::{
+    database::{
+        initialize::{connect_to_database, connect_to_database_as, setup_database},
+        partitions::maintain_fill_partitions,
+        roles::DbRole,
+        telemetry::monitor_pools,
+    },
+    utils::settings::PgConfig,
+    worker::candle_batching::{
+        batch_for_market, BatchLimiter, BatchOptions, BatchingConfig, OutlierConfig,
+    },
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{
+    collections::HashMap, path::PathBuf, str::FromStr, sync::Arc, time::Duration as WaitDuration,
+};
+use strum::IntoEnumIterator;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::SharedConfig;
+
+/// How long in-flight batches get to finish after SIGTERM, within the usual 30s grace period
+const SHUTDOWN_TIMEOUT: WaitDuration = WaitDuration::from_secs(25);
+
+/// Ingests fills and batches them into candles until SIGTERM. On SIGHUP, or when the markets
+/// file changes, the configuration is read again and applied to the running tasks.
+pub async fn run(shared: SharedConfig, markets_json_path: String) -> anyhow::Result<()> {
+    let SharedConfig {
+        rpc_url,
+        markets: market_infos,
+    } = shared;
+    let mut target_markets = HashMap::new();
+    for m in market_infos.clone() {
+        target_markets.insert(Pubkey::from_str(&m.address)?, m.name);
+    }
+    info!("{:?}", target_markets);
+    log_resolutions(&market_infos);
+
+    let shutdown = listen_for_shutdown();
+    let mut reload = listen_for_reload(Some(PathBuf::from(&markets_json_path)));
+    let ingestion_config = IngestionConfig::from_env()?;
+    let commitment = ingestion_config.commitment_config()?;
+
+    let setup_pool = connect_to_database().await?;
+    setup_database(&setup_pool).await?;
+    let pool = connect_to_database_as(DbRole::IngestWriter).await?;
+    let mut handles = vec![];
+
+    // creating partitions takes ownership of the fills table, which the worker's role lacks
+    let pg_config = PgConfig::from_env()?;
+    if pg_config.pg_partition_fills {
+        let months_ahead = pg_config.pg_fill_partition_months_ahead;
+        handles.push(tokio::spawn(async move {
+            maintain_fill_partitions(&setup_pool, months_ahead).await;
+        }));
+    } else {
+        drop(setup_pool);
+    }
+
+    let cluster_config = ClusterConfig::from_env()?;
+    let assignment = join_cluster(&pool, &cluster_config).await?;
+    if cluster_config.worker_cluster_enabled {
+        let cluster_pool = pool.clone();
+        let cluster_assignment = assignment.clone();
+        handles.push(tokio::spawn(async move {
+            maintain_membership(&cluster_pool, &cluster_config, cluster_assignment)
+                .await
+                .unwrap();
+        }));
+    }
+
+    let retention_config = RetentionConfig::from_env()?;
+    if retention_config.is_enabled() {
+        let retention_pool = pool.clone();
+        handles.push(tokio::spawn(async move {
+            schedule_pruning(&retention_pool).await.unwrap();
+        }));
+    }
+
+    // with a shared cache backend, fresh candles are pushed to it after every batch
+    let cache_backend = cache_backend_from_env().await?;
+    let shared_cache = if cache_backend.is_shared() {
+        Some(Arc::new(CandleCache::with_shared_backend(cache_backend)))
+    } else {
+        None
+    };
+
+    // candle batching
+    if pg_config.pg_timescale_minute_aggregate && !pg_config.pg_use_timescale {
+        warn!("PG_TIMESCALE_MINUTE_AGGREGATE needs PG_USE_TIMESCALE, ignoring it");
+    }
+    let batching_config = BatchingConfig::from_env()?;
+    let batch_options = BatchOptions {
+        finality_lag: ingestion_config.finality_lag(),
+        outlier_filter: OutlierConfig::from_env()?.outlier_filter(),
+        minute_aggregate: pg_config.pg_use_timescale && pg_config.pg_timescale_minute_aggregate,
+        late_fill_window: batching_config.late_fill_window(),
+    };
+    if batch_options.minute_aggregate && batch_options.outlier_filter.is_some() {
+        warn!(
+            "Minute candles come from the continuous aggregate, the outlier filter is not applied"
+        );
+    }
+
+    let mut tasks = MarketTasks {
+        market_handles: spawn_market_tasks(
+            &pool,
+            &assignment,
+            &market_infos,
+            &rpc_url,
+            batch_options,
+        )?,
+        pool: pool.clone(),
+        assignment,
+        shutdown: shutdown.clone(),
+        commitment,
+        shared_cache,
+        batch_options,
+        batch_limiter: batching_config.limiter(),
+        markets: LiveMarkets::new(market_infos.clone()),
+        rpc_url,
+        batches: HashMap::new(),
+        phoenix_handles: vec![],
+    };
+    tasks.start_ingestion(&market_infos).await?;
+    for market in market_infos.into_iter() {
+        tasks.start_batches(market);
+    }
+
+    // the consumer group assigns partitions, not markets, so one consumer follows every reload
+    #[cfg(feature = "kafka")]
+    {
+        use openbook_candles::worker::ingestion::kafka::{KafkaConfig, KafkaFillSource};
+
+        let kafka_config = KafkaConfig::from_env()?;
+        if kafka_config.is_enabled() {
+            let source = KafkaFillSource::new(&kafka_config)?;
+            handles.push(spawn_ingestion(&pool, source, &tasks.markets));
+        }
+    }
+
+    let monitor_pool = pool.clone();
+    handles.push(tokio::spawn(async move {
+        // TODO: maybe break this out into a new function
+        loop {
+            let pool_status = monitor_pool.status();
+            METRIC_DB_POOL_AVAILABLE.set(pool_status.available as i64);
+            METRIC_DB_POOL_SIZE.set(pool_status.size as i64);
+
+            tokio::time::sleep(WaitDuration::from_secs(10)).await;
+        }
+    }));
+
+    let pools_to_monitor = vec![("primary", pool.clone())];
+    handles.push(tokio::spawn(async move {
+        monitor_pools(pools_to_monitor).await;
+    }));
+
+    handles.push(tokio::spawn(async move {
+        // TODO: this is ugly af
+        serve_metrics().await.unwrap().await.unwrap();
+    }));
+
+    // the other tasks hold no state worth finishing, they're dropped once the batches are done
+    let static_tasks = futures::future::join_all(handles);
+    tokio::pin!(static_tasks);
+    let mut main_shutdown = shutdown.clone();
+    loop {
+        tokio::select! {
+            _ = &mut static_tasks => break,
+            _ = main_shutdown.requested() => {
+                let batch_handles: Vec<_> = tasks.batches.drain().map(|(_, b)| b.handle).collect();
+                let drained = tokio::time::timeout(
+                    SHUTDOWN_TIMEOUT,
+                    futures::future::join_all(batch_handles),
+                )
+                .await;
+                if drained.is_err() {
+                    error!("Batches still running after {:?}, exiting anyway", SHUTDOWN_TIMEOUT);
+                }
+                break;
+            }
+            _ = reload.requested() => {
+                if let Err(e) = tasks.reload(&markets_json_path).await {
+                    error!("Failed to reload the configuration, keeping the current one: {:?}", e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn log_resolutions(markets: &[MarketInfo]) {
+    for m in markets.iter() {
+        if m.resolutions.len() < Resolution::iter().count() {
+            let resolutions: Vec<String> = m.resolutions.iter().map(|r| r.to_string()).collect();
+            info!("{} keeps {} candles", m.name, resolutions.join(", "));
+        }
+    }
+}
+
+/// The batching task of a market, stopped at its next safe point when the market is removed
+struct BatchTask {
+    stop: StopHandle,
+    handle: JoinHandle<()>,
+}
+
+/// Everything that depends on the configured markets or the RPC endpoint, so a reload only
+/// touches the markets that changed and ingestion of the others carries on without a gap.
+struct MarketTasks {
+    pool: Pool,
+    assignment: MarketAssignment,
+    shutdown: Shutdown,
+    commitment: CommitmentConfig,
+    shared_cache: Option<Arc<CandleCache>>,
+    batch_options: BatchOptions,
+    batch_limiter: BatchLimiter,
+    /// Filters ingested fills, shared with every ingestion task
+    markets: LiveMarkets,
+    rpc_url: String,
+    /// By market address
+    batches: HashMap<String, BatchTask>,
+    /// Tasks over the whole market list, restarted on every reload
+    market_handles: Vec<JoinHandle<()>>,
+    /// Phoenix fills are polled over RPC, these move to a changed endpoint
+    phoenix_handles: Vec<JoinHandle<()>>,
+}
+
+impl MarketTasks {
+    /// Starts batching a market, after its previous task finished if it had one.
+    fn start_batches(&mut self, market: MarketInfo) {
+        let previous = self.batches.remove(&market.address).map(|b| {
+            b.stop.stop();
+            b.handle
+        });
+        let (stop, shutdown) = self.shutdown.stoppable();
+        let address = market.address.clone();
+        let pool = self.pool.clone();
+        let assignment = self.assignment.clone();
+        let cache = self.shared_cache.clone();
+        let options = self.batch_options;
+        let limiter = self.batch_limiter.clone();
+        let handle = tokio::spawn(async move {
+            // two tasks batching one market would race on its open buckets
+            if let Some(previous) = previous {
+                previous.await.ok();
+            }
+            batch_for_market(
+                &pool,
+                &market,
+                &assignment,
+                cache,
+                options,
+                limiter,
+                shutdown,
+            )
+            .await
+            .unwrap();
+            info!("batching stopped for market {}", &market.name);
+        });
+        self.batches.insert(address, BatchTask { stop, handle });
+    }
+
+    /// Subscribes to the fills of `markets`, next to the sources already running.
+    async fn start_ingestion(&mut self, markets: &[MarketInfo]) -> anyhow::Result<()> {
+        // order books and event queues are OpenBook accounts, markets of other venues are only
+        // ingested and batched
+        let openbook_markets: Vec<MarketInfo> = markets
+            .iter()
+            .filter(|m| m.venue == Venue::OpenBook)
+            .cloned()
+            .collect();
+        let phoenix_markets: Vec<MarketInfo> = markets
+            .iter()
+            .filter(|m| m.venue == Venue::Phoenix)
+            .cloned()
+            .collect();
+
+        if !openbook_markets.is_empty() {
+            #[cfg(feature = "geyser")]
+            {
+                use openbook_candles::worker::ingestion::geyser::{GeyserConfig, GeyserFillSource};
+
+                let geyser_config = GeyserConfig::from_env()?;
+                if geyser_config.is_enabled() {
+                    let source = GeyserFillSource::connect(
+                        &geyser_config,
+                        self.commitment,
+                        &openbook_markets,
+                    )
+                    .await?;
+                    spawn_ingestion(&self.pool, source, &self.markets);
+                }
+            }
+
+            let websocket_config = WebsocketConfig::from_env()?;
+            if websocket_config.is_enabled() {
+                let source = WebsocketFillSource::connect(
+                    &websocket_config,
+                    self.commitment,
+                    &openbook_markets,
+                )
+                .await?;
+                spawn_ingestion(&self.pool, source, &self.markets);
+            }
+        }
+
+        if !phoenix_markets.is_empty() {
+            let phoenix_config = PhoenixConfig::from_env()?;
+            let source = PhoenixFillSource::connect(
+                &phoenix_config,
+                self.rpc_url.clone(),
+                self.commitment,
+                &phoenix_markets,
+            )
+            .await?;
+            self.phoenix_handles
+                .push(spawn_ingestion(&self.pool, source, &self.markets));
+        }
+        Ok(())
+    }
+
+    /// Reads the `.env` file and the markets file again and applies what changed. Markets are
+    /// matched by address, only added, removed and changed ones have their tasks started or
+    /// stopped.
+    async fn reload(&mut self, markets_json_path: &str) -> anyhow::Result<()> {
+        reload_env_file()?;
+        let SharedConfig { rpc_url, markets } = SharedConfig::load(markets_json_path).await?;
+
+        // settings these read from the environment may have changed as well, so they're always
+        // restarted. A configuration they reject leaves everything as it was.
+        let market_handles = spawn_market_tasks(
+            &self.pool,
+            &self.assignment,
+            &markets,
+            &rpc_url,
+            self.batch_options,
+        )?;
+        for handle in std::mem::replace(&mut self.market_handles, market_handles) {
+            handle.abort();
+        }
+
+        let current: HashMap<String, MarketInfo> = self
+            .markets
+            .get()
+            .into_iter()
+            .map(|m| (m.address.clone(), m))
+            .collect();
+        let added: Vec<MarketInfo> = markets
+            .iter()
+            .filter(|m| !current.contains_key(&m.address))
+            .cloned()
+            .collect();
+        let changed: Vec<MarketInfo> = markets
+            .iter()
+            .filter(|m| match current.get(&m.address) {
+                Some(c) => c.name != m.name || c.resolutions != m.resolutions,
+                None => false,
+            })
+            .cloned()
+            .collect();
+        let removed: Vec<MarketInfo> = current
+            .into_values()
+            .filter(|c| !markets.iter().any(|m| m.address == c.address))
+            .collect();
+        let rpc_changed = rpc_url != self.rpc_url;
+
+        // fills of added markets are kept from here on
+        self.markets.set(markets.clone());
+        self.rpc_url = rpc_url;
+
+        for market in removed.iter() {
+            if let Some(batch) = self.batches.remove(&market.address) {
+                batch.stop.stop();
+            }
+            info!("Removed market {}", market.name);
+        }
+        for market in changed.iter() {
+            info!("Restarting batches of changed market {}", market.name);
+            self.start_batches(market.clone());
+        }
+        for market in added.iter() {
+            info!("Added market {}", market.name);
+            self.start_batches(market.clone());
+        }
+        log_resolutions(&changed);
+        log_resolutions(&added);
+
+        let mut new_sources = added;
+        if rpc_changed {
+            info!("RPC endpoint changed, reconnecting Phoenix ingestion");
+            for handle in self.phoenix_handles.drain(..) {
+                handle.abort();
+            }
+            new_sources.retain(|m| m.venue != Venue::Phoenix);
+            new_sources.extend(markets.into_iter().filter(|m| m.venue == Venue::Phoenix));
+        }
+        if let Err(e) = self.start_ingestion(&new_sources).await {
+            error!("Failed to start ingestion of the added markets: {:?}", e);
+        }
+        info!("Configuration reloaded");
+        Ok(())
+    }
+}
+
+fn spawn_ingestion<S: TradeSource + 'static>(
+    pool: &Pool,
+    mut source: S,
+    markets: &LiveMarkets,
+) -> JoinHandle<()> {
+    let ingest_pool = pool.clone();
+    let ingest_markets = markets.clone();
+    tokio::spawn(async move {
+        ingest_fills(&ingest_pool, &mut source, &ingest_markets)
+            .await
+            .unwrap();
+    })
+}
+
+/// Starts the tasks that work over the whole market list. Their settings are all read before
+/// anything is spawned, so an invalid one starts nothing.
+fn spawn_market_tasks(
+    pool: &Pool,
+    assignment: &MarketAssignment,
+    market_infos: &[MarketInfo],
+    rpc_url: &str,
+    batch_options: BatchOptions,
+) -> anyhow::Result<Vec<JoinHandle<()>>> {
+    let spread_config = SpreadConfig::from_env()?;
+    let uptime_config = UptimeConfig::from_env()?;
+    let oracle_config = OracleConfig::from_env()?;
+    let comparator_config = ComparatorConfig::from_env()?;
+    let pattern_config = PatternWebhookConfig::from_env()?;
+    let alert_config = AlertConfig::from_env()?;
+    let reconciliation_config = ReconciliationConfig::from_env()?;
+    let job_config = JobConfig::from_env()?;
+    let retention_config = RetentionConfig::from_env()?;
+
+    let openbook_markets: Vec<MarketInfo> = market_infos
+        .iter()
+        .filter(|m| m.venue == Venue::OpenBook)
+        .cloned()
+        .collect();
+    let mut handles = vec![];
+
+    let leaderboard_pool = pool.clone();
+    let leaderboard_markets = market_infos.to_vec();
+    handles.push(tokio::spawn(async move {
+        materialize_leaderboards(&leaderboard_pool, &leaderboard_markets)
+            .await
+            .unwrap();
+    }));
+
+    let depth_pool = pool.clone();
+    let depth_markets = openbook_markets.clone();
+    let depth_rpc_url = rpc_url.to_string();
+    let depth_assignment = assignment.clone();
+    handles.push(tokio::spawn(async move {
+        record_depth_stats(&depth_pool, depth_rpc_url, depth_markets, depth_assignment)
+            .await
+            .unwrap();
+    }));
+
+    let spread_pool = pool.clone();
+    let spread_markets = openbook_markets.clone();
+    let spread_rpc_url = rpc_url.to_string();
+    let spread_assignment = assignment.clone();
+    handles.push(tokio::spawn(async move {
+        record_spread_samples(
+            &spread_pool,
+            spread_rpc_url,
+            spread_markets,
+            spread_assignment,
+            &spread_config,
+        )
+        .await
+        .unwrap();
+    }));
+
+    let uptime_pool = pool.clone();
+    let uptime_markets = openbook_markets;
+    let uptime_rpc_url = rpc_url.to_string();
+    let uptime_assignment = assignment.clone();
+    handles.push(tokio::spawn(async move {
+        record_maker_uptime(
+            &uptime_pool,
+            uptime_rpc_url,
+            uptime_markets,
+            uptime_assignment,
+            &uptime_config,
+        )
+        .await
+        .unwrap();
+    }));
+
+    let composites = composite_markets(market_infos);
+    if !composites.is_empty() {
+        info!(
+            "Merging candles of {:?}",
+            composites.iter().map(|c| &c.info.name).collect::<Vec<_>>()
+        );
+        let composite_pool = pool.clone();
+        let composite_assignment = assignment.clone();
+        handles.push(tokio::spawn(async move {
+            batch_composite_candles(&composite_pool, composites, composite_assignment)
+                .await
+                .unwrap();
+        }));
+    }
+
+    if oracle_config.is_enabled() {
+        let oracle_pool = pool.clone();
+        let oracle_rpc_url = rpc_url.to_string();
+        handles.push(tokio::spawn(async move {
+            record_oracle_prices(&oracle_pool, oracle_rpc_url, &oracle_config)
+                .await
+                .unwrap();
+        }));
+    }
+
+    if comparator_config.is_enabled() {
+        let comparator_pool = pool.clone();
+        let comparator_markets = comparator_config.selected_markets(market_infos);
+        handles.push(tokio::spawn(async move {
+            run_comparator(&comparator_pool, &comparator_config, comparator_markets)
+                .await
+                .unwrap();
+        }));
+    }
+
+    if pattern_config.is_enabled() {
+        let pattern_pool = pool.clone();
+        let pattern_markets = market_infos.to_vec();
+        let pattern_assignment = assignment.clone();
+        handles.push(tokio::spawn(async move {
+            post_pattern_detections(
+                &pattern_config,
+                &pattern_pool,
+                pattern_markets,
+                pattern_assignment,
+            )
+            .await
+            .unwrap();
+        }));
+    }
+
+    if alert_config.is_enabled() {
+        let alert_pool = pool.clone();
+        let alert_markets = market_infos.to_vec();
+        let alert_assignment = assignment.clone();
+        handles.push(tokio::spawn(async move {
+            watch_for_alerts(&alert_config, &alert_pool, alert_markets, alert_assignment)
+                .await
+                .unwrap();
+        }));
+    }
+
+    if reconciliation_config.is_enabled() {
+        let reconciliation_pool = pool.clone();
+        let reconciliation_markets = market_infos.to_vec();
+        let reconciliation_rpc_url = rpc_url.to_string();
+        let reconciliation_assignment = assignment.clone();
+        handles.push(tokio::spawn(async move {
+            reconcile_fills(
+                &reconciliation_pool,
+                &reconciliation_config,
+                reconciliation_rpc_url,
+                reconciliation_markets,
+                reconciliation_assignment,
+            )
+            .await
+            .unwrap();
+        }));
+    }
+
+    // maintenance queued through the admin API or scheduled by retention, a job cut short by a
+    // reload is taken over once its heartbeat lapses
+    let jobs_pool = pool.clone();
+    let job_context = JobContext {
+        markets: market_infos.to_vec(),
+        outlier_filter: batch_options.outlier_filter,
+        retention: retention_config,
+    };
+    handles.push(tokio::spawn(async move {
+        run_jobs(&jobs_pool, job_config, job_context).await.unwrap();
+    }));
+
+    Ok(handles)
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+/// Marks the market's maker fills with these sequence numbers as anomalous prints.
+#[instrument(skip(pool, seq_nums), level = "debug", err)]
+pub async fn flag_anomalous_fills(
+    pool: &Pool,
+    market_address: &str,
+    seq_nums: &[i64],
+) -> anyhow::Result<u64> {
+    if seq_nums.is_empty() {
+        return Ok(0);
+    }
+    let client = pool.get().await?;
+
+    let stmt = r#"UPDATE openbook.openbook_fill_events
+        SET anomalous = true
+        WHERE market = $1
+        AND maker = true
+        AND seq_num = ANY($2)
+        AND NOT anomalous"#;
+
+    Ok(client.execute(stmt, &[&market_address, &seq_nums]).await?)
+}
+
+/// Sequence numbers of the market's maker fills flagged as anomalous between two times.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_anomalous_seq_nums(
+    pool: &Pool,
+    market_address: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<i64>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT seq_num
+        FROM openbook.openbook_fill_events
+        WHERE market = $1
+        AND maker = true
+        AND anomalous
+        AND block_datetime >= $2::timestamptz
+        AND block_datetime < $3::timestamptz"#;
+
+    let rows = client
+        .query(stmt, &[&market_address, &start_time, &end_time])
+        .await?;
+    Ok(rows.into_iter().map(|r| r.get(0)).collect())
+}
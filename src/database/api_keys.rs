@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use deadpool_postgres::Pool;
+
+use crate::structs::api_keys::{ApiKey, ApiKeyUsage};
+
+pub async fn fetch_api_keys(pool: &Pool) -> anyhow::Result<Vec<ApiKey>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT
+        id as "id",
+        key_hash as "key_hash",
+        name as "name",
+        requests_per_minute as "requests_per_minute",
+        created_at as "created_at",
+        revoked_at as "revoked_at",
+        realtime as "realtime"
+        from openbook.api_keys
+        ORDER BY id"#;
+
+    let rows = client.query(stmt, &[]).await?;
+
+    Ok(rows.into_iter().map(ApiKey::from_row).collect())
+}
+
+pub async fn insert_api_key(
+    pool: &Pool,
+    key_hash: &str,
+    name: &str,
+    requests_per_minute: i32,
+    realtime: bool,
+) -> anyhow::Result<ApiKey> {
+    let client = pool.get().await?;
+
+    let stmt = r#"INSERT INTO openbook.api_keys (key_hash, name, requests_per_minute, realtime)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, key_hash, name, requests_per_minute, created_at, revoked_at, realtime"#;
+
+    let row = client
+        .query_one(stmt, &[&key_hash, &name, &requests_per_minute, &realtime])
+        .await?;
+    Ok(ApiKey::from_row(row))
+}
+
+/// Returns the revoked key, or `None` if there is no active key with this id.
+pub async fn revoke_api_key(pool: &Pool, id: i64) -> anyhow::Result<Option<ApiKey>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"UPDATE openbook.api_keys
+        SET revoked_at = now()
+        WHERE id = $1 AND revoked_at IS NULL
+        RETURNING id, key_hash, name, requests_per_minute, created_at, revoked_at, realtime"#;
+
+    let row = client.query_opt(stmt, &[&id]).await?;
+    Ok(row.map(ApiKey::from_row))
+}
+
+/// Adds request counts per key id to today's (UTC) usage.
+pub async fn record_api_key_usage(pool: &Pool, usage: &HashMap<i64, i64>) -> anyhow::Result<()> {
+    if usage.is_empty() {
+        return Ok(());
+    }
+    let client = pool.get().await?;
+
+    let (ids, requests): (Vec<i64>, Vec<i64>) = usage.iter().map(|(id, n)| (*id, *n)).unzip();
+    let stmt = r#"INSERT INTO openbook.api_key_usage (api_key_id, day, requests)
+        SELECT u.id, (now() AT TIME ZONE 'UTC')::date, u.requests
+        FROM unnest($1::bigint[], $2::bigint[]) AS u(id, requests)
+        ON CONFLICT (api_key_id, day) DO UPDATE SET
+        requests = api_key_usage.requests + excluded.requests"#;
+
+    client.execute(stmt, &[&ids, &requests]).await?;
+    Ok(())
+}
+
+pub async fn fetch_api_key_usage(
+    pool: &Pool,
+    id: i64,
+    days: i32,
+) -> anyhow::Result<Vec<ApiKeyUsage>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT
+        day as "day",
+        requests as "requests"
+        from openbook.api_key_usage
+        where api_key_id = $1
+        and day > (now() AT TIME ZONE 'UTC')::date - $2
+        ORDER BY day"#;
+
+    let rows = client.query(stmt, &[&id, &days]).await?;
+
+    Ok(rows.into_iter().map(ApiKeyUsage::from_row).collect())
+}
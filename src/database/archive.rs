@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+
+use crate::structs::{candle::Candle, openbook::OpenBookFill};
+
+pub async fn fetch_archive_fills(
+    pool: &Pool,
+    market_address: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<OpenBookFill>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        signature as "signature",
+        slot as "slot",
+        block_datetime as "block_datetime",
+        market as "market",
+        open_orders_owner as "open_orders_owner",
+        bid as "bid",
+        maker as "maker",
+        native_quantity_paid as "native_quantity_paid",
+        native_quantity_received as "native_quantity_received",
+        native_fee_or_rebate as "native_fee_or_rebate",
+        price as "price",
+        size as "size",
+        seq_num as "seq_num",
+        instruction_num as "instruction_num",
+        fee as "fee",
+        referrer_rebate as "referrer_rebate"
+        from openbook.openbook_fill_events
+        where market = $1
+        and block_datetime >= $2
+        and block_datetime < $3
+        ORDER BY block_datetime asc, seq_num asc"#;
+
+    let rows = client
+        .query(stmt, &[&market_address, &start_time, &end_time])
+        .await?;
+    Ok(rows.into_iter().map(OpenBookFill::from_row).collect())
+}
+
+/// Fetches candles of every resolution for the market that start within the range.
+pub async fn fetch_archive_candles(
+    pool: &Pool,
+    market_name: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<Candle>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
+        from openbook.candles
+        where market_name = $1
+        and start_time >= $2
+        and start_time < $3
+        ORDER BY resolution asc, start_time asc"#;
+
+    let rows = client
+        .query(stmt, &[&market_name, &start_time, &end_time])
+        .await?;
+    Ok(rows.into_iter().map(Candle::from_row).collect())
+}
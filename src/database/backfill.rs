@@ -1,32 +1,7 @@
 use crate::structs::{candle::Candle, openbook::PgOpenBookFill};
 use chrono::{DateTime, Utc};
 use deadpool_postgres::{GenericClient, Object};
-
-pub async fn fetch_earliest_fill_multiple_markets(
-    conn_object: &Object,
-    market_address_strings: &Vec<String>,
-) -> anyhow::Result<Option<PgOpenBookFill>> {
-    let stmt = r#"SELECT 
-        block_datetime as "time",
-        market as "market_key",
-        bid as "bid",
-        maker as "maker",
-        price as "price",
-        size as "size"
-        from openbook.openbook_fill_events 
-        where market = ANY($1)
-        and maker = true
-        ORDER BY time asc LIMIT 1"#;
-
-    let row = conn_object
-        .query_opt(stmt, &[&market_address_strings])
-        .await?;
-
-    match row {
-        Some(r) => Ok(Some(PgOpenBookFill::from_row(r))),
-        None => Ok(None),
-    }
-}
+use std::collections::HashMap;
 
 pub async fn fetch_fills_multiple_markets_from(
     conn_object: &Object,
@@ -40,7 +15,9 @@ pub async fn fetch_fills_multiple_markets_from(
          bid as "bid",
          maker as "maker",
          price as "price",
-         size as "size"
+         size as "size",
+         slot as "slot",
+         seq_num as "seq_num"
          from openbook.openbook_fill_events 
          where market = ANY($1)
          and block_datetime >= $2::timestamptz
@@ -65,7 +42,12 @@ pub async fn fetch_last_minute_candles(conn_object: &Object) -> anyhow::Result<V
         c.high as "high",
         c.low as "low",
         c.volume as "volume",
-        c.complete as "complete"
+        c.complete as "complete",
+        c.vwap as "vwap",
+        c.trade_count as "trade_count",
+        c.quote_volume as "quote_volume",
+        c.priced_volume as "priced_volume",
+        c.priced_quote_volume as "priced_quote_volume"
          from   
          (
             select market_name, max(start_time) as max_start_time from openbook.candles
@@ -80,3 +62,18 @@ pub async fn fetch_last_minute_candles(conn_object: &Object) -> anyhow::Result<V
     let rows = conn_object.query(stmt, &[]).await?;
     Ok(rows.into_iter().map(Candle::from_row).collect())
 }
+
+/// Per market address, the time before which fills were pruned by the retention job
+pub async fn fetch_fill_retention_watermarks(
+    conn_object: &Object,
+    market_address_strings: &Vec<String>,
+) -> anyhow::Result<HashMap<String, DateTime<Utc>>> {
+    let stmt = r#"SELECT 
+        market as "market",
+        pruned_before as "pruned_before"
+        from openbook.fill_retention
+        where market = ANY($1)"#;
+
+    let rows = conn_object.query(stmt, &[&market_address_strings]).await?;
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
@@ -0,0 +1,54 @@
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::structs::checkpoint::WorkerCheckpoint;
+
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_worker_checkpoint(
+    pool: &Pool,
+    market_name: &str,
+) -> anyhow::Result<Option<WorkerCheckpoint>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        candles_through as "candles_through",
+        last_price as "last_price",
+        last_fill_time as "last_fill_time",
+        last_fill_slot as "last_fill_slot"
+        from openbook.worker_checkpoints
+        where market_name = $1"#;
+
+    let row = client.query_opt(stmt, &[&market_name]).await?;
+    Ok(row.map(WorkerCheckpoint::from_row))
+}
+
+pub async fn save_worker_checkpoint(
+    pool: &Pool,
+    checkpoint: &WorkerCheckpoint,
+) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let stmt = r#"INSERT INTO openbook.worker_checkpoints (market_name, candles_through, last_price, last_fill_time, last_fill_slot)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (market_name) DO UPDATE SET
+        candles_through = excluded.candles_through,
+        last_price = excluded.last_price,
+        last_fill_time = excluded.last_fill_time,
+        last_fill_slot = excluded.last_fill_slot,
+        updated_at = now()"#;
+
+    client
+        .execute(
+            stmt,
+            &[
+                &checkpoint.market_name,
+                &checkpoint.candles_through,
+                &checkpoint.last_price,
+                &checkpoint.last_fill_time,
+                &checkpoint.last_fill_slot,
+            ],
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{GenericClient, Pool};
+
+use crate::structs::{candle::Candle, resolution::Resolution};
+
+/// A stored candle together with its row id
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgCandleRow {
+    pub id: i64,
+    pub candle: Candle,
+}
+
+impl PgCandleRow {
+    pub fn from_row(row: tokio_postgres::Row) -> Self {
+        let id = row.get(15);
+        PgCandleRow {
+            id,
+            candle: Candle::from_row(row),
+        }
+    }
+}
+
+/// Fetches every row sharing a (market, start_time, resolution) key with another row.
+pub async fn fetch_duplicate_candles(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+) -> anyhow::Result<Vec<PgCandleRow>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume",
+        id as "id"
+        from openbook.candles
+        where market_name = $1
+        and resolution = $2
+        and start_time in (
+            select start_time from openbook.candles
+            where market_name = $1
+            and resolution = $2
+            group by start_time
+            having count(*) > 1
+        )
+        ORDER BY start_time asc, id asc"#;
+
+    let rows = client
+        .query(stmt, &[&market_name, &resolution.to_string()])
+        .await?;
+
+    Ok(rows.into_iter().map(PgCandleRow::from_row).collect())
+}
+
+/// Fetches rows whose start or length doesn't line up with the resolution's buckets.
+pub async fn fetch_misaligned_candles(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+) -> anyhow::Result<Vec<PgCandleRow>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume",
+        id as "id"
+        from openbook.candles
+        where market_name = $1
+        and resolution = $2
+        and (
+            mod(extract(epoch from start_time)::bigint, $3::bigint) <> 0
+            or end_time - start_time <> $3::bigint * interval '1 second'
+        )
+        ORDER BY start_time asc, id asc"#;
+
+    let rows = client
+        .query(
+            stmt,
+            &[
+                &market_name,
+                &resolution.to_string(),
+                &resolution.get_duration().num_seconds(),
+            ],
+        )
+        .await?;
+
+    Ok(rows.into_iter().map(PgCandleRow::from_row).collect())
+}
+
+pub async fn candle_exists(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+    start_time: DateTime<Utc>,
+) -> anyhow::Result<bool> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 1 from openbook.candles
+        where market_name = $1
+        and resolution = $2
+        and start_time = $3
+        and end_time - start_time = $4::bigint * interval '1 second'"#;
+
+    let row = client
+        .query_opt(
+            stmt,
+            &[
+                &market_name,
+                &resolution.to_string(),
+                &start_time,
+                &resolution.get_duration().num_seconds(),
+            ],
+        )
+        .await?;
+    Ok(row.is_some())
+}
+
+pub async fn delete_candles_by_id(pool: &Pool, ids: &Vec<i64>) -> anyhow::Result<u64> {
+    let client = pool.get().await?;
+
+    let deleted = client
+        .execute("DELETE FROM openbook.candles WHERE id = ANY($1)", &[ids])
+        .await?;
+    Ok(deleted)
+}
@@ -0,0 +1,86 @@
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::{
+    database::revisions::{revision_transaction, REVISION_REASON_COMPOSITE},
+    structs::resolution::Resolution,
+};
+
+/// Merges the candles of `market_names` into the composite's candles of the same buckets. Prices
+/// are averaged weighted by each market's volume in the bucket, plainly where none traded, and
+/// volumes and trade counts add up. Buckets are merged from one before the oldest newest candle
+/// among the composite and its markets, or from the start while the composite has none. Returns
+/// the number of candles written or changed.
+#[instrument(skip(pool, market_names), level = "debug", err)]
+pub async fn merge_composite_candles(
+    pool: &Pool,
+    composite_name: &str,
+    market_names: &[String],
+    resolution: Resolution,
+) -> anyhow::Result<u64> {
+    let mut client = pool.get().await?;
+    let transaction = revision_transaction(&mut client, REVISION_REASON_COMPOSITE).await?;
+
+    let stmt = r#"WITH latest AS (
+        SELECT name, (
+            SELECT max(start_time)
+            from openbook.candles
+            where market_name = name
+            and resolution = $3
+        ) as start_time
+        from unnest(array_append($2::text[], $1::text)) as name
+    ), since AS (
+        SELECT CASE WHEN bool_or(name = $1 AND start_time IS NOT NULL) THEN min(start_time) END as start_time
+        from latest
+    )
+    INSERT INTO openbook.candles (market_name, start_time, end_time, resolution, open, close, high, low, volume, complete, vwap, trade_count, quote_volume, priced_volume, priced_quote_volume, source, source_fill_count)
+    SELECT
+        $1,
+        c.start_time,
+        max(c.end_time),
+        $3,
+        coalesce(sum(c.open * c.volume) / nullif(sum(c.volume), 0), avg(c.open)),
+        coalesce(sum(c.close * c.volume) / nullif(sum(c.volume), 0), avg(c.close)),
+        coalesce(sum(c.high * c.volume) / nullif(sum(c.volume), 0), avg(c.high)),
+        coalesce(sum(c.low * c.volume) / nullif(sum(c.volume), 0), avg(c.low)),
+        sum(c.volume),
+        bool_and(c.complete),
+        coalesce(sum(coalesce(c.priced_quote_volume, c.vwap * c.volume)) / nullif(sum(coalesce(c.priced_volume, c.volume)), 0), avg(c.vwap)),
+        sum(c.trade_count)::bigint,
+        sum(c.quote_volume),
+        sum(coalesce(c.priced_volume, c.volume)),
+        sum(coalesce(c.priced_quote_volume, c.vwap * c.volume)),
+        'composite',
+        sum(c.source_fill_count)::bigint
+        from openbook.candles c, since
+        where c.market_name = ANY($2)
+        and c.resolution = $3
+        and (since.start_time IS NULL OR c.start_time >= since.start_time - (c.end_time - c.start_time))
+        GROUP BY c.start_time
+    ON CONFLICT (market_name, start_time, resolution)
+    DO UPDATE SET
+    end_time=excluded.end_time,
+    open=excluded.open,
+    close=excluded.close,
+    high=excluded.high,
+    low=excluded.low,
+    volume=excluded.volume,
+    complete=excluded.complete,
+    vwap=excluded.vwap,
+    trade_count=excluded.trade_count,
+    quote_volume=excluded.quote_volume,
+    priced_volume=excluded.priced_volume,
+    priced_quote_volume=excluded.priced_quote_volume,
+    source_fill_count=excluded.source_fill_count
+    WHERE (candles.end_time, candles.open, candles.close, candles.high, candles.low, candles.volume, candles.complete, candles.vwap, candles.trade_count, candles.quote_volume, candles.priced_volume, candles.priced_quote_volume)
+    IS DISTINCT FROM (excluded.end_time, excluded.open, excluded.close, excluded.high, excluded.low, excluded.volume, excluded.complete, excluded.vwap, excluded.trade_count, excluded.quote_volume, excluded.priced_volume, excluded.priced_quote_volume)"#;
+
+    let count = transaction
+        .execute(
+            stmt,
+            &[&composite_name, &market_names, &resolution.to_string()],
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok(count)
+}
@@ -1,18 +1,33 @@
+use crate::database::telemetry::get_client;
 use crate::structs::{
-    candle::Candle,
+    candle::{Candle, CandlePage},
+    changes::CandleChange,
     coingecko::{PgCoinGecko24HighLow, PgCoinGecko24HourVolume},
+    divergence::{CandleDivergence, DivergenceSummary},
+    market_summary::MarketSummary,
     openbook::PgOpenBookFill,
+    oracle::OraclePrice,
+    orderbook::DepthSnapshot,
+    price_change::PRICE_CHANGE_WINDOWS,
     resolution::Resolution,
-    trader::PgTrader,
+    rolling::RollingCandle,
+    spread::SpreadBucket,
+    trade::{Trade, TradeBucket, TradeFilter, TradeGrouping},
+    trader::{LeaderboardPeriod, PgLeaderboardEntry, PgTrader, TraderRole, VolumeType},
+    volume_profile::PgVolumeBin,
 };
 use chrono::{DateTime, Utc};
-use deadpool_postgres::{GenericClient, Pool};
+use deadpool_postgres::Pool;
+use futures::future::try_join_all;
+use std::collections::HashSet;
+use tracing::instrument;
 
+#[instrument(skip(pool), level = "debug", err)]
 pub async fn fetch_earliest_fill(
     pool: &Pool,
     market_address_string: &str,
 ) -> anyhow::Result<Option<PgOpenBookFill>> {
-    let client = pool.get().await?;
+    let client = get_client(pool).await?;
 
     let stmt = r#"SELECT 
         block_datetime as "time",
@@ -20,7 +35,9 @@ pub async fn fetch_earliest_fill(
         bid as "bid",
         maker as "maker",
         price as "price",
-        size as "size"
+        size as "size",
+        slot as "slot",
+        seq_num as "seq_num"
         from openbook.openbook_fill_events 
         where market = $1 
         and maker = true
@@ -34,40 +51,48 @@ pub async fn fetch_earliest_fill(
     }
 }
 
+/// Statement of `fetch_fills_from`: maker fills of market `$1` from `$2` until `$3`.
+pub const FILLS_FROM: &str = r#"SELECT 
+    block_datetime as "time",
+    market as "market_key",
+    bid as "bid",
+    maker as "maker",
+    price as "price",
+    size as "size",
+    slot as "slot",
+    seq_num as "seq_num"
+    from openbook.openbook_fill_events 
+    where market = $1
+    and block_datetime >= $2::timestamptz
+    and block_datetime < $3::timestamptz
+    and maker = true
+    ORDER BY time asc"#;
+
+#[instrument(skip(pool), level = "debug", err)]
 pub async fn fetch_fills_from(
     pool: &Pool,
     market_address_string: &str,
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
 ) -> anyhow::Result<Vec<PgOpenBookFill>> {
-    let client = pool.get().await?;
-
-    let stmt = r#"SELECT 
-         block_datetime as "time",
-         market as "market_key",
-         bid as "bid",
-         maker as "maker",
-         price as "price",
-         size as "size"
-         from openbook.openbook_fill_events 
-         where market = $1
-         and block_datetime >= $2::timestamptz
-         and block_datetime < $3::timestamptz
-         and maker = true
-         ORDER BY time asc"#;
+    let client = get_client(pool).await?;
 
     let rows = client
-        .query(stmt, &[&market_address_string, &start_time, &end_time])
+        .query(
+            FILLS_FROM,
+            &[&market_address_string, &start_time, &end_time],
+        )
         .await?;
     Ok(rows.into_iter().map(PgOpenBookFill::from_row).collect())
 }
 
+#[instrument(skip(pool), level = "debug", err)]
 pub async fn fetch_latest_finished_candle(
     pool: &Pool,
     market_name: &str,
     resolution: Resolution,
 ) -> anyhow::Result<Option<Candle>> {
-    let client = pool.get().await?;
+    let client = get_client(pool).await?;
 
     let stmt = r#"SELECT 
         market_name as "market_name",
@@ -79,7 +104,12 @@ pub async fn fetch_latest_finished_candle(
         high as "high",
         low as "low",
         volume as "volume",
-        complete as "complete"
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
         from openbook.candles
         where market_name = $1
         and resolution = $2
@@ -96,14 +126,76 @@ pub async fn fetch_latest_finished_candle(
     }
 }
 
+/// Fetches when each market's candles of a resolution were last written, which moves whenever a
+/// batch changes something.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_latest_candle_updates(
+    pool: &Pool,
+    resolution: Resolution,
+) -> anyhow::Result<Vec<(String, DateTime<Utc>)>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        market_name as "market_name",
+        max(updated_at) as "updated_at"
+        from openbook.candles
+        where resolution = $1
+        and start_time > now() - interval '1 day'
+        GROUP BY market_name"#;
+
+    let rows = client.query(stmt, &[&resolution.to_string()]).await?;
+
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// Fetches the last candle of the market that started at or before `at`.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_candle_at(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+    at: DateTime<Utc>,
+) -> anyhow::Result<Option<Candle>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
+        from openbook.candles
+        where market_name = $1
+        and resolution = $2
+        and start_time <= $3
+        ORDER BY start_time desc LIMIT 1"#;
+
+    let row = client
+        .query_opt(stmt, &[&market_name, &resolution.to_string(), &at])
+        .await?;
+
+    Ok(row.map(Candle::from_row))
+}
+
 /// Fetches all of the candles for the given market and resolution, starting from the earliest.
 /// Note that this function will fetch at most 2000 candles.
+#[instrument(skip(pool), level = "debug", err)]
 pub async fn fetch_earliest_candles(
     pool: &Pool,
     market_name: &str,
     resolution: Resolution,
 ) -> anyhow::Result<Vec<Candle>> {
-    let client = pool.get().await?;
+    let client = get_client(pool).await?;
 
     let stmt = r#"SELECT 
         market_name as "market_name",
@@ -115,7 +207,12 @@ pub async fn fetch_earliest_candles(
         high as "high",
         low as "low",
         volume as "volume",
-        complete as "complete"
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
         from openbook.candles
         where market_name = $1
         and resolution = $2
@@ -136,8 +233,104 @@ pub async fn fetch_candles_from(
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
 ) -> anyhow::Result<Vec<Candle>> {
-    let client = pool.get().await?;
+    fetch_candles_page(
+        pool,
+        market_name,
+        resolution,
+        start_time,
+        end_time,
+        CandlePage::default(),
+    )
+    .await
+}
+
+macro_rules! candles_page {
+    ($order:literal) => {
+        concat!(
+            r#"SELECT 
+    market_name as "market_name",
+    start_time as "start_time",
+    end_time as "end_time",
+    resolution as "resolution",
+    open as "open",
+    close as "close",
+    high as "high",
+    low as "low",
+    volume as "volume",
+    complete as "complete",
+    vwap as "vwap",
+    trade_count as "trade_count",
+    quote_volume as "quote_volume",
+    priced_volume as "priced_volume",
+    priced_quote_volume as "priced_quote_volume"
+    from openbook.candles
+    where market_name = $1
+    and resolution = $2
+    and start_time >= $3
+    and end_time <= $4
+    ORDER BY start_time "#,
+            $order,
+            "\n    LIMIT $5 OFFSET $6"
+        )
+    };
+}
+
+/// Statements of `fetch_candles_page`: candles of market `$1` at resolution `$2` from `$3` until
+/// `$4`, `$5` at most (NULL for all) after skipping `$6`.
+pub const CANDLES_PAGE_ASC: &str = candles_page!("asc");
+pub const CANDLES_PAGE_DESC: &str = candles_page!("desc");
+
+/// Candles within the range, ordered and cut down to `page`.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_candles_page(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    page: CandlePage,
+) -> anyhow::Result<Vec<Candle>> {
+    let client = get_client(pool).await?;
+
+    let stmt = if page.descending {
+        CANDLES_PAGE_DESC
+    } else {
+        CANDLES_PAGE_ASC
+    };
 
+    // a NULL limit is no limit
+    let limit = page.limit.map(|l| l as i64);
+    let offset = page.offset as i64;
+    let rows = client
+        .query(
+            stmt,
+            &[
+                &market_name,
+                &resolution.to_string(),
+                &start_time,
+                &end_time,
+                &limit,
+                &offset,
+            ],
+        )
+        .await?;
+
+    Ok(rows.into_iter().map(Candle::from_row).collect())
+}
+
+/// Candles of several market and resolution pairs within the same range in one query, sorted by
+/// market, resolution and start time.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_candles_of_pairs(
+    pool: &Pool,
+    pairs: &[(String, Resolution)],
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<Candle>> {
+    let client = get_client(pool).await?;
+
+    let market_names: Vec<&str> = pairs.iter().map(|(m, _)| m.as_str()).collect();
+    let resolutions: Vec<String> = pairs.iter().map(|(_, r)| r.to_string()).collect();
     let stmt = r#"SELECT 
         market_name as "market_name",
         start_time as "start_time",
@@ -148,49 +341,162 @@ pub async fn fetch_candles_from(
         high as "high",
         low as "low",
         volume as "volume",
-        complete as "complete"
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
         from openbook.candles
-        where market_name = $1
-        and resolution = $2
+        where (market_name, resolution) IN (SELECT * FROM unnest($1::text[], $2::text[]))
         and start_time >= $3
         and end_time <= $4
-        ORDER BY start_time asc"#;
+        ORDER BY market_name, resolution, start_time asc"#;
 
     let rows = client
-        .query(
-            stmt,
-            &[
-                &market_name,
-                &resolution.to_string(),
-                &start_time,
-                &end_time,
-            ],
-        )
+        .query(stmt, &[&market_names, &resolutions, &start_time, &end_time])
         .await?;
 
     Ok(rows.into_iter().map(Candle::from_row).collect())
 }
 
+/// Close of the market's newest minute candle starting before `as_of`, followed by the close of the
+/// newest one starting before each of `PRICE_CHANGE_WINDOWS` earlier, in a single query.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_price_change_closes(
+    pool: &Pool,
+    market_name: &str,
+    as_of: DateTime<Utc>,
+) -> anyhow::Result<Vec<Option<f64>>> {
+    let client = get_client(pool).await?;
+
+    let close_before = |interval: &str| {
+        format!(
+            r#"(SELECT close from openbook.candles
+            where market_name = $1
+            and resolution = '1M'
+            and start_time < $2 - interval '{}'
+            ORDER BY start_time desc LIMIT 1)"#,
+            interval
+        )
+    };
+    let columns: Vec<String> = std::iter::once("0 seconds")
+        .chain(PRICE_CHANGE_WINDOWS.iter().map(|(_, interval)| *interval))
+        .map(close_before)
+        .collect();
+    let stmt = format!("SELECT {}", columns.join(",\n"));
+
+    let row = client.query_one(&stmt, &[&market_name, &as_of]).await?;
+    Ok((0..columns.len()).map(|i| row.get(i)).collect())
+}
+
+/// Aggregates the market's minute candles starting in `[start_time, end_time)` into a single
+/// candle, `None` when there are none.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_rolling_candle(
+    pool: &Pool,
+    market_name: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Option<RollingCandle>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        (array_agg(open ORDER BY start_time asc))[1] as "open",
+        max(high) as "high",
+        min(low) as "low",
+        (array_agg(close ORDER BY start_time desc))[1] as "close",
+        sum(volume) as "volume",
+        coalesce(sum(quote_volume), 0) as "quote_volume",
+        coalesce(sum(trade_count), 0)::bigint as "trade_count",
+        min(start_time) as "start_time",
+        max(end_time) as "end_time"
+        from openbook.candles
+        where market_name = $1
+        and resolution = '1M'
+        and start_time >= $2
+        and start_time < $3
+        HAVING count(*) > 0"#;
+
+    let row = client
+        .query_opt(stmt, &[&market_name, &start_time, &end_time])
+        .await?;
+    Ok(row.map(|r| RollingCandle::from_row(market_name.to_string(), r)))
+}
+
+/// Fetches the `n` most recent complete candles for the given market and resolution, in ascending order.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_recent_candles(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+    n: i64,
+) -> anyhow::Result<Vec<Candle>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
+        from openbook.candles
+        where market_name = $1
+        and resolution = $2
+        and complete = true
+        ORDER BY start_time desc
+        LIMIT $3"#;
+
+    let rows = client
+        .query(stmt, &[&market_name, &resolution.to_string(), &n])
+        .await?;
+
+    let mut candles: Vec<Candle> = rows.into_iter().map(Candle::from_row).collect();
+    candles.reverse();
+    Ok(candles)
+}
+
+#[instrument(skip(pool), level = "debug", err)]
 pub async fn fetch_top_traders_by_base_volume_from(
     pool: &Pool,
     market_address_string: &str,
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
+    role: TraderRole,
 ) -> anyhow::Result<Vec<PgTrader>> {
-    let client = pool.get().await?;
+    let client = get_client(pool).await?;
 
     let stmt = r#"SELECT 
             open_orders_owner, 
             sum(
             native_quantity_paid * CASE bid WHEN true THEN 0 WHEN false THEN 1 END
-            ) as "raw_ask_size",
+            )::bigint as "raw_ask_size",
             sum(
             native_quantity_received * CASE bid WHEN true THEN 1 WHEN false THEN 0 END
-            ) as "raw_bid_size"
+            )::bigint as "raw_bid_size",
+            sum(
+            CASE bid WHEN true THEN native_quantity_received WHEN false THEN native_quantity_paid END
+            * CASE maker WHEN true THEN 1 WHEN false THEN 0 END
+            )::bigint as "raw_maker_size",
+            sum(
+            CASE bid WHEN true THEN native_quantity_received WHEN false THEN native_quantity_paid END
+            * CASE maker WHEN true THEN 0 WHEN false THEN 1 END
+            )::bigint as "raw_taker_size"
         FROM openbook.openbook_fill_events
     WHERE  market = $1
             AND time >= $2
             AND time < $3
+            AND ($4::bool IS NULL OR maker = $4)
     GROUP  BY open_orders_owner
     ORDER  BY 
         sum(native_quantity_paid * CASE bid WHEN true THEN 0 WHEN false THEN 1 END) 
@@ -200,32 +506,51 @@ pub async fn fetch_top_traders_by_base_volume_from(
     LIMIT 10000"#;
 
     let rows = client
-        .query(stmt, &[&market_address_string, &start_time, &end_time])
+        .query(
+            stmt,
+            &[
+                &market_address_string,
+                &start_time,
+                &end_time,
+                &role.maker_filter(),
+            ],
+        )
         .await?;
 
     Ok(rows.into_iter().map(PgTrader::from_row).collect())
 }
 
+#[instrument(skip(pool), level = "debug", err)]
 pub async fn fetch_top_traders_by_quote_volume_from(
     pool: &Pool,
     market_address_string: &str,
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
+    role: TraderRole,
 ) -> anyhow::Result<Vec<PgTrader>> {
-    let client = pool.get().await?;
+    let client = get_client(pool).await?;
 
     let stmt = r#"SELECT 
             open_orders_owner, 
             sum(
                 native_quantity_received * CASE bid WHEN true THEN 0 WHEN false THEN 1 END
-            ) as "raw_ask_size",
+            )::bigint as "raw_ask_size",
             sum(
                 native_quantity_paid * CASE bid WHEN true THEN 1 WHEN false THEN 0 END
-            ) as "raw_bid_size"
+            )::bigint as "raw_bid_size",
+            sum(
+                CASE bid WHEN true THEN native_quantity_paid WHEN false THEN native_quantity_received END
+                * CASE maker WHEN true THEN 1 WHEN false THEN 0 END
+            )::bigint as "raw_maker_size",
+            sum(
+                CASE bid WHEN true THEN native_quantity_paid WHEN false THEN native_quantity_received END
+                * CASE maker WHEN true THEN 0 WHEN false THEN 1 END
+            )::bigint as "raw_taker_size"
           FROM openbook.openbook_fill_events
      WHERE  market = $1
             AND time >= $2
             AND time < $3
+            AND ($4::bool IS NULL OR maker = $4)
      GROUP  BY open_orders_owner
      ORDER  BY 
         sum(native_quantity_received * CASE bid WHEN true THEN 0 WHEN false THEN 1 END) 
@@ -235,41 +560,315 @@ pub async fn fetch_top_traders_by_quote_volume_from(
     LIMIT 10000"#;
 
     let rows = client
-        .query(stmt, &[&market_address_string, &start_time, &end_time])
+        .query(
+            stmt,
+            &[
+                &market_address_string,
+                &start_time,
+                &end_time,
+                &role.maker_filter(),
+            ],
+        )
         .await?;
 
     Ok(rows.into_iter().map(PgTrader::from_row).collect())
 }
 
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_trader_leaderboard(
+    pool: &Pool,
+    market_address_string: &str,
+    period: LeaderboardPeriod,
+    period_start: DateTime<Utc>,
+    volume_type: VolumeType,
+    role: TraderRole,
+) -> anyhow::Result<Vec<PgLeaderboardEntry>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+            open_orders_owner,
+            volume,
+            maker_volume,
+            taker_volume
+        FROM openbook.trader_leaderboard
+    WHERE  market = $1
+            AND period = $2
+            AND period_start = $3
+            AND volume_type = $4
+            AND role = $5
+    ORDER  BY rank asc"#;
+
+    let rows = client
+        .query(
+            stmt,
+            &[
+                &market_address_string,
+                &period.to_string(),
+                &period_start,
+                &volume_type.to_string(),
+                &role.to_string(),
+            ],
+        )
+        .await?;
+
+    Ok(rows.into_iter().map(PgLeaderboardEntry::from_row).collect())
+}
+
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_divergence_summary(
+    pool: &Pool,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<DivergenceSummary>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+            market_name,
+            count(*) as "bars",
+            avg(abs(close_delta_pct)) as "avg_abs_close_delta_pct",
+            max(abs(close_delta_pct)) as "max_abs_close_delta_pct",
+            avg(abs(volume_delta_pct)) as "avg_abs_volume_delta_pct"
+        FROM openbook.candle_divergence
+    WHERE  start_time >= $1
+            AND start_time < $2
+    GROUP  BY market_name
+    ORDER  BY max(abs(close_delta_pct)) DESC"#;
+
+    let rows = client.query(stmt, &[&start_time, &end_time]).await?;
+
+    Ok(rows.into_iter().map(DivergenceSummary::from_row).collect())
+}
+
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_divergences_from(
+    pool: &Pool,
+    market_name: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<CandleDivergence>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+            market_name,
+            resolution,
+            provider,
+            start_time,
+            close,
+            provider_close,
+            volume,
+            provider_volume,
+            close_delta_pct,
+            volume_delta_pct
+        FROM openbook.candle_divergence
+    WHERE  market_name = $1
+            AND start_time >= $2
+            AND start_time < $3
+    ORDER  BY start_time asc"#;
+
+    let rows = client
+        .query(stmt, &[&market_name, &start_time, &end_time])
+        .await?;
+
+    Ok(rows.into_iter().map(CandleDivergence::from_row).collect())
+}
+
+/// Newest price of the symbol from each provider.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_latest_oracle_prices(
+    pool: &Pool,
+    symbol: &str,
+) -> anyhow::Result<Vec<OraclePrice>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT DISTINCT ON (provider)
+            symbol as "symbol",
+            provider as "provider",
+            time as "time",
+            price as "price",
+            confidence as "confidence"
+        FROM openbook.oracle_prices
+    WHERE  symbol = $1
+    ORDER  BY provider, time desc"#;
+
+    let rows = client.query(stmt, &[&symbol]).await?;
+
+    Ok(rows.into_iter().map(OraclePrice::from_row).collect())
+}
+
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_oracle_prices_from(
+    pool: &Pool,
+    symbol: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: i64,
+) -> anyhow::Result<Vec<OraclePrice>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+            symbol as "symbol",
+            provider as "provider",
+            time as "time",
+            price as "price",
+            confidence as "confidence"
+        FROM openbook.oracle_prices
+    WHERE  symbol = $1
+            AND time >= $2
+            AND time < $3
+    ORDER  BY time asc
+    LIMIT  $4"#;
+
+    let rows = client
+        .query(stmt, &[&symbol, &start_time, &end_time, &limit])
+        .await?;
+
+    Ok(rows.into_iter().map(OraclePrice::from_row).collect())
+}
+
+/// Depth snapshots of the market in the range, oldest first.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_depth_snapshots_from(
+    pool: &Pool,
+    market_name: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    limit: i64,
+) -> anyhow::Result<Vec<DepthSnapshot>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+            market_name as "market_name",
+            time as "time",
+            best_bid as "best_bid",
+            best_ask as "best_ask",
+            mid_price as "mid_price",
+            bid_depth_1pct as "bid_depth_1pct",
+            ask_depth_1pct as "ask_depth_1pct",
+            bid_depth_2pct as "bid_depth_2pct",
+            ask_depth_2pct as "ask_depth_2pct",
+            bid_depth_5pct as "bid_depth_5pct",
+            ask_depth_5pct as "ask_depth_5pct"
+        FROM openbook.depth_snapshots
+    WHERE  market_name = $1
+            AND time >= $2
+            AND time < $3
+    ORDER  BY time asc
+    LIMIT  $4"#;
+
+    let rows = client
+        .query(stmt, &[&market_name, &start_time, &end_time, &limit])
+        .await?;
+
+    Ok(rows.into_iter().map(DepthSnapshot::from_row).collect())
+}
+
+/// Newest depth snapshot of the market.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_latest_depth_snapshot(
+    pool: &Pool,
+    market_name: &str,
+) -> anyhow::Result<Option<DepthSnapshot>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+            market_name as "market_name",
+            time as "time",
+            best_bid as "best_bid",
+            best_ask as "best_ask",
+            mid_price as "mid_price",
+            bid_depth_1pct as "bid_depth_1pct",
+            ask_depth_1pct as "ask_depth_1pct",
+            bid_depth_2pct as "bid_depth_2pct",
+            ask_depth_2pct as "ask_depth_2pct",
+            bid_depth_5pct as "bid_depth_5pct",
+            ask_depth_5pct as "ask_depth_5pct"
+        FROM openbook.depth_snapshots
+    WHERE  market_name = $1
+    ORDER  BY time desc
+    LIMIT  1"#;
+
+    let row = client.query_opt(stmt, &[&market_name]).await?;
+
+    Ok(row.map(DepthSnapshot::from_row))
+}
+
+/// Spread samples of the market in the range, aggregated per `bucket_secs` long bucket.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_spread_buckets(
+    pool: &Pool,
+    market_name: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    bucket_secs: i64,
+) -> anyhow::Result<Vec<SpreadBucket>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+            to_timestamp(floor(extract(epoch from time) / $4::float8) * $4::float8) as "start_time",
+            count(*) as "samples",
+            avg(spread_bps) as "avg_spread_bps",
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY spread_bps) as "median_spread_bps",
+            max(spread_bps) as "max_spread_bps",
+            avg(mid_price) as "avg_mid_price"
+        FROM (
+            SELECT time, mid_price, (best_ask - best_bid) / mid_price * 10000 as spread_bps
+            FROM openbook.spread_samples
+            WHERE market_name = $1
+            AND time >= $2
+            AND time < $3
+        ) samples
+    GROUP  BY 1
+    ORDER  BY 1 asc"#;
+
+    let rows = client
+        .query(
+            stmt,
+            &[&market_name, &start_time, &end_time, &(bucket_secs as f64)],
+        )
+        .await?;
+
+    Ok(rows.into_iter().map(SpreadBucket::from_row).collect())
+}
+
+/// 24h volumes of the markets, queried at most `max_markets_per_query` at a time with the batches
+/// running concurrently.
 pub async fn fetch_coingecko_24h_volume(
     pool: &Pool,
     market_address_strings: &Vec<&str>,
+    max_markets_per_query: usize,
 ) -> anyhow::Result<Vec<PgCoinGecko24HourVolume>> {
-    let client = pool.get().await?;
+    let batches = market_address_strings
+        .chunks(max_markets_per_query.max(1))
+        .map(|batch| fetch_coingecko_24h_volume_batch(pool, batch));
+    Ok(try_join_all(batches).await?.into_iter().flatten().collect())
+}
+
+#[instrument(skip(pool, market_address_strings), level = "debug", err)]
+async fn fetch_coingecko_24h_volume_batch(
+    pool: &Pool,
+    market_address_strings: &[&str],
+) -> anyhow::Result<Vec<PgCoinGecko24HourVolume>> {
+    let client = get_client(pool).await?;
 
+    // every trade is stored as a maker and a taker fill, counting the maker side of both buys and
+    // sells counts each trade exactly once
     let stmt = r#"SELECT 
             t1.market, 
             COALESCE(t2.base_size, 0) as "base_size",
-            COALESCE(t3.quote_size, 0) as "quote_size"
+            COALESCE(t2.quote_size, 0) as "quote_size"
         FROM (
             SELECT unnest($1::text[]) as market 
         ) t1
         LEFT JOIN (
             select market,
-            sum("size") as "base_size"
-            from openbook.openbook_fill_events 
-            where block_datetime >= current_timestamp - interval '1 day' 
-            and bid = true
-            group by market
-        ) t2 ON t1.market = t2.market
-        LEFT JOIN (
-            select market,
+            sum("size") as "base_size",
             sum("size" * price) as "quote_size"
             from openbook.openbook_fill_events 
             where block_datetime >= current_timestamp - interval '1 day' 
-            and bid = true
+            and maker = true
+            and market = any($1::text[])
             group by market
-        ) t3 ON t1.market = t3.market"#;
+        ) t2 ON t1.market = t2.market"#;
 
     let rows = client.query(stmt, &[&market_address_strings]).await?;
 
@@ -279,11 +878,24 @@ pub async fn fetch_coingecko_24h_volume(
         .collect())
 }
 
+/// Same batching as `fetch_coingecko_24h_volume`.
 pub async fn fetch_coingecko_24h_high_low(
     pool: &Pool,
     market_address_strings: &Vec<&str>,
+    max_markets_per_query: usize,
+) -> anyhow::Result<Vec<PgCoinGecko24HighLow>> {
+    let batches = market_address_strings
+        .chunks(max_markets_per_query.max(1))
+        .map(|batch| fetch_coingecko_24h_high_low_batch(pool, batch));
+    Ok(try_join_all(batches).await?.into_iter().flatten().collect())
+}
+
+#[instrument(skip(pool, market_address_strings), level = "debug", err)]
+async fn fetch_coingecko_24h_high_low_batch(
+    pool: &Pool,
+    market_address_strings: &[&str],
 ) -> anyhow::Result<Vec<PgCoinGecko24HighLow>> {
-    let client = pool.get().await?;
+    let client = get_client(pool).await?;
 
     let stmt = r#"
     with markets as (
@@ -358,3 +970,425 @@ pub async fn fetch_coingecko_24h_high_low(
         .map(PgCoinGecko24HighLow::from_row)
         .collect())
 }
+
+/// 24h summaries of the `(name, address)` markets in the given order, including markets without
+/// any fills. Batched like `fetch_coingecko_24h_volume`.
+pub async fn fetch_market_summaries(
+    pool: &Pool,
+    markets: &[(&str, &str)],
+    max_markets_per_query: usize,
+) -> anyhow::Result<Vec<MarketSummary>> {
+    let batches = markets
+        .chunks(max_markets_per_query.max(1))
+        .map(|batch| fetch_market_summaries_batch(pool, batch));
+    Ok(try_join_all(batches).await?.into_iter().flatten().collect())
+}
+
+#[instrument(skip(pool, markets), level = "debug", err)]
+async fn fetch_market_summaries_batch(
+    pool: &Pool,
+    markets: &[(&str, &str)],
+) -> anyhow::Result<Vec<MarketSummary>> {
+    let client = get_client(pool).await?;
+
+    let (market_names, market_addresses): (Vec<&str>, Vec<&str>) = markets.iter().copied().unzip();
+
+    // volumes count maker fills so each trade is counted once, like the coingecko volumes
+    let stmt = r#"SELECT 
+            m.market_name as "market_name",
+            m.address as "address",
+            last_fill.price as "last_price",
+            COALESCE(d.base_size, 0) as "base_size",
+            COALESCE(d.quote_size, 0) as "quote_size",
+            d.high as "high",
+            d.low as "low",
+            COALESCE(open_fill.price, d.first_price) as "open_price"
+        FROM unnest($1::text[], $2::text[]) WITH ORDINALITY as m(market_name, address, ord)
+        LEFT JOIN LATERAL (
+            select price
+            from openbook.openbook_fill_events
+            where market = m.address
+            order by block_datetime desc, seq_num desc
+            limit 1
+        ) last_fill ON true
+        LEFT JOIN LATERAL (
+            select price
+            from openbook.openbook_fill_events
+            where market = m.address
+            and block_datetime < current_timestamp - interval '1 day'
+            order by block_datetime desc, seq_num desc
+            limit 1
+        ) open_fill ON true
+        LEFT JOIN LATERAL (
+            select
+            sum("size") filter (where maker = true) as "base_size",
+            sum("size" * price) filter (where maker = true) as "quote_size",
+            max(price) as "high",
+            min(price) as "low",
+            (array_agg(price order by block_datetime, seq_num))[1] as "first_price"
+            from openbook.openbook_fill_events
+            where market = m.address
+            and block_datetime >= current_timestamp - interval '1 day'
+        ) d ON true
+        ORDER BY m.ord"#;
+
+    let rows = client
+        .query(stmt, &[&market_names, &market_addresses])
+        .await?;
+
+    Ok(rows.into_iter().map(MarketSummary::from_row).collect())
+}
+
+/// Candle inserts and updates after the `since` version, oldest first. Changes from the last few
+/// seconds are held back so a slow transaction can't commit a lower version behind the cursor.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_candle_changes(
+    pool: &Pool,
+    since: i64,
+    limit: i64,
+) -> anyhow::Result<Vec<CandleChange>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume",
+        version as "version"
+        from openbook.candles
+        where version > $1
+        and updated_at < now() - interval '5 seconds'
+        ORDER BY version asc
+        LIMIT $2"#;
+
+    let rows = client.query(stmt, &[&since, &limit]).await?;
+    Ok(rows.into_iter().map(CandleChange::from_row).collect())
+}
+
+/// The newest candle of every resolution of the market, as changes carrying their version.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_latest_candle_changes(
+    pool: &Pool,
+    market_name: &str,
+) -> anyhow::Result<Vec<CandleChange>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT DISTINCT ON (resolution)
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume",
+        version as "version"
+        from openbook.candles
+        where market_name = $1
+        and start_time > now() - interval '2 days'
+        ORDER BY resolution, start_time desc"#;
+
+    let rows = client.query(stmt, &[&market_name]).await?;
+    Ok(rows.into_iter().map(CandleChange::from_row).collect())
+}
+
+/// Fetches trades, read from the maker fill of each unless the filter asks for the taker's,
+/// that pass the filter.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_trades(
+    pool: &Pool,
+    market_address_string: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    filter: &TradeFilter,
+    limit: i64,
+    quote_decimals: u8,
+) -> anyhow::Result<Vec<Trade>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        block_datetime as "time",
+        price as "price",
+        size as "size",
+        bid as "bid",
+        signature as "signature",
+        maker as "maker",
+        native_fee_or_rebate as "native_fee_or_rebate",
+        fee as "fee",
+        referrer_rebate as "referrer_rebate"
+        from openbook.openbook_fill_events
+        where market = $1
+        and block_datetime >= $2::timestamptz
+        and block_datetime < $3::timestamptz
+        and maker = $7
+        and ($4::bool IS NULL OR bid = $4)
+        and size >= $5
+        ORDER BY block_datetime asc, seq_num asc
+        LIMIT $6"#;
+
+    let maker = !filter.taker;
+    let fill_bid = filter.side.map(|s| s.fill_bid(maker));
+    let rows = client
+        .query(
+            stmt,
+            &[
+                &market_address_string,
+                &start_time,
+                &end_time,
+                &fill_bid,
+                &filter.min_size,
+                &limit,
+                &maker,
+            ],
+        )
+        .await?;
+    rows.into_iter()
+        .map(|r| Trade::from_row(r, quote_decimals))
+        .collect()
+}
+
+/// Same filters as `fetch_trades`, aggregated per interval of `grouping`.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_trade_buckets(
+    pool: &Pool,
+    market_address_string: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    filter: &TradeFilter,
+    grouping: TradeGrouping,
+    limit: i64,
+) -> anyhow::Result<Vec<TradeBucket>> {
+    let client = get_client(pool).await?;
+
+    let stmt = format!(
+        r#"SELECT
+        date_trunc('{}', block_datetime) as "start_time",
+        count(*) as "trade_count",
+        sum(size) as "volume",
+        sum(price * size) as "quote_volume",
+        coalesce(sum(size) FILTER (WHERE bid = false), 0) as "buy_volume",
+        coalesce(sum(size) FILTER (WHERE bid = true), 0) as "sell_volume",
+        sum(price * size) / sum(size) as "vwap",
+        max(price) as "high",
+        min(price) as "low"
+        from openbook.openbook_fill_events
+        where market = $1
+        and block_datetime >= $2::timestamptz
+        and block_datetime < $3::timestamptz
+        and maker = true
+        and ($4::bool IS NULL OR bid = $4)
+        and size >= $5
+        GROUP BY 1
+        ORDER BY 1 asc
+        LIMIT $6"#,
+        grouping.date_trunc_field()
+    );
+
+    let maker_bid = filter.side.map(|s| s.maker_bid());
+    let rows = client
+        .query(
+            &stmt,
+            &[
+                &market_address_string,
+                &start_time,
+                &end_time,
+                &maker_bid,
+                &filter.min_size,
+                &limit,
+            ],
+        )
+        .await?;
+    Ok(rows.into_iter().map(TradeBucket::from_row).collect())
+}
+
+/// Maker fills of the range bucketed into `bins` price levels of equal width between the lowest and
+/// highest price. Only bins something traded in are returned, all of them when every fill has the
+/// same price.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_volume_profile(
+    pool: &Pool,
+    market_address_string: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    bins: i32,
+) -> anyhow::Result<Vec<PgVolumeBin>> {
+    let client = get_client(pool).await?;
+
+    // width_bucket puts the highest price into an overflow bin and fails on an empty range
+    let stmt = r#"WITH fills AS (
+        SELECT price, size, bid
+        from openbook.openbook_fill_events
+        where market = $1
+        and block_datetime >= $2::timestamptz
+        and block_datetime < $3::timestamptz
+        and maker = true
+    ),
+    bounds AS (
+        SELECT min(price) as low, max(price) as high from fills
+    )
+    SELECT
+        CASE WHEN high > low
+            THEN least(width_bucket(price, low, high, $4::int), $4::int)
+            ELSE 1
+        END as "bin",
+        count(*) as "trade_count",
+        sum(size) as "volume",
+        sum(price * size) as "quote_volume",
+        coalesce(sum(size) FILTER (WHERE bid = false), 0) as "buy_volume",
+        coalesce(sum(size) FILTER (WHERE bid = true), 0) as "sell_volume",
+        low as "low",
+        high as "high"
+        from fills, bounds
+        GROUP BY 1, low, high
+        ORDER BY 1 asc"#;
+
+    let rows = client
+        .query(
+            stmt,
+            &[&market_address_string, &start_time, &end_time, &bins],
+        )
+        .await?;
+    Ok(rows.into_iter().map(PgVolumeBin::from_row).collect())
+}
+
+/// `(market, seq_num, maker)` of the fills among the given market and sequence number pairs that
+/// are already stored.
+#[instrument(skip(pool, market_address_strings, seq_nums), level = "debug", err)]
+pub async fn fetch_known_fill_keys(
+    pool: &Pool,
+    market_address_strings: &Vec<&str>,
+    seq_nums: &Vec<i64>,
+) -> anyhow::Result<HashSet<(String, i64, bool)>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        market as "market",
+        seq_num as "seq_num",
+        maker as "maker"
+        from openbook.openbook_fill_events
+        where (market, seq_num) IN (SELECT * FROM unnest($1::text[], $2::bigint[]))"#;
+
+    let rows = client
+        .query(stmt, &[&market_address_strings, &seq_nums])
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2)))
+        .collect())
+}
+
+/// Slot of the newest fill of any of the markets in the last day.
+#[instrument(skip(pool, market_address_strings), level = "debug", err)]
+pub async fn fetch_latest_fill_slot(
+    pool: &Pool,
+    market_address_strings: &Vec<&str>,
+) -> anyhow::Result<Option<i64>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT max(slot) as "slot"
+        from openbook.openbook_fill_events
+        where market = ANY($1)
+        and block_datetime > now() - interval '1 day'"#;
+
+    let row = client.query_one(stmt, &[&market_address_strings]).await?;
+    Ok(row.get(0))
+}
+
+/// Newest fill slot of every market with fills in the last day.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_latest_fill_slots(pool: &Pool) -> anyhow::Result<Vec<(String, i64)>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        market as "market",
+        max(slot) as "slot"
+        from openbook.openbook_fill_events
+        where block_datetime > now() - interval '1 day'
+        GROUP BY market"#;
+
+    let rows = client.query(stmt, &[]).await?;
+
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// End time of the newest complete candle of every market and resolution, looking back far
+/// enough to cover a complete day candle.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_complete_candle_end_times(
+    pool: &Pool,
+) -> anyhow::Result<Vec<(String, String, DateTime<Utc>)>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        market_name as "market_name",
+        resolution as "resolution",
+        max(end_time) as "end_time"
+        from openbook.candles
+        where complete = true
+        and start_time > now() - interval '2 days'
+        GROUP BY market_name, resolution"#;
+
+    let rows = client.query(stmt, &[]).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2)))
+        .collect())
+}
+
+/// Highest candle version of every market with candles in the last day. Every market gets a new
+/// minute candle each batch, so this moves whenever any of its candles are written.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_candle_data_versions(pool: &Pool) -> anyhow::Result<Vec<(String, i64)>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        market_name as "market_name",
+        max(version) as "version"
+        from openbook.candles
+        where start_time > now() - interval '1 day'
+        GROUP BY market_name"#;
+
+    let rows = client.query(stmt, &[]).await?;
+
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// End time of the newest candle of every market with candles of the resolution in the last day.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_newest_candle_end_times(
+    pool: &Pool,
+    resolution: Resolution,
+) -> anyhow::Result<Vec<(String, DateTime<Utc>)>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        market_name as "market_name",
+        max(end_time) as "end_time"
+        from openbook.candles
+        where resolution = $1
+        and start_time > now() - interval '1 day'
+        GROUP BY market_name"#;
+
+    let rows = client.query(stmt, &[&resolution.to_string()]).await?;
+
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
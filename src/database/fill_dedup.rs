@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::{info, instrument};
+
+const FILL_KEY_INDEX: &str = "idx_fill_events_market_seq_num";
+
+/// Fills of the market in the window that repeat the `(market, seq_num, maker)` of an earlier
+/// one, identified by their primary key. The first by time, signature and instruction is kept.
+const DUPLICATE_FILLS: &str = r#"SELECT signature, instruction_num, seq_num, block_datetime
+        FROM (
+            SELECT
+            signature,
+            instruction_num,
+            seq_num,
+            block_datetime,
+            row_number() OVER (
+                PARTITION BY seq_num, maker
+                ORDER BY block_datetime, signature, instruction_num
+            ) as "n"
+            from openbook.openbook_fill_events
+            where market = $1
+            and block_datetime >= $2
+            and block_datetime < $3
+        ) r
+        WHERE n > 1"#;
+
+/// Counts, or with `apply` deletes, the market's fills in the window that duplicate an earlier
+/// fill of the window. Duplicates further apart than the window are left, and make building
+/// the unique index fail.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn dedup_fills(
+    pool: &Pool,
+    market_address: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    apply: bool,
+) -> anyhow::Result<u64> {
+    let client = pool.get().await?;
+
+    if apply {
+        let stmt = format!(
+            r#"DELETE FROM openbook.openbook_fill_events f
+            USING ({}) d
+            WHERE f.market = $1
+            AND f.block_datetime >= $2
+            AND f.block_datetime < $3
+            AND f.signature = d.signature
+            AND f.instruction_num = d.instruction_num
+            AND f.seq_num = d.seq_num
+            AND f.block_datetime = d.block_datetime"#,
+            DUPLICATE_FILLS
+        );
+        Ok(client
+            .execute(&stmt, &[&market_address, &start_time, &end_time])
+            .await?)
+    } else {
+        let stmt = format!("SELECT count(*) FROM ({}) d", DUPLICATE_FILLS);
+        let count: i64 = client
+            .query_one(&stmt, &[&market_address, &start_time, &end_time])
+            .await?
+            .get(0);
+        Ok(count as u64)
+    }
+}
+
+/// Builds the unique index on `(market, seq_num, maker)` that migration 16 leaves out for
+/// tables that already hold fills. Plain tables are indexed concurrently, so fills keep being
+/// written meanwhile, and hypertables one chunk at a time, since they can't be. An index left
+/// invalid by an interrupted build is rebuilt. Returns whether an index was built.
+pub async fn create_fill_key_index(pool: &Pool) -> anyhow::Result<bool> {
+    let client = pool.get().await?;
+
+    let valid: Option<bool> = client
+        .query_opt(
+            r#"SELECT x.indisvalid
+            FROM pg_index x
+            JOIN pg_class i ON i.oid = x.indexrelid
+            JOIN pg_namespace n ON n.oid = i.relnamespace
+            WHERE n.nspname = 'openbook' AND i.relname = $1"#,
+            &[&FILL_KEY_INDEX],
+        )
+        .await?
+        .map(|r| r.get(0));
+    let timescale = client
+        .query_one(
+            "SELECT to_regclass('timescaledb_information.hypertables') IS NOT NULL",
+            &[],
+        )
+        .await?
+        .get::<usize, bool>(0);
+    let hypertable = timescale
+        && client
+            .query_opt(
+                r#"SELECT 1 FROM timescaledb_information.hypertables
+                WHERE hypertable_schema = 'openbook' AND hypertable_name = 'openbook_fill_events'"#,
+                &[],
+            )
+            .await?
+            .is_some();
+
+    match valid {
+        Some(true) => return Ok(false),
+        Some(false) => {
+            info!("Dropping invalid index {}", FILL_KEY_INDEX);
+            let concurrently = if hypertable { "" } else { "CONCURRENTLY " };
+            client
+                .batch_execute(&format!(
+                    "DROP INDEX {}openbook.{}",
+                    concurrently, FILL_KEY_INDEX
+                ))
+                .await?;
+        }
+        None => {}
+    }
+
+    info!("Building index {}", FILL_KEY_INDEX);
+    let stmt = if hypertable {
+        // unique indexes on a hypertable have to include the time column
+        format!(
+            "CREATE UNIQUE INDEX {} ON openbook.openbook_fill_events USING btree (market, seq_num, maker, block_datetime) WITH (timescaledb.transaction_per_chunk)",
+            FILL_KEY_INDEX
+        )
+    } else {
+        format!(
+            "CREATE UNIQUE INDEX CONCURRENTLY {} ON openbook.openbook_fill_events USING btree (market, seq_num, maker)",
+            FILL_KEY_INDEX
+        )
+    };
+    client.batch_execute(&stmt).await?;
+    Ok(true)
+}
@@ -0,0 +1,99 @@
+use deadpool_postgres::Pool;
+use futures::pin_mut;
+use tokio_postgres::{binary_copy::BinaryCopyInWriter, types::Type};
+use tracing::instrument;
+
+use crate::structs::openbook::OpenBookFill;
+
+/// Loads fills with `COPY ... FROM STDIN BINARY` into a temporary table and moves the ones not
+/// stored yet into `openbook.openbook_fill_events`, since `COPY` itself can't skip duplicates.
+/// Returns the number of fills inserted.
+#[instrument(skip_all, fields(count = fills.len()), err)]
+pub async fn copy_fills(pool: &Pool, fills: &[OpenBookFill]) -> anyhow::Result<u64> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    transaction
+        .batch_execute(
+            r#"CREATE TEMP TABLE fill_import (
+                signature text NOT NULL,
+                slot bigint NOT NULL,
+                block_datetime timestamptz NOT NULL,
+                market text NOT NULL,
+                open_orders_owner text NOT NULL,
+                bid bool NOT NULL,
+                maker bool NOT NULL,
+                native_quantity_paid double precision NOT NULL,
+                native_quantity_received double precision NOT NULL,
+                native_fee_or_rebate double precision NOT NULL,
+                price double precision NOT NULL,
+                size double precision NOT NULL,
+                seq_num bigint NOT NULL,
+                instruction_num int NOT NULL,
+                fee double precision,
+                referrer_rebate double precision
+            ) ON COMMIT DROP"#,
+        )
+        .await?;
+
+    let sink = transaction
+        .copy_in("COPY fill_import FROM STDIN BINARY")
+        .await?;
+    let writer = BinaryCopyInWriter::new(
+        sink,
+        &[
+            Type::TEXT,
+            Type::INT8,
+            Type::TIMESTAMPTZ,
+            Type::TEXT,
+            Type::TEXT,
+            Type::BOOL,
+            Type::BOOL,
+            Type::FLOAT8,
+            Type::FLOAT8,
+            Type::FLOAT8,
+            Type::FLOAT8,
+            Type::FLOAT8,
+            Type::INT8,
+            Type::INT4,
+            Type::FLOAT8,
+            Type::FLOAT8,
+        ],
+    );
+    pin_mut!(writer);
+    for fill in fills.iter() {
+        writer
+            .as_mut()
+            .write(&[
+                &fill.signature,
+                &fill.slot,
+                &fill.block_datetime,
+                &fill.market,
+                &fill.open_orders_owner,
+                &fill.bid,
+                &fill.maker,
+                &fill.native_quantity_paid,
+                &fill.native_quantity_received,
+                &fill.native_fee_or_rebate,
+                &fill.price,
+                &fill.size,
+                &fill.seq_num,
+                &fill.instruction_num,
+                &fill.fee,
+                &fill.referrer_rebate,
+            ])
+            .await?;
+    }
+    writer.finish().await?;
+
+    let inserted = transaction
+        .execute(
+            r#"INSERT INTO openbook.openbook_fill_events (signature, slot, block_datetime, market, open_orders_owner, bid, maker, native_quantity_paid, native_quantity_received, native_fee_or_rebate, price, size, seq_num, instruction_num, fee, referrer_rebate)
+            SELECT signature, slot, block_datetime, market, open_orders_owner, bid, maker, native_quantity_paid, native_quantity_received, native_fee_or_rebate, price, size, seq_num, instruction_num, fee, referrer_rebate
+            FROM fill_import
+            ON CONFLICT DO NOTHING"#,
+            &[],
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok(inserted)
+}
@@ -1,63 +1,88 @@
-use std::{fs, time::Duration};
+use std::time::Duration;
 
 use deadpool_postgres::{
     ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime, SslMode, Timeouts,
 };
-use native_tls::{Certificate, Identity, TlsConnector};
-use postgres_native_tls::MakeTlsConnector;
+use tracing::{error, info, warn};
 
-use crate::utils::PgConfig;
+use crate::{
+    database::{
+        migrations::run_migrations,
+        minute_aggregate::setup_minute_aggregate,
+        partitions::setup_fill_partitions,
+        roles::{setup_roles, DbRole},
+        tls::PgTls,
+    },
+    utils::settings::PgConfig,
+};
 
+/// Connects as `PG_USER`, which owns the schema and runs setup.
 pub async fn connect_to_database() -> anyhow::Result<Pool> {
+    connect(PgConfig::from_env()?, Timeouts::default()).await
+}
+
+/// Connects as the given role when `PG_USE_ROLES` is set, otherwise as `PG_USER`.
+pub async fn connect_to_database_as(role: DbRole) -> anyhow::Result<Pool> {
     let mut pg_config = PgConfig::from_env()?;
+    if pg_config.pg_use_roles {
+        pg_config.pg.password = Some(role.password(&pg_config)?.to_string());
+        pg_config.pg.user = Some(role.name().to_string());
+    }
+    connect(pg_config, Timeouts::default()).await
+}
 
+/// Connects to `PG_READ_URL` as the given role, `None` when no read replica is configured.
+/// Connection attempts time out so queries fall back to the primary quickly when it goes down.
+pub async fn connect_to_read_replica_as(role: DbRole) -> anyhow::Result<Option<Pool>> {
+    let mut pg_config = PgConfig::from_env()?;
+    let url = match pg_config.pg_read_url.take().filter(|u| !u.is_empty()) {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+    if pg_config.pg_use_roles {
+        pg_config.pg.password = Some(role.password(&pg_config)?.to_string());
+        pg_config.pg.user = Some(role.name().to_string());
+    }
+    // these would override the ones in the url
+    pg_config.pg.host = None;
+    pg_config.pg.port = None;
+    pg_config.pg.dbname = None;
+    pg_config.pg.url = Some(url);
+    let timeouts = Timeouts {
+        wait: Some(REPLICA_TIMEOUT),
+        create: Some(REPLICA_TIMEOUT),
+        recycle: Some(REPLICA_TIMEOUT),
+    };
+    connect(pg_config, timeouts).await.map(Some)
+}
+
+const REPLICA_TIMEOUT: Duration = Duration::from_secs(2);
+
+async fn connect(mut pg_config: PgConfig, mut timeouts: Timeouts) -> anyhow::Result<Pool> {
+    if timeouts.wait.is_none() {
+        timeouts.wait = pg_config.pg_pool_wait_timeout_secs.map(Duration::from_secs);
+    }
     pg_config.pg.manager = Some(ManagerConfig {
         recycling_method: RecyclingMethod::Fast,
     });
     pg_config.pg.pool = Some(PoolConfig {
         max_size: pg_config.pg_max_pool_connections,
-        timeouts: Timeouts::default(),
+        timeouts,
     });
 
-    // openssl pkcs12 -export -in client.cer -inkey client-key.cer -out client.pks
-    // base64 -i ca.cer -o ca.cer.b64 && base64 -i client.pks -o client.pks.b64
-    // fly secrets set PG_CA_CERT=- < ./ca.cer.b64 -a APP-NAME
-    // fly secrets set PG_CLIENT_KEY=- < ./client.pks.b64 -a APP-NAME
-    let tls = if pg_config.pg_use_ssl {
+    let tls = PgTls::from_config(&pg_config)?;
+    if pg_config.pg_use_ssl {
         pg_config.pg.ssl_mode = Some(SslMode::Require);
-        let ca_cert = fs::read(pg_config.pg_ca_cert_path.expect("reading ca cert from env"))
-            .expect("reading ca cert from file");
-        let client_key = fs::read(
-            pg_config
-                .pg_client_key_path
-                .expect("reading client key from env"),
-        )
-        .expect("reading client key from file");
-        MakeTlsConnector::new(
-            TlsConnector::builder()
-                .add_root_certificate(Certificate::from_pem(&ca_cert)?)
-                // TODO: make this configurable
-                .identity(Identity::from_pkcs12(&client_key, "pass")?)
-                .danger_accept_invalid_certs(false)
-                .build()?,
-        )
-    } else {
-        MakeTlsConnector::new(
-            TlsConnector::builder()
-                .danger_accept_invalid_certs(true)
-                .build()
-                .unwrap(),
-        )
-    };
+    }
 
     let pool = pg_config
         .pg
         .create_pool(Some(Runtime::Tokio1), tls)
         .unwrap();
     match pool.get().await {
-        Ok(_) => println!("Database connected"),
+        Ok(_) => info!("Database connected"),
         Err(e) => {
-            println!("Failed to connect to database: {}, retrying", e.to_string());
+            warn!("Failed to connect to database: {}, retrying", e.to_string());
             tokio::time::sleep(Duration::from_millis(500)).await;
         }
     }
@@ -66,44 +91,183 @@ pub async fn connect_to_database() -> anyhow::Result<Pool> {
 }
 
 pub async fn setup_database(pool: &Pool) -> anyhow::Result<()> {
-    match create_candles_table(pool).await {
+    let pg_config = PgConfig::from_env()?;
+    if pg_config.pg_use_timescale && pg_config.pg_partition_fills {
+        anyhow::bail!("PG_PARTITION_FILLS can't be combined with PG_USE_TIMESCALE");
+    }
+    let mut result = match run_migrations(pool).await {
+        Ok(_) if pg_config.pg_use_timescale => setup_timescale(pool, &pg_config).await,
+        Ok(_) if pg_config.pg_partition_fills => setup_fill_partitions(pool, &pg_config).await,
+        r => r,
+    };
+    if result.is_ok() && pg_config.pg_use_roles {
+        result = setup_roles(pool, &pg_config).await;
+    }
+    match result {
         Ok(_) => {
-            println!("Successfully configured database");
+            info!("Successfully configured database");
             Ok(())
         }
         Err(e) => {
-            println!("Failed to configure database: {e}");
+            error!("Failed to configure database: {e}");
             Err(e)
         }
     }
 }
 
-pub async fn create_candles_table(pool: &Pool) -> anyhow::Result<()> {
-    let client = pool.get().await?;
+struct Hypertable {
+    table: &'static str,
+    time_column: &'static str,
+    segment_by: &'static str,
+}
+
+const HYPERTABLES: [Hypertable; 2] = [
+    Hypertable {
+        table: "openbook.candles",
+        time_column: "start_time",
+        segment_by: "market_name, resolution",
+    },
+    Hypertable {
+        table: "openbook.openbook_fill_events",
+        time_column: "block_datetime",
+        segment_by: "market",
+    },
+];
 
+/// Converts the candle and fill tables to hypertables and keeps their chunk interval and
+/// compression policy in line with the config. Safe to run on every startup.
+async fn setup_timescale(pool: &Pool, pg_config: &PgConfig) -> anyhow::Result<()> {
+    let client = pool.get().await?;
     client
-        .execute(
-            "CREATE TABLE IF NOT EXISTS openbook.candles (
-            id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
-            market_name text,
-            start_time timestamptz,
-            end_time timestamptz,
-            resolution text,
-            open double precision,
-            close double precision,
-            high double precision,
-            low double precision,
-            volume double precision,
-            complete bool
-        )",
-            &[],
-        )
+        .batch_execute("CREATE EXTENSION IF NOT EXISTS timescaledb")
         .await?;
 
-    client.execute(
-        "CREATE UNIQUE INDEX IF NOT EXISTS idx_market_time_resolution ON openbook.candles USING btree (market_name, start_time, resolution);",
-        &[]
-    ).await?;
+    let chunk_interval = format!(
+        "INTERVAL '{} days'",
+        pg_config.pg_timescale_chunk_interval_days
+    );
+    let compress_after = format!(
+        "INTERVAL '{} days'",
+        pg_config.pg_timescale_compress_after_days
+    );
 
+    for h in HYPERTABLES.iter() {
+        let (schema, name) = h.table.split_once('.').unwrap();
+        let exists = client
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM pg_tables WHERE schemaname = $1 AND tablename = $2)",
+                &[&schema, &name],
+            )
+            .await?
+            .get::<usize, bool>(0);
+        if !exists {
+            continue;
+        }
+
+        let hypertable = client
+            .query_opt(
+                r#"SELECT compression_enabled
+                FROM timescaledb_information.hypertables
+                WHERE hypertable_schema = $1 AND hypertable_name = $2"#,
+                &[&schema, &name],
+            )
+            .await?;
+
+        let compression_enabled = match hypertable {
+            Some(row) => {
+                client
+                    .batch_execute(&format!(
+                        "SELECT set_chunk_time_interval('{}', {})",
+                        h.table, chunk_interval
+                    ))
+                    .await?;
+                row.get::<usize, bool>(0)
+            }
+            None => {
+                // unique constraints on a hypertable have to include the time column
+                let primary_key = client
+                    .query_opt(
+                        r#"SELECT c.conname::text, array_agg(a.attname::text ORDER BY a.attnum)
+                        FROM pg_constraint c
+                        JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = ANY(c.conkey)
+                        WHERE c.conrelid = $1::text::regclass AND c.contype = 'p'
+                        GROUP BY c.conname"#,
+                        &[&h.table],
+                    )
+                    .await?;
+                if let Some(row) = primary_key {
+                    let constraint: String = row.get(0);
+                    let mut columns: Vec<String> = row.get(1);
+                    if !columns.iter().any(|c| c == h.time_column) {
+                        columns.push(h.time_column.to_string());
+                        client
+                            .batch_execute(&format!(
+                                "ALTER TABLE {} DROP CONSTRAINT {}, ADD PRIMARY KEY ({})",
+                                h.table,
+                                constraint,
+                                columns.join(", ")
+                            ))
+                            .await?;
+                    }
+                }
+                let unique_indexes = client
+                    .query(
+                        r#"SELECT i.relname::text, array_agg(a.attname::text ORDER BY k.ord)
+                        FROM pg_index x
+                        JOIN pg_class i ON i.oid = x.indexrelid
+                        CROSS JOIN LATERAL unnest(x.indkey::int2[]) WITH ORDINALITY k(attnum, ord)
+                        JOIN pg_attribute a ON a.attrelid = x.indrelid AND a.attnum = k.attnum
+                        WHERE x.indrelid = $1::text::regclass AND x.indisunique AND NOT x.indisprimary
+                        GROUP BY i.relname"#,
+                        &[&h.table],
+                    )
+                    .await?;
+                for row in unique_indexes {
+                    let index: String = row.get(0);
+                    let mut columns: Vec<String> = row.get(1);
+                    if !columns.iter().any(|c| c == h.time_column) {
+                        columns.push(h.time_column.to_string());
+                        client
+                            .batch_execute(&format!(
+                                "DROP INDEX {0}.{1}; CREATE UNIQUE INDEX {1} ON {2} USING btree ({3})",
+                                schema,
+                                index,
+                                h.table,
+                                columns.join(", ")
+                            ))
+                            .await?;
+                    }
+                }
+
+                info!("Converting {} to a hypertable", h.table);
+                client
+                    .batch_execute(&format!(
+                        "SELECT create_hypertable('{}', '{}', chunk_time_interval => {}, migrate_data => true)",
+                        h.table, h.time_column, chunk_interval
+                    ))
+                    .await?;
+                false
+            }
+        };
+
+        if !compression_enabled {
+            client
+                .batch_execute(&format!(
+                    "ALTER TABLE {} SET (timescaledb.compress, timescaledb.compress_segmentby = '{}', timescaledb.compress_orderby = '{} DESC')",
+                    h.table, h.segment_by, h.time_column
+                ))
+                .await?;
+        }
+        client
+            .batch_execute(&format!(
+                "SELECT remove_compression_policy('{0}', if_exists => true);
+                SELECT add_compression_policy('{0}', {1});",
+                h.table, compress_after
+            ))
+            .await?;
+    }
+    if pg_config.pg_timescale_minute_aggregate {
+        setup_minute_aggregate(&client).await?;
+    }
     Ok(())
 }
@@ -1,10 +1,23 @@
-use crate::structs::candle::Candle;
+use chrono::{DateTime, Utc};
+use tokio_postgres::types::ToSql;
 
+use crate::structs::{
+    candle::Candle,
+    divergence::CandleDivergence,
+    openbook::OpenBookFill,
+    oracle::OraclePrice,
+    orderbook::{DepthSnapshot, DepthStat},
+    spread::SpreadSample,
+    trader::{LeaderboardPeriod, Trader, TraderRole, VolumeType},
+    venue::Venue,
+};
+
+/// Candles built from fills, their trade count is also the number of fills behind them.
 pub fn build_candles_upsert_statement(candles: &Vec<Candle>) -> String {
-    let mut stmt = String::from("INSERT INTO openbook.candles (market_name, start_time, end_time, resolution, open, close, high, low, volume, complete) VALUES");
+    let mut stmt = String::from("INSERT INTO openbook.candles (market_name, start_time, end_time, resolution, open, close, high, low, volume, complete, vwap, trade_count, quote_volume, priced_volume, priced_quote_volume, source_fill_count) VALUES");
     for (idx, candle) in candles.iter().enumerate() {
         let val_str = format!(
-            "(\'{}\', \'{}\', \'{}\', \'{}\', {}, {}, {}, {}, {}, {})",
+            "(\'{}\', \'{}\', \'{}\', \'{}\', {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
             candle.market_name,
             candle.start_time.to_rfc3339(),
             candle.end_time.to_rfc3339(),
@@ -15,6 +28,12 @@ pub fn build_candles_upsert_statement(candles: &Vec<Candle>) -> String {
             candle.low,
             candle.volume,
             candle.complete,
+            candle.vwap,
+            candle.trade_count,
+            candle.quote_volume,
+            candle.priced_volume,
+            candle.priced_quote_volume,
+            candle.trade_count,
         );
 
         if idx == 0 {
@@ -31,9 +50,402 @@ pub fn build_candles_upsert_statement(candles: &Vec<Candle>) -> String {
     high=excluded.high, 
     low=excluded.low,
     volume=excluded.volume,
-    complete=excluded.complete
+    complete=excluded.complete,
+    vwap=excluded.vwap,
+    trade_count=excluded.trade_count,
+    quote_volume=excluded.quote_volume,
+    priced_volume=excluded.priced_volume,
+    priced_quote_volume=excluded.priced_quote_volume,
+    source=excluded.source,
+    source_fill_count=excluded.source_fill_count
+    WHERE (candles.open, candles.close, candles.high, candles.low, candles.volume, candles.complete, candles.vwap, candles.trade_count, candles.quote_volume, candles.priced_volume, candles.priced_quote_volume, candles.source)
+    IS DISTINCT FROM (excluded.open, excluded.close, excluded.high, excluded.low, excluded.volume, excluded.complete, excluded.vwap, excluded.trade_count, excluded.quote_volume, excluded.priced_volume, excluded.priced_quote_volume, excluded.source)
     ";
 
     stmt = format!("{} {}", stmt, handle_conflict);
     stmt
 }
+
+/// The candle upsert, returning for every row it inserted or changed its market, its resolution,
+/// whether it was an insert and whether the row it replaced was already complete. Rows left as
+/// they were are not returned. The join reads the table as it was before the upsert. Takes one
+/// array per column (see `CandleColumns`) so the statement text never changes and can be prepared
+/// once per connection. The trade counts double as the number of fills behind the candles.
+pub const CANDLES_UPSERT_RETURNING_CHANGES: &str = r#"WITH upserted AS (
+        INSERT INTO openbook.candles (market_name, start_time, end_time, resolution, open, close, high, low, volume, complete, vwap, trade_count, quote_volume, priced_volume, priced_quote_volume, source_fill_count)
+        SELECT * FROM unnest(
+            $1::text[], $2::timestamptz[], $3::timestamptz[], $4::text[], $5::float8[], $6::float8[],
+            $7::float8[], $8::float8[], $9::float8[], $10::bool[], $11::float8[], $12::int8[], $13::float8[],
+            $14::float8[], $15::float8[], $12::int8[]
+        )
+        ON CONFLICT (market_name, start_time, resolution)
+        DO UPDATE SET
+        open=excluded.open,
+        close=excluded.close,
+        high=excluded.high,
+        low=excluded.low,
+        volume=excluded.volume,
+        complete=excluded.complete,
+        vwap=excluded.vwap,
+        trade_count=excluded.trade_count,
+        quote_volume=excluded.quote_volume,
+        priced_volume=excluded.priced_volume,
+        priced_quote_volume=excluded.priced_quote_volume,
+        source=excluded.source,
+        source_fill_count=excluded.source_fill_count
+        WHERE (candles.open, candles.close, candles.high, candles.low, candles.volume, candles.complete, candles.vwap, candles.trade_count, candles.quote_volume, candles.priced_volume, candles.priced_quote_volume, candles.source)
+        IS DISTINCT FROM (excluded.open, excluded.close, excluded.high, excluded.low, excluded.volume, excluded.complete, excluded.vwap, excluded.trade_count, excluded.quote_volume, excluded.priced_volume, excluded.priced_quote_volume, excluded.source)
+        RETURNING market_name, start_time, resolution, xmax = 0 AS inserted
+    )
+    SELECT
+        upserted.market_name as "market_name",
+        upserted.resolution as "resolution",
+        upserted.inserted as "inserted",
+        coalesce(previous.complete, false) as "was_complete"
+    FROM upserted
+    LEFT JOIN openbook.candles previous
+    ON previous.market_name = upserted.market_name
+    AND previous.start_time = upserted.start_time
+    AND previous.resolution = upserted.resolution"#;
+
+/// Candles split into one array per column, the parameters of `CANDLES_UPSERT_RETURNING_CHANGES`
+#[derive(Default)]
+pub struct CandleColumns {
+    market_name: Vec<String>,
+    start_time: Vec<DateTime<Utc>>,
+    end_time: Vec<DateTime<Utc>>,
+    resolution: Vec<String>,
+    open: Vec<f64>,
+    close: Vec<f64>,
+    high: Vec<f64>,
+    low: Vec<f64>,
+    volume: Vec<f64>,
+    complete: Vec<bool>,
+    vwap: Vec<f64>,
+    trade_count: Vec<i64>,
+    quote_volume: Vec<f64>,
+    priced_volume: Vec<f64>,
+    priced_quote_volume: Vec<f64>,
+}
+
+impl CandleColumns {
+    pub fn from_candles(candles: &[Candle]) -> Self {
+        let mut columns = CandleColumns::default();
+        for candle in candles.iter() {
+            columns.market_name.push(candle.market_name.clone());
+            columns.start_time.push(candle.start_time);
+            columns.end_time.push(candle.end_time);
+            columns.resolution.push(candle.resolution.clone());
+            columns.open.push(candle.open);
+            columns.close.push(candle.close);
+            columns.high.push(candle.high);
+            columns.low.push(candle.low);
+            columns.volume.push(candle.volume);
+            columns.complete.push(candle.complete);
+            columns.vwap.push(candle.vwap);
+            columns.trade_count.push(candle.trade_count);
+            columns.quote_volume.push(candle.quote_volume);
+            columns.priced_volume.push(candle.priced_volume);
+            columns.priced_quote_volume.push(candle.priced_quote_volume);
+        }
+        columns
+    }
+
+    pub fn params(&self) -> [&(dyn ToSql + Sync); 15] {
+        [
+            &self.market_name,
+            &self.start_time,
+            &self.end_time,
+            &self.resolution,
+            &self.open,
+            &self.close,
+            &self.high,
+            &self.low,
+            &self.volume,
+            &self.complete,
+            &self.vwap,
+            &self.trade_count,
+            &self.quote_volume,
+            &self.priced_volume,
+            &self.priced_quote_volume,
+        ]
+    }
+}
+
+/// Inserts candles from an external source tagged with `source`. Candles that already exist,
+/// whether built from fills or imported before, are left alone.
+pub fn build_imported_candles_insert_statement(candles: &Vec<Candle>, source: &str) -> String {
+    let mut stmt = String::from("INSERT INTO openbook.candles (market_name, start_time, end_time, resolution, open, close, high, low, volume, complete, vwap, trade_count, quote_volume, priced_volume, priced_quote_volume, source) VALUES");
+    for (idx, candle) in candles.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', \'{}\', \'{}\', \'{}\', {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, \'{}\')",
+            candle.market_name,
+            candle.start_time.to_rfc3339(),
+            candle.end_time.to_rfc3339(),
+            candle.resolution,
+            candle.open,
+            candle.close,
+            candle.high,
+            candle.low,
+            candle.volume,
+            candle.complete,
+            candle.vwap,
+            candle.trade_count,
+            candle.quote_volume,
+            candle.priced_volume,
+            candle.priced_quote_volume,
+            source,
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+
+    stmt = format!(
+        "{} ON CONFLICT (market_name, start_time, resolution) DO NOTHING",
+        stmt
+    );
+    stmt
+}
+
+pub fn build_leaderboard_insert_statement(
+    market_address: &str,
+    period: LeaderboardPeriod,
+    period_start: DateTime<Utc>,
+    volume_type: VolumeType,
+    role: TraderRole,
+    traders: &[Trader],
+) -> String {
+    let mut stmt = String::from("INSERT INTO openbook.trader_leaderboard (market, period, period_start, volume_type, role, rank, open_orders_owner, volume, maker_volume, taker_volume, updated_at) VALUES");
+    for (idx, trader) in traders.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', \'{}\', \'{}\', \'{}\', \'{}\', {}, \'{}\', {}, {}, {}, now())",
+            market_address,
+            period,
+            period_start.to_rfc3339(),
+            volume_type,
+            role,
+            idx + 1,
+            trader.pubkey,
+            trader.volume,
+            trader.maker_volume,
+            trader.taker_volume,
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+    stmt
+}
+
+/// Upserts divergences given one array per column, see `DivergenceColumns`
+pub const DIVERGENCE_UPSERT: &str = r#"INSERT INTO openbook.candle_divergence (market_name, resolution, provider, start_time, close, provider_close, volume, provider_volume, close_delta_pct, volume_delta_pct, recorded_at)
+    SELECT *, now() FROM unnest(
+        $1::text[], $2::text[], $3::text[], $4::timestamptz[], $5::float8[], $6::float8[],
+        $7::float8[], $8::float8[], $9::float8[], $10::float8[]
+    )
+    ON CONFLICT (market_name, resolution, provider, start_time)
+    DO UPDATE SET
+    close=excluded.close,
+    provider_close=excluded.provider_close,
+    volume=excluded.volume,
+    provider_volume=excluded.provider_volume,
+    close_delta_pct=excluded.close_delta_pct,
+    volume_delta_pct=excluded.volume_delta_pct,
+    recorded_at=excluded.recorded_at"#;
+
+/// Divergences split into one array per column, the parameters of `DIVERGENCE_UPSERT`
+#[derive(Default)]
+pub struct DivergenceColumns {
+    market_name: Vec<String>,
+    resolution: Vec<String>,
+    provider: Vec<String>,
+    start_time: Vec<DateTime<Utc>>,
+    close: Vec<f64>,
+    provider_close: Vec<f64>,
+    volume: Vec<f64>,
+    provider_volume: Vec<f64>,
+    close_delta_pct: Vec<f64>,
+    volume_delta_pct: Vec<f64>,
+}
+
+impl DivergenceColumns {
+    pub fn from_divergences(divergences: &[CandleDivergence]) -> Self {
+        let mut columns = DivergenceColumns::default();
+        for d in divergences.iter() {
+            columns.market_name.push(d.market_name.clone());
+            columns.resolution.push(d.resolution.clone());
+            columns.provider.push(d.provider.clone());
+            columns.start_time.push(d.start_time);
+            columns.close.push(d.close);
+            columns.provider_close.push(d.provider_close);
+            columns.volume.push(d.volume);
+            columns.provider_volume.push(d.provider_volume);
+            columns.close_delta_pct.push(d.close_delta_pct);
+            columns.volume_delta_pct.push(d.volume_delta_pct);
+        }
+        columns
+    }
+
+    pub fn params(&self) -> [&(dyn ToSql + Sync); 10] {
+        [
+            &self.market_name,
+            &self.resolution,
+            &self.provider,
+            &self.start_time,
+            &self.close,
+            &self.provider_close,
+            &self.volume,
+            &self.provider_volume,
+            &self.close_delta_pct,
+            &self.volume_delta_pct,
+        ]
+    }
+}
+
+pub fn build_depth_stats_insert_statement(stats: &Vec<DepthStat>) -> String {
+    let mut stmt = String::from("INSERT INTO openbook.market_depth_stats (market_name, time, mid_price, bid_depth_usd, ask_depth_usd) VALUES");
+    for (idx, stat) in stats.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', \'{}\', {}, {}, {})",
+            stat.market_name,
+            stat.time.to_rfc3339(),
+            stat.mid_price,
+            stat.bid_depth_usd,
+            stat.ask_depth_usd,
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+    stmt
+}
+
+pub fn build_depth_snapshots_insert_statement(snapshots: &Vec<DepthSnapshot>) -> String {
+    let mut stmt = String::from("INSERT INTO openbook.depth_snapshots (market_name, time, best_bid, best_ask, mid_price, bid_depth_1pct, ask_depth_1pct, bid_depth_2pct, ask_depth_2pct, bid_depth_5pct, ask_depth_5pct) VALUES");
+    let price = |p: Option<f64>| p.map_or("NULL".to_string(), |p| p.to_string());
+    for (idx, snapshot) in snapshots.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', \'{}\', {}, {}, {}, {}, {}, {}, {}, {}, {})",
+            snapshot.market_name,
+            snapshot.time.to_rfc3339(),
+            price(snapshot.best_bid),
+            price(snapshot.best_ask),
+            snapshot.mid_price,
+            snapshot.bid_depth_1pct,
+            snapshot.ask_depth_1pct,
+            snapshot.bid_depth_2pct,
+            snapshot.ask_depth_2pct,
+            snapshot.bid_depth_5pct,
+            snapshot.ask_depth_5pct,
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+    stmt = format!("{} ON CONFLICT (market_name, time) DO NOTHING", stmt);
+    stmt
+}
+
+pub fn build_spread_samples_insert_statement(samples: &Vec<SpreadSample>) -> String {
+    let mut stmt = String::from("INSERT INTO openbook.spread_samples (market_name, time, best_bid, best_ask, mid_price) VALUES");
+    for (idx, sample) in samples.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', \'{}\', {}, {}, {})",
+            sample.market_name,
+            sample.time.to_rfc3339(),
+            sample.best_bid,
+            sample.best_ask,
+            sample.mid_price,
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+    stmt = format!("{} ON CONFLICT (market_name, time) DO NOTHING", stmt);
+    stmt
+}
+
+pub fn build_oracle_prices_insert_statement(prices: &Vec<OraclePrice>) -> String {
+    let mut stmt = String::from(
+        "INSERT INTO openbook.oracle_prices (symbol, provider, time, price, confidence) VALUES",
+    );
+    for (idx, price) in prices.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', \'{}\', \'{}\', {}, {})",
+            price.symbol,
+            price.provider,
+            price.time.to_rfc3339(),
+            price.price,
+            price
+                .confidence
+                .map_or("NULL".to_string(), |c| c.to_string()),
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+
+    let handle_conflict = "ON CONFLICT (symbol, provider, time) DO NOTHING";
+
+    stmt = format!("{} {}", stmt, handle_conflict);
+    stmt
+}
+
+pub fn build_fills_insert_statement(fills: &Vec<OpenBookFill>, venue: Venue) -> String {
+    let mut stmt = String::from("INSERT INTO openbook.openbook_fill_events (signature, slot, block_datetime, market, open_orders_owner, bid, maker, native_quantity_paid, native_quantity_received, native_fee_or_rebate, price, size, seq_num, instruction_num, fee, referrer_rebate, venue) VALUES");
+    for (idx, fill) in fills.iter().enumerate() {
+        let val_str = format!(
+            "(\'{}\', {}, \'{}\', \'{}\', \'{}\', {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, \'{}\')",
+            fill.signature,
+            fill.slot,
+            fill.block_datetime.to_rfc3339(),
+            fill.market,
+            fill.open_orders_owner,
+            fill.bid,
+            fill.maker,
+            fill.native_quantity_paid,
+            fill.native_quantity_received,
+            fill.native_fee_or_rebate,
+            fill.price,
+            fill.size,
+            fill.seq_num,
+            fill.instruction_num,
+            sql_option(fill.fee),
+            sql_option(fill.referrer_rebate),
+            venue,
+        );
+
+        if idx == 0 {
+            stmt = format!("{} {}", &stmt, val_str);
+        } else {
+            stmt = format!("{}, {}", &stmt, val_str);
+        }
+    }
+
+    // replayed messages hit the unique key and are dropped
+    stmt = format!("{} ON CONFLICT DO NOTHING", stmt);
+    stmt
+}
+
+fn sql_option<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or("NULL".to_string(), |v| v.to_string())
+}
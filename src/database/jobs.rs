@@ -0,0 +1,195 @@
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::structs::job::{Job, JobStatus, NewJob};
+
+const JOB_COLUMNS: &str = "id, kind, status, market_name, resolutions, start_time, end_time, \
+    candles_written, error, created_at, started_at, finished_at, priority, attempts, \
+    max_attempts, run_after";
+
+/// A running job whose worker sent no heartbeat for this long is taken to be abandoned
+const JOB_STALE_AFTER: &str = "2 minutes";
+
+pub async fn insert_job(pool: &Pool, job: &NewJob) -> anyhow::Result<Job> {
+    let client = pool.get().await?;
+
+    let stmt = format!(
+        r#"INSERT INTO openbook.jobs (kind, market_name, resolutions, start_time, end_time, priority, max_attempts)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {}"#,
+        JOB_COLUMNS
+    );
+
+    let row = client
+        .query_one(
+            &stmt,
+            &[
+                &job.kind,
+                &job.market_name,
+                &job.resolutions,
+                &job.start_time,
+                &job.end_time,
+                &job.priority,
+                &job.max_attempts,
+            ],
+        )
+        .await?;
+    Ok(Job::from_row(row))
+}
+
+/// Queues the job unless one of the same kind and market is already queued or running, for
+/// jobs scheduled by several replicas. Returns the queued job, `None` when one was pending.
+pub async fn insert_job_unless_pending(pool: &Pool, job: &NewJob) -> anyhow::Result<Option<Job>> {
+    let client = pool.get().await?;
+
+    let stmt = format!(
+        r#"INSERT INTO openbook.jobs (kind, market_name, resolutions, start_time, end_time, priority, max_attempts)
+        SELECT $1, $2, $3, $4, $5, $6, $7
+        WHERE NOT EXISTS (
+            SELECT 1 FROM openbook.jobs
+            WHERE kind = $1
+            AND market_name IS NOT DISTINCT FROM $2
+            AND status IN ('queued', 'running')
+        )
+        RETURNING {}"#,
+        JOB_COLUMNS
+    );
+
+    let row = client
+        .query_opt(
+            &stmt,
+            &[
+                &job.kind,
+                &job.market_name,
+                &job.resolutions,
+                &job.start_time,
+                &job.end_time,
+                &job.priority,
+                &job.max_attempts,
+            ],
+        )
+        .await?;
+    Ok(row.map(Job::from_row))
+}
+
+pub async fn fetch_job(pool: &Pool, id: i64) -> anyhow::Result<Option<Job>> {
+    let client = pool.get().await?;
+
+    let stmt = format!("SELECT {} FROM openbook.jobs WHERE id = $1", JOB_COLUMNS);
+
+    let row = client.query_opt(&stmt, &[&id]).await?;
+    Ok(row.map(Job::from_row))
+}
+
+/// The newest jobs, optionally only those with `status` or of `kind`.
+pub async fn fetch_jobs(
+    pool: &Pool,
+    status: Option<JobStatus>,
+    kind: Option<&str>,
+    limit: i64,
+) -> anyhow::Result<Vec<Job>> {
+    let client = pool.get().await?;
+
+    let stmt = format!(
+        r#"SELECT {} FROM openbook.jobs
+        WHERE ($1::text IS NULL OR status = $1)
+        AND ($2::text IS NULL OR kind = $2)
+        ORDER BY id desc
+        LIMIT $3"#,
+        JOB_COLUMNS
+    );
+
+    let status = status.map(|s| s.to_string());
+    let rows = client.query(&stmt, &[&status, &kind, &limit]).await?;
+    Ok(rows.into_iter().map(Job::from_row).collect())
+}
+
+/// Marks the next job due as running and returns it: the queued or abandoned job with the highest
+/// priority, oldest first. Abandoned jobs without attempts left fail instead. Workers claiming at
+/// the same time each get a different job.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn claim_next_job(pool: &Pool) -> anyhow::Result<Option<Job>> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    transaction
+        .execute(
+            &format!(
+                r#"UPDATE openbook.jobs
+                SET status = 'failed', error = 'abandoned by its worker', finished_at = now()
+                WHERE status = 'running'
+                AND heartbeat_at < now() - interval '{}'
+                AND attempts >= max_attempts"#,
+                JOB_STALE_AFTER
+            ),
+            &[],
+        )
+        .await?;
+    let stmt = format!(
+        r#"UPDATE openbook.jobs
+        SET status = 'running', started_at = now(), heartbeat_at = now(), attempts = attempts + 1
+        WHERE id = (
+            SELECT id FROM openbook.jobs
+            WHERE (status = 'queued' AND run_after <= now())
+            OR (status = 'running' AND heartbeat_at < now() - interval '{}')
+            ORDER BY priority desc, id
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {}"#,
+        JOB_STALE_AFTER, JOB_COLUMNS
+    );
+    let row = transaction.query_opt(&stmt, &[]).await?;
+    transaction.commit().await?;
+    Ok(row.map(Job::from_row))
+}
+
+/// Tells other workers the job is still being worked on.
+pub async fn record_job_heartbeat(pool: &Pool, id: i64) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE openbook.jobs SET heartbeat_at = now() WHERE id = $1 AND status = 'running'",
+            &[&id],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Marks the job done with the number of candles it wrote, if it counts them. A failed job is
+/// queued again after `retry_delay_secs` while it has attempts left, and failed otherwise.
+pub async fn finish_job(
+    pool: &Pool,
+    job: &Job,
+    result: &anyhow::Result<Option<u64>>,
+    retry_delay_secs: i64,
+) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let (status, candles_written, error) = match result {
+        Ok(count) => (JobStatus::Done, count.map(|c| c as i64), None),
+        Err(e) if job.attempts < job.max_attempts => {
+            (JobStatus::Queued, None, Some(format!("{:#}", e)))
+        }
+        Err(e) => (JobStatus::Failed, None, Some(format!("{:#}", e))),
+    };
+    client
+        .execute(
+            r#"UPDATE openbook.jobs
+            SET status = $2,
+            candles_written = $3,
+            error = $4,
+            run_after = now() + make_interval(secs => $5),
+            finished_at = CASE WHEN $2 = 'queued' THEN NULL ELSE now() END
+            WHERE id = $1"#,
+            &[
+                &job.id,
+                &status.to_string(),
+                &candles_written,
+                &error,
+                &(retry_delay_secs as f64),
+            ],
+        )
+        .await?;
+    Ok(())
+}
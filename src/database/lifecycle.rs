@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::{database::fetch::fetch_earliest_fill, structs::market_lifecycle::MarketLifecycle};
+
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_market_lifecycles(pool: &Pool) -> anyhow::Result<Vec<MarketLifecycle>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        market as "market",
+        first_fill_at as "first_fill_at",
+        last_fill_at as "last_fill_at",
+        candles_through as "candles_through"
+        from openbook.market_lifecycle"#;
+
+    let rows = client.query(stmt, &[]).await?;
+    Ok(rows.into_iter().map(MarketLifecycle::from_row).collect())
+}
+
+/// Time of the market's first fill. Falls back to scanning the fills table the first time a
+/// market is seen and records the result, so that scan happens once per market.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_first_fill_time(
+    pool: &Pool,
+    market_address: &str,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT first_fill_at as "first_fill_at"
+        from openbook.market_lifecycle
+        where market = $1"#;
+
+    let recorded: Option<DateTime<Utc>> = client
+        .query_opt(stmt, &[&market_address])
+        .await?
+        .and_then(|r| r.get(0));
+    if recorded.is_some() {
+        return Ok(recorded);
+    }
+
+    match fetch_earliest_fill(pool, market_address).await? {
+        Some(fill) => {
+            record_fills_seen(pool, market_address, fill.time, fill.time).await?;
+            Ok(Some(fill.time))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Widens the market's first and last seen times to cover fills between `first` and `last`.
+pub async fn record_fills_seen(
+    pool: &Pool,
+    market_address: &str,
+    first: DateTime<Utc>,
+    last: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let stmt = r#"INSERT INTO openbook.market_lifecycle (market, first_fill_at, last_fill_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (market) DO UPDATE SET
+        first_fill_at = least(openbook.market_lifecycle.first_fill_at, excluded.first_fill_at),
+        last_fill_at = greatest(openbook.market_lifecycle.last_fill_at, excluded.last_fill_at),
+        updated_at = now()"#;
+
+    client
+        .execute(stmt, &[&market_address, &first, &last])
+        .await?;
+    Ok(())
+}
+
+/// Records that the market's minute candles are complete up to `through`. Only ever moves forward.
+pub async fn record_candles_through(
+    pool: &Pool,
+    market_address: &str,
+    through: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let stmt = r#"INSERT INTO openbook.market_lifecycle (market, candles_through)
+        VALUES ($1, $2)
+        ON CONFLICT (market) DO UPDATE SET
+        candles_through = greatest(openbook.market_lifecycle.candles_through, excluded.candles_through),
+        updated_at = now()"#;
+
+    client.execute(stmt, &[&market_address, &through]).await?;
+    Ok(())
+}
+
+/// Counts a failed batch of the market.
+pub async fn record_batch_error(pool: &Pool, market_address: &str) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let stmt = r#"INSERT INTO openbook.market_lifecycle (market, batch_errors, last_batch_error_at)
+        VALUES ($1, 1, now())
+        ON CONFLICT (market) DO UPDATE SET
+        batch_errors = openbook.market_lifecycle.batch_errors + 1,
+        last_batch_error_at = now(),
+        updated_at = now()"#;
+
+    client.execute(stmt, &[&market_address]).await?;
+    Ok(())
+}
+
+/// Batch errors and the time of the last one per market address.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_batch_errors(
+    pool: &Pool,
+) -> anyhow::Result<Vec<(String, i64, Option<DateTime<Utc>>)>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT
+        market as "market",
+        batch_errors as "batch_errors",
+        last_batch_error_at as "last_batch_error_at"
+        from openbook.market_lifecycle"#;
+
+    let rows = client.query(stmt, &[]).await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2)))
+        .collect())
+}
@@ -0,0 +1,234 @@
+use deadpool_postgres::{Object, Pool};
+use tracing::info;
+
+/// A schema change, applied at most once per database and recorded in `openbook.schema_migrations`.
+/// Migrations are append-only: never edit one that has shipped, add a new one instead.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_candles",
+        sql: include_str!("migrations/0001_create_candles.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "candle_vwap_trade_count_quote_volume",
+        sql: include_str!("migrations/0002_candle_vwap_trade_count_quote_volume.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "create_trader_leaderboard",
+        sql: include_str!("migrations/0003_create_trader_leaderboard.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "create_candle_divergence",
+        sql: include_str!("migrations/0004_create_candle_divergence.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "create_market_depth_stats",
+        sql: include_str!("migrations/0005_create_market_depth_stats.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "fill_events_indexes",
+        sql: include_str!("migrations/0006_fill_events_indexes.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "create_fill_events",
+        sql: include_str!("migrations/0007_create_fill_events.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "create_fill_retention",
+        sql: include_str!("migrations/0008_create_fill_retention.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "candle_versions",
+        sql: include_str!("migrations/0009_candle_versions.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "create_worker_replicas",
+        sql: include_str!("migrations/0010_create_worker_replicas.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "create_api_keys",
+        sql: include_str!("migrations/0011_create_api_keys.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "api_key_usage",
+        sql: include_str!("migrations/0012_api_key_usage.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "create_market_lifecycle",
+        sql: include_str!("migrations/0013_create_market_lifecycle.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "create_worker_checkpoints",
+        sql: include_str!("migrations/0014_create_worker_checkpoints.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "fill_fees",
+        sql: include_str!("migrations/0015_fill_fees.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "fill_events_dedup",
+        sql: include_str!("migrations/0016_fill_events_dedup.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "candle_source",
+        sql: include_str!("migrations/0017_candle_source.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "api_key_realtime",
+        sql: include_str!("migrations/0018_api_key_realtime.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "market_batch_errors",
+        sql: include_str!("migrations/0019_market_batch_errors.sql"),
+    },
+    Migration {
+        version: 20,
+        name: "create_oracle_prices",
+        sql: include_str!("migrations/0020_create_oracle_prices.sql"),
+    },
+    Migration {
+        version: 21,
+        name: "fill_anomalous_flag",
+        sql: include_str!("migrations/0021_fill_anomalous_flag.sql"),
+    },
+    Migration {
+        version: 22,
+        name: "create_depth_snapshots",
+        sql: include_str!("migrations/0022_create_depth_snapshots.sql"),
+    },
+    Migration {
+        version: 23,
+        name: "create_spread_samples",
+        sql: include_str!("migrations/0023_create_spread_samples.sql"),
+    },
+    Migration {
+        version: 24,
+        name: "create_maker_uptime",
+        sql: include_str!("migrations/0024_create_maker_uptime.sql"),
+    },
+    Migration {
+        version: 25,
+        name: "fill_venue",
+        sql: include_str!("migrations/0025_fill_venue.sql"),
+    },
+    Migration {
+        version: 26,
+        name: "candle_revisions",
+        sql: include_str!("migrations/0026_candle_revisions.sql"),
+    },
+    Migration {
+        version: 27,
+        name: "create_jobs",
+        sql: include_str!("migrations/0027_create_jobs.sql"),
+    },
+    Migration {
+        version: 28,
+        name: "job_queue",
+        sql: include_str!("migrations/0028_job_queue.sql"),
+    },
+    Migration {
+        version: 29,
+        name: "candle_priced_volume",
+        sql: include_str!("migrations/0029_candle_priced_volume.sql"),
+    },
+    Migration {
+        version: 30,
+        name: "trader_leaderboard_roles",
+        sql: include_str!("migrations/0030_trader_leaderboard_roles.sql"),
+    },
+];
+
+/// Arbitrary key for the advisory lock held while migrating, so that replicas starting at the
+/// same time don't race each other.
+const MIGRATION_LOCK_ID: i64 = 0x6f62_6361_6e64;
+
+pub async fn run_migrations(pool: &Pool) -> anyhow::Result<()> {
+    let mut client = pool.get().await?;
+
+    client
+        .batch_execute(
+            "CREATE SCHEMA IF NOT EXISTS openbook;
+            CREATE TABLE IF NOT EXISTS openbook.schema_migrations (
+                version int PRIMARY KEY,
+                name text NOT NULL,
+                applied_at timestamptz NOT NULL DEFAULT now()
+            );",
+        )
+        .await?;
+
+    client
+        .execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_ID])
+        .await?;
+    let result = apply_pending_migrations(&mut client).await;
+    client
+        .execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK_ID])
+        .await?;
+    result
+}
+
+async fn apply_pending_migrations(client: &mut Object) -> anyhow::Result<()> {
+    let applied: Vec<i32> = client
+        .query("SELECT version FROM openbook.schema_migrations", &[])
+        .await?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        let transaction = client.transaction().await?;
+        transaction.batch_execute(migration.sql).await?;
+        transaction
+            .execute(
+                "INSERT INTO openbook.schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+        transaction.commit().await?;
+        info!(
+            "Applied migration {:04} {}",
+            migration.version, migration.name
+        );
+    }
+    Ok(())
+}
+
+/// Versions this build knows about that the database hasn't applied yet. Errors when the
+/// migrations table doesn't exist, i.e. the schema was never set up.
+pub async fn fetch_pending_migrations(pool: &Pool) -> anyhow::Result<Vec<i32>> {
+    let client = pool.get().await?;
+    let applied: Vec<i32> = client
+        .query("SELECT version FROM openbook.schema_migrations", &[])
+        .await?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    Ok(MIGRATIONS
+        .iter()
+        .map(|m| m.version)
+        .filter(|v| !applied.contains(v))
+        .collect())
+}
@@ -0,0 +1,17 @@
+CREATE SCHEMA IF NOT EXISTS openbook;
+
+CREATE TABLE IF NOT EXISTS openbook.candles (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    market_name text,
+    start_time timestamptz,
+    end_time timestamptz,
+    resolution text,
+    open double precision,
+    close double precision,
+    high double precision,
+    low double precision,
+    volume double precision,
+    complete bool
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_market_time_resolution ON openbook.candles USING btree (market_name, start_time, resolution);
@@ -0,0 +1,6 @@
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS vwap double precision;
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS trade_count bigint;
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS quote_volume double precision;
+
+UPDATE openbook.candles SET vwap = close, trade_count = 0 WHERE vwap IS NULL;
+UPDATE openbook.candles SET quote_volume = volume * vwap WHERE quote_volume IS NULL;
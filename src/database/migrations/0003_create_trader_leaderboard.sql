@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS openbook.trader_leaderboard (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    market text,
+    period text,
+    period_start timestamptz,
+    volume_type text,
+    role text,
+    rank int,
+    open_orders_owner text,
+    volume double precision,
+    maker_volume double precision,
+    taker_volume double precision,
+    updated_at timestamptz
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_leaderboard_market_period_owner ON openbook.trader_leaderboard USING btree (market, period, period_start, volume_type, role, open_orders_owner);
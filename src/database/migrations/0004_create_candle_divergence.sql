@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS openbook.candle_divergence (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    market_name text,
+    resolution text,
+    provider text,
+    start_time timestamptz,
+    close double precision,
+    provider_close double precision,
+    volume double precision,
+    provider_volume double precision,
+    close_delta_pct double precision,
+    volume_delta_pct double precision,
+    recorded_at timestamptz
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_divergence_market_resolution_provider_time ON openbook.candle_divergence USING btree (market_name, resolution, provider, start_time);
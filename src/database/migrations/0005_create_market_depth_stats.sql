@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS openbook.market_depth_stats (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    market_name text,
+    time timestamptz,
+    mid_price double precision,
+    bid_depth_usd double precision,
+    ask_depth_usd double precision
+);
+
+CREATE INDEX IF NOT EXISTS idx_depth_stats_market_time ON openbook.market_depth_stats USING btree (market_name, time);
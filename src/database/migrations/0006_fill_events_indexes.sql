@@ -0,0 +1,8 @@
+-- The fill events table is written by the fills service and may not exist yet
+DO $$
+BEGIN
+    IF to_regclass('openbook.openbook_fill_events') IS NOT NULL THEN
+        CREATE INDEX IF NOT EXISTS idx_fill_events_market_time ON openbook.openbook_fill_events USING btree (market, block_datetime);
+    END IF;
+END
+$$;
@@ -0,0 +1,27 @@
+-- Deployments fed by the fills service already have this table; create it for ones that ingest
+-- fills through a FillSource instead
+DO $$
+BEGIN
+    IF to_regclass('openbook.openbook_fill_events') IS NULL THEN
+        CREATE TABLE openbook.openbook_fill_events (
+            signature text NOT NULL,
+            slot bigint NOT NULL,
+            block_datetime timestamptz NOT NULL,
+            time timestamptz GENERATED ALWAYS AS (block_datetime) STORED,
+            market text NOT NULL,
+            open_orders_owner text NOT NULL,
+            bid bool NOT NULL,
+            maker bool NOT NULL,
+            native_quantity_paid double precision NOT NULL,
+            native_quantity_received double precision NOT NULL,
+            native_fee_or_rebate double precision NOT NULL,
+            price double precision NOT NULL,
+            size double precision NOT NULL,
+            seq_num bigint NOT NULL,
+            instruction_num int NOT NULL,
+            PRIMARY KEY (signature, instruction_num, seq_num)
+        );
+        CREATE INDEX idx_fill_events_market_time ON openbook.openbook_fill_events USING btree (market, block_datetime);
+    END IF;
+END
+$$;
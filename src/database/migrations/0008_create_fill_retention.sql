@@ -0,0 +1,6 @@
+-- Fills before pruned_before have been deleted, candles before then can't be rebuilt from fills
+CREATE TABLE IF NOT EXISTS openbook.fill_retention (
+    market text PRIMARY KEY,
+    pruned_before timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
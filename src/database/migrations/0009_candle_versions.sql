@@ -0,0 +1,19 @@
+-- Every insert or update of a candle gets a new, increasing version, which /changes pages through
+CREATE SEQUENCE IF NOT EXISTS openbook.candle_version_seq;
+
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS version bigint NOT NULL DEFAULT nextval('openbook.candle_version_seq');
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS updated_at timestamptz NOT NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS idx_candles_version ON openbook.candles USING btree (version);
+
+CREATE OR REPLACE FUNCTION openbook.set_candle_version() RETURNS trigger AS $$
+BEGIN
+    NEW.version := nextval('openbook.candle_version_seq');
+    NEW.updated_at := now();
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS candle_version ON openbook.candles;
+CREATE TRIGGER candle_version BEFORE INSERT OR UPDATE ON openbook.candles
+    FOR EACH ROW EXECUTE FUNCTION openbook.set_candle_version();
@@ -0,0 +1,5 @@
+-- Registry of running worker replicas, markets are split between the ones with a recent heartbeat
+CREATE TABLE IF NOT EXISTS openbook.worker_replicas (
+    replica_id text PRIMARY KEY,
+    heartbeat_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,8 @@
+-- Keys that get their own rate limit quota, only the sha256 hex digest of a key is stored
+CREATE TABLE IF NOT EXISTS openbook.api_keys (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    key_hash text NOT NULL UNIQUE,
+    name text NOT NULL,
+    requests_per_minute integer NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,10 @@
+-- Revoked keys are kept for their usage history
+ALTER TABLE openbook.api_keys ADD COLUMN IF NOT EXISTS revoked_at timestamptz;
+
+-- Requests per key per UTC day
+CREATE TABLE IF NOT EXISTS openbook.api_key_usage (
+    api_key_id bigint NOT NULL REFERENCES openbook.api_keys (id),
+    day date NOT NULL,
+    requests bigint NOT NULL,
+    PRIMARY KEY (api_key_id, day)
+);
@@ -0,0 +1,16 @@
+-- When each market was first and last seen trading, and how far its candles are complete.
+-- Maintained by the worker so nothing has to scan the fills table for it.
+CREATE TABLE IF NOT EXISTS openbook.market_lifecycle (
+    market text PRIMARY KEY,
+    first_fill_at timestamptz,
+    last_fill_at timestamptz,
+    candles_through timestamptz,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+INSERT INTO openbook.market_lifecycle (market, first_fill_at, last_fill_at)
+SELECT market, min(block_datetime), max(block_datetime)
+FROM openbook.openbook_fill_events
+WHERE maker = true
+GROUP BY market
+ON CONFLICT (market) DO NOTHING;
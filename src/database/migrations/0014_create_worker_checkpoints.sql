@@ -0,0 +1,9 @@
+-- Where each market's minute batching left off, restarts resume from here
+CREATE TABLE IF NOT EXISTS openbook.worker_checkpoints (
+    market_name text PRIMARY KEY,
+    candles_through timestamptz NOT NULL,
+    last_price double precision NOT NULL,
+    last_fill_time timestamptz,
+    last_fill_slot bigint,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,4 @@
+-- Fee of each fill in quote tokens (negative for maker rebates) and the referrer's share of it.
+-- Left NULL by the fills service and for older rows, readers derive them from native_fee_or_rebate.
+ALTER TABLE openbook.openbook_fill_events ADD COLUMN IF NOT EXISTS fee double precision;
+ALTER TABLE openbook.openbook_fill_events ADD COLUMN IF NOT EXISTS referrer_rebate double precision;
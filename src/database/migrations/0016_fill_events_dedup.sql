@@ -0,0 +1,25 @@
+-- A fill is identified by its event queue sequence number, whichever transaction or source it was
+-- read from. Maker and taker events are kept apart in case a source reports both under one number.
+-- Only an empty table gets the unique index here: stored fills may hold duplicates, and deleting
+-- them and building the index over a large table would hold up startup and block writes for
+-- hours. `dedup-fills --apply` does both in batches and builds the index without blocking writes.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM openbook.openbook_fill_events LIMIT 1) THEN
+        RAISE NOTICE 'openbook_fill_events is not empty, run dedup-fills --apply to add its unique index';
+        RETURN;
+    END IF;
+
+    IF to_regclass('timescaledb_information.hypertables') IS NOT NULL THEN
+        -- unique indexes on a hypertable have to include the time column
+        IF EXISTS (
+            SELECT 1 FROM timescaledb_information.hypertables
+            WHERE hypertable_schema = 'openbook' AND hypertable_name = 'openbook_fill_events'
+        ) THEN
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_fill_events_market_seq_num ON openbook.openbook_fill_events USING btree (market, seq_num, maker, block_datetime);
+            RETURN;
+        END IF;
+    END IF;
+    CREATE UNIQUE INDEX IF NOT EXISTS idx_fill_events_market_seq_num ON openbook.openbook_fill_events USING btree (market, seq_num, maker);
+END
+$$;
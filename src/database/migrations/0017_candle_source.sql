@@ -0,0 +1,3 @@
+-- Where a candle came from: 'fills' for candles built by the worker, otherwise the tag given to
+-- import-candles for history that predates fill ingestion.
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS source text NOT NULL DEFAULT 'fills';
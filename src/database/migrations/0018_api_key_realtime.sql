@@ -0,0 +1,2 @@
+-- Keys that see embargoed markets without the delay
+ALTER TABLE openbook.api_keys ADD COLUMN IF NOT EXISTS realtime boolean NOT NULL DEFAULT false;
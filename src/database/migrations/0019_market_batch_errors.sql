@@ -0,0 +1,3 @@
+-- How often batching failed per market, so staleness can be told apart from a quiet market
+ALTER TABLE openbook.market_lifecycle ADD COLUMN IF NOT EXISTS batch_errors bigint NOT NULL DEFAULT 0;
+ALTER TABLE openbook.market_lifecycle ADD COLUMN IF NOT EXISTS last_batch_error_at timestamptz;
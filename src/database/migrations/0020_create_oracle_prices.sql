@@ -0,0 +1,10 @@
+-- USD prices of tokens read from Pyth and Switchboard feeds. Several workers may poll the same feed,
+-- the publish time keys the rows so each update is stored once.
+CREATE TABLE IF NOT EXISTS openbook.oracle_prices (
+    symbol text NOT NULL,
+    provider text NOT NULL,
+    time timestamptz NOT NULL,
+    price double precision NOT NULL,
+    confidence double precision,
+    PRIMARY KEY (symbol, provider, time)
+);
@@ -0,0 +1,3 @@
+-- Fills whose price the outlier filter kept out of the candles. They are still stored and still
+-- count towards volume.
+ALTER TABLE openbook.openbook_fill_events ADD COLUMN IF NOT EXISTS anomalous boolean NOT NULL DEFAULT false;
@@ -0,0 +1,16 @@
+-- Best prices and order book depth within 1%, 2% and 5% of the mid price, in quote tokens,
+-- recorded once a minute by the worker that owns the market.
+CREATE TABLE IF NOT EXISTS openbook.depth_snapshots (
+    market_name text NOT NULL,
+    time timestamptz NOT NULL,
+    best_bid double precision,
+    best_ask double precision,
+    mid_price double precision NOT NULL,
+    bid_depth_1pct double precision NOT NULL,
+    ask_depth_1pct double precision NOT NULL,
+    bid_depth_2pct double precision NOT NULL,
+    ask_depth_2pct double precision NOT NULL,
+    bid_depth_5pct double precision NOT NULL,
+    ask_depth_5pct double precision NOT NULL,
+    PRIMARY KEY (market_name, time)
+);
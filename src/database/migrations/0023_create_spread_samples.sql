@@ -0,0 +1,9 @@
+-- Best bid and ask of each market, sampled every SPREAD_SAMPLE_SECS by the worker that owns it.
+CREATE TABLE IF NOT EXISTS openbook.spread_samples (
+    market_name text NOT NULL,
+    time timestamptz NOT NULL,
+    best_bid double precision NOT NULL,
+    best_ask double precision NOT NULL,
+    mid_price double precision NOT NULL,
+    PRIMARY KEY (market_name, time)
+);
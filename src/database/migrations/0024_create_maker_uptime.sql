@@ -0,0 +1,16 @@
+-- Order book samples taken per market and UTC day, and how many of them found each open orders
+-- account quoting both sides within the configured spread and size. Uptime is the ratio of the two.
+CREATE TABLE IF NOT EXISTS openbook.maker_uptime_samples (
+    market text NOT NULL,
+    day date NOT NULL,
+    samples integer NOT NULL,
+    PRIMARY KEY (market, day)
+);
+
+CREATE TABLE IF NOT EXISTS openbook.maker_uptime (
+    market text NOT NULL,
+    day date NOT NULL,
+    open_orders_owner text NOT NULL,
+    quoted_samples integer NOT NULL,
+    PRIMARY KEY (market, day, open_orders_owner)
+);
@@ -0,0 +1,3 @@
+-- The DEX a fill was scraped from. Fills written before other venues were ingested are all
+-- OpenBook fills.
+ALTER TABLE openbook.openbook_fill_events ADD COLUMN IF NOT EXISTS venue text NOT NULL DEFAULT 'openbook';
@@ -0,0 +1,64 @@
+-- Fills a candle was computed from, NULL for imported candles that have no fills behind them
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS source_fill_count bigint;
+-- When the candle was first marked complete, kept when it's reopened so its recomputation is
+-- still recorded as a revision
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS completed_at timestamptz;
+
+-- Every change to the prices, volume or trade count of a candle that had already been complete,
+-- with the values before and after and why it was recomputed
+CREATE TABLE IF NOT EXISTS openbook.candle_revisions (
+    id bigserial PRIMARY KEY,
+    market_name text NOT NULL,
+    start_time timestamptz NOT NULL,
+    resolution text NOT NULL,
+    old_open double precision NOT NULL,
+    old_high double precision NOT NULL,
+    old_low double precision NOT NULL,
+    old_close double precision NOT NULL,
+    old_volume double precision NOT NULL,
+    old_trade_count bigint,
+    new_open double precision NOT NULL,
+    new_high double precision NOT NULL,
+    new_low double precision NOT NULL,
+    new_close double precision NOT NULL,
+    new_volume double precision NOT NULL,
+    new_trade_count bigint,
+    reason text NOT NULL,
+    revised_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_candle_revisions_market ON openbook.candle_revisions USING btree (market_name, id);
+
+-- Writers name the reason with set_config('openbook.revision_reason', ..., true) in the
+-- transaction of the update, other updates are recorded as 'manual'
+CREATE OR REPLACE FUNCTION openbook.record_candle_revision() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        NEW.completed_at := coalesce(OLD.completed_at, CASE WHEN OLD.complete THEN OLD.updated_at END);
+        IF NEW.completed_at IS NOT NULL
+            AND (OLD.open, OLD.high, OLD.low, OLD.close, OLD.volume, OLD.trade_count)
+            IS DISTINCT FROM (NEW.open, NEW.high, NEW.low, NEW.close, NEW.volume, NEW.trade_count)
+        THEN
+            INSERT INTO openbook.candle_revisions (
+                market_name, start_time, resolution,
+                old_open, old_high, old_low, old_close, old_volume, old_trade_count,
+                new_open, new_high, new_low, new_close, new_volume, new_trade_count,
+                reason
+            ) VALUES (
+                NEW.market_name, NEW.start_time, NEW.resolution,
+                OLD.open, OLD.high, OLD.low, OLD.close, OLD.volume, OLD.trade_count,
+                NEW.open, NEW.high, NEW.low, NEW.close, NEW.volume, NEW.trade_count,
+                coalesce(nullif(current_setting('openbook.revision_reason', true), ''), 'manual')
+            );
+        END IF;
+    END IF;
+    IF NEW.complete AND NEW.completed_at IS NULL THEN
+        NEW.completed_at := now();
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS candle_revision ON openbook.candles;
+CREATE TRIGGER candle_revision BEFORE INSERT OR UPDATE ON openbook.candles
+    FOR EACH ROW EXECUTE FUNCTION openbook.record_candle_revision();
@@ -0,0 +1,18 @@
+-- Maintenance work queued through the admin API and carried out by a worker. `kind` names the
+-- task, the other columns are its parameters and progress.
+CREATE TABLE IF NOT EXISTS openbook.jobs (
+    id bigserial PRIMARY KEY,
+    kind text NOT NULL,
+    status text NOT NULL DEFAULT 'queued',
+    market_name text,
+    resolutions text[] NOT NULL DEFAULT '{}',
+    start_time timestamptz,
+    end_time timestamptz,
+    candles_written bigint,
+    error text,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    started_at timestamptz,
+    finished_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_jobs_status ON openbook.jobs USING btree (status, id);
@@ -0,0 +1,11 @@
+-- Jobs are claimed by priority, then in the order they were queued. A failed job is queued again
+-- after `run_after` until it used up `max_attempts`, and a running job whose worker stopped
+-- sending heartbeats is taken over by another.
+ALTER TABLE openbook.jobs ADD COLUMN IF NOT EXISTS priority integer NOT NULL DEFAULT 0;
+ALTER TABLE openbook.jobs ADD COLUMN IF NOT EXISTS attempts integer NOT NULL DEFAULT 0;
+ALTER TABLE openbook.jobs ADD COLUMN IF NOT EXISTS max_attempts integer NOT NULL DEFAULT 3;
+ALTER TABLE openbook.jobs ADD COLUMN IF NOT EXISTS run_after timestamptz NOT NULL DEFAULT now();
+ALTER TABLE openbook.jobs ADD COLUMN IF NOT EXISTS heartbeat_at timestamptz;
+
+DROP INDEX IF EXISTS openbook.idx_jobs_status;
+CREATE INDEX IF NOT EXISTS idx_jobs_queue ON openbook.jobs USING btree (status, priority DESC, id);
@@ -0,0 +1,5 @@
+-- Volume and quote volume of the fills that set prices, higher resolutions build their vwap from
+-- these so anomalous fills stay out of it. NULL for candles stored before they were kept, which
+-- are read as if every fill had set prices.
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS priced_volume double precision;
+ALTER TABLE openbook.candles ADD COLUMN IF NOT EXISTS priced_quote_volume double precision;
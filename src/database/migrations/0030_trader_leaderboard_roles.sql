@@ -0,0 +1,10 @@
+-- Migration 3 creates the leaderboard with the role and maker/taker split, but tables created
+-- before they existed were kept as they were. Their rows can't be split by role, so they are
+-- dropped; the worker materializes the current periods again within 15 minutes.
+ALTER TABLE openbook.trader_leaderboard ADD COLUMN IF NOT EXISTS role text;
+ALTER TABLE openbook.trader_leaderboard ADD COLUMN IF NOT EXISTS maker_volume double precision;
+ALTER TABLE openbook.trader_leaderboard ADD COLUMN IF NOT EXISTS taker_volume double precision;
+DELETE FROM openbook.trader_leaderboard WHERE role IS NULL;
+
+DROP INDEX IF EXISTS openbook.idx_leaderboard_market_period_owner;
+CREATE UNIQUE INDEX idx_leaderboard_market_period_owner ON openbook.trader_leaderboard USING btree (market, period, period_start, volume_type, role, open_orders_owner);
@@ -0,0 +1,112 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Object, Pool};
+use tracing::{info, instrument};
+
+use crate::structs::minute_aggregate::MinuteAggregate;
+
+/// Continuous aggregate the minute candles are built from when `PG_TIMESCALE_MINUTE_AGGREGATE`
+/// is set
+pub const MINUTE_AGGREGATE_VIEW: &str = "openbook.minute_fill_aggregates";
+
+/// Fills that turn up later than this are still folded into the aggregate by its refresh policy
+const REFRESH_WINDOW: &str = "INTERVAL '7 days'";
+
+/// Creates the continuous aggregate and its refresh policy. A new aggregate is materialized over
+/// all existing fills once, which can take a while on a large fills table. Safe to run on every
+/// startup.
+pub async fn setup_minute_aggregate(client: &Object) -> anyhow::Result<()> {
+    let exists = client
+        .query_opt(
+            r#"SELECT 1
+            FROM timescaledb_information.continuous_aggregates
+            WHERE view_schema = 'openbook' AND view_name = 'minute_fill_aggregates'"#,
+            &[],
+        )
+        .await?
+        .is_some();
+    if !exists {
+        info!("Creating {}", MINUTE_AGGREGATE_VIEW);
+        // recent minutes are read from the fills at query time, the policy only materializes
+        // the ones that have settled
+        client
+            .batch_execute(&format!(
+                r#"CREATE MATERIALIZED VIEW {}
+                WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+                SELECT
+                    market,
+                    time_bucket(INTERVAL '1 minute', block_datetime) as start_time,
+                    first(price, block_datetime) as open,
+                    last(price, block_datetime) as close,
+                    max(price) as high,
+                    min(price) as low,
+                    sum(size) as volume,
+                    sum(price * size) as quote_volume,
+                    count(*) as trade_count,
+                    min(block_datetime) as first_fill_time,
+                    max(block_datetime) as last_fill_time,
+                    max(slot) as last_slot
+                FROM openbook.openbook_fill_events
+                WHERE maker = true
+                GROUP BY market, time_bucket(INTERVAL '1 minute', block_datetime)
+                WITH NO DATA"#,
+                MINUTE_AGGREGATE_VIEW
+            ))
+            .await?;
+        info!(
+            "Materializing {} over existing fills",
+            MINUTE_AGGREGATE_VIEW
+        );
+        client
+            .batch_execute(&format!(
+                "CALL refresh_continuous_aggregate('{}', NULL, now() - INTERVAL '1 minute')",
+                MINUTE_AGGREGATE_VIEW
+            ))
+            .await?;
+    }
+    client
+        .batch_execute(&format!(
+            "SELECT add_continuous_aggregate_policy('{}',
+                start_offset => {},
+                end_offset => INTERVAL '1 minute',
+                schedule_interval => INTERVAL '1 minute',
+                if_not_exists => true)",
+            MINUTE_AGGREGATE_VIEW, REFRESH_WINDOW
+        ))
+        .await?;
+    Ok(())
+}
+
+/// The market's minutes with fills from `start_time` until `end_time`, oldest first.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_minute_aggregates(
+    pool: &Pool,
+    market_address: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<MinuteAggregate>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        market as "market",
+        start_time as "start_time",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        quote_volume as "quote_volume",
+        trade_count as "trade_count",
+        first_fill_time as "first_fill_time",
+        last_fill_time as "last_fill_time",
+        last_slot as "last_slot"
+        from openbook.minute_fill_aggregates
+        where market = $1
+        and start_time >= $2
+        and start_time < $3
+        ORDER BY start_time asc"#;
+
+    let rows = client
+        .query(stmt, &[&market_address, &start_time, &end_time])
+        .await?;
+    Ok(rows.into_iter().map(MinuteAggregate::from_row).collect())
+}
@@ -1,4 +1,28 @@
+pub mod anomalies;
+pub mod api_keys;
+pub mod archive;
 pub mod backfill;
+pub mod checkpoints;
+pub mod compaction;
+pub mod composite;
 pub mod fetch;
+pub mod fill_dedup;
+pub mod fill_import;
 pub mod initialize;
 pub mod insert;
+pub mod jobs;
+pub mod lifecycle;
+pub mod migrations;
+pub mod minute_aggregate;
+pub mod partitions;
+pub mod rebuild;
+pub mod reconciliation;
+pub mod replica;
+pub mod rescale;
+pub mod retention;
+pub mod revisions;
+pub mod roles;
+pub mod telemetry;
+pub mod tls;
+pub mod uptime;
+pub mod verification;
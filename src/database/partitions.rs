@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use deadpool_postgres::Pool;
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::utils::settings::PgConfig;
+
+const FILLS_TABLE: &str = "openbook.openbook_fill_events";
+
+/// Converts the fills table into one partitioned by month on `block_datetime` and creates the
+/// partitions up to `PG_FILL_PARTITION_MONTHS_AHEAD` months ahead. The fills already stored are
+/// kept as a single partition holding everything up to the end of the current month. Safe to run
+/// on every startup.
+pub async fn setup_fill_partitions(pool: &Pool, pg_config: &PgConfig) -> anyhow::Result<()> {
+    let mut client = pool.get().await?;
+
+    let kind: String = client
+        .query_one(
+            "SELECT relkind::text FROM pg_class WHERE oid = $1::text::regclass",
+            &[&FILLS_TABLE],
+        )
+        .await?
+        .get(0);
+    if kind == "r" {
+        let legacy_until = add_months(month_start(Utc::now()), 1);
+        let is_empty = client
+            .query_opt(&format!("SELECT 1 FROM {} LIMIT 1", FILLS_TABLE), &[])
+            .await?
+            .is_none();
+
+        info!("Converting {} to a partitioned table", FILLS_TABLE);
+        let transaction = client.transaction().await?;
+        // the primary key and unique index of a partitioned table have to include the partition
+        // key, so they're rebuilt on the new table instead of copied
+        transaction
+            .batch_execute(&format!(
+                "ALTER TABLE {0} RENAME TO openbook_fill_events_unpartitioned;
+                ALTER TABLE openbook.openbook_fill_events_unpartitioned
+                    DROP CONSTRAINT IF EXISTS openbook_fill_events_pkey;
+                DROP INDEX IF EXISTS openbook.idx_fill_events_market_time;
+                DROP INDEX IF EXISTS openbook.idx_fill_events_market_seq_num;
+                CREATE TABLE {0} (
+                    LIKE openbook.openbook_fill_events_unpartitioned
+                    INCLUDING DEFAULTS INCLUDING GENERATED
+                ) PARTITION BY RANGE (block_datetime);
+                ALTER TABLE {0} ADD PRIMARY KEY (signature, instruction_num, seq_num, block_datetime);
+                CREATE INDEX idx_fill_events_market_time ON {0} USING btree (market, block_datetime);
+                CREATE UNIQUE INDEX idx_fill_events_market_seq_num ON {0} USING btree (market, seq_num, maker, block_datetime);
+                CREATE TABLE IF NOT EXISTS openbook.openbook_fill_events_default PARTITION OF {0} DEFAULT;",
+                FILLS_TABLE
+            ))
+            .await?;
+        if is_empty {
+            transaction
+                .batch_execute("DROP TABLE openbook.openbook_fill_events_unpartitioned")
+                .await?;
+        } else {
+            transaction
+                .batch_execute(&format!(
+                    "ALTER TABLE openbook.openbook_fill_events_unpartitioned RENAME TO openbook_fill_events_legacy;
+                    ALTER TABLE {} ATTACH PARTITION openbook.openbook_fill_events_legacy
+                        FOR VALUES FROM (MINVALUE) TO ('{}');",
+                    FILLS_TABLE,
+                    legacy_until.to_rfc3339()
+                ))
+                .await?;
+        }
+        transaction.commit().await?;
+    } else if kind != "p" {
+        anyhow::bail!("{} is neither a table nor a partitioned table", FILLS_TABLE);
+    }
+
+    create_fill_partitions(pool, pg_config.pg_fill_partition_months_ahead).await
+}
+
+/// Creates the monthly partitions after the newest existing one through `months_ahead` months
+/// from now. Fills outside of every partition, e.g. imported ones older than the first, land in
+/// the default partition.
+pub async fn create_fill_partitions(pool: &Pool, months_ahead: u32) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT max(
+            substring(pg_get_expr(c.relpartbound, c.oid) from 'TO \(''([^'']+)''\)')::timestamptz
+        )
+        FROM pg_inherits i
+        JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE i.inhparent = $1::text::regclass"#;
+    let covered_until: Option<DateTime<Utc>> =
+        client.query_one(stmt, &[&FILLS_TABLE]).await?.get(0);
+
+    let current_month = month_start(Utc::now());
+    let until = add_months(current_month, months_ahead + 1);
+    let mut month = covered_until.unwrap_or(current_month).max(current_month);
+    while month < until {
+        let next = add_months(month, 1);
+        client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS openbook.openbook_fill_events_p{} PARTITION OF {}
+                FOR VALUES FROM ('{}') TO ('{}')",
+                month.format("%Y_%m"),
+                FILLS_TABLE,
+                month.to_rfc3339(),
+                next.to_rfc3339()
+            ))
+            .await?;
+        info!("Created fills partition for {}", month.format("%Y-%m"));
+        month = next;
+    }
+    Ok(())
+}
+
+fn month_start(time: DateTime<Utc>) -> DateTime<Utc> {
+    let date = NaiveDate::from_ymd_opt(time.year(), time.month(), 1).unwrap();
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn add_months(month: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    let months = month.month0() + months;
+    let date =
+        NaiveDate::from_ymd_opt(month.year() + (months / 12) as i32, months % 12 + 1, 1).unwrap();
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+/// Keeps creating the upcoming monthly partitions, once a day. Has to connect as the owner of
+/// the fills table.
+pub async fn maintain_fill_partitions(pool: &Pool, months_ahead: u32) {
+    loop {
+        sleep(Duration::from_secs(24 * 60 * 60)).await;
+        if let Err(e) = create_fill_partitions(pool, months_ahead).await {
+            error!("Failed to create fills partitions: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::{
+    database::{
+        insert::build_candles_upsert_statement,
+        revisions::{revision_transaction, REVISION_REASON_REBUILD},
+    },
+    structs::candle::Candle,
+};
+
+/// Replaces the market's candles of `resolutions` starting within `range` with `candles` in one
+/// transaction. Stored candles the rebuild doesn't have, e.g. misaligned ones, are deleted, the
+/// others are upserted so changes to complete candles are recorded as revisions. Returns the
+/// number of candles deleted, inserted or changed.
+#[instrument(skip(pool, candles), level = "debug", err)]
+pub async fn replace_candles(
+    pool: &Pool,
+    market_name: &str,
+    resolutions: &[String],
+    range: Range<DateTime<Utc>>,
+    candles: &Vec<Candle>,
+) -> anyhow::Result<u64> {
+    let mut client = pool.get().await?;
+    let transaction = revision_transaction(&mut client, REVISION_REASON_REBUILD).await?;
+
+    let start_times: Vec<DateTime<Utc>> = candles.iter().map(|c| c.start_time).collect();
+    let candle_resolutions: Vec<String> = candles.iter().map(|c| c.resolution.clone()).collect();
+    let deleted = transaction
+        .execute(
+            r#"DELETE FROM openbook.candles
+            WHERE market_name = $1
+            AND resolution = ANY($2)
+            AND start_time >= $3
+            AND start_time < $4
+            AND (start_time, resolution) NOT IN (
+                SELECT * FROM unnest($5::timestamptz[], $6::text[])
+            )"#,
+            &[
+                &market_name,
+                &resolutions,
+                &range.start,
+                &range.end,
+                &start_times,
+                &candle_resolutions,
+            ],
+        )
+        .await?;
+    let upserted = if candles.is_empty() {
+        0
+    } else {
+        transaction
+            .execute(&build_candles_upsert_statement(candles), &[])
+            .await?
+    };
+    transaction.commit().await?;
+    Ok(deleted + upserted)
+}
+
+/// Starts of the UTC days between `start_time` and `end_time` on which the market has fewer minute
+/// candles than the day has minutes. The day of the first minute candle is left out, it starts
+/// at the first fill.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_days_missing_minute_candles(
+    pool: &Pool,
+    market_name: &str,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> anyhow::Result<Vec<DateTime<Utc>>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"WITH first AS (
+            SELECT date_trunc('day', min(start_time)) + interval '1 day' as "day"
+            from openbook.candles
+            where market_name = $1
+            and resolution = '1M'
+        )
+        SELECT d.day as "day"
+        from first,
+        generate_series(
+            greatest(first.day, $2::timestamptz),
+            $3::timestamptz - interval '1 day',
+            interval '1 day'
+        ) as d(day)
+        where first.day IS NOT NULL
+        and (
+            SELECT count(*)
+            from openbook.candles c
+            where c.market_name = $1
+            and c.resolution = '1M'
+            and c.start_time >= d.day
+            and c.start_time < d.day + interval '1 day'
+        ) < 1440
+        ORDER BY 1"#;
+
+    let rows = client
+        .query(stmt, &[&market_name, &start_time, &end_time])
+        .await?;
+    Ok(rows.into_iter().map(|r| r.get(0)).collect())
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+/// Signatures of the markets' fills between `after` and `through`, with the time of their first
+/// fill, oldest first. Fills from event queue sources carry no signature and are left out.
+#[instrument(skip(pool, market_address_strings), level = "debug", err)]
+pub async fn fetch_fill_signatures_between(
+    pool: &Pool,
+    market_address_strings: &Vec<&str>,
+    after: DateTime<Utc>,
+    through: DateTime<Utc>,
+) -> anyhow::Result<Vec<(String, DateTime<Utc>)>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT
+        signature as "signature",
+        min(block_datetime) as "block_datetime"
+        from openbook.openbook_fill_events
+        where market = ANY($1)
+        and block_datetime > $2
+        and block_datetime <= $3
+        and signature NOT LIKE 'slot-%'
+        GROUP BY signature
+        ORDER BY 2 asc"#;
+
+    let rows = client
+        .query(stmt, &[&market_address_strings, &after, &through])
+        .await?;
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// Deletes every fill of the transactions and returns the earliest deleted fill time per market.
+pub async fn delete_fills_of_signatures(
+    pool: &Pool,
+    signatures: &Vec<String>,
+) -> anyhow::Result<Vec<(String, DateTime<Utc>)>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"WITH deleted AS (
+            DELETE FROM openbook.openbook_fill_events
+            WHERE signature = ANY($1)
+            RETURNING market, block_datetime
+        )
+        SELECT market as "market", min(block_datetime) as "block_datetime"
+        FROM deleted
+        GROUP BY market"#;
+
+    let rows = client.query(stmt, &[&signatures]).await?;
+    Ok(rows.into_iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// Marks every candle of the market ending after `from` as incomplete and moves the worker
+/// checkpoint back before it, so the batcher rebuilds them from the remaining fills.
+pub async fn invalidate_candles_from(
+    pool: &Pool,
+    market_name: &str,
+    from: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    transaction
+        .execute(
+            r#"UPDATE openbook.candles SET complete = false
+            WHERE market_name = $1 AND end_time > $2 AND complete = true"#,
+            &[&market_name, &from],
+        )
+        .await?;
+    transaction
+        .execute(
+            r#"DELETE FROM openbook.worker_checkpoints
+            WHERE market_name = $1 AND candles_through > $2"#,
+            &[&market_name, &from],
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Start of the earliest complete minute candle of the market between `from` and `through` whose
+/// trade count differs from the maker fills stored for its minute, i.e. fills turned up or went
+/// away after it was closed.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_first_stale_minute(
+    pool: &Pool,
+    market_name: &str,
+    market_address: &str,
+    from: DateTime<Utc>,
+    through: DateTime<Utc>,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"WITH fills AS (
+            SELECT date_trunc('minute', block_datetime) as "minute", count(*) as "trades"
+            FROM openbook.openbook_fill_events
+            WHERE market = $2
+            AND maker = true
+            AND block_datetime >= $3
+            AND block_datetime < $4
+            GROUP BY 1
+        )
+        SELECT min(c.start_time) as "start_time"
+        FROM openbook.candles c
+        LEFT JOIN fills f ON f.minute = c.start_time
+        WHERE c.market_name = $1
+        AND c.resolution = '1M'
+        AND c.complete = true
+        AND c.start_time >= $3
+        AND c.end_time <= $4
+        AND coalesce(c.trade_count, 0) <> coalesce(f.trades, 0)"#;
+
+    let row = client
+        .query_one(stmt, &[&market_name, &market_address, &from, &through])
+        .await?;
+    Ok(row.get(0))
+}
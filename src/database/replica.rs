@@ -0,0 +1,106 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use deadpool_postgres::{Pool, PoolError};
+use tracing::{info, warn};
+
+/// How often the replica is probed
+const REPLICA_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Pool for read only queries: the read replica while it answers, the primary otherwise.
+pub struct ReadPool {
+    primary: Pool,
+    replica: Option<Pool>,
+    replica_up: AtomicBool,
+}
+
+impl ReadPool {
+    pub fn new(primary: Pool, replica: Option<Pool>) -> Self {
+        ReadPool {
+            primary,
+            replica_up: AtomicBool::new(replica.is_some()),
+            replica,
+        }
+    }
+
+    fn get(&self) -> &Pool {
+        match &self.replica {
+            Some(replica) if self.replica_up.load(Ordering::Relaxed) => replica,
+            _ => &self.primary,
+        }
+    }
+
+    /// Runs `read` on the replica, and again on the primary when no connection to the replica
+    /// could be had or it dropped. The replica then stays out of rotation until the next probe
+    /// finds it answering, so requests don't each wait on it in the meantime.
+    pub async fn run<'a, T, F, Fut>(&'a self, read: F) -> anyhow::Result<T>
+    where
+        F: Fn(&'a Pool) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let pool = self.get();
+        match read(pool).await {
+            Err(e) if !std::ptr::eq(pool, &self.primary) && is_connection_error(&e) => {
+                if self.replica_up.swap(false, Ordering::Relaxed) {
+                    warn!("Read replica failed, reading from the primary: {:?}", e);
+                }
+                read(&self.primary).await
+            }
+            result => result,
+        }
+    }
+
+    pub fn replica(&self) -> Option<&Pool> {
+        self.replica.as_ref()
+    }
+
+    pub fn has_replica(&self) -> bool {
+        self.replica.is_some()
+    }
+
+    pub fn replica_up(&self) -> bool {
+        self.replica.is_some() && self.replica_up.load(Ordering::Relaxed)
+    }
+
+    /// Probes the replica and routes reads to the primary while it doesn't answer.
+    async fn check_replica(&self) {
+        let replica = match &self.replica {
+            Some(r) => r,
+            None => return,
+        };
+        let up = match replica.get().await {
+            Ok(client) => client.query_one("SELECT 1", &[]).await.is_ok(),
+            Err(_) => false,
+        };
+        let was_up = self.replica_up.swap(up, Ordering::Relaxed);
+        match (was_up, up) {
+            (true, false) => warn!("Read replica unreachable, reading from the primary"),
+            (false, true) => info!("Read replica reachable again"),
+            _ => {}
+        }
+    }
+}
+
+/// Whether `e` comes from not getting a connection or losing it, rather than from the query.
+pub fn is_connection_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause.is::<PoolError>()
+            || cause
+                .downcast_ref::<tokio_postgres::Error>()
+                .map_or(false, |e| e.is_closed())
+    })
+}
+
+/// Keeps probing the read replica, returns right away when none is configured.
+pub async fn monitor_read_replica(pool: &ReadPool) {
+    if !pool.has_replica() {
+        return;
+    }
+    loop {
+        pool.check_replica().await;
+        tokio::time::sleep(REPLICA_CHECK_INTERVAL).await;
+    }
+}
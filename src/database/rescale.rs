@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+/// Relative difference below which a stored price or size is considered already scaled
+const TOLERANCE: f64 = 1e-9;
+
+/// Re-derives every fill's UI price and size from its native quantities, the same way
+/// `MarketInfo::native_to_ui_price_and_size` does for new fills. Fills without a base amount are
+/// left out.
+const RESCALED_FILLS: &str = r#"SELECT
+        seq_num,
+        maker,
+        block_datetime,
+        price,
+        size,
+        (quote_native / base_native * power(10::numeric, $2::int - $3::int))::float8 as "ui_price",
+        (base_native / power(10::numeric, $2::int))::float8 as "ui_size"
+        FROM (
+            SELECT
+            seq_num,
+            maker,
+            block_datetime,
+            price,
+            size,
+            (CASE WHEN bid THEN native_quantity_received ELSE native_quantity_paid END)::numeric as base_native,
+            (CASE
+                WHEN bid AND maker THEN native_quantity_paid + native_fee_or_rebate
+                WHEN bid THEN greatest(native_quantity_paid - native_fee_or_rebate, 0)
+                WHEN maker THEN greatest(native_quantity_received - native_fee_or_rebate, 0)
+                ELSE native_quantity_received + native_fee_or_rebate
+            END)::numeric as quote_native
+            from openbook.openbook_fill_events
+            where market = $1
+            and block_datetime >= $4
+            and block_datetime < $5
+        ) n
+        WHERE base_native > 0"#;
+
+/// Counts, or with `apply` rewrites, the market's fills in the window whose stored price or size
+/// doesn't match the one derived from its native quantities.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn rescale_fills(
+    pool: &Pool,
+    market_address: &str,
+    base_decimals: u8,
+    quote_decimals: u8,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    apply: bool,
+) -> anyhow::Result<u64> {
+    let client = pool.get().await?;
+
+    let mismatched = r#"abs(r.ui_price - r.price) > $6 * abs(r.ui_price)
+        OR abs(r.ui_size - r.size) > $6 * abs(r.ui_size)"#;
+    let stmt = if apply {
+        format!(
+            r#"UPDATE openbook.openbook_fill_events f
+            SET price = r.ui_price, size = r.ui_size
+            FROM ({}) r
+            WHERE f.market = $1
+            AND f.seq_num = r.seq_num
+            AND f.maker = r.maker
+            AND f.block_datetime = r.block_datetime
+            AND ({})"#,
+            RESCALED_FILLS, mismatched
+        )
+    } else {
+        format!(
+            r#"SELECT count(*) FROM ({}) r WHERE {}"#,
+            RESCALED_FILLS, mismatched
+        )
+    };
+    let params: [&(dyn tokio_postgres::types::ToSql + Sync); 6] = [
+        &market_address,
+        &(base_decimals as i32),
+        &(quote_decimals as i32),
+        &start_time,
+        &end_time,
+        &TOLERANCE,
+    ];
+
+    if apply {
+        Ok(client.execute(&stmt, &params).await?)
+    } else {
+        let count: i64 = client.query_one(&stmt, &params).await?.get(0);
+        Ok(count as u64)
+    }
+}
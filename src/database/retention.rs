@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+
+use crate::structs::resolution::Resolution;
+
+/// The time up to which every resolution the market keeps has complete candles, i.e. the fills
+/// before it are no longer needed by the batcher. Weeks and months are made up from daily
+/// candles, not fills, and don't hold fills back.
+pub async fn fetch_candles_complete_through(
+    pool: &Pool,
+    market_name: &str,
+    resolutions: &[Resolution],
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT 
+        CASE WHEN count(*) = $2 THEN least(min(r.complete_through), min(r.first_incomplete)) END as "complete_through"
+        from (
+            select 
+                resolution,
+                max(end_time) FILTER (WHERE complete = true) as complete_through,
+                min(start_time) FILTER (WHERE complete = false) as first_incomplete
+            from openbook.candles
+            where market_name = $1
+            and resolution = ANY($3)
+            group by resolution
+        ) r
+        where r.complete_through is not null"#;
+
+    let resolutions: Vec<String> = resolutions
+        .iter()
+        .filter(|r| !r.is_calendar())
+        .map(|r| r.to_string())
+        .collect();
+    let resolution_count = resolutions.len() as i64;
+    let row = client
+        .query_one(stmt, &[&market_name, &resolution_count, &resolutions])
+        .await?;
+    Ok(row.get(0))
+}
+
+pub async fn fetch_fill_retention_watermark(
+    pool: &Pool,
+    market_address: &str,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT pruned_before as "pruned_before"
+        from openbook.fill_retention
+        where market = $1"#;
+
+    let row = client.query_opt(stmt, &[&market_address]).await?;
+    Ok(row.map(|r| r.get(0)))
+}
+
+/// Records that fills before `pruned_before` are about to be deleted. Only ever moves forward.
+pub async fn record_fill_retention_watermark(
+    pool: &Pool,
+    market_address: &str,
+    pruned_before: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    let stmt = r#"INSERT INTO openbook.fill_retention (market, pruned_before)
+        VALUES ($1, $2)
+        ON CONFLICT (market) DO UPDATE SET
+        pruned_before = greatest(openbook.fill_retention.pruned_before, excluded.pruned_before),
+        updated_at = now()"#;
+
+    client
+        .execute(stmt, &[&market_address, &pruned_before])
+        .await?;
+    Ok(())
+}
+
+/// Deletes up to `limit` fills of the market older than `before`, returns the number deleted.
+pub async fn delete_fills_before(
+    pool: &Pool,
+    market_address: &str,
+    before: DateTime<Utc>,
+    limit: i64,
+) -> anyhow::Result<u64> {
+    let client = pool.get().await?;
+
+    // a ctid is only unique within one partition or chunk
+    let stmt = r#"DELETE FROM openbook.openbook_fill_events
+        where (tableoid, ctid) IN (
+            select tableoid, ctid from openbook.openbook_fill_events
+            where market = $1
+            and block_datetime < $2
+            LIMIT $3
+        )"#;
+
+    Ok(client
+        .execute(stmt, &[&market_address, &before, &limit])
+        .await?)
+}
@@ -0,0 +1,67 @@
+use deadpool_postgres::{Object, Pool, Transaction};
+use tracing::instrument;
+
+use crate::{database::telemetry::get_client, structs::revision::CandleRevision};
+
+/// Why candles written by the batcher changed, usually fills that arrived late or were removed
+pub const REVISION_REASON_BATCH: &str = "batch";
+pub const REVISION_REASON_BACKFILL: &str = "backfill";
+pub const REVISION_REASON_COMPOSITE: &str = "composite";
+pub const REVISION_REASON_REBUILD: &str = "rebuild";
+
+/// A transaction whose changes to complete candles are recorded in `openbook.candle_revisions`
+/// with `reason`.
+pub async fn revision_transaction<'a>(
+    client: &'a mut Object,
+    reason: &str,
+) -> anyhow::Result<Transaction<'a>> {
+    let transaction = client.transaction().await?;
+    transaction
+        .execute(
+            "SELECT set_config('openbook.revision_reason', $1, true)",
+            &[&reason],
+        )
+        .await?;
+    Ok(transaction)
+}
+
+/// Revisions after the `since` id, oldest first, of one market or all of them. Revisions from the
+/// last few seconds are held back so a slow transaction can't commit a lower id behind the cursor.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_candle_revisions(
+    pool: &Pool,
+    market_name: Option<&str>,
+    since: i64,
+    limit: i64,
+) -> anyhow::Result<Vec<CandleRevision>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"SELECT
+        id as "id",
+        market_name as "market_name",
+        resolution as "resolution",
+        start_time as "start_time",
+        old_open as "old_open",
+        old_high as "old_high",
+        old_low as "old_low",
+        old_close as "old_close",
+        old_volume as "old_volume",
+        coalesce(old_trade_count, 0) as "old_trade_count",
+        new_open as "new_open",
+        new_high as "new_high",
+        new_low as "new_low",
+        new_close as "new_close",
+        new_volume as "new_volume",
+        coalesce(new_trade_count, 0) as "new_trade_count",
+        reason as "reason",
+        revised_at as "revised_at"
+        from openbook.candle_revisions
+        where id > $1
+        and ($2::text IS NULL OR market_name = $2)
+        and revised_at < now() - interval '5 seconds'
+        ORDER BY id asc
+        LIMIT $3"#;
+
+    let rows = client.query(stmt, &[&since, &market_name, &limit]).await?;
+    Ok(rows.into_iter().map(CandleRevision::from_row).collect())
+}
@@ -0,0 +1,89 @@
+use deadpool_postgres::Pool;
+
+use crate::utils::settings::PgConfig;
+
+/// Least privileged roles the binaries connect as when `PG_USE_ROLES` is set. `PG_USER` itself
+/// only runs setup and migrations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DbRole {
+    /// The worker: reads and writes fills, candles and the worker's own tables
+    IngestWriter,
+    /// The public API: reads everything except revoked API keys, writes API key usage
+    ApiReader,
+    /// Maintenance tools and the admin endpoints: reads and writes every table
+    Admin,
+}
+
+impl DbRole {
+    pub fn name(self) -> &'static str {
+        match self {
+            DbRole::IngestWriter => "openbook_ingest_writer",
+            DbRole::ApiReader => "openbook_api_reader",
+            DbRole::Admin => "openbook_admin",
+        }
+    }
+
+    pub fn password(self, pg_config: &PgConfig) -> anyhow::Result<&str> {
+        let password = match self {
+            DbRole::IngestWriter => &pg_config.pg_ingest_writer_password,
+            DbRole::ApiReader => &pg_config.pg_api_reader_password,
+            DbRole::Admin => &pg_config.pg_admin_password,
+        };
+        password
+            .as_deref()
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("no password configured for role {}", self.name()))
+    }
+}
+
+const ROLES: [DbRole; 3] = [DbRole::IngestWriter, DbRole::ApiReader, DbRole::Admin];
+
+/// Creates the roles, keeps their passwords in line with the config and (re)grants their
+/// privileges on every table that exists now. Safe to run on every startup.
+pub async fn setup_roles(pool: &Pool, pg_config: &PgConfig) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+
+    for role in ROLES {
+        let password = role.password(pg_config)?.replace('\'', "''");
+        client
+            .batch_execute(&format!(
+                "DO $$
+                BEGIN
+                    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = '{0}') THEN
+                        CREATE ROLE {0};
+                    END IF;
+                END
+                $$;
+                ALTER ROLE {0} WITH LOGIN PASSWORD '{1}';
+                GRANT USAGE ON SCHEMA openbook TO {0};
+                GRANT USAGE ON ALL SEQUENCES IN SCHEMA openbook TO {0};",
+                role.name(),
+                password
+            ))
+            .await?;
+    }
+
+    let writer = DbRole::IngestWriter.name();
+    let reader = DbRole::ApiReader.name();
+    let admin = DbRole::Admin.name();
+    client
+        .batch_execute(&format!(
+            "GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA openbook TO {writer};
+            REVOKE ALL ON openbook.api_keys, openbook.api_key_usage FROM {writer};
+
+            GRANT SELECT ON ALL TABLES IN SCHEMA openbook TO {reader};
+            GRANT INSERT, UPDATE ON openbook.api_key_usage TO {reader};
+
+            GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA openbook TO {admin};
+
+            ALTER TABLE openbook.api_keys ENABLE ROW LEVEL SECURITY;
+            DROP POLICY IF EXISTS api_keys_active ON openbook.api_keys;
+            CREATE POLICY api_keys_active ON openbook.api_keys FOR SELECT TO {reader}
+                USING (revoked_at IS NULL);
+            DROP POLICY IF EXISTS api_keys_admin ON openbook.api_keys;
+            CREATE POLICY api_keys_admin ON openbook.api_keys TO {admin}
+                USING (true) WITH CHECK (true);"
+        ))
+        .await?;
+    Ok(())
+}
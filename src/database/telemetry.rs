@@ -0,0 +1,190 @@
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use deadpool_postgres::{Object, Pool, PoolError};
+use lazy_static::lazy_static;
+use prometheus::{Histogram, HistogramOpts, IntCounter, IntGaugeVec, Opts, Registry};
+use tokio_postgres::{types::ToSql, Row};
+use tracing::warn;
+
+use crate::utils::settings::PgConfig;
+
+fn default_slow_query_threshold_ms() -> u64 {
+    1000
+}
+
+lazy_static! {
+    static ref DB_METRICS: RwLock<Option<DbMetrics>> = RwLock::new(None);
+    static ref SLOW_QUERY_THRESHOLD: Option<Duration> = {
+        let ms = PgConfig::from_env()
+            .ok()
+            .and_then(|c| c.pg_slow_query_threshold_ms)
+            .unwrap_or_else(default_slow_query_threshold_ms);
+        (ms > 0).then(|| Duration::from_millis(ms))
+    };
+    static ref SLOW_QUERY_LOG_PARAMS: bool = PgConfig::from_env()
+        .map(|c| c.pg_slow_query_log_params)
+        .unwrap_or(false);
+}
+
+/// Connection pool and query metrics, kept by whichever binary registered them
+struct DbMetrics {
+    pool_wait_seconds: Histogram,
+    pool_timeouts_total: IntCounter,
+    pool_connections: IntGaugeVec,
+    slow_queries_total: IntCounter,
+}
+
+/// Registers the connection pool and slow query metrics, `namespace` is prepended to their names.
+pub fn register_database_metrics(registry: &Registry, namespace: &str) -> prometheus::Result<()> {
+    let metrics = DbMetrics {
+        pool_wait_seconds: Histogram::with_opts(
+            HistogramOpts::new(
+                "db_pool_wait_seconds",
+                "Time spent waiting for a connection from the pool",
+            )
+            .namespace(namespace),
+        )?,
+        pool_timeouts_total: IntCounter::with_opts(
+            Opts::new(
+                "db_pool_timeouts_total",
+                "Connection requests that timed out waiting for the pool",
+            )
+            .namespace(namespace),
+        )?,
+        pool_connections: IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Connections per pool by state: in_use, idle or waiting for a connection",
+            )
+            .namespace(namespace),
+            &["pool", "state"],
+        )?,
+        slow_queries_total: IntCounter::with_opts(
+            Opts::new(
+                "db_slow_queries_total",
+                "Queries slower than PG_SLOW_QUERY_THRESHOLD_MS",
+            )
+            .namespace(namespace),
+        )?,
+    };
+    registry.register(Box::new(metrics.pool_wait_seconds.clone()))?;
+    registry.register(Box::new(metrics.pool_timeouts_total.clone()))?;
+    registry.register(Box::new(metrics.pool_connections.clone()))?;
+    registry.register(Box::new(metrics.slow_queries_total.clone()))?;
+    *DB_METRICS.write().unwrap() = Some(metrics);
+    Ok(())
+}
+
+fn with_metrics(f: impl FnOnce(&DbMetrics)) {
+    if let Some(metrics) = DB_METRICS.read().unwrap().as_ref() {
+        f(metrics);
+    }
+}
+
+/// Samples the connection counts of the named pools every 10 seconds.
+pub async fn monitor_pools(pools: Vec<(&'static str, Pool)>) {
+    loop {
+        for (name, pool) in pools.iter() {
+            let status = pool.status();
+            let idle = status.available.max(0) as i64;
+            let waiting = (-status.available).max(0) as i64;
+            with_metrics(|m| {
+                let set = |state: &str, value: i64| {
+                    m.pool_connections
+                        .with_label_values(&[*name, state])
+                        .set(value)
+                };
+                set("in_use", status.size as i64 - idle);
+                set("idle", idle);
+                set("waiting", waiting);
+            });
+        }
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
+
+/// Takes a connection from the pool, recording how long that took and whether it timed out.
+pub async fn get_client(pool: &Pool) -> Result<TimedClient, PoolError> {
+    let started = Instant::now();
+    let client = pool.get().await;
+    with_metrics(|m| {
+        m.pool_wait_seconds.observe(started.elapsed().as_secs_f64());
+        if matches!(client, Err(PoolError::Timeout(_))) {
+            m.pool_timeouts_total.inc();
+        }
+    });
+    Ok(TimedClient { client: client? })
+}
+
+/// A pooled connection that logs queries slower than `PG_SLOW_QUERY_THRESHOLD_MS` together with
+/// their duration and parameter count, or values with `PG_SLOW_QUERY_LOG_PARAMS`. Statements are prepared once per connection and reused, so
+/// they must not embed values that vary from call to call.
+pub struct TimedClient {
+    client: Object,
+}
+
+impl TimedClient {
+    pub async fn query(
+        &self,
+        statement: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, tokio_postgres::Error> {
+        let started = Instant::now();
+        let prepared = self.client.prepare_cached(statement).await?;
+        let result = self.client.query(&prepared, params).await;
+        log_if_slow(statement, params, started.elapsed());
+        result
+    }
+
+    pub async fn query_opt(
+        &self,
+        statement: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Option<Row>, tokio_postgres::Error> {
+        let started = Instant::now();
+        let prepared = self.client.prepare_cached(statement).await?;
+        let result = self.client.query_opt(&prepared, params).await;
+        log_if_slow(statement, params, started.elapsed());
+        result
+    }
+
+    pub async fn query_one(
+        &self,
+        statement: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Row, tokio_postgres::Error> {
+        let started = Instant::now();
+        let prepared = self.client.prepare_cached(statement).await?;
+        let result = self.client.query_one(&prepared, params).await;
+        log_if_slow(statement, params, started.elapsed());
+        result
+    }
+}
+
+fn log_if_slow(statement: &str, params: &[&(dyn ToSql + Sync)], elapsed: Duration) {
+    match *SLOW_QUERY_THRESHOLD {
+        Some(threshold) if elapsed >= threshold => {}
+        _ => return,
+    }
+    with_metrics(|m| m.slow_queries_total.inc());
+    // statements are indented raw strings, keep the log line on one line
+    let statement = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    if *SLOW_QUERY_LOG_PARAMS {
+        warn!(
+            duration_ms = elapsed.as_millis() as u64,
+            params = ?params,
+            "Slow query: {}",
+            statement
+        );
+    } else {
+        warn!(
+            duration_ms = elapsed.as_millis() as u64,
+            param_count = params.len(),
+            "Slow query: {}",
+            statement
+        );
+    }
+}
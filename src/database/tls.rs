@@ -0,0 +1,198 @@
+use std::{
+    fmt::Debug,
+    fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use native_tls::{Certificate, Identity, TlsConnector};
+use postgres_native_tls::{MakeTlsConnector, TlsConnect, TlsStream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_postgres::tls::MakeTlsConnect;
+use tracing::{error, info};
+
+use crate::utils::settings::PgConfig;
+
+/// The PKCS#12 password used when `PG_CLIENT_KEY_PASSWORD` is unset, it used to be fixed
+const DEFAULT_PKCS12_PASSWORD: &str = "pass";
+
+/// How often the certificate files are read again when `PG_TLS_RELOAD_SECS` is unset
+const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where the client identity comes from
+enum IdentityFiles {
+    /// A PKCS#12 archive holding the certificate and key
+    Pkcs12 { path: String, password: String },
+    /// A PEM certificate chain and an unencrypted PEM PKCS#8 key
+    Pem { cert_path: String, key_path: String },
+}
+
+struct TlsFiles {
+    ca_cert_path: String,
+    identity: IdentityFiles,
+}
+
+impl TlsFiles {
+    fn paths(&self) -> Vec<&str> {
+        let mut paths = vec![self.ca_cert_path.as_str()];
+        match &self.identity {
+            IdentityFiles::Pkcs12 { path, .. } => paths.push(path),
+            IdentityFiles::Pem {
+                cert_path,
+                key_path,
+            } => paths.extend([cert_path.as_str(), key_path.as_str()]),
+        }
+        paths
+    }
+
+    fn read(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+        self.paths()
+            .into_iter()
+            .map(|path| fs::read(path).with_context(|| format!("reading {}", path)))
+            .collect()
+    }
+
+    /// Builds a connector from the contents returned by `read`.
+    fn connector(&self, contents: &[Vec<u8>]) -> anyhow::Result<MakeTlsConnector> {
+        let ca_cert = Certificate::from_pem(&contents[0]).context("parsing the ca cert")?;
+        let identity = match &self.identity {
+            IdentityFiles::Pkcs12 { password, .. } => Identity::from_pkcs12(&contents[1], password)
+                .context("parsing the PKCS#12 client key")?,
+            IdentityFiles::Pem { .. } => Identity::from_pkcs8(&contents[1], &contents[2])
+                .context("parsing the PEM client cert and key")?,
+        };
+        Ok(MakeTlsConnector::new(
+            TlsConnector::builder()
+                .add_root_certificate(ca_cert)
+                .identity(identity)
+                .danger_accept_invalid_certs(false)
+                .build()?,
+        ))
+    }
+}
+
+struct Reloading {
+    files: TlsFiles,
+    interval: Duration,
+    contents: Vec<Vec<u8>>,
+    connector: MakeTlsConnector,
+    checked_at: Instant,
+}
+
+impl Reloading {
+    /// Reads the files again once the interval has passed, and switches to a connector built
+    /// from them when they changed. A file that fails to load keeps the current connector.
+    fn refresh(&mut self) {
+        if self.interval.is_zero() || self.checked_at.elapsed() < self.interval {
+            return;
+        }
+        self.checked_at = Instant::now();
+        let contents = match self.files.read() {
+            Ok(contents) if contents != self.contents => contents,
+            Ok(_) => return,
+            Err(e) => {
+                error!(
+                    "Failed to read the database certificates, keeping the loaded ones: {:?}",
+                    e
+                );
+                return;
+            }
+        };
+        match self.files.connector(&contents) {
+            Ok(connector) => {
+                info!("Database certificates changed, new connections use the new ones");
+                self.connector = connector;
+                self.contents = contents;
+            }
+            Err(e) => error!("Failed to load the changed database certificates: {:?}", e),
+        }
+    }
+}
+
+/// TLS for the database connections. With `PG_USE_SSL`, the certificate files are read again
+/// every `PG_TLS_RELOAD_SECS` as new connections are made, so rotated or renewed certificates
+/// are picked up without a restart. Connections already in the pool keep the ones they were
+/// made with.
+#[derive(Clone)]
+pub struct PgTls(Connectors);
+
+#[derive(Clone)]
+enum Connectors {
+    Disabled(MakeTlsConnector),
+    Enabled(Arc<Mutex<Reloading>>),
+}
+
+impl PgTls {
+    pub fn from_config(pg_config: &PgConfig) -> anyhow::Result<Self> {
+        if !pg_config.pg_use_ssl {
+            return Ok(PgTls(Connectors::Disabled(MakeTlsConnector::new(
+                TlsConnector::builder()
+                    .danger_accept_invalid_certs(true)
+                    .build()?,
+            ))));
+        }
+        let key_path = pg_config
+            .pg_client_key_path
+            .clone()
+            .context("PG_CLIENT_KEY_PATH: required when PG_USE_SSL is true")?;
+        let identity = match pg_config.pg_client_cert_path.clone() {
+            Some(cert_path) if !cert_path.is_empty() => IdentityFiles::Pem {
+                cert_path,
+                key_path,
+            },
+            _ => IdentityFiles::Pkcs12 {
+                path: key_path,
+                password: pg_config
+                    .pg_client_key_password
+                    .clone()
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or_else(|| DEFAULT_PKCS12_PASSWORD.to_string()),
+            },
+        };
+        let files = TlsFiles {
+            ca_cert_path: pg_config
+                .pg_ca_cert_path
+                .clone()
+                .context("PG_CA_CERT_PATH: required when PG_USE_SSL is true")?,
+            identity,
+        };
+        let contents = files.read()?;
+        let connector = files.connector(&contents)?;
+        let reloading = Reloading {
+            files,
+            interval: pg_config
+                .pg_tls_reload_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RELOAD_INTERVAL),
+            contents,
+            connector,
+            checked_at: Instant::now(),
+        };
+        Ok(PgTls(Connectors::Enabled(Arc::new(Mutex::new(reloading)))))
+    }
+
+    fn current(&self) -> MakeTlsConnector {
+        match &self.0 {
+            Connectors::Disabled(connector) => connector.clone(),
+            Connectors::Enabled(reloading) => {
+                let mut reloading = reloading.lock().unwrap();
+                reloading.refresh();
+                reloading.connector.clone()
+            }
+        }
+    }
+}
+
+impl<S> MakeTlsConnect<S> for PgTls
+where
+    S: AsyncRead + AsyncWrite + Unpin + Debug + 'static + Sync + Send,
+{
+    type Stream = TlsStream<S>;
+    type TlsConnect = TlsConnect<S>;
+    type Error = native_tls::Error;
+
+    fn make_tls_connect(&mut self, domain: &str) -> Result<TlsConnect<S>, native_tls::Error> {
+        MakeTlsConnect::<S>::make_tls_connect(&mut self.current(), domain)
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::NaiveDate;
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::{database::telemetry::get_client, structs::uptime::MakerUptime};
+
+/// Counts one order book sample of the market on `day`, and one quoted sample for each of
+/// `owners`.
+#[instrument(skip(pool, owners), level = "debug", err)]
+pub async fn record_uptime_sample(
+    pool: &Pool,
+    market_address: &str,
+    day: NaiveDate,
+    owners: &[String],
+) -> anyhow::Result<()> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    transaction
+        .execute(
+            r#"INSERT INTO openbook.maker_uptime_samples (market, day, samples)
+            VALUES ($1, $2, 1)
+            ON CONFLICT (market, day) DO UPDATE SET samples = maker_uptime_samples.samples + 1"#,
+            &[&market_address, &day],
+        )
+        .await?;
+    if !owners.is_empty() {
+        transaction
+            .execute(
+                r#"INSERT INTO openbook.maker_uptime (market, day, open_orders_owner, quoted_samples)
+                SELECT $1, $2, owner, 1 FROM unnest($3::text[]) as owner
+                ON CONFLICT (market, day, open_orders_owner)
+                DO UPDATE SET quoted_samples = maker_uptime.quoted_samples + 1"#,
+                &[&market_address, &day, &owners],
+            )
+            .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Uptime of every open orders account that quoted the market between the two days, both
+/// included, highest first.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_maker_uptime(
+    pool: &Pool,
+    market_address: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> anyhow::Result<Vec<MakerUptime>> {
+    let client = get_client(pool).await?;
+
+    let stmt = r#"WITH samples AS (
+        SELECT coalesce(sum(samples), 0)::bigint as samples
+        from openbook.maker_uptime_samples
+        where market = $1
+        and day >= $2
+        and day <= $3
+    )
+    SELECT
+        open_orders_owner as "open_orders_owner",
+        sum(quoted_samples)::bigint as "quoted_samples",
+        samples.samples as "samples"
+        from openbook.maker_uptime, samples
+        where market = $1
+        and day >= $2
+        and day <= $3
+        GROUP BY open_orders_owner, samples.samples
+        ORDER BY 2 desc, 1 asc"#;
+
+    let rows = client.query(stmt, &[&market_address, &from, &to]).await?;
+    Ok(rows.into_iter().map(MakerUptime::from_row).collect())
+}
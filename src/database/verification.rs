@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::instrument;
+
+use crate::structs::{candle::Candle, resolution::Resolution};
+
+/// Fetches up to `limit` randomly chosen complete candles built from fills since `since`, sorted
+/// by start time.
+#[instrument(skip(pool), level = "debug", err)]
+pub async fn fetch_sampled_candles(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> anyhow::Result<Vec<Candle>> {
+    let client = pool.get().await?;
+
+    let stmt = r#"SELECT * FROM (
+        SELECT 
+        market_name as "market_name",
+        start_time as "start_time",
+        end_time as "end_time",
+        resolution as "resolution",
+        open as "open",
+        close as "close",
+        high as "high",
+        low as "low",
+        volume as "volume",
+        complete as "complete",
+        vwap as "vwap",
+        trade_count as "trade_count",
+        quote_volume as "quote_volume",
+        priced_volume as "priced_volume",
+        priced_quote_volume as "priced_quote_volume"
+        from openbook.candles
+        where market_name = $1
+        and resolution = $2
+        and start_time >= $3
+        and complete = true
+        and source = 'fills'
+        ORDER BY random() LIMIT $4
+        ) sampled
+        ORDER BY start_time asc"#;
+
+    let rows = client
+        .query(
+            stmt,
+            &[&market_name, &resolution.to_string(), &since, &limit],
+        )
+        .await?;
+    Ok(rows.into_iter().map(Candle::from_row).collect())
+}
@@ -0,0 +1,330 @@
+use actix_web::{
+    delete, get,
+    http::header::AUTHORIZATION,
+    post,
+    web::{self, Data},
+    HttpRequest, HttpResponse, Scope,
+};
+use deadpool_postgres::Pool;
+use openbook_candles::{
+    database::{
+        api_keys::{fetch_api_key_usage, fetch_api_keys, insert_api_key, revoke_api_key},
+        jobs::{fetch_job, fetch_jobs, insert_job},
+    },
+    structs::{
+        api_keys::{generate_api_key, hash_api_key, IssuedApiKey},
+        job::{JobStatus, NewJob, JOB_KINDS, JOB_KIND_REBUILD},
+        resolution::Resolution,
+        venue::Venue,
+    },
+    utils::{to_timestampz, WebContext},
+};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use super::{
+    server_error::ServerError,
+    validation::{check_range, parse_resolution, resolve_market},
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AdminConfig {
+    /// Bearer token for the admin endpoints, which reject every request unless it is set
+    #[serde(serialize_with = "openbook_candles::utils::settings::redact")]
+    pub admin_token: Option<String>,
+}
+
+impl AdminConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        openbook_candles::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.admin_token.as_deref().map_or(false, |t| !t.is_empty())
+    }
+}
+
+pub fn service(config: AdminConfig) -> Scope {
+    web::scope("/admin")
+        .app_data(Data::new(config))
+        .service(create_key)
+        .service(list_keys)
+        .service(revoke_key)
+        .service(key_usage)
+        .service(rebuild)
+        .service(create_job)
+        .service(list_jobs)
+        .service(get_job)
+}
+
+fn authorize(req: &HttpRequest, config: &AdminConfig) -> Result<(), ServerError> {
+    let expected = match &config.admin_token {
+        Some(token) if config.is_enabled() => token,
+        _ => return Err(ServerError::Unauthorized),
+    };
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(ServerError::Unauthorized)?;
+    // comparing digests keeps the comparison time independent of how much of the token matched
+    if hash_api_key(provided) != hash_api_key(expected) {
+        return Err(ServerError::Unauthorized);
+    }
+    Ok(())
+}
+
+fn admin_pool(context: &WebContext) -> Result<&Pool, ServerError> {
+    context.admin_pool.as_ref().ok_or(ServerError::Unauthorized)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateKeyParams {
+    pub name: String,
+    pub requests_per_minute: i32,
+    /// Skip the delay of embargoed markets
+    #[serde(default)]
+    pub realtime: bool,
+}
+
+#[post("/keys")]
+pub async fn create_key(
+    req: HttpRequest,
+    params: web::Json<CreateKeyParams>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    if params.name.is_empty() || params.requests_per_minute <= 0 {
+        return Err(ServerError::WrongParameters);
+    }
+
+    let key = generate_api_key();
+    let api_key = insert_api_key(
+        admin_pool(&context)?,
+        &hash_api_key(&key),
+        &params.name,
+        params.requests_per_minute,
+        params.realtime,
+    )
+    .await
+    .map_err(ServerError::db)?;
+    context.rate_limiter.add_api_key(api_key.clone());
+
+    Ok(HttpResponse::Created().json(IssuedApiKey { key, api_key }))
+}
+
+#[get("/keys")]
+pub async fn list_keys(
+    req: HttpRequest,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let keys = fetch_api_keys(admin_pool(&context)?)
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+#[delete("/keys/{id}")]
+pub async fn revoke_key(
+    req: HttpRequest,
+    id: web::Path<i64>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let api_key = revoke_api_key(admin_pool(&context)?, *id)
+        .await
+        .map_err(ServerError::db)?
+        .ok_or(ServerError::ApiKeyNotFound)?;
+    // other instances stop accepting the key on their next reload
+    context.rate_limiter.remove_api_key(api_key.id);
+    Ok(HttpResponse::Ok().json(api_key))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageParams {
+    pub days: Option<i32>,
+}
+
+#[get("/keys/{id}/usage")]
+pub async fn key_usage(
+    req: HttpRequest,
+    id: web::Path<i64>,
+    info: web::Query<UsageParams>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let days = info.days.unwrap_or(30).clamp(1, 366);
+    let usage = fetch_api_key_usage(admin_pool(&context)?, *id, days)
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(usage))
+}
+
+fn default_job_max_attempts() -> i32 {
+    3
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobParams {
+    /// `rebuild`, `backfill`, `prune` or `gap_repair`
+    pub kind: String,
+    /// Every market when left out, except for rebuilds which need one
+    pub market_name: Option<String>,
+    /// Every resolution when left out, only used by rebuilds
+    pub resolutions: Option<Vec<String>>,
+    /// Unix seconds, needed by rebuilds and limits gap repairs
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// Higher runs first
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_job_max_attempts")]
+    pub max_attempts: i32,
+}
+
+/// Checks the parameters a job of its kind needs and turns market keys and resolutions into
+/// the names they are stored under.
+fn new_job(params: &JobParams, context: &WebContext) -> Result<NewJob, ServerError> {
+    if !JOB_KINDS.contains(&params.kind.as_str()) || params.max_attempts < 1 {
+        return Err(ServerError::WrongParameters);
+    }
+    let market_name = match &params.market_name {
+        Some(key) => {
+            let market = resolve_market(key, context)?;
+            // composites have no fills of their own, they follow their markets
+            if market.venue == Venue::All {
+                return Err(ServerError::WrongParameters);
+            }
+            Some(market.name.clone())
+        }
+        None => None,
+    };
+    let resolutions: Vec<String> = match &params.resolutions {
+        Some(resolutions) if !resolutions.is_empty() => resolutions
+            .iter()
+            .map(|r| parse_resolution(r).map(|r| r.to_string()))
+            .collect::<Result<_, _>>()?,
+        _ => Resolution::iter().map(|r| r.to_string()).collect(),
+    };
+    let (start_time, end_time) = match (params.from, params.to) {
+        (Some(from), Some(to)) => {
+            let (from, to) = (to_timestampz(from), to_timestampz(to));
+            check_range(from, to)?;
+            (Some(from), Some(to))
+        }
+        (None, None) => (None, None),
+        _ => return Err(ServerError::WrongParameters),
+    };
+    if params.kind == JOB_KIND_REBUILD && (market_name.is_none() || start_time.is_none()) {
+        return Err(ServerError::WrongParameters);
+    }
+
+    Ok(NewJob {
+        kind: params.kind.clone(),
+        market_name,
+        resolutions,
+        start_time,
+        end_time,
+        priority: params.priority,
+        max_attempts: params.max_attempts,
+    })
+}
+
+#[post("/jobs")]
+pub async fn create_job(
+    req: HttpRequest,
+    params: web::Json<JobParams>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let job = insert_job(admin_pool(&context)?, &new_job(&params, &context)?)
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RebuildParams {
+    pub market_name: String,
+    /// Every resolution when left out
+    pub resolutions: Option<Vec<String>>,
+    /// Unix seconds
+    pub from: u64,
+    pub to: u64,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// Shorthand for queuing a rebuild job
+#[post("/rebuild")]
+pub async fn rebuild(
+    req: HttpRequest,
+    params: web::Json<RebuildParams>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let params = params.into_inner();
+    let job_params = JobParams {
+        kind: JOB_KIND_REBUILD.to_string(),
+        market_name: Some(params.market_name),
+        resolutions: params.resolutions,
+        from: Some(params.from),
+        to: Some(params.to),
+        priority: params.priority,
+        max_attempts: default_job_max_attempts(),
+    };
+    let job = insert_job(admin_pool(&context)?, &new_job(&job_params, &context)?)
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobsParams {
+    pub status: Option<JobStatus>,
+    pub kind: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[get("/jobs")]
+pub async fn list_jobs(
+    req: HttpRequest,
+    info: web::Query<JobsParams>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let limit = info.limit.unwrap_or(100).clamp(1, 1000);
+    let jobs = fetch_jobs(
+        admin_pool(&context)?,
+        info.status,
+        info.kind.as_deref(),
+        limit,
+    )
+    .await
+    .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+#[get("/jobs/{id}")]
+pub async fn get_job(
+    req: HttpRequest,
+    id: web::Path<i64>,
+    config: Data<AdminConfig>,
+    context: Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    authorize(&req, &config)?;
+    let job = fetch_job(admin_pool(&context)?, *id)
+        .await
+        .map_err(ServerError::db)?
+        .ok_or(ServerError::JobNotFound)?;
+    Ok(HttpResponse::Ok().json(job))
+}
@@ -0,0 +1,79 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use openbook_candles::{
+    database::fetch::{fetch_latest_candle_changes, fetch_latest_candle_updates},
+    structs::resolution::Resolution,
+    utils::WebContext,
+};
+use tracing::warn;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Watches for new minute candle batches and refreshes the most requested chart windows of a
+/// market as soon as its batch lands. Streaming clients get the market's newest candles then too.
+pub async fn warm_candle_cache(context: Data<WebContext>) {
+    // when each market's minute candles were last written, moves whenever a batch lands
+    let mut last_seen: HashMap<String, DateTime<Utc>> = HashMap::new();
+    loop {
+        match context
+            .read_pool
+            .run(|pool| fetch_latest_candle_updates(pool, Resolution::R1m))
+            .await
+        {
+            Ok(updates) => {
+                for (market_name, updated_at) in updates {
+                    if last_seen.get(&market_name) == Some(&updated_at) {
+                        continue;
+                    }
+                    last_seen.insert(market_name.clone(), updated_at);
+                    context
+                        .candle_cache
+                        .record_batch(&market_name, updated_at)
+                        .await;
+                    warm_market(&context, &market_name).await;
+                    publish_candle_updates(&context, &market_name).await;
+                }
+            }
+            Err(e) => warn!("Failed to check for new candle batches: {:?}", e),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn publish_candle_updates(context: &Data<WebContext>, market_name: &str) {
+    if context.candle_updates.receiver_count() == 0 {
+        return;
+    }
+    match context
+        .read_pool
+        .run(|pool| fetch_latest_candle_changes(pool, market_name))
+        .await
+    {
+        Ok(changes) => {
+            for change in changes {
+                // only fails when the last subscriber left in the meantime
+                let _ = context.candle_updates.send(change);
+            }
+        }
+        Err(e) => warn!("Failed to publish candles for {}: {:?}", market_name, e),
+    }
+}
+
+async fn warm_market(context: &Data<WebContext>, market_name: &str) {
+    let now = Utc::now();
+    for (resolution, span) in context.candle_cache.hot_windows(market_name).await {
+        if let Err(e) = context
+            .read_pool
+            .run(|pool| {
+                context
+                    .candle_cache
+                    .fetch_candles(pool, market_name, resolution, now - span, now)
+            })
+            .await
+        {
+            warn!("Failed to warm candles for {}: {:?}", market_name, e);
+        }
+    }
+}
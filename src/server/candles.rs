@@ -1,44 +1,493 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
 use openbook_candles::{
-    database::fetch::fetch_candles_from,
-    structs::{markets::valid_market, resolution::Resolution, tradingview::TvResponse},
+    database::fetch::{
+        fetch_candles_of_pairs, fetch_fills_from, fetch_recent_candles, fetch_rolling_candle,
+    },
+    structs::{
+        candle::{fill_candle_gaps, heikin_ashi, Candle, CandlePage},
+        markets::MarketInfo,
+        resolution::Resolution,
+        rolling::RollingCandle,
+        tradingview::TvResponse,
+        venue::Venue,
+    },
     utils::{to_timestampz, WebContext},
+    worker::candle_batching::aggregate::{
+        aggregate_fills_to_candles, combine_session_candles, session_start, AggregationOptions,
+    },
 };
 
-use crate::server_error::ServerError;
+use super::{
+    embargo::{drop_embargoed_candles, embargoed_candle_count, visible_until},
+    freshness::{envelope, EnvelopeParams},
+    server_error::{ErrorBody, ServerError},
+    usd::candle_usd_volumes,
+    validation::{
+        check_candle_range, check_range, check_resolution, parse_resolution, parse_utc_offset,
+        resolve_market, resolve_market_on,
+    },
+};
 
 use {
-    actix_web::{get, web, HttpResponse},
-    serde::Deserialize,
+    actix_web::{get, web, HttpRequest, HttpResponse},
+    serde::{Deserialize, Serialize},
+    utoipa::{IntoParams, ToSchema},
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CandleParams {
+    /// Market name or address
+    pub market_name: String,
+    /// Unix seconds
+    pub from: Option<u64>,
+    /// Unix seconds
+    pub to: u64,
+    /// 1M, 3M, 5M, 15M, 30M, 1H, 2H, 4H, D, W (weeks from Monday) or M (calendar months)
+    pub resolution: String,
+    /// Number of candles ending at `to`, used instead of `from`
+    pub countback: Option<u16>,
+    pub limit: Option<u16>,
+    pub offset: Option<u32>,
+    #[param(inline)]
+    pub order: Option<CandleOrder>,
+    /// Synthesize zero-volume candles for buckets without trades
+    pub fill_gaps: Option<bool>,
+    /// Rebuild the candles from every fill, including anomalous prints
+    pub raw: Option<bool>,
+    /// openbook, phoenix or all (composites), names can then leave out their `@<venue>` suffix
+    #[param(inline)]
+    pub venue: Option<Venue>,
+    /// UTC offset daily candles start at midnight of, e.g. `+09:00` or `-05:00`. Only with D
+    pub tz: Option<String>,
+    /// Candles as stored, or transformed into Heikin-Ashi candles
+    #[serde(rename = "type")]
+    #[param(inline)]
+    pub candle_type: Option<CandleType>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CandleOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CandleType {
+    Regular,
+    HeikinAshi,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecentCandleParams {
+    /// Market name or address
+    pub market_name: String,
+    pub resolution: String,
+    /// Number of candles, at most 2000
+    pub n: u16,
+    /// openbook, phoenix or all (composites), names can then leave out their `@<venue>` suffix
+    #[param(inline)]
+    pub venue: Option<Venue>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RollingCandleParams {
+    /// Market name or address
     pub market_name: String,
+    /// Length of the trailing window, 24 by default and at most 168
+    pub hours: Option<u16>,
+    /// openbook, phoenix or all (composites), names can then leave out their `@<venue>` suffix
+    #[param(inline)]
+    pub venue: Option<Venue>,
+}
+
+/// Longest trailing window of `/candles/rolling`, a week of minute candles
+const MAX_ROLLING_HOURS: u16 = 168;
+
+/// Upper bound on the number of candles returned by `/candles/recent`
+const MAX_RECENT_CANDLES: u16 = 2000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BatchCandleParams {
+    /// Comma separated `market:resolution` pairs, markets by name or address
+    pub pairs: String,
     pub from: u64,
     pub to: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchCandles {
+    pub market_name: String,
     pub resolution: String,
+    pub candles: TvResponse,
+}
+
+/// Upper bound on the number of pairs per `/candles/batch` request
+const MAX_BATCH_PAIRS: usize = 50;
+
+/// Upper bound on `countback` and `limit` of `/candles`
+const MAX_CANDLES_PER_PAGE: u16 = 5000;
+
+/// Coarsest resolution the market keeps whose candles fit into days starting `offset` after UTC
+/// midnight.
+fn session_constituent(offset: Duration, market: &MarketInfo) -> Resolution {
+    [
+        Resolution::R4h,
+        Resolution::R2h,
+        Resolution::R1h,
+        Resolution::R30m,
+        Resolution::R15m,
+        Resolution::R5m,
+    ]
+    .into_iter()
+    .filter(|r| market.keeps(*r))
+    .find(|r| offset.num_minutes() % r.get_duration().num_minutes() == 0)
+    .unwrap_or(Resolution::R1m)
+}
+
+/// Longest range `/candles` rebuilds from fills with `raw=true`
+fn max_raw_range() -> Duration {
+    Duration::days(7)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/candles",
+    tag = "candles",
+    params(CandleParams, EnvelopeParams),
+    responses(
+        (status = 200, description = "Candles of the range", body = TvResponse),
+        (status = 400, description = "Unknown resolution or invalid parameters", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`, or range too long", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
 #[get("/candles")]
 pub async fn get_candles(
+    req: HttpRequest,
     info: web::Query<CandleParams>,
+    envelope_params: web::Query<EnvelopeParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let resolution = parse_resolution(&info.resolution)?;
+    let session = match &info.tz {
+        Some(tz) => parse_utc_offset(tz)?,
+        None => None,
+    };
+    if session.is_some() && resolution != Resolution::R1d {
+        return Err(ServerError::WrongParameters);
+    }
+
+    let market = resolve_market_on(&info.market_name, info.venue, &context)?;
+    // raw candles are built from the fills in any resolution
+    if info.raw != Some(true) {
+        check_resolution(market, resolution)?;
+    }
+
+    if info
+        .countback
+        .map_or(false, |n| n == 0 || n > MAX_CANDLES_PER_PAGE)
+        || info.limit.map_or(false, |n| n > MAX_CANDLES_PER_PAGE)
+    {
+        return Err(ServerError::WrongParameters);
+    }
+    if let (None, Some(from)) = (info.countback, info.from) {
+        let (from, to) = (to_timestampz(from), to_timestampz(info.to));
+        match info.limit {
+            // a page of a long range is bounded by its limit already
+            Some(_) => check_range(from, to)?,
+            None => check_candle_range(resolution, from, to, context.max_range_candles)?,
+        }
+    }
+
+    let until = visible_until(&req, &context, &market.name);
+    let to = match until {
+        Some(until) => to_timestampz(info.to).min(until),
+        None => to_timestampz(info.to),
+    };
+    let from = match (info.countback, info.from) {
+        (Some(n), _) => to - resolution.get_duration() * n as i32,
+        (None, Some(from)) => to_timestampz(from),
+        (None, None) => return Err(ServerError::WrongParameters),
+    };
+    // days of another offset are put together from candles that fit into them
+    let (fetch_resolution, from) = match session {
+        Some(offset) => (
+            session_constituent(offset, market),
+            session_start(from, offset),
+        ),
+        None => (resolution, from),
+    };
+    // Heikin-Ashi candles are seeded by the candle before the range
+    let heikin_ashi_candles = info.candle_type == Some(CandleType::HeikinAshi);
+    let fetch_from = if heikin_ashi_candles {
+        from - resolution.get_duration()
+    } else {
+        from
+    };
+
+    context
+        .candle_cache
+        .record_access(&market.name, fetch_resolution, fetch_from, to)
+        .await;
+    // chart clients tend to ask for the same window at the same moment, right after a bar closes
+    let request_key = format!(
+        "{}:{}:{}:{}",
+        market.name,
+        fetch_resolution,
+        fetch_from.timestamp(),
+        to.timestamp()
+    );
+    let candles = if info.raw == Some(true) {
+        raw_candles(&context, market, fetch_resolution, fetch_from, to).await?
+    } else {
+        context
+            .candle_requests
+            .run(&request_key, || {
+                context.read_pool.run(|pool| {
+                    context.candle_cache.fetch_candles(
+                        pool,
+                        &market.name,
+                        fetch_resolution,
+                        fetch_from,
+                        to,
+                    )
+                })
+            })
+            .await
+            .map_err(ServerError::db)?
+    };
+    let candles = match session {
+        Some(offset) => combine_session_candles(&candles, offset),
+        None => candles,
+    };
+    let mut candles = drop_embargoed_candles(candles, until, usize::MAX);
+    if info.fill_gaps == Some(true) {
+        candles = fill_candle_gaps(candles, resolution);
+    }
+    if heikin_ashi_candles {
+        candles = heikin_ashi(candles);
+        candles.retain(|c| c.start_time >= from);
+    }
+    if let Some(n) = info.countback {
+        let excess = candles.len().saturating_sub(n as usize);
+        candles.drain(..excess);
+    }
+    let page = CandlePage {
+        descending: info.order == Some(CandleOrder::Desc),
+        offset: info.offset.unwrap_or(0) as usize,
+        limit: info.limit.map(|n| n as usize),
+    };
+    let candles = page.apply(candles);
+    let volume_usd = candle_usd_volumes(&context, &market.name, resolution, &candles).await?;
+
+    let response = TvResponse::candles_to_tv(candles).with_volume_usd(volume_usd);
+    if envelope_params.envelope {
+        let wrapped = envelope(&context, &[&market.name], until, response).await;
+        return Ok(HttpResponse::Ok().json(wrapped));
+    }
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Candles rebuilt from every stored fill of the range, without the outlier filter the worker
+/// may apply. Fills pruned by retention are gone, so are the candles they made up.
+async fn raw_candles(
+    context: &WebContext,
+    market: &MarketInfo,
+    resolution: Resolution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<Candle>, ServerError> {
+    // composites have no fills of their own
+    if market.venue == Venue::All {
+        return Err(ServerError::WrongParameters);
+    }
+    if to - from > max_raw_range() {
+        return Err(ServerError::InvalidRange(format!(
+            "raw candles span at most {} days",
+            max_raw_range().num_days()
+        )));
+    }
+    let fills = context
+        .read_pool
+        .run(|pool| fetch_fills_from(pool, &market.address, from, to))
+        .await
+        .map_err(ServerError::db)?;
+    let options = AggregationOptions {
+        market_name: market.name.clone(),
+        last_price: None,
+        as_of: Utc::now(),
+        outlier_filter: None,
+    };
+    Ok(aggregate_fills_to_candles(
+        &fills,
+        resolution,
+        from..to,
+        &options,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/candles/recent",
+    tag = "candles",
+    params(RecentCandleParams, EnvelopeParams),
+    responses(
+        (status = 200, description = "The newest candles", body = TvResponse),
+        (status = 400, description = "Unknown resolution or invalid count", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/candles/recent")]
+pub async fn get_recent_candles(
+    req: HttpRequest,
+    info: web::Query<RecentCandleParams>,
+    envelope_params: web::Query<EnvelopeParams>,
     context: web::Data<WebContext>,
 ) -> Result<HttpResponse, ServerError> {
-    let resolution =
-        Resolution::from_str(info.resolution.as_str()).map_err(|_| ServerError::WrongResolution)?;
+    let resolution = parse_resolution(&info.resolution)?;
 
-    if !valid_market(&info.market_name, &context.markets) {
+    let market = resolve_market_on(&info.market_name, info.venue, &context)?;
+    check_resolution(market, resolution)?;
+    if info.n == 0 || info.n > MAX_RECENT_CANDLES {
+        return Err(ServerError::WrongParameters);
+    }
+
+    let until = visible_until(&req, &context, &market.name);
+    let limit = info.n as i64 + embargoed_candle_count(until, resolution);
+    let candles = match context
+        .read_pool
+        .run(|pool| fetch_recent_candles(pool, &market.name, resolution, limit))
+        .await
+    {
+        Ok(c) => drop_embargoed_candles(c, until, info.n as usize),
+        Err(e) => return Err(ServerError::db(e)),
+    };
+    let volume_usd = candle_usd_volumes(&context, &market.name, resolution, &candles).await?;
+
+    let response = TvResponse::candles_to_tv(candles).with_volume_usd(volume_usd);
+    if envelope_params.envelope {
+        let wrapped = envelope(&context, &[&market.name], until, response).await;
+        return Ok(HttpResponse::Ok().json(wrapped));
+    }
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// A single candle over the trailing window, e.g. the last 24 hours for a ticker, aggregated from
+/// minute candles so no fills are scanned.
+#[utoipa::path(
+    get,
+    path = "/api/candles/rolling",
+    tag = "candles",
+    params(RollingCandleParams),
+    responses(
+        (status = 200, description = "Candle of the trailing window", body = RollingCandle),
+        (status = 400, description = "Invalid window", body = ErrorBody),
+        (status = 404, description = "Unknown market or no candles in the window", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/candles/rolling")]
+pub async fn get_rolling_candle(
+    req: HttpRequest,
+    info: web::Query<RollingCandleParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market_on(&info.market_name, info.venue, &context)?;
+    let hours = info.hours.unwrap_or(24);
+    if hours == 0 || hours > MAX_ROLLING_HOURS {
+        return Err(ServerError::WrongParameters);
+    }
+    // the newest minute is included while it's in progress, unless the market is embargoed
+    let end = match visible_until(&req, &context, &market.name) {
+        Some(until) => until.duration_trunc(Duration::minutes(1)).unwrap(),
+        None => Utc::now(),
+    };
+    let start = end - Duration::hours(hours as i64);
+
+    let candle = context
+        .read_pool
+        .run(|pool| fetch_rolling_candle(pool, &market.name, start, end))
+        .await
+        .map_err(ServerError::db)?
+        .ok_or(ServerError::PriceNotFound)?;
+    Ok(HttpResponse::Ok().json(candle))
+}
+
+/// Candles of several markets and resolutions over the same range, read in a single query.
+#[utoipa::path(
+    get,
+    path = "/api/candles/batch",
+    tag = "candles",
+    params(BatchCandleParams),
+    responses(
+        (status = 200, description = "Candles per requested pair", body = [BatchCandles]),
+        (status = 400, description = "Unknown resolution or too many pairs", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`, or range too long", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/candles/batch")]
+pub async fn get_batch_candles(
+    req: HttpRequest,
+    info: web::Query<BatchCandleParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let mut pairs: Vec<(String, Resolution)> = vec![];
+    for pair in info.pairs.split(',').filter(|p| !p.is_empty()) {
+        // market names and addresses never contain colons
+        let (market_name, resolution) =
+            pair.rsplit_once(':').ok_or(ServerError::WrongParameters)?;
+        let resolution = parse_resolution(resolution)?;
+        let market = resolve_market(market_name, &context)?;
+        check_resolution(market, resolution)?;
+        let pair = (market.name.clone(), resolution);
+        if !pairs.contains(&pair) {
+            pairs.push(pair);
+        }
+    }
+    if pairs.is_empty() || pairs.len() > MAX_BATCH_PAIRS {
         return Err(ServerError::WrongParameters);
     }
 
     let from = to_timestampz(info.from);
     let to = to_timestampz(info.to);
+    for (_, resolution) in pairs.iter() {
+        check_candle_range(*resolution, from, to, context.max_range_candles)?;
+    }
+    let mut candles = context
+        .read_pool
+        .run(|pool| fetch_candles_of_pairs(pool, &pairs, from, to))
+        .await
+        .map_err(ServerError::db)?;
 
-    let candles =
-        match fetch_candles_from(&context.pool, &info.market_name, resolution, from, to).await {
-            Ok(c) => c,
-            Err(_) => return Err(ServerError::DbQueryError),
-        };
-
-    Ok(HttpResponse::Ok().json(TvResponse::candles_to_tv(candles)))
+    let response: Vec<BatchCandles> = pairs
+        .into_iter()
+        .map(|(market_name, resolution)| {
+            let resolution = resolution.to_string();
+            let (pair_candles, rest): (Vec<Candle>, Vec<Candle>) = candles
+                .into_iter()
+                .partition(|c| c.market_name == market_name && c.resolution == resolution);
+            candles = rest;
+            let until = visible_until(&req, &context, &market_name);
+            BatchCandles {
+                candles: TvResponse::candles_to_tv(drop_embargoed_candles(
+                    pair_candles,
+                    until,
+                    usize::MAX,
+                )),
+                market_name,
+                resolution,
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(response))
 }
@@ -0,0 +1,41 @@
+use super::server_error::ServerError;
+use actix_web::{get, web, HttpResponse};
+use openbook_candles::{
+    database::fetch::fetch_candle_changes, structs::changes::ChangesResponse, utils::WebContext,
+};
+use serde::Deserialize;
+
+/// Upper bound on the number of changes returned per page
+const MAX_CHANGES: u32 = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesParams {
+    /// Cursor from the previous page, 0 starts from the beginning
+    #[serde(default)]
+    pub since: i64,
+    pub limit: Option<u32>,
+}
+
+#[get("/changes")]
+pub async fn get_changes(
+    info: web::Query<ChangesParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let limit = info.limit.unwrap_or(MAX_CHANGES);
+    if info.since < 0 || limit == 0 || limit > MAX_CHANGES {
+        return Err(ServerError::WrongParameters);
+    }
+
+    let changes = context
+        .read_pool
+        .run(|pool| fetch_candle_changes(pool, info.since, limit as i64))
+        .await
+        .map_err(ServerError::db)?;
+
+    let next_cursor = changes.last().map(|c| c.version).unwrap_or(info.since);
+    Ok(HttpResponse::Ok().json(ChangesResponse {
+        has_more: changes.len() == limit as usize,
+        changes,
+        next_cursor,
+    }))
+}
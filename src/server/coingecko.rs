@@ -1,21 +1,30 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration as StdDuration, SystemTime, UNIX_EPOCH};
 
-use crate::server_error::ServerError;
+use super::{
+    freshness::{envelope, EnvelopeParams},
+    server_error::{ErrorBody, ServerError},
+};
 use actix_web::{get, web, HttpResponse, Scope};
+use chrono::Duration;
 use futures::join;
 use openbook_candles::{
     database::fetch::{fetch_coingecko_24h_high_low, fetch_coingecko_24h_volume},
     structs::{
+        cache_backend::{get_json, set_json},
         coingecko::{
             CoinGeckoOrderBook, CoinGeckoPair, CoinGeckoTicker, PgCoinGecko24HighLow,
             PgCoinGecko24HourVolume,
         },
+        orderbook::quote_usd_price,
         slab::get_orderbooks_with_depth,
+        venue::Venue,
     },
     utils::WebContext,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use solana_client::nonblocking::rpc_client::RpcClient;
+use tracing::warn;
+use utoipa::IntoParams;
 
 pub fn service() -> Scope {
     web::scope("/coingecko")
@@ -24,12 +33,46 @@ pub fn service() -> Scope {
         .service(orderbook)
 }
 
-#[derive(Debug, Deserialize)]
+fn default_coingecko_max_markets_per_query() -> usize {
+    200
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CoinGeckoConfig {
+    /// Ticker aggregates are split into queries of at most this many markets, which run
+    /// concurrently. One query over hundreds of markets gets slow to plan.
+    #[serde(default = "default_coingecko_max_markets_per_query")]
+    pub coingecko_max_markets_per_query: usize,
+}
+
+impl CoinGeckoConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        openbook_candles::utils::settings::load()
+    }
+}
+
+/// Top of book older than this is left out of ticker responses
+fn max_snapshot_age() -> Duration {
+    Duration::seconds(60)
+}
+
+/// Tickers and order books are served from the response cache for this long
+const RESPONSE_CACHE_TTL: StdDuration = StdDuration::from_secs(5);
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct OrderBookParams {
     pub ticker_id: String, // market_name
+    /// Levels per side
     pub depth: usize,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/coingecko/pairs",
+    tag = "coingecko",
+    responses((status = 200, description = "Every tracked market", body = [CoinGeckoPair]))
+)]
 #[get("/pairs")]
 pub async fn pairs(context: web::Data<WebContext>) -> Result<HttpResponse, ServerError> {
     let markets = context.markets.clone();
@@ -47,27 +90,72 @@ pub async fn pairs(context: web::Data<WebContext>) -> Result<HttpResponse, Serve
     Ok(HttpResponse::Ok().json(pairs))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/coingecko/tickers",
+    tag = "coingecko",
+    params(EnvelopeParams),
+    responses(
+        (status = 200, description = "24 hour tickers of every market", body = [CoinGeckoTicker]),
+    )
+)]
 #[get("/tickers")]
-pub async fn tickers(context: web::Data<WebContext>) -> Result<HttpResponse, ServerError> {
-    // let client = RpcClient::new(context.rpc_url.clone());
+pub async fn tickers(
+    envelope_params: web::Query<EnvelopeParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let cache_key = "coingecko:tickers";
+    let tickers = match get_json::<Vec<CoinGeckoTicker>>(context.cache.as_ref(), cache_key).await {
+        Some(tickers) => tickers,
+        None => fetch_tickers(&context, cache_key).await?,
+    };
+    if envelope_params.envelope {
+        let market_names: Vec<&str> = context.markets.iter().map(|m| m.name.as_str()).collect();
+        let wrapped = envelope(&context, &market_names, None, tickers).await;
+        return Ok(HttpResponse::Ok().json(wrapped));
+    }
+    Ok(HttpResponse::Ok().json(tickers))
+}
+
+/// Builds the tickers once for concurrent requests and caches them.
+async fn fetch_tickers(
+    context: &WebContext,
+    cache_key: &str,
+) -> Result<Vec<CoinGeckoTicker>, ServerError> {
+    let tickers = context
+        .ticker_requests
+        .run(cache_key, || build_tickers(context))
+        .await
+        .map_err(ServerError::db)?;
+
+    set_json(
+        context.cache.as_ref(),
+        cache_key,
+        &tickers,
+        RESPONSE_CACHE_TTL,
+    )
+    .await;
+    Ok(tickers)
+}
+
+async fn build_tickers(context: &WebContext) -> anyhow::Result<Vec<CoinGeckoTicker>> {
     let markets = &context.markets;
     let market_addresses = markets.iter().map(|x| x.address.as_str()).collect();
 
-    // let bba_fut = get_best_bids_and_asks(client, markets);
-    let volume_fut = fetch_coingecko_24h_volume(&context.pool, &market_addresses);
-    let high_low_fut = fetch_coingecko_24h_high_low(&context.pool, &market_addresses);
+    let batch_size = context.coingecko_max_markets_per_query;
+    let volume_fut = context
+        .read_pool
+        .run(|pool| fetch_coingecko_24h_volume(pool, &market_addresses, batch_size));
+    let high_low_fut = context
+        .read_pool
+        .run(|pool| fetch_coingecko_24h_high_low(pool, &market_addresses, batch_size));
 
     let (volume_query, high_low_quey) = join!(volume_fut, high_low_fut,);
 
-    let raw_volumes = match volume_query {
-        Ok(c) => c,
-        Err(_) => return Err(ServerError::DbQueryError),
-    };
-    let high_low = match high_low_quey {
-        Ok(c) => c,
-        Err(_) => return Err(ServerError::DbQueryError),
-    };
+    let raw_volumes = volume_query?;
+    let high_low = high_low_quey?;
 
+    let snapshots = context.orderbook_snapshots.read().await;
     let default_hl = PgCoinGecko24HighLow::default();
     let default_volume = PgCoinGecko24HourVolume::default();
     let tickers = markets
@@ -82,6 +170,17 @@ pub async fn tickers(context: web::Data<WebContext>) -> Result<HttpResponse, Ser
                 .iter()
                 .find(|x| x.address == m.address)
                 .unwrap_or(&default_volume);
+            let snapshot = snapshots
+                .get(&m.address)
+                .filter(|s| s.is_fresh(max_snapshot_age()));
+            let usd_price = quote_usd_price(m, markets, &snapshots);
+            let (plus_depth, minus_depth) = match (snapshot, usd_price) {
+                (Some(s), Some(p)) => (
+                    Some((s.ask_depth * p).to_string()),
+                    Some((s.bid_depth * p).to_string()),
+                ),
+                _ => (None, None),
+            };
             CoinGeckoTicker {
                 ticker_id: m.name.clone(),
                 address: m.address.clone(),
@@ -90,37 +189,66 @@ pub async fn tickers(context: web::Data<WebContext>) -> Result<HttpResponse, Ser
                 last_price: high_low.close.to_string(),
                 base_volume: volume.base_size.to_string(),
                 target_volume: volume.quote_size.to_string(),
+                bid: snapshot.and_then(|s| s.best_bid).map(|p| p.to_string()),
+                ask: snapshot.and_then(|s| s.best_ask).map(|p| p.to_string()),
+                plus_2_percent_depth: plus_depth,
+                minus_2_percent_depth: minus_depth,
                 high: high_low.high.to_string(),
                 low: high_low.low.to_string(),
             }
         })
         .collect::<Vec<CoinGeckoTicker>>();
-
-    Ok(HttpResponse::Ok().json(tickers))
+    Ok(tickers)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/coingecko/orderbook",
+    tag = "coingecko",
+    params(OrderBookParams),
+    responses(
+        (status = 200, description = "Order book of the market", body = CoinGeckoOrderBook),
+        (status = 404, description = "Unknown ticker_id, or a market outside OpenBook", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
 #[get("/orderbook")] // TODO: implement an optional geyser version
 pub async fn orderbook(
     info: web::Query<OrderBookParams>,
     context: web::Data<WebContext>,
 ) -> Result<HttpResponse, ServerError> {
     let client = RpcClient::new(context.rpc_url.clone());
-    let market_name = &info.ticker_id;
+    // only OpenBook books are read
     let market = context
-        .markets
-        .iter()
-        .find(|m| m.name == *market_name)
+        .find_market(&info.ticker_id)
+        .filter(|m| m.venue == Venue::OpenBook)
         .ok_or(ServerError::MarketNotFound)?;
     let depth = info.depth;
+    let cache_key = format!("coingecko:orderbook:{}:{}", market.name, depth);
+    if let Some(result) = get_json::<CoinGeckoOrderBook>(context.cache.as_ref(), &cache_key).await {
+        return Ok(HttpResponse::Ok().json(result));
+    }
 
     let now = SystemTime::now();
     let timestamp = now.duration_since(UNIX_EPOCH).unwrap().as_millis();
-    let (bid_levels, ask_levels) = get_orderbooks_with_depth(client, market, depth).await;
+    let (bid_levels, ask_levels) = get_orderbooks_with_depth(client, market, depth)
+        .await
+        .map_err(|e| {
+            warn!("Failed to read the order book of {}: {:?}", market.name, e);
+            ServerError::InternalError
+        })?;
     let result = CoinGeckoOrderBook {
         timestamp: timestamp.to_string(),
         ticker_id: market.name.clone(),
         bids: bid_levels,
         asks: ask_levels,
     };
+    set_json(
+        context.cache.as_ref(),
+        &cache_key,
+        &result,
+        RESPONSE_CACHE_TTL,
+    )
+    .await;
     Ok(HttpResponse::Ok().json(result))
 }
@@ -0,0 +1,81 @@
+use actix_cors::Cors;
+use actix_web::http::header::{HeaderName, AUTHORIZATION, CONTENT_TYPE, ETAG, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+
+use super::rate_limit::API_KEY_HEADER;
+
+fn default_cors_max_age_secs() -> usize {
+    3600
+}
+
+/// Origins browsers may call the API from. Without any, no CORS headers are sent and browsers
+/// only allow same origin requests.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CorsConfig {
+    /// Comma separated origins allowed on the public read endpoints, or `*` for any
+    pub cors_allowed_origins: Option<String>,
+    /// Comma separated origins allowed on the admin endpoints. A wildcard is not accepted here.
+    pub cors_admin_allowed_origins: Option<String>,
+    /// How long browsers may cache a preflight response
+    #[serde(default = "default_cors_max_age_secs")]
+    pub cors_max_age_secs: usize,
+}
+
+impl CorsConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let config: CorsConfig = openbook_candles::utils::settings::load()?;
+        if origins(&config.cors_admin_allowed_origins).contains(&"*") {
+            anyhow::bail!("CORS_ADMIN_ALLOWED_ORIGINS must list origins, not a wildcard");
+        }
+        Ok(config)
+    }
+
+    pub fn public_enabled(&self) -> bool {
+        !origins(&self.cors_allowed_origins).is_empty()
+    }
+
+    pub fn admin_enabled(&self) -> bool {
+        !origins(&self.cors_admin_allowed_origins).is_empty()
+    }
+
+    /// GET only, with the API key header and the caching and rate limit headers readable.
+    pub fn public(&self) -> Cors {
+        let allowed = origins(&self.cors_allowed_origins);
+        let cors = if allowed.contains(&"*") {
+            Cors::default().allow_any_origin()
+        } else {
+            allowed
+                .into_iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        };
+        cors.allowed_methods(["GET"])
+            .allowed_header(API_KEY_HEADER)
+            .expose_headers([
+                ETAG,
+                RETRY_AFTER,
+                HeaderName::from_static("x-ratelimit-limit"),
+                HeaderName::from_static("x-ratelimit-remaining"),
+                HeaderName::from_static("x-ratelimit-reset"),
+            ])
+            .max_age(self.cors_max_age_secs)
+    }
+
+    /// Only the listed origins, with the methods and headers the admin endpoints need.
+    pub fn admin(&self) -> Cors {
+        origins(&self.cors_admin_allowed_origins)
+            .into_iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .allowed_methods(["GET", "POST", "DELETE"])
+            .allowed_headers([AUTHORIZATION, CONTENT_TYPE])
+            .max_age(self.cors_max_age_secs)
+    }
+}
+
+fn origins(list: &Option<String>) -> Vec<&str> {
+    list.as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .collect()
+}
@@ -0,0 +1,73 @@
+use actix_web::{get, web, HttpResponse};
+use openbook_candles::{
+    database::fetch::{fetch_depth_snapshots_from, fetch_latest_depth_snapshot},
+    structs::orderbook::DepthSnapshot,
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::{
+    server_error::{ErrorBody, ServerError},
+    validation::{check_range, resolve_market},
+};
+
+/// Upper bound on the number of snapshots returned for a range, about three and a half days
+const MAX_DEPTH_SNAPSHOTS: i64 = 5000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DepthHistoryParams {
+    /// Market name or address
+    pub market_name: String,
+    /// Unix seconds, leave out with `to` for the newest snapshot only
+    pub from: Option<u64>,
+    /// Unix seconds
+    pub to: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/depth-history",
+    tag = "markets",
+    params(DepthHistoryParams),
+    responses(
+        (status = 200, description = "Depth snapshots of the range, oldest first", body = [DepthSnapshot]),
+        (status = 400, description = "Only one of `from` and `to`", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/depth-history")]
+pub async fn get_depth_history(
+    info: web::Query<DepthHistoryParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&info.market_name, &context)?;
+    let snapshots = match (info.from, info.to) {
+        (Some(from), Some(to)) => {
+            check_range(to_timestampz(from), to_timestampz(to))?;
+            context
+                .read_pool
+                .run(|pool| {
+                    fetch_depth_snapshots_from(
+                        pool,
+                        &market.name,
+                        to_timestampz(from),
+                        to_timestampz(to),
+                        MAX_DEPTH_SNAPSHOTS,
+                    )
+                })
+                .await
+        }
+        (None, None) => context
+            .read_pool
+            .run(|pool| fetch_latest_depth_snapshot(pool, &market.name))
+            .await
+            .map(|snapshot| snapshot.into_iter().collect()),
+        _ => return Err(ServerError::WrongParameters),
+    }
+    .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(snapshots))
+}
@@ -0,0 +1,64 @@
+use super::{
+    server_error::ServerError,
+    validation::{check_range, resolve_market},
+};
+use actix_web::{get, web, HttpResponse};
+use futures::join;
+use openbook_candles::{
+    database::fetch::{fetch_divergence_summary, fetch_divergences_from},
+    structs::divergence::DivergenceResponse,
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct DivergenceParams {
+    pub market_name: Option<String>,
+    pub from: u64,
+    pub to: u64,
+}
+
+#[get("/divergence")]
+pub async fn get_divergence(
+    info: web::Query<DivergenceParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let from = to_timestampz(info.from);
+    let to = to_timestampz(info.to);
+    check_range(from, to)?;
+
+    let (summary, bars) = match &info.market_name {
+        Some(market) => {
+            let market_name = &resolve_market(market, &context)?.name;
+            let (summary_query, bars_query) = join!(
+                context
+                    .read_pool
+                    .run(|pool| fetch_divergence_summary(pool, from, to)),
+                context
+                    .read_pool
+                    .run(|pool| fetch_divergences_from(pool, market_name, from, to))
+            );
+            let summary = summary_query
+                .map_err(ServerError::db)?
+                .into_iter()
+                .filter(|s| s.market_name == *market_name)
+                .collect();
+            (summary, bars_query.map_err(ServerError::db)?)
+        }
+        None => {
+            let summary = context
+                .read_pool
+                .run(|pool| fetch_divergence_summary(pool, from, to))
+                .await
+                .map_err(ServerError::db)?;
+            (summary, vec![])
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(DivergenceResponse {
+        start_time: info.from,
+        end_time: info.to,
+        summary,
+        bars,
+    }))
+}
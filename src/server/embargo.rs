@@ -0,0 +1,47 @@
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use openbook_candles::{
+    structs::{candle::Candle, resolution::Resolution},
+    utils::WebContext,
+};
+
+use super::rate_limit::API_KEY_HEADER;
+
+/// The latest time of the market's data this request may see, `None` if the market isn't
+/// embargoed or the request carries a realtime API key.
+pub fn visible_until(
+    req: &HttpRequest,
+    context: &WebContext,
+    market_name: &str,
+) -> Option<DateTime<Utc>> {
+    let realtime = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |key| context.rate_limiter.is_realtime_key(key));
+    context.embargo.visible_until(market_name, realtime)
+}
+
+/// How many of the most recent candles fall into the embargo window, fetched on top of the
+/// requested number so that many are left once they're dropped.
+pub fn embargoed_candle_count(until: Option<DateTime<Utc>>, resolution: Resolution) -> i64 {
+    match until {
+        Some(until) => {
+            (Utc::now() - until).num_seconds() / resolution.get_duration().num_seconds() + 1
+        }
+        None => 0,
+    }
+}
+
+/// Drops candles that end after `until` and keeps the `n` most recent of the rest.
+pub fn drop_embargoed_candles(
+    mut candles: Vec<Candle>,
+    until: Option<DateTime<Utc>>,
+    n: usize,
+) -> Vec<Candle> {
+    if let Some(until) = until {
+        candles.retain(|c| c.end_time <= until);
+    }
+    let excess = candles.len().saturating_sub(n);
+    candles.split_off(excess)
+}
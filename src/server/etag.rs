@@ -0,0 +1,81 @@
+use actix_web::{
+    body::{to_bytes, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorInternalServerError,
+    http::{
+        header::{self, HeaderValue},
+        Method, StatusCode,
+    },
+    Error,
+};
+use sha2::{Digest, Sha256};
+
+/// Routes whose responses are tagged. Charting clients ask for the same completed candle ranges
+/// over and over, and tickers only change when a batch lands.
+const ETAG_ROUTES: [&str; 4] = [
+    "/api/candles",
+    "/api/candles/recent",
+    "/api/candles/batch",
+    "/api/coingecko/tickers",
+];
+
+/// The validator a client sent with a tagged route, read before the request is handed on.
+pub struct Conditional {
+    if_none_match: Option<String>,
+}
+
+impl Conditional {
+    /// `None` for requests whose responses aren't tagged.
+    pub fn for_request(req: &ServiceRequest) -> Option<Self> {
+        if req.method() != Method::GET || !ETAG_ROUTES.contains(&req.path()) {
+            return None;
+        }
+        Some(Conditional {
+            if_none_match: req
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        })
+    }
+
+    fn matches(&self, etag: &str) -> bool {
+        self.if_none_match.as_deref().map_or(false, |v| {
+            v.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+            })
+        })
+    }
+}
+
+/// Tags a successful response with an ETag of its body and answers with an empty 304 instead
+/// when the client already has that body. The tag is weak since compression changes the bytes
+/// on the wire but not the content.
+pub async fn tag_response<B: MessageBody + 'static>(
+    res: ServiceResponse<B>,
+    conditional: Option<Conditional>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let conditional = match conditional {
+        Some(c) if res.status() == StatusCode::OK => c,
+        _ => return Ok(res.map_into_boxed_body()),
+    };
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = to_bytes(body)
+        .await
+        .map_err(|e| ErrorInternalServerError(Into::<Box<dyn std::error::Error>>::into(e)))?;
+    let etag = format!("W/\"{:x}\"", Sha256::digest(&bytes));
+    res.headers_mut().insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).map_err(ErrorInternalServerError)?,
+    );
+    let res = if conditional.matches(&etag) {
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        res.set_body(()).map_into_boxed_body()
+    } else {
+        res.set_body(bytes).map_into_boxed_body()
+    };
+    Ok(ServiceResponse::new(req, res))
+}
@@ -0,0 +1,75 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use openbook_candles::{
+    database::{fetch::fetch_candle_data_versions, lifecycle::fetch_market_lifecycles},
+    structs::envelope::{Envelope, MarketFreshness},
+    utils::WebContext,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::IntoParams;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EnvelopeParams {
+    /// Wrap the response in an `Envelope`
+    #[serde(default)]
+    pub envelope: bool,
+}
+
+/// Keeps the last fill time and data version of every market fresh, so enveloped responses
+/// don't cost an extra query.
+pub async fn refresh_freshness(context: Data<WebContext>) {
+    loop {
+        if let Err(e) = refresh_freshness_inner(&context).await {
+            warn!("Failed to refresh market freshness: {:?}", e);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn refresh_freshness_inner(context: &WebContext) -> anyhow::Result<()> {
+    let lifecycles = context.read_pool.run(fetch_market_lifecycles).await?;
+    let versions: HashMap<String, i64> = context
+        .read_pool
+        .run(fetch_candle_data_versions)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut freshness = context.freshness.write().await;
+    for market in context.markets.iter() {
+        let last_fill_at = lifecycles
+            .iter()
+            .find(|l| l.market == market.address)
+            .and_then(|l| l.last_fill_at);
+        let entry = freshness.entry(market.name.clone()).or_default();
+        entry.last_fill_at = last_fill_at;
+        // never step back, e.g. when a market's last candles age out of the lookup window
+        entry.data_version = entry
+            .data_version
+            .max(versions.get(&market.name).copied().unwrap_or(0));
+    }
+    Ok(())
+}
+
+/// Wraps `data` with the combined freshness of `market_names`. `until` caps the reported last
+/// fill time for embargoed markets.
+pub async fn envelope<T: Serialize>(
+    context: &WebContext,
+    market_names: &[&str],
+    until: Option<DateTime<Utc>>,
+    data: T,
+) -> Envelope<T> {
+    let freshness = context.freshness.read().await;
+    let mut combined =
+        MarketFreshness::combine(market_names.iter().filter_map(|m| freshness.get(*m)));
+    if let Some(until) = until {
+        combined.last_fill_at = combined.last_fill_at.map(|t| t.min(until));
+    }
+    Envelope::new(combined, data)
+}
@@ -0,0 +1,343 @@
+use actix_web::{get, web, HttpResponse, Scope};
+use chrono::{DateTime, Utc};
+use openbook_candles::{
+    database::{
+        fetch::{fetch_latest_fill_slot, fetch_newest_candle_end_times},
+        lifecycle::fetch_market_lifecycles,
+    },
+    structs::resolution::Resolution,
+    utils::WebContext,
+};
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use super::startup::StartupGate;
+
+fn default_health_max_slot_lag() -> u64 {
+    // about five minutes of slots
+    750
+}
+
+fn default_health_max_candle_staleness_secs() -> i64 {
+    300
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HealthConfig {
+    /// Not ready when the newest scraped fill is more slots than this behind the chain tip
+    #[serde(default = "default_health_max_slot_lag")]
+    pub health_max_slot_lag: u64,
+    /// Not ready when a market's newest minute candle ended longer ago than this
+    #[serde(default = "default_health_max_candle_staleness_secs")]
+    pub health_max_candle_staleness_secs: i64,
+}
+
+impl HealthConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        openbook_candles::utils::settings::load()
+    }
+}
+
+pub fn service(config: HealthConfig, startup_gate: web::Data<StartupGate>) -> Scope {
+    web::scope("/health")
+        .app_data(web::Data::new(config))
+        .app_data(startup_gate)
+        .service(live)
+        .service(ready)
+        .service(status)
+        .service(badge)
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn from_result<T>(result: &anyhow::Result<T>) -> Self {
+        match result {
+            Ok(_) => Check {
+                ok: true,
+                error: None,
+            },
+            Err(e) => Check {
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SlotLagCheck {
+    #[serde(flatten)]
+    check: Check,
+    latest_fill_slot: Option<i64>,
+    chain_slot: Option<u64>,
+    lag: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct MarketStaleness {
+    market_name: String,
+    /// Unix seconds, missing when the market has no candles in the last day
+    newest_candle_end: Option<i64>,
+    staleness_secs: Option<i64>,
+    ok: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CandlesCheck {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    markets: Vec<MarketStaleness>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReadinessResponse {
+    ready: bool,
+    startup: Check,
+    database: Check,
+    slot_lag: SlotLagCheck,
+    candles: CandlesCheck,
+}
+
+/// The process is up and serving requests.
+#[get("/live")]
+pub async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "live": true }))
+}
+
+/// The startup gate has passed, Postgres is reachable, the scraper keeps up with the chain and
+/// every market has fresh candles.
+#[get("/ready")]
+pub async fn ready(
+    config: web::Data<HealthConfig>,
+    startup_gate: web::Data<StartupGate>,
+    context: web::Data<WebContext>,
+) -> HttpResponse {
+    let startup = Check {
+        ok: startup_gate.has_passed(),
+        error: (!startup_gate.has_passed()).then(|| "waiting for startup data".to_string()),
+    };
+    let database = Check::from_result(&check_database(&context).await);
+    let slot_lag = check_slot_lag(&context, &config).await;
+    let candles = check_candles(&context, &config).await;
+
+    let response = ReadinessResponse {
+        ready: startup.ok && database.ok && slot_lag.check.ok && candles.ok,
+        startup,
+        database,
+        slot_lag,
+        candles,
+    };
+    if response.ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+async fn check_database(context: &WebContext) -> anyhow::Result<()> {
+    let client = context.pool.get().await?;
+    client.query_one("SELECT 1", &[]).await?;
+    Ok(())
+}
+
+async fn check_slot_lag(context: &WebContext, config: &HealthConfig) -> SlotLagCheck {
+    let market_addresses = context.markets.iter().map(|m| m.address.as_str()).collect();
+    let latest_fill_slot = context
+        .read_pool
+        .run(|pool| fetch_latest_fill_slot(pool, &market_addresses))
+        .await;
+    let chain_slot = RpcClient::new(context.rpc_url.clone())
+        .get_slot()
+        .await
+        .map_err(anyhow::Error::from);
+
+    let (check, lag) = match (&latest_fill_slot, &chain_slot) {
+        (Ok(Some(fill_slot)), Ok(chain_slot)) => {
+            let lag = chain_slot.saturating_sub(*fill_slot as u64);
+            let check = Check {
+                ok: lag <= config.health_max_slot_lag,
+                error: None,
+            };
+            (check, Some(lag))
+        }
+        (Ok(None), Ok(_)) => (
+            Check {
+                ok: false,
+                error: Some("no fills in the last day".to_string()),
+            },
+            None,
+        ),
+        (Err(_), _) => (Check::from_result(&latest_fill_slot), None),
+        (_, Err(_)) => (Check::from_result(&chain_slot), None),
+    };
+    SlotLagCheck {
+        check,
+        latest_fill_slot: latest_fill_slot.ok().flatten(),
+        chain_slot: chain_slot.ok(),
+        lag,
+    }
+}
+
+async fn check_candles(context: &WebContext, config: &HealthConfig) -> CandlesCheck {
+    let newest = match context
+        .read_pool
+        .run(|pool| fetch_newest_candle_end_times(pool, Resolution::R1m))
+        .await
+    {
+        Ok(newest) => newest,
+        Err(e) => {
+            return CandlesCheck {
+                ok: false,
+                error: Some(e.to_string()),
+                markets: vec![],
+            }
+        }
+    };
+
+    let now = Utc::now();
+    let markets: Vec<MarketStaleness> = context
+        .markets
+        .iter()
+        .map(|m| {
+            let end = newest.iter().find(|(name, _)| *name == m.name).map(|n| n.1);
+            let staleness_secs = end.map(|e| (now - e).num_seconds().max(0));
+            MarketStaleness {
+                market_name: m.name.clone(),
+                newest_candle_end: end.map(|e| e.timestamp()),
+                staleness_secs,
+                ok: staleness_secs.map_or(false, |s| s <= config.health_max_candle_staleness_secs),
+            }
+        })
+        .collect();
+    CandlesCheck {
+        ok: markets.iter().all(|m| m.ok),
+        error: None,
+        markets,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StatusSummary {
+    /// `operational`, `degraded` when candles lag or `down` when the database is unreachable
+    status: &'static str,
+    markets_tracked: usize,
+    /// First fill of the oldest market, unix seconds
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    coverage_start: Option<DateTime<Utc>>,
+    /// Seconds since the newest complete minute candle of any market ended
+    lag_secs: Option<i64>,
+}
+
+/// Cheap overall status for status pages, built from the market lifecycle table only.
+async fn status_summary(context: &WebContext, config: &HealthConfig) -> StatusSummary {
+    let markets_tracked = context.markets.len();
+    let lifecycles = match context.read_pool.run(fetch_market_lifecycles).await {
+        Ok(lifecycles) => lifecycles,
+        Err(_) => {
+            return StatusSummary {
+                status: "down",
+                markets_tracked,
+                coverage_start: None,
+                lag_secs: None,
+            }
+        }
+    };
+    let tracked = lifecycles
+        .iter()
+        .filter(|l| context.markets.iter().any(|m| m.address == l.market));
+    let coverage_start = tracked.clone().filter_map(|l| l.first_fill_at).min();
+    let lag_secs = tracked
+        .filter_map(|l| l.candles_through)
+        .max()
+        .map(|t| (Utc::now() - t).num_seconds().max(0));
+    let fresh = lag_secs.map_or(false, |lag| lag <= config.health_max_candle_staleness_secs);
+    StatusSummary {
+        status: if fresh { "operational" } else { "degraded" },
+        markets_tracked,
+        coverage_start,
+        lag_secs,
+    }
+}
+
+/// Service status, markets tracked, coverage start and lag in one response.
+#[get("/status")]
+pub async fn status(
+    config: web::Data<HealthConfig>,
+    context: web::Data<WebContext>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(status_summary(&context, &config).await)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BadgeMetric {
+    #[default]
+    Status,
+    Markets,
+    Coverage,
+    Lag,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BadgeParams {
+    #[serde(default)]
+    pub metric: BadgeMetric,
+}
+
+/// shields.io endpoint badge, see https://shields.io/badges/endpoint-badge
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Badge {
+    schema_version: u8,
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
+
+/// One status figure in the shields.io endpoint badge format.
+#[get("/badge")]
+pub async fn badge(
+    params: web::Query<BadgeParams>,
+    config: web::Data<HealthConfig>,
+    context: web::Data<WebContext>,
+) -> HttpResponse {
+    let summary = status_summary(&context, &config).await;
+    let status_color = match summary.status {
+        "operational" => "brightgreen",
+        "degraded" => "yellow",
+        _ => "red",
+    };
+    let (label, message, color) = match params.metric {
+        BadgeMetric::Status => ("status", summary.status.to_string(), status_color),
+        BadgeMetric::Markets => ("markets", summary.markets_tracked.to_string(), "blue"),
+        BadgeMetric::Coverage => (
+            "coverage since",
+            summary
+                .coverage_start
+                .map_or("unknown".to_string(), |t| t.format("%Y-%m-%d").to_string()),
+            "blue",
+        ),
+        BadgeMetric::Lag => (
+            "lag",
+            summary
+                .lag_secs
+                .map_or("unknown".to_string(), |lag| format!("{}s", lag)),
+            status_color,
+        ),
+    };
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "max-age=60"))
+        .json(Badge {
+            schema_version: 1,
+            label,
+            message,
+            color,
+        })
+}
@@ -0,0 +1,121 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use openbook_candles::{
+    structs::{
+        candle::fill_candle_gaps,
+        indicator::{parse_indicator, IndicatorResponse, IndicatorSeries},
+    },
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::{
+    embargo::{drop_embargoed_candles, visible_until},
+    server_error::{ErrorBody, ServerError},
+    validation::{check_candle_range, check_resolution, parse_resolution, resolve_market},
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IndicatorParams {
+    /// Market name or address
+    pub market: String,
+    pub resolution: String,
+    /// sma, ema, rsi or macd
+    pub indicator: String,
+    /// Comma separated periods: `sma`, `ema` and `rsi` take one (20, 20 and 14 by default),
+    /// `macd` the fast, slow and signal periods (12, 26 and 9 by default)
+    pub params: Option<String>,
+    /// Unix seconds
+    pub from: u64,
+    /// Unix seconds
+    pub to: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/indicators",
+    tag = "candles",
+    params(IndicatorParams),
+    responses(
+        (status = 200, description = "Indicator values per candle of the range", body = IndicatorResponse),
+        (status = 400, description = "Unknown resolution or indicator, or invalid parameters", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`, or range too long", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/indicators")]
+pub async fn get_indicator(
+    req: HttpRequest,
+    info: web::Query<IndicatorParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&info.market, &context)?;
+    let resolution = parse_resolution(&info.resolution)?;
+    check_resolution(market, resolution)?;
+    let params = match info.params.as_deref() {
+        Some(params) if !params.is_empty() => params
+            .split(',')
+            .map(|p| p.trim().parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| ServerError::WrongParameters)?,
+        _ => vec![],
+    };
+    let indicator = parse_indicator(&info.indicator.to_lowercase(), &params)
+        .ok_or(ServerError::WrongParameters)?;
+
+    let from = to_timestampz(info.from);
+    check_candle_range(
+        resolution,
+        from,
+        to_timestampz(info.to),
+        context.max_range_candles,
+    )?;
+    let until = visible_until(&req, &context, &market.name);
+    let to = match until {
+        Some(until) => to_timestampz(info.to).min(until),
+        None => to_timestampz(info.to),
+    };
+    // the candles before the range settle the values at its start
+    let candles = context
+        .read_pool
+        .run(|pool| {
+            context.candle_cache.fetch_candles(
+                pool,
+                &market.name,
+                resolution,
+                (from - resolution.get_duration() * indicator.warmup() as i32)
+                    .max(to_timestampz(0)),
+                to,
+            )
+        })
+        .await
+        .map_err(ServerError::db)?;
+    let candles = fill_candle_gaps(
+        drop_embargoed_candles(candles, until, usize::MAX),
+        resolution,
+    );
+
+    let skip = candles.partition_point(|c| c.start_time < from);
+    let series = indicator
+        .outputs()
+        .iter()
+        .zip(indicator.compute(&candles))
+        .map(|(name, values)| IndicatorSeries {
+            name: name.to_string(),
+            values: values[skip..].to_vec(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(IndicatorResponse {
+        market_name: market.name.clone(),
+        resolution: resolution.to_string(),
+        indicator: info.indicator.to_lowercase(),
+        params: indicator.params(),
+        time: candles[skip..]
+            .iter()
+            .map(|c| c.start_time.timestamp())
+            .collect(),
+        series,
+    }))
+}
@@ -0,0 +1,112 @@
+use actix_web::{
+    body::{to_bytes, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorInternalServerError,
+    http::header,
+    web::Query,
+    Error,
+};
+use openbook_candles::utils::settings::CommonConfig;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Naming convention for the keys of JSON responses. Handlers always serialize snake_case (or
+/// whatever their structs are renamed to) and responses are converted here when asked for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCase {
+    Snake,
+    Camel,
+}
+
+#[derive(Deserialize)]
+struct CaseParams {
+    case: Option<KeyCase>,
+}
+
+impl KeyCase {
+    /// Reads the default from `RESPONSE_KEY_CASE`, snake_case unless set to `camel`.
+    pub fn from_env() -> Self {
+        let config = CommonConfig::from_env().ok();
+        match config.and_then(|c| c.response_key_case).as_deref() {
+            Some("camel") => KeyCase::Camel,
+            _ => KeyCase::Snake,
+        }
+    }
+
+    /// A `?case=` parameter wins over the configured default. The CoinGecko routes follow
+    /// CoinGecko's spec, so they ignore the default and only convert when asked explicitly.
+    pub fn for_request(req: &ServiceRequest, default: KeyCase) -> Self {
+        let requested = Query::<CaseParams>::from_query(req.query_string())
+            .ok()
+            .and_then(|p| p.case);
+        match requested {
+            Some(case) => case,
+            None if req.path().starts_with("/api/coingecko") => KeyCase::Snake,
+            None => default,
+        }
+    }
+}
+
+/// Rewrites the keys of a JSON response body to the requested case.
+pub async fn convert_response_keys<B: MessageBody + 'static>(
+    res: ServiceResponse<B>,
+    case: KeyCase,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map_or(false, |v| v.as_bytes().starts_with(b"application/json"));
+    if case == KeyCase::Snake || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = to_bytes(body)
+        .await
+        .map_err(|e| ErrorInternalServerError(Into::<Box<dyn std::error::Error>>::into(e)))?;
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut value) => {
+            camel_case_keys(&mut value);
+            serde_json::to_vec(&value)?
+        }
+        Err(_) => bytes.to_vec(),
+    };
+    Ok(ServiceResponse::new(
+        req,
+        res.set_body(body).map_into_boxed_body(),
+    ))
+}
+
+fn camel_case_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut v)| {
+                    camel_case_keys(&mut v);
+                    (to_camel_case(&key), v)
+                })
+                .collect();
+        }
+        Value::Array(items) => items.iter_mut().for_each(camel_case_keys),
+        _ => {}
+    }
+}
+
+fn to_camel_case(key: &str) -> String {
+    let mut camel = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper_next = !camel.is_empty();
+        } else if upper_next {
+            camel.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
@@ -1,9 +1,90 @@
-use crate::server_error::ServerError;
+use super::{server_error::ServerError, usd::add_summary_usd_volumes};
 use actix_web::{get, web, HttpResponse};
-use openbook_candles::utils::WebContext;
+use openbook_candles::{
+    database::{fetch::fetch_market_summaries, lifecycle::fetch_market_lifecycles},
+    structs::{
+        cache_backend::{get_json, set_json},
+        market_lifecycle::MarketLifecycle,
+        market_summary::MarketSummary,
+        markets::MarketInfo,
+        venue::Venue,
+    },
+    utils::WebContext,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Summaries are served from the response cache for this long
+const SUMMARY_CACHE_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+struct MarketResponse<'a> {
+    #[serde(flatten)]
+    market: &'a MarketInfo,
+    #[serde(flatten)]
+    lifecycle: MarketLifecycle,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarketsParams {
+    /// Only the markets of this venue
+    pub venue: Option<Venue>,
+}
 
 #[get("/markets")]
-pub async fn get_markets(context: web::Data<WebContext>) -> Result<HttpResponse, ServerError> {
-    let markets = &context.markets;
+pub async fn get_markets(
+    info: web::Query<MarketsParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let lifecycles = context
+        .read_pool
+        .run(fetch_market_lifecycles)
+        .await
+        .map_err(ServerError::db)?;
+    let markets: Vec<MarketResponse> = context
+        .markets
+        .iter()
+        .chain(context.composite_markets.iter())
+        .filter(|m| info.venue.map_or(true, |venue| m.venue == venue))
+        .map(|m| MarketResponse {
+            market: m,
+            lifecycle: lifecycles
+                .iter()
+                .find(|l| l.market == m.address)
+                .cloned()
+                .unwrap_or_default(),
+        })
+        .collect();
     Ok(HttpResponse::Ok().json(markets))
 }
+
+/// Last price, 24h volume, high, low and change of every registered market.
+#[get("/markets/summary")]
+pub async fn get_market_summaries(
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let cache_key = "markets:summary";
+    if let Some(summaries) = get_json::<Vec<MarketSummary>>(context.cache.as_ref(), cache_key).await
+    {
+        return Ok(HttpResponse::Ok().json(summaries));
+    }
+    let markets: Vec<(&str, &str)> = context
+        .markets
+        .iter()
+        .map(|m| (m.name.as_str(), m.address.as_str()))
+        .collect();
+    let mut summaries = context
+        .read_pool
+        .run(|pool| fetch_market_summaries(pool, &markets, context.coingecko_max_markets_per_query))
+        .await
+        .map_err(ServerError::db)?;
+    add_summary_usd_volumes(&context, &mut summaries);
+    set_json(
+        context.cache.as_ref(),
+        cache_key,
+        &summaries,
+        SUMMARY_CACHE_TTL,
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(summaries))
+}
@@ -1,4 +1,36 @@
+pub mod admin;
+pub mod candle_cache_warmer;
 pub mod candles;
-pub mod traders;
+pub mod changes;
+pub mod coingecko;
+pub mod cors;
+pub mod depth_history;
+pub mod divergence;
+pub mod embargo;
+pub mod etag;
+pub mod freshness;
+pub mod health;
+pub mod indicators;
+pub mod key_case;
 pub mod markets;
-pub mod coingecko;
\ No newline at end of file
+pub mod openapi;
+pub mod oracle;
+pub mod orderbook_snapshots;
+pub mod patterns;
+pub mod price_change;
+pub mod privacy;
+pub mod rate;
+pub mod rate_limit;
+pub mod returns;
+pub mod revisions;
+pub mod server_error;
+pub mod session;
+pub mod spread_history;
+pub mod sse;
+pub mod startup;
+pub mod status;
+pub mod traders;
+pub mod trades;
+pub mod usd;
+pub mod validation;
+pub mod volume_profile;
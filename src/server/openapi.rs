@@ -0,0 +1,76 @@
+use openbook_candles::structs::{
+    coingecko::{CoinGeckoOrderBook, CoinGeckoPair, CoinGeckoTicker},
+    indicator::{IndicatorResponse, IndicatorSeries},
+    market_status::MarketStatus,
+    orderbook::DepthSnapshot,
+    rolling::RollingCandle,
+    spread::{SpreadBucket, SpreadHistoryResponse},
+    trade::{Trade, TradeBucket, TradeSide, TradesResponse},
+    trader::{LeaderboardResponse, Trader, TraderResponse},
+    tradingview::TvResponse,
+    uptime::{MakerUptime, UptimeResponse},
+    volume_profile::{VolumeLevel, VolumeProfileResponse},
+};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::{
+    candles, coingecko, depth_history, indicators, server_error::ErrorBody, spread_history, status,
+    traders, trades, volume_profile,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "openbook-candles"),
+    paths(
+        candles::get_candles,
+        candles::get_recent_candles,
+        candles::get_rolling_candle,
+        candles::get_batch_candles,
+        indicators::get_indicator,
+        trades::get_trades,
+        volume_profile::get_volume_profile,
+        traders::get_top_traders_by_base_volume,
+        traders::get_top_traders_by_quote_volume,
+        traders::get_trader_leaderboard,
+        traders::get_maker_uptime,
+        coingecko::pairs,
+        coingecko::tickers,
+        coingecko::orderbook,
+        status::get_market_statuses,
+        depth_history::get_depth_history,
+        spread_history::get_spread_history,
+    ),
+    components(schemas(
+        TvResponse,
+        candles::BatchCandles,
+        RollingCandle,
+        IndicatorResponse,
+        IndicatorSeries,
+        TradesResponse,
+        Trade,
+        TradeBucket,
+        TradeSide,
+        VolumeProfileResponse,
+        VolumeLevel,
+        TraderResponse,
+        LeaderboardResponse,
+        Trader,
+        UptimeResponse,
+        MakerUptime,
+        CoinGeckoPair,
+        CoinGeckoTicker,
+        CoinGeckoOrderBook,
+        MarketStatus,
+        DepthSnapshot,
+        SpreadHistoryResponse,
+        SpreadBucket,
+        ErrorBody,
+    ))
+)]
+pub struct ApiDoc;
+
+/// Swagger UI at `/docs`, reading the spec from `/docs/openapi.json`.
+pub fn service() -> SwaggerUi {
+    SwaggerUi::new("/docs/{_:.*}").url("/docs/openapi.json", ApiDoc::openapi())
+}
@@ -0,0 +1,55 @@
+use super::{server_error::ServerError, validation::check_range};
+use actix_web::{get, web, HttpResponse};
+use openbook_candles::{
+    database::fetch::{fetch_latest_oracle_prices, fetch_oracle_prices_from},
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+
+/// Upper bound on the number of prices returned for a range
+const MAX_ORACLE_PRICES: i64 = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct OracleParams {
+    pub symbol: String,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+/// Stored oracle prices of a token, the newest per provider unless a range is given.
+#[get("/oracle")]
+pub async fn get_oracle_prices(
+    info: web::Query<OracleParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let symbol = info.symbol.to_uppercase();
+    let prices = match (info.from, info.to) {
+        (Some(from), Some(to)) => {
+            check_range(to_timestampz(from), to_timestampz(to))?;
+            context
+                .read_pool
+                .run(|pool| {
+                    fetch_oracle_prices_from(
+                        pool,
+                        &symbol,
+                        to_timestampz(from),
+                        to_timestampz(to),
+                        MAX_ORACLE_PRICES,
+                    )
+                })
+                .await
+        }
+        (None, None) => {
+            context
+                .read_pool
+                .run(|pool| fetch_latest_oracle_prices(pool, &symbol))
+                .await
+        }
+        _ => return Err(ServerError::WrongParameters),
+    }
+    .map_err(ServerError::db)?;
+    if prices.is_empty() {
+        return Err(ServerError::SymbolNotFound);
+    }
+    Ok(HttpResponse::Ok().json(prices))
+}
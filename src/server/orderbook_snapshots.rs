@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use actix_web::web::Data;
+use openbook_candles::{
+    structs::{markets::MarketInfo, slab::get_orderbook_snapshots, venue::Venue},
+    utils::WebContext,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use tracing::warn;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Keeps the top of book for every OpenBook market fresh so ticker requests don't hit RPC.
+pub async fn refresh_orderbook_snapshots(context: Data<WebContext>) {
+    let client = RpcClient::new(context.rpc_url.clone());
+    let markets: Vec<MarketInfo> = context
+        .markets
+        .iter()
+        .filter(|m| m.venue == Venue::OpenBook)
+        .cloned()
+        .collect();
+    loop {
+        match get_orderbook_snapshots(&client, &markets).await {
+            Ok(new_snapshots) => {
+                let mut snapshots = context.orderbook_snapshots.write().await;
+                for (market, snapshot) in markets.iter().zip(new_snapshots) {
+                    if let Some(snapshot) = snapshot {
+                        snapshots.insert(market.address.clone(), snapshot);
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to refresh order book snapshots: {:?}", e),
+        }
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+    }
+}
@@ -0,0 +1,49 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use openbook_candles::{
+    database::fetch::fetch_recent_candles, structs::pattern::detect_patterns, utils::WebContext,
+};
+use serde::Deserialize;
+
+use super::{
+    embargo::{drop_embargoed_candles, embargoed_candle_count, visible_until},
+    server_error::ServerError,
+    validation::{check_resolution, parse_resolution, resolve_market},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PatternParams {
+    pub resolution: String,
+    pub limit: Option<u16>,
+}
+
+/// Candles scanned for patterns unless `limit` says otherwise
+const DEFAULT_PATTERN_CANDLES: u16 = 100;
+const MAX_PATTERN_CANDLES: u16 = 1000;
+
+/// Patterns completed by each of the market's most recent complete candles.
+#[get("/markets/{market_name:.+}/patterns")]
+pub async fn get_patterns(
+    req: HttpRequest,
+    path: web::Path<String>,
+    info: web::Query<PatternParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&path.into_inner(), &context)?;
+    let resolution = parse_resolution(&info.resolution)?;
+    check_resolution(market, resolution)?;
+    let market_name = market.name.clone();
+    let limit = info.limit.unwrap_or(DEFAULT_PATTERN_CANDLES);
+    if limit == 0 || limit > MAX_PATTERN_CANDLES {
+        return Err(ServerError::WrongParameters);
+    }
+
+    let until = visible_until(&req, &context, &market_name);
+    let fetch_limit = limit as i64 + embargoed_candle_count(until, resolution);
+    let candles = context
+        .read_pool
+        .run(|pool| fetch_recent_candles(pool, &market_name, resolution, fetch_limit))
+        .await
+        .map_err(ServerError::db)?;
+    let candles = drop_embargoed_candles(candles, until, limit as usize);
+    Ok(HttpResponse::Ok().json(detect_patterns(&candles)))
+}
@@ -0,0 +1,34 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use openbook_candles::{
+    database::fetch::fetch_price_change_closes, structs::price_change::PriceChangeResponse,
+    utils::WebContext,
+};
+use serde::Deserialize;
+
+use super::{embargo::visible_until, server_error::ServerError, validation::resolve_market};
+
+#[derive(Debug, Deserialize)]
+pub struct PriceChangeParams {
+    pub market: String,
+}
+
+/// Percent change of the market's price over the last hour, day and week.
+#[get("/stats/price-change")]
+pub async fn get_price_change(
+    req: HttpRequest,
+    info: web::Query<PriceChangeParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&info.market, &context)?;
+    let as_of = visible_until(&req, &context, &market.name).unwrap_or_else(Utc::now);
+    let closes = context
+        .read_pool
+        .run(|pool| fetch_price_change_closes(pool, &market.name, as_of))
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(PriceChangeResponse::from_closes(
+        market.name.clone(),
+        &closes,
+    )))
+}
@@ -0,0 +1,18 @@
+use actix_web::HttpRequest;
+use openbook_candles::utils::WebContext;
+
+use super::rate_limit::API_KEY_HEADER;
+
+/// Whether trader addresses in the response have to be replaced with pseudonyms, which is the
+/// case in privacy mode unless the request carries a valid API key.
+pub fn anonymize_traders(req: &HttpRequest, context: &WebContext) -> bool {
+    if !context.anonymizer.is_enabled() {
+        return false;
+    }
+    let authenticated = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |key| context.rate_limiter.is_known_key(key));
+    !authenticated
+}
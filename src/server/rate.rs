@@ -0,0 +1,64 @@
+use super::server_error::ServerError;
+use actix_web::{get, web, HttpResponse};
+use chrono::Utc;
+use openbook_candles::{
+    database::fetch::fetch_candle_at,
+    structs::{
+        rate::{find_rate_route, RateLeg, RateResponse},
+        resolution::Resolution,
+    },
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct RateParams {
+    pub base_mint: String,
+    pub quote_mint: String,
+    /// Unix seconds, defaults to now
+    pub at: Option<u64>,
+}
+
+#[get("/rate")]
+pub async fn get_rate(
+    info: web::Query<RateParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let at = info.at.unwrap_or(Utc::now().timestamp() as u64);
+    let route = find_rate_route(&info.base_mint, &info.quote_mint, &context.markets)
+        .ok_or(ServerError::MarketNotFound)?;
+
+    let mut rate = 1.0;
+    let mut legs = vec![];
+    for hop in route {
+        let candle = context
+            .read_pool
+            .run(|pool| fetch_candle_at(pool, &hop.market.name, Resolution::R1m, to_timestampz(at)))
+            .await
+            .map_err(ServerError::db)?
+            .ok_or(ServerError::PriceNotFound)?;
+        if candle.close == 0.0 {
+            return Err(ServerError::PriceNotFound);
+        }
+
+        rate *= if hop.inverted {
+            1.0 / candle.close
+        } else {
+            candle.close
+        };
+        legs.push(RateLeg {
+            market_name: hop.market.name,
+            inverted: hop.inverted,
+            close: candle.close,
+            candle_time: candle.start_time.timestamp(),
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(RateResponse {
+        base_mint: info.base_mint.clone(),
+        quote_mint: info.quote_mint.clone(),
+        at,
+        rate,
+        route: legs,
+    }))
+}
@@ -0,0 +1,138 @@
+use std::{future::Future, time::Duration};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    web::Data,
+    Error, HttpResponse,
+};
+use futures::future::{ready, Either};
+use openbook_candles::{
+    database::api_keys::{fetch_api_keys, record_api_key_usage},
+    structs::rate_limit::{RateLimitConfig, RateLimitError, RateLimitStatus},
+    utils::{
+        reload::{listen_for_reload, reload_env_file},
+        WebContext,
+    },
+};
+use serde_json::json;
+use tracing::{info, warn};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Flushes per key request counts and reloads API keys and their quotas, so new keys, quota
+/// changes and revocations from other instances apply without a restart.
+pub async fn sync_api_keys(context: Data<WebContext>) {
+    loop {
+        let usage = context.rate_limiter.take_usage();
+        if let Err(e) = record_api_key_usage(&context.pool, &usage).await {
+            warn!("Failed to record API key usage: {:?}", e);
+            context.rate_limiter.restore_usage(usage);
+        }
+        match fetch_api_keys(&context.pool).await {
+            Ok(keys) => context.rate_limiter.set_api_keys(keys),
+            Err(e) => warn!("Failed to refresh API keys: {:?}", e),
+        }
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+    }
+}
+
+/// Applies the anonymous limit again on SIGHUP, from the environment and the `.env` file.
+pub async fn reload_rate_limit(context: Data<WebContext>) {
+    let mut reload = listen_for_reload(None);
+    loop {
+        reload.requested().await;
+        if let Err(e) = reload_env_file() {
+            warn!("Failed to read .env, keeping the current limit: {:?}", e);
+            continue;
+        }
+        match RateLimitConfig::from_env() {
+            Ok(config) => {
+                let limit = config.rate_limit_anonymous_per_minute;
+                context.rate_limiter.set_anonymous_per_minute(limit);
+                info!("Anonymous rate limit is now {:?} per minute", limit);
+            }
+            Err(e) => warn!("Invalid rate limit, keeping the current one: {:?}", e),
+        }
+    }
+}
+
+/// Charges the request to its API key or client IP and rejects it with a 429 once the quota is
+/// used up. Every limited response carries the `X-RateLimit-*` headers.
+pub fn limit_request<S, B>(
+    context: &WebContext,
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<BoxBody>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    let api_key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let client_ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string();
+
+    // probes from the orchestrator shouldn't eat into anyone's quota
+    let result = if req.path().starts_with("/health/") {
+        Ok(None)
+    } else {
+        context.rate_limiter.check(api_key.as_deref(), &client_ip)
+    };
+    match result {
+        Ok(status) => {
+            let fut = srv.call(req);
+            Either::Left(async move {
+                let mut res = fut.await?;
+                if let Some(status) = status {
+                    insert_rate_limit_headers(res.headers_mut(), &status);
+                }
+                Ok(res.map_into_boxed_body())
+            })
+        }
+        Err(RateLimitError::UnknownApiKey) => {
+            let response = HttpResponse::Unauthorized().json(json!({
+                "error": "Unknown API key",
+            }));
+            Either::Right(ready(Ok(req.into_response(response))))
+        }
+        Err(RateLimitError::Exceeded {
+            status,
+            retry_after_secs,
+        }) => {
+            let mut response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after_secs))
+                .json(json!({
+                    "error": "Rate limit exceeded",
+                    "limit": status.limit,
+                    "reset": status.reset_secs,
+                }));
+            insert_rate_limit_headers(response.headers_mut(), &status);
+            Either::Right(ready(Ok(req.into_response(response))))
+        }
+    }
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-limit"),
+        HeaderValue::from(status.limit),
+    );
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-remaining"),
+        HeaderValue::from(status.remaining),
+    );
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-reset"),
+        HeaderValue::from(status.reset_secs),
+    );
+}
@@ -0,0 +1,82 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use openbook_candles::{
+    structs::returns::{compute_returns, CandleReturn, ReturnKind},
+    utils::{to_timestampz, WebContext},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    embargo::{drop_embargoed_candles, visible_until},
+    server_error::ServerError,
+    validation::{check_candle_range, check_resolution, parse_resolution, resolve_market},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ReturnParams {
+    pub resolution: String,
+    pub from: u64,
+    pub to: u64,
+    #[serde(default)]
+    pub kind: ReturnKind,
+}
+
+#[derive(Serialize)]
+struct ReturnsResponse {
+    market_name: String,
+    resolution: String,
+    kind: ReturnKind,
+    returns: Vec<CandleReturn>,
+}
+
+/// Simple or log returns of the market's candle closes over the range.
+#[get("/markets/{market_name:.+}/returns")]
+pub async fn get_returns(
+    req: HttpRequest,
+    path: web::Path<String>,
+    info: web::Query<ReturnParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&path.into_inner(), &context)?;
+    let resolution = parse_resolution(&info.resolution)?;
+    check_resolution(market, resolution)?;
+    let market_name = market.name.clone();
+
+    let from = to_timestampz(info.from);
+    check_candle_range(
+        resolution,
+        from,
+        to_timestampz(info.to),
+        context.max_range_candles,
+    )?;
+    let until = visible_until(&req, &context, &market_name);
+    let to = match until {
+        Some(until) => to_timestampz(info.to).min(until),
+        None => to_timestampz(info.to),
+    };
+    // one candle more, so the first candle of the range has a close to compare with
+    let candles = context
+        .read_pool
+        .run(|pool| {
+            context.candle_cache.fetch_candles(
+                pool,
+                &market_name,
+                resolution,
+                from - resolution.get_duration(),
+                to,
+            )
+        })
+        .await
+        .map_err(ServerError::db)?;
+    let candles = drop_embargoed_candles(candles, until, usize::MAX);
+    let returns = compute_returns(&candles, info.kind)
+        .into_iter()
+        .filter(|r| r.start_time >= from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ReturnsResponse {
+        market_name,
+        resolution: resolution.to_string(),
+        kind: info.kind,
+        returns,
+    }))
+}
@@ -0,0 +1,48 @@
+use super::{server_error::ServerError, validation::resolve_market};
+use actix_web::{get, web, HttpResponse};
+use openbook_candles::{
+    database::revisions::fetch_candle_revisions, structs::revision::RevisionsResponse,
+    utils::WebContext,
+};
+use serde::Deserialize;
+
+/// Upper bound on the number of revisions returned per page
+const MAX_REVISIONS: u32 = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionsParams {
+    /// Only revisions of this market, all markets when unset
+    pub market_name: Option<String>,
+    /// Cursor from the previous page, 0 starts from the beginning
+    #[serde(default)]
+    pub since: i64,
+    pub limit: Option<u32>,
+}
+
+#[get("/revisions")]
+pub async fn get_revisions(
+    info: web::Query<RevisionsParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let limit = info.limit.unwrap_or(MAX_REVISIONS);
+    if info.since < 0 || limit == 0 || limit > MAX_REVISIONS {
+        return Err(ServerError::WrongParameters);
+    }
+    let market_name = match &info.market_name {
+        Some(key) => Some(resolve_market(key, &context)?.name.as_str()),
+        None => None,
+    };
+
+    let revisions = context
+        .read_pool
+        .run(|pool| fetch_candle_revisions(pool, market_name, info.since, limit as i64))
+        .await
+        .map_err(ServerError::db)?;
+
+    let next_cursor = revisions.last().map(|r| r.id).unwrap_or(info.since);
+    Ok(HttpResponse::Ok().json(RevisionsResponse {
+        has_more: revisions.len() == limit as usize,
+        revisions,
+        next_cursor,
+    }))
+}
@@ -1,9 +1,9 @@
-use actix_web::{
-    error,
-    http::{header::ContentType, StatusCode},
-    HttpResponse,
-};
+use actix_web::{error, http::StatusCode, HttpResponse};
 use derive_more::{Display, Error};
+use openbook_candles::database::replica::is_connection_error;
+use serde::Serialize;
+use serde_json::{json, Value};
+use utoipa::ToSchema;
 
 #[derive(Debug, Display, Error)]
 pub enum ServerError {
@@ -13,21 +13,82 @@ pub enum ServerError {
     WrongParameters,
     #[display(fmt = "Wrong resolution")]
     WrongResolution,
+    #[display(fmt = "Invalid time range: {}", _0)]
+    InvalidRange(#[error(not(source))] String),
     #[display(fmt = "DB error")]
     DbQueryError,
-    #[display(fmt = "Error getting connection")]
-    DbPoolError,
+    #[display(fmt = "Database unavailable")]
+    DbUnavailable,
     #[display(fmt = "Market not found")]
     MarketNotFound,
     #[display(fmt = "Request symbol not found")]
     SymbolNotFound,
+    #[display(fmt = "No price available")]
+    PriceNotFound,
+    #[display(fmt = "Unauthorized")]
+    Unauthorized,
+    #[display(fmt = "API key not found")]
+    ApiKeyNotFound,
+    #[display(fmt = "Job not found")]
+    JobNotFound,
+}
+
+/// Body of every error response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable snake_case identifier of the error, e.g. `market_not_found`
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+}
+
+impl ServerError {
+    /// Maps a failed query to `DbUnavailable` when no connection could be had or the
+    /// connection dropped, and to `DbQueryError` otherwise.
+    pub fn db<E: Into<anyhow::Error>>(e: E) -> Self {
+        if is_connection_error(&e.into()) {
+            ServerError::DbUnavailable
+        } else {
+            ServerError::DbQueryError
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ServerError::InternalError => "internal_error",
+            ServerError::WrongParameters => "invalid_parameters",
+            ServerError::WrongResolution => "invalid_resolution",
+            ServerError::InvalidRange(_) => "invalid_range",
+            ServerError::DbQueryError => "db_query_failed",
+            ServerError::DbUnavailable => "db_unavailable",
+            ServerError::MarketNotFound => "market_not_found",
+            ServerError::SymbolNotFound => "symbol_not_found",
+            ServerError::PriceNotFound => "price_not_found",
+            ServerError::Unauthorized => "unauthorized",
+            ServerError::ApiKeyNotFound => "api_key_not_found",
+            ServerError::JobNotFound => "job_not_found",
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match *self {
+            ServerError::WrongResolution => Some(json!({
+                "accepted": ["1M", "3M", "5M", "15M", "30M", "1H", "2H", "4H", "1D", "1W", "1MO"]
+            })),
+            _ => None,
+        }
+    }
 }
 
 impl error::ResponseError for ServerError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code())
-            .insert_header(ContentType::html())
-            .body(self.to_string())
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details(),
+        })
     }
 
     fn status_code(&self) -> StatusCode {
@@ -35,10 +96,15 @@ impl error::ResponseError for ServerError {
             ServerError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
             ServerError::WrongParameters => StatusCode::BAD_REQUEST,
             ServerError::WrongResolution => StatusCode::BAD_REQUEST,
+            ServerError::InvalidRange(_) => StatusCode::UNPROCESSABLE_ENTITY,
             ServerError::DbQueryError => StatusCode::INTERNAL_SERVER_ERROR,
-            ServerError::DbPoolError => StatusCode::INTERNAL_SERVER_ERROR,
-            ServerError::MarketNotFound => StatusCode::BAD_REQUEST,
-            ServerError::SymbolNotFound => StatusCode::BAD_REQUEST,
+            ServerError::DbUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ServerError::MarketNotFound => StatusCode::NOT_FOUND,
+            ServerError::SymbolNotFound => StatusCode::NOT_FOUND,
+            ServerError::PriceNotFound => StatusCode::NOT_FOUND,
+            ServerError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ServerError::ApiKeyNotFound => StatusCode::NOT_FOUND,
+            ServerError::JobNotFound => StatusCode::NOT_FOUND,
         }
     }
 }
@@ -0,0 +1,107 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_web::{get, web, web::Data, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use openbook_candles::{
+    database::fetch::fetch_candles_from,
+    structs::{
+        resolution::Resolution,
+        session::{SessionConfig, SessionStats},
+    },
+    utils::WebContext,
+};
+use tracing::warn;
+
+use super::{embargo::visible_until, server_error::ServerError, validation::resolve_market};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Stats over the complete minute candles of the current session, and where they end.
+struct SessionTracker {
+    session_start: DateTime<Utc>,
+    complete: Option<SessionStats>,
+    through: DateTime<Utc>,
+}
+
+/// Keeps the session stats of every market current. Complete minute candles are folded in once,
+/// so each refresh only reads the candles since the last complete one.
+pub async fn refresh_session_stats(context: Data<WebContext>, config: SessionConfig) {
+    let mut trackers: HashMap<String, SessionTracker> = HashMap::new();
+    loop {
+        for market in context.markets.iter() {
+            if let Err(e) = refresh_market(&context, &config, &mut trackers, &market.name).await {
+                warn!(
+                    "Failed to refresh session stats of {}: {:?}",
+                    market.name, e
+                );
+            }
+        }
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+    }
+}
+
+async fn refresh_market(
+    context: &WebContext,
+    config: &SessionConfig,
+    trackers: &mut HashMap<String, SessionTracker>,
+    market_name: &str,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let session_start = config.session_start(now);
+    let tracker = trackers
+        .entry(market_name.to_string())
+        .or_insert(SessionTracker {
+            session_start,
+            complete: None,
+            through: session_start,
+        });
+    if tracker.session_start != session_start {
+        *tracker = SessionTracker {
+            session_start,
+            complete: None,
+            through: session_start,
+        };
+    }
+
+    // the current minute's candle ends after now
+    let end = now + Resolution::R1m.get_duration();
+    let candles = context
+        .read_pool
+        .run(|pool| fetch_candles_from(pool, market_name, Resolution::R1m, tracker.through, end))
+        .await?;
+    let complete_count = candles.iter().take_while(|c| c.complete).count();
+    let (complete, live) = candles.split_at(complete_count);
+    if let Some(last) = complete.last() {
+        tracker.complete = SessionStats::fold(tracker.complete.take(), session_start, complete);
+        tracker.through = last.end_time;
+    }
+
+    // a new session without candles yet has no stats, rather than the last session's
+    let stats = SessionStats::fold(tracker.complete.clone(), session_start, live);
+    let mut session_stats = context.session_stats.write().await;
+    match stats {
+        Some(stats) => session_stats.insert(market_name.to_string(), stats),
+        None => session_stats.remove(market_name),
+    };
+    Ok(())
+}
+
+/// Open, high, low and volume of the market's current session.
+#[get("/markets/{market_name:.+}/session")]
+pub async fn get_session_stats(
+    req: HttpRequest,
+    path: web::Path<String>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market_name = resolve_market(&path.into_inner(), &context)?.name.clone();
+    // the stats are live, which embargoed markets only are for realtime keys
+    if visible_until(&req, &context, &market_name).is_some() {
+        return Err(ServerError::Unauthorized);
+    }
+
+    let session_stats = context.session_stats.read().await;
+    match session_stats.get(&market_name) {
+        Some(stats) => Ok(HttpResponse::Ok().json(stats)),
+        None => Err(ServerError::PriceNotFound),
+    }
+}
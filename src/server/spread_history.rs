@@ -0,0 +1,74 @@
+use actix_web::{get, web, HttpResponse};
+use openbook_candles::{
+    database::fetch::fetch_spread_buckets,
+    structs::spread::SpreadHistoryResponse,
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::{
+    server_error::{ErrorBody, ServerError},
+    validation::{check_candle_range, parse_resolution, resolve_market},
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SpreadHistoryParams {
+    /// Market name or address
+    pub market: String,
+    /// Unix seconds
+    pub from: u64,
+    /// Unix seconds
+    pub to: u64,
+    /// Bucket size, any candle resolution, 1H by default
+    pub resolution: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/spread-history",
+    tag = "markets",
+    params(SpreadHistoryParams),
+    responses(
+        (status = 200, description = "Spread statistics per bucket of the range", body = SpreadHistoryResponse),
+        (status = 400, description = "Invalid resolution", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to` or too many buckets", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/spread-history")]
+pub async fn get_spread_history(
+    info: web::Query<SpreadHistoryParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&info.market, &context)?;
+    let resolution = parse_resolution(info.resolution.as_deref().unwrap_or("1H"))?;
+    // spreads are bucketed by a fixed number of seconds
+    if resolution.is_calendar() {
+        return Err(ServerError::WrongResolution);
+    }
+    let from = to_timestampz(info.from);
+    let to = to_timestampz(info.to);
+    check_candle_range(resolution, from, to, context.max_range_candles)?;
+
+    let buckets = context
+        .read_pool
+        .run(|pool| {
+            fetch_spread_buckets(
+                pool,
+                &market.name,
+                from,
+                to,
+                resolution.get_duration().num_seconds(),
+            )
+        })
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(SpreadHistoryResponse {
+        market_name: market.name.clone(),
+        resolution: resolution.to_string(),
+        buckets,
+    }))
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use actix_web::{get, http::header::ContentEncoding, web, HttpRequest, HttpResponse, Scope};
+use openbook_candles::utils::WebContext;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{
+    embargo::visible_until,
+    server_error::ServerError,
+    validation::{check_resolution, parse_resolution, resolve_market},
+};
+
+/// Comment sent when there was nothing else to send for this long, so proxies keep the stream open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+pub fn service() -> Scope {
+    web::scope("/sse").service(stream_candles)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamCandleParams {
+    pub market: String,
+    pub resolution: String,
+}
+
+#[get("/candles")]
+pub async fn stream_candles(
+    req: HttpRequest,
+    info: web::Query<StreamCandleParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let resolution = parse_resolution(&info.resolution)?;
+    let market = resolve_market(&info.market, &context)?;
+    check_resolution(market, resolution)?;
+    let market_name = market.name.clone();
+    // live updates of an embargoed market are only for realtime keys
+    if visible_until(&req, &context, &market_name).is_some() {
+        return Err(ServerError::Unauthorized);
+    }
+
+    let resolution = resolution.to_string();
+    let receiver = context.candle_updates.subscribe();
+    let events = futures::stream::unfold(receiver, move |mut receiver| {
+        let market_name = market_name.clone();
+        let resolution = resolution.clone();
+        async move {
+            loop {
+                let event = match tokio::time::timeout(KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+                    Err(_) => ": keep-alive\n\n".to_string(),
+                    Ok(Ok(change))
+                        if change.market_name == market_name && change.resolution == resolution =>
+                    {
+                        change.to_sse_event()
+                    }
+                    Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => continue,
+                    Ok(Err(RecvError::Closed)) => return None,
+                };
+                return Some((Ok::<_, actix_web::Error>(web::Bytes::from(event)), receiver));
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // compressing would hold events back until the encoder's buffer fills
+        .insert_header(ContentEncoding::Identity)
+        .streaming(events))
+}
@@ -0,0 +1,124 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use chrono::Utc;
+use openbook_candles::{
+    database::{fetch::fetch_newest_candle_end_times, migrations::fetch_pending_migrations},
+    structs::resolution::Resolution,
+    utils::WebContext,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+fn default_startup_max_candle_age_secs() -> i64 {
+    900
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StartupConfig {
+    /// Comma separated names or addresses of markets that need a recent minute candle before the
+    /// server is ready
+    pub startup_critical_markets: Option<String>,
+    /// How old the newest minute candle of a critical market may be
+    #[serde(default = "default_startup_max_candle_age_secs")]
+    pub startup_max_candle_age_secs: i64,
+    /// Don't bind the public port until the gate has passed
+    #[serde(default)]
+    pub startup_wait_before_bind: bool,
+}
+
+impl StartupConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        openbook_candles::utils::settings::load()
+    }
+
+    fn critical_markets(&self) -> Vec<&str> {
+        self.startup_critical_markets
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+}
+
+/// Holds readiness back after a deploy until the schema is migrated and the critical markets have
+/// recent candles. Once passed it stays passed, later staleness is the readiness check's job.
+pub struct StartupGate {
+    pub config: StartupConfig,
+    passed: AtomicBool,
+}
+
+impl StartupGate {
+    pub fn new(config: StartupConfig) -> Self {
+        StartupGate {
+            config,
+            passed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.passed.load(Ordering::Relaxed)
+    }
+
+    /// Polls until the gate passes.
+    pub async fn wait(&self, context: &WebContext) {
+        while !self.has_passed() {
+            match self.unmet_conditions(context).await {
+                Ok(unmet) if unmet.is_empty() => {
+                    info!("Startup gate passed");
+                    self.passed.store(true, Ordering::Relaxed);
+                }
+                Ok(unmet) => {
+                    info!("Waiting for startup data: {}", unmet.join(", "));
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Err(e) => {
+                    warn!("Failed to check startup data: {:?}", e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn unmet_conditions(&self, context: &WebContext) -> anyhow::Result<Vec<String>> {
+        let mut unmet = vec![];
+        let pending = context.read_pool.run(fetch_pending_migrations).await?;
+        if !pending.is_empty() {
+            unmet.push(format!("{} pending migrations", pending.len()));
+        }
+
+        let critical = self.config.critical_markets();
+        if critical.is_empty() {
+            return Ok(unmet);
+        }
+        let newest = context
+            .read_pool
+            .run(|pool| fetch_newest_candle_end_times(pool, Resolution::R1m))
+            .await?;
+        let now = Utc::now();
+        for key in critical {
+            let name = match context.find_market(key) {
+                Some(market) => &market.name,
+                None => {
+                    // an unknown market would hold the gate forever
+                    warn!("Startup critical market {} is not configured", key);
+                    continue;
+                }
+            };
+            let fresh = newest.iter().any(|(market_name, end)| {
+                market_name == name
+                    && (now - *end).num_seconds() <= self.config.startup_max_candle_age_secs
+            });
+            if !fresh {
+                unmet.push(format!("no recent candle for {}", name));
+            }
+        }
+        Ok(unmet)
+    }
+}
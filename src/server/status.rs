@@ -0,0 +1,81 @@
+use std::collections::{BTreeMap, HashMap};
+
+use actix_web::{get, web, HttpResponse};
+use chrono::Utc;
+use futures::try_join;
+use openbook_candles::{
+    database::{
+        fetch::{fetch_complete_candle_end_times, fetch_latest_fill_slots},
+        lifecycle::{fetch_batch_errors, fetch_market_lifecycles},
+    },
+    structs::market_status::MarketStatus,
+    utils::WebContext,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use super::server_error::ServerError;
+
+/// Ingestion and batching state of every tracked market.
+#[utoipa::path(
+    get,
+    path = "/api/status/markets",
+    tag = "status",
+    responses((status = 200, description = "State of every market", body = [MarketStatus]))
+)]
+#[get("/status/markets")]
+pub async fn get_market_statuses(
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let (lifecycles, fill_slots, candle_ends, batch_errors) = try_join!(
+        context.read_pool.run(fetch_market_lifecycles),
+        context.read_pool.run(fetch_latest_fill_slots),
+        context.read_pool.run(fetch_complete_candle_end_times),
+        context.read_pool.run(fetch_batch_errors),
+    )
+    .map_err(ServerError::db)?;
+    // slot lags are left out rather than failing the request when RPC is down
+    let chain_slot = RpcClient::new(context.rpc_url.clone())
+        .get_slot()
+        .await
+        .ok();
+
+    let fill_slots: HashMap<String, i64> = fill_slots.into_iter().collect();
+    let mut complete_through: HashMap<String, BTreeMap<String, i64>> = HashMap::new();
+    for (market_name, resolution, end_time) in candle_ends {
+        complete_through
+            .entry(market_name)
+            .or_default()
+            .insert(resolution, end_time.timestamp());
+    }
+
+    let now = Utc::now();
+    let statuses: Vec<MarketStatus> = context
+        .markets
+        .iter()
+        .map(|m| {
+            let last_fill_at = lifecycles
+                .iter()
+                .find(|l| l.market == m.address)
+                .and_then(|l| l.last_fill_at);
+            let last_fill_slot = fill_slots.get(&m.address).copied();
+            let (batch_errors, last_batch_error_at) = batch_errors
+                .iter()
+                .find(|(market, _, _)| *market == m.address)
+                .map_or((0, None), |(_, count, at)| (*count, *at));
+            MarketStatus {
+                market_name: m.name.clone(),
+                address: m.address.clone(),
+                last_fill_at,
+                last_fill_slot,
+                lag_slots: chain_slot
+                    .zip(last_fill_slot)
+                    .map(|(chain, fill)| chain.saturating_sub(fill as u64)),
+                lag_secs: last_fill_at.map(|t| (now - t).num_seconds()),
+                complete_candles_through: complete_through.remove(&m.name).unwrap_or_default(),
+                batch_errors,
+                last_batch_error_at,
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(statuses))
+}
@@ -1,97 +1,311 @@
-use crate::server_error::ServerError;
+use super::{
+    privacy::anonymize_traders,
+    server_error::{ErrorBody, ServerError},
+    validation::{check_range, resolve_market},
+};
+use chrono::Utc;
 use openbook_candles::{
-    database::fetch::{
-        fetch_top_traders_by_base_volume_from, fetch_top_traders_by_quote_volume_from,
+    database::{
+        fetch::{
+            fetch_top_traders_by_base_volume_from, fetch_top_traders_by_quote_volume_from,
+            fetch_trader_leaderboard,
+        },
+        uptime::fetch_maker_uptime,
+    },
+    structs::{
+        trader::{
+            calculate_trader_volume, LeaderboardPeriod, LeaderboardResponse, Trader,
+            TraderResponse, TraderRole, VolumeType,
+        },
+        uptime::UptimeResponse,
     },
-    structs::trader::{calculate_trader_volume, Trader, TraderResponse, VolumeType},
     utils::{to_timestampz, WebContext},
 };
 use {
-    actix_web::{get, web, HttpResponse},
+    actix_web::{get, web, HttpRequest, HttpResponse},
     serde::Deserialize,
+    tracing::error,
+    utoipa::IntoParams,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TraderParams {
+    /// Market name or address
     pub market_name: String,
+    /// Unix seconds
     pub from: u64,
+    /// Unix seconds
     pub to: u64,
+    /// maker, taker or all (default)
+    pub role: Option<String>,
+}
+
+fn parse_role(role: &Option<String>) -> Result<TraderRole, ServerError> {
+    match role {
+        Some(r) => TraderRole::from_str(r).map_err(|_| ServerError::WrongParameters),
+        None => Ok(TraderRole::All),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardParams {
+    /// Market name or address
+    pub market_name: String,
+    /// 1D or 1W
+    pub period: String,
+    /// base or quote
+    pub volume_type: String,
+    pub role: Option<String>,
+    /// Any timestamp within the requested period, defaults to the current period
+    pub time: Option<u64>,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UptimeParams {
+    /// Market name or address
+    pub market_name: String,
+    /// Unix seconds, counted from the start of its UTC day
+    pub from: u64,
+    /// Unix seconds, counted until the end of its UTC day
+    pub to: u64,
+}
+
+/// Replaces the traders' addresses with pseudonyms when the request has to be anonymized.
+fn anonymize(req: &HttpRequest, context: &WebContext, mut traders: Vec<Trader>) -> Vec<Trader> {
+    if anonymize_traders(req, context) {
+        for trader in traders.iter_mut() {
+            trader.pubkey = context.anonymizer.pseudonym(&trader.pubkey);
+        }
+    }
+    traders
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/traders/base-volume",
+    tag = "traders",
+    params(TraderParams),
+    responses(
+        (status = 200, description = "Traders ranked by base volume", body = TraderResponse),
+        (status = 400, description = "Unknown role", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
 #[get("/traders/base-volume")]
 pub async fn get_top_traders_by_base_volume(
+    req: HttpRequest,
     info: web::Query<TraderParams>,
     context: web::Data<WebContext>,
 ) -> Result<HttpResponse, ServerError> {
-    let selected_market = context.markets.iter().find(|x| x.name == info.market_name);
-    if selected_market.is_none() {
-        return Err(ServerError::MarketNotFound);
-    }
-    let selected_market = selected_market.unwrap();
+    let selected_market = resolve_market(&info.market_name, &context)?;
+    let role = parse_role(&info.role)?;
     let from = to_timestampz(info.from);
     let to = to_timestampz(info.to);
+    check_range(from, to)?;
 
-    let raw_traders = match fetch_top_traders_by_base_volume_from(
-        &context.pool,
-        &selected_market.address,
-        from,
-        to,
-    )
-    .await
+    let raw_traders = match context
+        .read_pool
+        .run(|pool| {
+            fetch_top_traders_by_base_volume_from(pool, &selected_market.address, from, to, role)
+        })
+        .await
     {
         Ok(c) => c,
-        Err(_) => return Err(ServerError::DbQueryError),
+        Err(e) => return Err(ServerError::db(e)),
     };
 
     let traders = raw_traders
         .into_iter()
         .map(|t| calculate_trader_volume(t, selected_market.base_decimals))
-        .collect::<Vec<Trader>>();
+        .collect::<anyhow::Result<Vec<Trader>>>()
+        .map_err(|e| {
+            error!("Failed to scale trader volumes: {:?}", e);
+            ServerError::InternalError
+        })?;
+    let traders = anonymize(&req, &context, traders);
 
     let response = TraderResponse {
         start_time: info.from,
         end_time: info.to,
         traders,
         volume_type: VolumeType::Base.to_string(),
+        role: role.to_string(),
     };
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/traders/quote-volume",
+    tag = "traders",
+    params(TraderParams),
+    responses(
+        (status = 200, description = "Traders ranked by quote volume", body = TraderResponse),
+        (status = 400, description = "Unknown role", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
 #[get("/traders/quote-volume")]
 pub async fn get_top_traders_by_quote_volume(
+    req: HttpRequest,
     info: web::Query<TraderParams>,
     context: web::Data<WebContext>,
 ) -> Result<HttpResponse, ServerError> {
-    let selected_market = context.markets.iter().find(|x| x.name == info.market_name);
-    if selected_market.is_none() {
-        return Err(ServerError::MarketNotFound);
-    }
-    let selected_market = selected_market.unwrap();
+    let selected_market = resolve_market(&info.market_name, &context)?;
+    let role = parse_role(&info.role)?;
     let from = to_timestampz(info.from);
     let to = to_timestampz(info.to);
+    check_range(from, to)?;
 
-    let raw_traders = match fetch_top_traders_by_quote_volume_from(
-        &context.pool,
-        &selected_market.address,
-        from,
-        to,
-    )
-    .await
+    let raw_traders = match context
+        .read_pool
+        .run(|pool| {
+            fetch_top_traders_by_quote_volume_from(pool, &selected_market.address, from, to, role)
+        })
+        .await
     {
         Ok(c) => c,
-        Err(_) => return Err(ServerError::DbQueryError),
+        Err(e) => return Err(ServerError::db(e)),
     };
 
     let traders = raw_traders
         .into_iter()
         .map(|t| calculate_trader_volume(t, selected_market.quote_decimals))
-        .collect::<Vec<Trader>>();
+        .collect::<anyhow::Result<Vec<Trader>>>()
+        .map_err(|e| {
+            error!("Failed to scale trader volumes: {:?}", e);
+            ServerError::InternalError
+        })?;
+    let traders = anonymize(&req, &context, traders);
 
     let response = TraderResponse {
         start_time: info.from,
         end_time: info.to,
         traders,
         volume_type: VolumeType::Quote.to_string(),
+        role: role.to_string(),
     };
     Ok(HttpResponse::Ok().json(response))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/traders/top",
+    tag = "traders",
+    params(LeaderboardParams),
+    responses(
+        (status = 200, description = "Leaderboard of the period", body = LeaderboardResponse),
+        (status = 400, description = "Unknown period, volume type or role", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/traders/top")]
+pub async fn get_trader_leaderboard(
+    req: HttpRequest,
+    info: web::Query<LeaderboardParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let selected_market = resolve_market(&info.market_name, &context)?;
+    let period =
+        LeaderboardPeriod::from_str(&info.period).map_err(|_| ServerError::WrongParameters)?;
+    let volume_type =
+        VolumeType::from_str(&info.volume_type).map_err(|_| ServerError::WrongParameters)?;
+    let role = parse_role(&info.role)?;
+    let time = match info.time {
+        Some(t) => to_timestampz(t),
+        None => Utc::now(),
+    };
+    let period_start = period.period_start(time);
+    let period_end = period_start + period.get_duration();
+
+    let entries = match context
+        .read_pool
+        .run(|pool| {
+            fetch_trader_leaderboard(
+                pool,
+                &selected_market.address,
+                period,
+                period_start,
+                volume_type.clone(),
+                role,
+            )
+        })
+        .await
+    {
+        Ok(c) => c,
+        Err(e) => return Err(ServerError::db(e)),
+    };
+
+    let traders = entries
+        .into_iter()
+        .map(|e| Trader {
+            pubkey: e.open_orders_owner,
+            volume: e.volume,
+            maker_volume: e.maker_volume,
+            taker_volume: e.taker_volume,
+        })
+        .collect::<Vec<Trader>>();
+    let traders = anonymize(&req, &context, traders);
+
+    let response = LeaderboardResponse {
+        market_name: selected_market.name.clone(),
+        period: period.to_string(),
+        start_time: period_start.timestamp() as u64,
+        end_time: period_end.timestamp() as u64,
+        volume_type: volume_type.to_string(),
+        role: role.to_string(),
+        traders,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/traders/uptime",
+    tag = "traders",
+    params(UptimeParams),
+    responses(
+        (status = 200, description = "Share of order book samples each maker quoted in", body = UptimeResponse),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/traders/uptime")]
+pub async fn get_maker_uptime(
+    req: HttpRequest,
+    info: web::Query<UptimeParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let selected_market = resolve_market(&info.market_name, &context)?;
+    let from = to_timestampz(info.from);
+    let to = to_timestampz(info.to);
+    check_range(from, to)?;
+    let (from, to) = (from.date_naive(), to.date_naive());
+
+    let mut makers = context
+        .read_pool
+        .run(|pool| fetch_maker_uptime(pool, &selected_market.address, from, to))
+        .await
+        .map_err(ServerError::db)?;
+    if anonymize_traders(&req, &context) {
+        for maker in makers.iter_mut() {
+            maker.open_orders_owner = context.anonymizer.pseudonym(&maker.open_orders_owner);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(UptimeResponse {
+        market_name: selected_market.name.clone(),
+        from,
+        to,
+        makers,
+    }))
+}
@@ -0,0 +1,139 @@
+use super::{
+    embargo::visible_until,
+    privacy::anonymize_traders,
+    server_error::{ErrorBody, ServerError},
+    validation::{check_range, resolve_market_on},
+};
+use openbook_candles::{
+    database::fetch::{fetch_trade_buckets, fetch_trades},
+    structs::{
+        trade::{TradeFilter, TradeGrouping, TradeSide, TradesResponse},
+        venue::Venue,
+    },
+    utils::{to_timestampz, WebContext},
+};
+use {
+    actix_web::{get, web, HttpRequest, HttpResponse},
+    serde::Deserialize,
+    utoipa::IntoParams,
+};
+
+const DEFAULT_TRADES_LIMIT: i64 = 1000;
+const MAX_TRADES_LIMIT: i64 = 5000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TradesParams {
+    /// Market name or address
+    pub market_name: String,
+    /// Unix seconds
+    pub from: u64,
+    /// Unix seconds
+    pub to: u64,
+    /// buy or sell, the taker's side
+    pub side: Option<String>,
+    /// Minimum base size of a trade
+    pub min_size: Option<f64>,
+    /// Aggregate trades per interval instead of listing them, only `minute` is supported
+    pub group_by: Option<String>,
+    pub limit: Option<i64>,
+    /// maker or taker, whose fill and fees are returned. Buckets are always built from maker fills
+    pub role: Option<String>,
+    /// openbook or phoenix, the market's name can then leave out its `@<venue>` suffix
+    #[param(inline)]
+    pub venue: Option<Venue>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/trades",
+    tag = "trades",
+    params(TradesParams),
+    responses(
+        (status = 200, description = "Trades or trade buckets of the range", body = TradesResponse),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/trades")]
+pub async fn get_trades(
+    req: HttpRequest,
+    info: web::Query<TradesParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let selected_market = resolve_market_on(&info.market_name, info.venue, &context)?;
+    let side = match &info.side {
+        Some(s) => Some(TradeSide::from_str(s).map_err(|_| ServerError::WrongParameters)?),
+        None => None,
+    };
+    let grouping = match &info.group_by {
+        Some(g) => Some(TradeGrouping::from_str(g).map_err(|_| ServerError::WrongParameters)?),
+        None => None,
+    };
+    let taker = match info.role.as_deref() {
+        None | Some("maker") => false,
+        Some("taker") if grouping.is_none() => true,
+        Some(_) => return Err(ServerError::WrongParameters),
+    };
+    let filter = TradeFilter {
+        side,
+        min_size: info.min_size.unwrap_or(0.0),
+        taker,
+    };
+    let limit = info
+        .limit
+        .unwrap_or(DEFAULT_TRADES_LIMIT)
+        .clamp(1, MAX_TRADES_LIMIT);
+    let from = to_timestampz(info.from);
+    check_range(from, to_timestampz(info.to))?;
+    let to = match visible_until(&req, &context, &selected_market.name) {
+        Some(until) => to_timestampz(info.to).min(until),
+        None => to_timestampz(info.to),
+    };
+
+    let response = match grouping {
+        Some(grouping) => context
+            .read_pool
+            .run(|pool| {
+                fetch_trade_buckets(
+                    pool,
+                    &selected_market.address,
+                    from,
+                    to,
+                    &filter,
+                    grouping,
+                    limit,
+                )
+            })
+            .await
+            .map(TradesResponse::Buckets),
+        None => context
+            .read_pool
+            .run(|pool| {
+                fetch_trades(
+                    pool,
+                    &selected_market.address,
+                    from,
+                    to,
+                    &filter,
+                    limit,
+                    selected_market.quote_decimals,
+                )
+            })
+            .await
+            .map(|mut trades| {
+                if anonymize_traders(&req, &context) {
+                    // the signature leads straight to the wallet on any explorer
+                    for trade in trades.iter_mut() {
+                        trade.signature = context.anonymizer.pseudonym(&trade.signature);
+                    }
+                }
+                TradesResponse::Trades(trades)
+            }),
+    }
+    .map_err(ServerError::db)?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,68 @@
+use openbook_candles::{
+    structs::{
+        candle::Candle,
+        market_summary::MarketSummary,
+        resolution::Resolution,
+        usd::{usd_reference, usd_volumes, UsdReference},
+    },
+    utils::WebContext,
+};
+
+use super::server_error::ServerError;
+
+/// USD quote volumes of a market's candles, valued with the candles of its reference market over
+/// the same range. All `None` when the quote token has no USD reference.
+pub async fn candle_usd_volumes(
+    context: &WebContext,
+    market_name: &str,
+    resolution: Resolution,
+    candles: &[Candle],
+) -> Result<Vec<Option<f64>>, ServerError> {
+    let market = context
+        .markets
+        .iter()
+        .find(|m| m.name == market_name)
+        .ok_or(ServerError::MarketNotFound)?;
+    let reference = match usd_reference(market, &context.markets) {
+        None => return Ok(vec![None; candles.len()]),
+        Some(UsdReference::Par) => return Ok(usd_volumes(candles, None)),
+        Some(UsdReference::Market(reference)) => reference,
+    };
+    let from = candles.iter().map(|c| c.start_time).min();
+    let to = candles.iter().map(|c| c.end_time).max();
+    let (from, to) = match from.zip(to) {
+        Some(range) => range,
+        None => return Ok(vec![]),
+    };
+    let reference_candles = context
+        .read_pool
+        .run(|pool| {
+            context
+                .candle_cache
+                .fetch_candles(pool, &reference.name, resolution, from, to)
+        })
+        .await
+        .map_err(ServerError::db)?;
+    Ok(usd_volumes(candles, Some(&reference_candles)))
+}
+
+/// Values each summary's 24h quote volume at the last price of its USD reference market.
+/// `summaries` must be in the order of `context.markets`.
+pub fn add_summary_usd_volumes(context: &WebContext, summaries: &mut [MarketSummary]) {
+    let usd_prices: Vec<Option<f64>> = context
+        .markets
+        .iter()
+        .map(|m| match usd_reference(m, &context.markets)? {
+            UsdReference::Par => Some(1.0),
+            UsdReference::Market(reference) => {
+                summaries
+                    .iter()
+                    .find(|s| s.address == reference.address)?
+                    .last_price
+            }
+        })
+        .collect();
+    for (summary, usd_price) in summaries.iter_mut().zip(usd_prices) {
+        summary.quote_volume_24h_usd = usd_price.map(|p| summary.quote_volume_24h * p);
+    }
+}
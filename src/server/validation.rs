@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, Utc};
+use openbook_candles::{
+    structs::{markets::MarketInfo, resolution::Resolution, venue::Venue},
+    utils::WebContext,
+};
+use serde::{Deserialize, Serialize};
+
+use super::server_error::ServerError;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ValidationConfig {
+    /// Longest range of a candle request, counted in candles of the requested resolution
+    #[serde(default = "default_max_range_candles")]
+    pub max_range_candles: i64,
+}
+
+fn default_max_range_candles() -> i64 {
+    50_000
+}
+
+impl ValidationConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        openbook_candles::utils::settings::load()
+    }
+}
+
+pub fn parse_resolution(resolution: &str) -> Result<Resolution, ServerError> {
+    Resolution::from_str(resolution).map_err(|_| ServerError::WrongResolution)
+}
+
+/// Rejects resolutions the market's candles aren't kept in.
+pub fn check_resolution(market: &MarketInfo, resolution: Resolution) -> Result<(), ServerError> {
+    if !market.keeps(resolution) {
+        return Err(ServerError::WrongResolution);
+    }
+    Ok(())
+}
+
+/// Offset from UTC of a `tz` parameter: `UTC`, or `+HH:MM`, `-HH:MM`, `+HHMM` or `+HH`, at most
+/// 14 hours either way. `None` for UTC itself.
+pub fn parse_utc_offset(tz: &str) -> Result<Option<Duration>, ServerError> {
+    if tz.eq_ignore_ascii_case("utc") || tz == "Z" {
+        return Ok(None);
+    }
+    let (sign, digits) = match tz.split_at(tz.len().min(1)) {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return Err(ServerError::WrongParameters),
+    };
+    let digits = digits.replace(':', "");
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ServerError::WrongParameters);
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i64>().unwrap(), 0),
+        4 => (
+            digits[..2].parse::<i64>().unwrap(),
+            digits[2..].parse::<i64>().unwrap(),
+        ),
+        _ => return Err(ServerError::WrongParameters),
+    };
+    let offset = hours * 60 + minutes;
+    if minutes >= 60 || offset > 14 * 60 {
+        return Err(ServerError::WrongParameters);
+    }
+    Ok((offset > 0).then(|| Duration::minutes(sign * offset)))
+}
+
+/// The market a request names by name or address.
+pub fn resolve_market<'a>(
+    key: &str,
+    context: &'a WebContext,
+) -> Result<&'a MarketInfo, ServerError> {
+    context.find_market(key).ok_or(ServerError::MarketNotFound)
+}
+
+/// The market a request names by name or address, on `venue` when the request gives one.
+pub fn resolve_market_on<'a>(
+    key: &str,
+    venue: Option<Venue>,
+    context: &'a WebContext,
+) -> Result<&'a MarketInfo, ServerError> {
+    match venue {
+        Some(venue) => context.find_market_on(key, venue),
+        None => context.find_market(key),
+    }
+    .ok_or(ServerError::MarketNotFound)
+}
+
+/// Rejects ranges that end before they start.
+pub fn check_range(from: DateTime<Utc>, to: DateTime<Utc>) -> Result<(), ServerError> {
+    if from > to {
+        return Err(ServerError::InvalidRange(format!(
+            "from ({}) is after to ({})",
+            from.timestamp(),
+            to.timestamp()
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects ranges that end before they start or span more than `max_candles` candles of the
+/// resolution.
+pub fn check_candle_range(
+    resolution: Resolution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    max_candles: i64,
+) -> Result<(), ServerError> {
+    check_range(from, to)?;
+    let max_range = resolution.get_duration() * max_candles as i32;
+    if to - from > max_range {
+        return Err(ServerError::InvalidRange(format!(
+            "at most {} {} candles per request",
+            max_candles, resolution
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use openbook_candles::{
+    database::fetch::fetch_volume_profile,
+    structs::volume_profile::VolumeProfileResponse,
+    utils::{to_timestampz, WebContext},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::{
+    embargo::visible_until,
+    server_error::{ErrorBody, ServerError},
+    validation::{check_range, resolve_market},
+};
+
+const DEFAULT_VOLUME_PROFILE_BINS: i32 = 50;
+const MAX_VOLUME_PROFILE_BINS: i32 = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VolumeProfileParams {
+    /// Market name or address
+    pub market: String,
+    /// Unix seconds
+    pub from: u64,
+    /// Unix seconds
+    pub to: u64,
+    /// Number of price levels, 50 by default and at most 1000
+    pub bins: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/volume-profile",
+    tag = "trades",
+    params(VolumeProfileParams),
+    responses(
+        (status = 200, description = "Traded volume per price level of the range", body = VolumeProfileResponse),
+        (status = 400, description = "Invalid number of bins", body = ErrorBody),
+        (status = 404, description = "Unknown market", body = ErrorBody),
+        (status = 422, description = "`from` after `to`", body = ErrorBody),
+        (status = 503, description = "Database unavailable", body = ErrorBody),
+    )
+)]
+#[get("/volume-profile")]
+pub async fn get_volume_profile(
+    req: HttpRequest,
+    info: web::Query<VolumeProfileParams>,
+    context: web::Data<WebContext>,
+) -> Result<HttpResponse, ServerError> {
+    let market = resolve_market(&info.market, &context)?;
+    let bins = info.bins.unwrap_or(DEFAULT_VOLUME_PROFILE_BINS);
+    if !(1..=MAX_VOLUME_PROFILE_BINS).contains(&bins) {
+        return Err(ServerError::WrongParameters);
+    }
+    let from = to_timestampz(info.from);
+    check_range(from, to_timestampz(info.to))?;
+    let to = match visible_until(&req, &context, &market.name) {
+        Some(until) => to_timestampz(info.to).min(until),
+        None => to_timestampz(info.to),
+    };
+
+    let rows = context
+        .read_pool
+        .run(|pool| fetch_volume_profile(pool, &market.address, from, to, bins))
+        .await
+        .map_err(ServerError::db)?;
+    Ok(HttpResponse::Ok().json(VolumeProfileResponse::from_bins(
+        market.name.clone(),
+        from.timestamp(),
+        to.timestamp(),
+        bins,
+        rows,
+    )))
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio_postgres::Row;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiKey {
+    pub id: i64,
+    #[serde(skip)]
+    pub key_hash: String,
+    pub name: String,
+    pub requests_per_minute: i32,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Sees embargoed markets without the delay
+    pub realtime: bool,
+}
+
+impl ApiKey {
+    pub fn from_row(row: Row) -> Self {
+        ApiKey {
+            id: row.get(0),
+            key_hash: row.get(1),
+            name: row.get(2),
+            requests_per_minute: row.get(3),
+            created_at: row.get(4),
+            revoked_at: row.get(5),
+            realtime: row.get(6),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+}
+
+/// A freshly issued key, the only time the plain key is ever returned.
+#[derive(Clone, Debug, Serialize)]
+pub struct IssuedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiKeyUsage {
+    pub day: NaiveDate,
+    pub requests: i64,
+}
+
+impl ApiKeyUsage {
+    pub fn from_row(row: Row) -> Self {
+        ApiKeyUsage {
+            day: row.get(0),
+            requests: row.get(1),
+        }
+    }
+}
+
+/// Keys are only stored as their sha256 hex digest.
+pub fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// 32 random bytes, hex encoded.
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::RwLock;
+
+use crate::utils::settings::CommonConfig;
+
+/// Key-value store for cached responses and candle buckets. The in-memory backend is per process,
+/// the Redis backend is shared by every server and worker pointed at the same instance.
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> anyhow::Result<()>;
+
+    /// Whether other processes see what this one writes
+    fn is_shared(&self) -> bool;
+}
+
+/// Reads and deserializes a JSON value, treating backend and decode errors as a miss.
+pub async fn get_json<T: DeserializeOwned>(backend: &dyn CacheBackend, key: &str) -> Option<T> {
+    match backend.get(key).await {
+        Ok(Some(bytes)) => serde_json::from_slice(&bytes).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Cache read of {} failed: {:?}", key, e);
+            None
+        }
+    }
+}
+
+/// Serializes and writes a JSON value, a failed write is logged and otherwise ignored.
+pub async fn set_json<T: Serialize>(
+    backend: &dyn CacheBackend,
+    key: &str,
+    value: &T,
+    ttl: Duration,
+) {
+    let result = match serde_json::to_vec(value) {
+        Ok(bytes) => backend.set(key, bytes, ttl).await,
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = result {
+        tracing::warn!("Cache write of {} failed: {:?}", key, e);
+    }
+}
+
+const MAX_MEMORY_ENTRIES: usize = 10_000;
+
+#[derive(Default)]
+pub struct MemoryCacheBackend {
+    entries: RwLock<HashMap<String, (Vec<u8>, Instant)>>,
+}
+
+#[async_trait]
+impl CacheBackend for MemoryCacheBackend {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let entries = self.entries.read().await;
+        Ok(entries
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> anyhow::Result<()> {
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        if entries.len() >= MAX_MEMORY_ENTRIES {
+            entries.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        entries.insert(key.to_string(), (value, now + ttl));
+        Ok(())
+    }
+
+    fn is_shared(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "redis")]
+pub struct RedisCacheBackend {
+    connection: redis::aio::ConnectionManager,
+}
+
+#[cfg(feature = "redis")]
+impl RedisCacheBackend {
+    pub async fn connect(redis_url: &str) -> anyhow::Result<Self> {
+        let client = redis::Client::open(redis_url)?;
+        let connection = redis::aio::ConnectionManager::new(client).await?;
+        Ok(RedisCacheBackend { connection })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl CacheBackend for RedisCacheBackend {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut connection = self.connection.clone();
+        let value: Option<Vec<u8>> = redis::cmd("GET")
+            .arg(key)
+            .query_async(&mut connection)
+            .await?;
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> anyhow::Result<()> {
+        let mut connection = self.connection.clone();
+        redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+        Ok(())
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+}
+
+/// Connects to `REDIS_URL` when it is set, otherwise falls back to a per-process memory cache.
+pub async fn cache_backend_from_env() -> anyhow::Result<Arc<dyn CacheBackend>> {
+    let redis_url = CommonConfig::from_env()?
+        .redis_url
+        .filter(|u| !u.is_empty());
+    match redis_url {
+        #[cfg(feature = "redis")]
+        Some(url) => Ok(Arc::new(RedisCacheBackend::connect(&url).await?)),
+        #[cfg(not(feature = "redis"))]
+        Some(_) => anyhow::bail!("REDIS_URL is set but the redis feature is not enabled"),
+        None => Ok(Arc::new(MemoryCacheBackend::default())),
+    }
+}
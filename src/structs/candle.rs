@@ -1,9 +1,12 @@
 use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
 
+use crate::utils::{f64_max, f64_min};
+
 use super::resolution::Resolution;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Candle {
     pub market_name: String,
     pub start_time: DateTime<Utc>,
@@ -15,6 +18,15 @@ pub struct Candle {
     pub low: f64,
     pub volume: f64,
     pub complete: bool,
+    /// Volume weighted average price, equal to close when there were no trades
+    pub vwap: f64,
+    pub trade_count: i64,
+    /// Sum of price * size over the bucket
+    pub quote_volume: f64,
+    /// Volume and quote volume of the fills that set prices, which leaves out anomalous fills.
+    /// The vwap is their ratio, and higher resolutions add them up to get theirs.
+    pub priced_volume: f64,
+    pub priced_quote_volume: f64,
 }
 
 impl Candle {
@@ -30,10 +42,17 @@ impl Candle {
             low: 0.0,
             volume: 0.0,
             complete: false,
+            vwap: 0.0,
+            trade_count: 0,
+            quote_volume: 0.0,
+            priced_volume: 0.0,
+            priced_quote_volume: 0.0,
         }
     }
 
     pub fn from_row(row: Row) -> Self {
+        let volume: f64 = row.get(8);
+        let vwap: f64 = row.get(10);
         Candle {
             market_name: row.get(0),
             start_time: row.get(1),
@@ -43,8 +62,97 @@ impl Candle {
             close: row.get(5),
             high: row.get(6),
             low: row.get(7),
-            volume: row.get(8),
+            volume,
             complete: row.get(9),
+            vwap,
+            trade_count: row.get(11),
+            quote_volume: row.get(12),
+            // candles stored before these were kept count every fill as priced
+            priced_volume: row.get::<usize, Option<f64>>(13).unwrap_or(volume),
+            priced_quote_volume: row.get::<usize, Option<f64>>(14).unwrap_or(vwap * volume),
+        }
+    }
+}
+
+/// Inserts a zero-volume candle carrying the previous close into every bucket missing between two
+/// candles. `candles` must be sorted by start time and all be of `resolution`; nothing is added
+/// before the first or after the last candle.
+pub fn fill_candle_gaps(candles: Vec<Candle>, resolution: Resolution) -> Vec<Candle> {
+    let mut filled: Vec<Candle> = Vec::with_capacity(candles.len());
+    for candle in candles {
+        while let Some(prev) = filled.last() {
+            let end_time = resolution.bucket_end(prev.end_time);
+            if end_time > candle.start_time {
+                break;
+            }
+            let empty = Candle {
+                start_time: prev.end_time,
+                end_time,
+                open: prev.close,
+                high: prev.close,
+                low: prev.close,
+                volume: 0.0,
+                complete: true,
+                vwap: prev.close,
+                trade_count: 0,
+                quote_volume: 0.0,
+                priced_volume: 0.0,
+                priced_quote_volume: 0.0,
+                ..prev.clone()
+            };
+            filled.push(empty);
+        }
+        filled.push(candle);
+    }
+    filled
+}
+
+/// Heikin-Ashi candles of `candles`, sorted by time: the close is the average of open, high, low
+/// and close, the open the midpoint of the previous Heikin-Ashi open and close, and high and low
+/// stretch to cover both. The first candle has no predecessor and opens at the midpoint of its
+/// own open and close, so pass the candle before a range along and drop it afterwards.
+pub fn heikin_ashi(candles: Vec<Candle>) -> Vec<Candle> {
+    let mut previous: Option<(f64, f64)> = None;
+    candles
+        .into_iter()
+        .map(|candle| {
+            let close = (candle.open + candle.high + candle.low + candle.close) / 4.0;
+            let open = match previous {
+                Some((open, close)) => (open + close) / 2.0,
+                None => (candle.open + candle.close) / 2.0,
+            };
+            previous = Some((open, close));
+            Candle {
+                open,
+                close,
+                high: f64_max(candle.high, f64_max(open, close)),
+                low: f64_min(candle.low, f64_min(open, close)),
+                ..candle
+            }
+        })
+        .collect()
+}
+
+/// Which part of a range of candles to return, and in which order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CandlePage {
+    /// Newest first instead of oldest first
+    pub descending: bool,
+    /// Candles skipped from the start of the order
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl CandlePage {
+    /// Applies the page to candles sorted by start time, as `fetch_candles_page` does in SQL.
+    pub fn apply(&self, mut candles: Vec<Candle>) -> Vec<Candle> {
+        if self.descending {
+            candles.reverse();
         }
+        candles
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
     }
 }
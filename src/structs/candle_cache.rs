@@ -0,0 +1,475 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use tokio::sync::RwLock;
+
+use super::{
+    cache_backend::{get_json, set_json, CacheBackend},
+    candle::Candle,
+    resolution::Resolution,
+};
+use crate::{database::fetch::fetch_candles_from, utils::to_timestampz};
+
+/// Requests older than this no longer count towards a chart window being hot
+pub fn hot_window_ttl() -> Duration {
+    Duration::minutes(15)
+}
+
+/// Incomplete candles are refetched after this even if no new batch was noticed
+fn tail_ttl() -> Duration {
+    Duration::seconds(60)
+}
+
+/// Empty buckets that ended this long ago are assumed to stay empty
+fn settle_delay() -> Duration {
+    Duration::minutes(5)
+}
+
+/// Buckets with only complete candles are kept this long in the shared cache
+fn settled_shared_ttl() -> Duration {
+    Duration::hours(24)
+}
+
+const CANDLES_PER_BUCKET: i32 = 1000;
+const MAX_CACHED_BUCKETS: usize = 2000;
+const MAX_HOT_WINDOWS_PER_MARKET: usize = 8;
+
+fn bucket_span(resolution: Resolution) -> Duration {
+    resolution.get_duration() * CANDLES_PER_BUCKET
+}
+
+/// A fixed, aligned block of `CANDLES_PER_BUCKET` candles of one market and resolution.
+#[derive(Clone, Hash, PartialEq, Eq)]
+struct BucketKey {
+    market_name: String,
+    resolution: Resolution,
+    index: i64,
+}
+
+impl BucketKey {
+    fn start(&self) -> DateTime<Utc> {
+        to_timestampz((self.index * bucket_span(self.resolution).num_seconds()) as u64)
+    }
+
+    fn end(&self) -> DateTime<Utc> {
+        self.start() + bucket_span(self.resolution)
+    }
+
+    fn shared_key(&self) -> String {
+        format!(
+            "candles:{}:{}:{}",
+            self.market_name, self.resolution, self.index
+        )
+    }
+}
+
+fn bucket_index(time: DateTime<Utc>, resolution: Resolution) -> i64 {
+    time.timestamp()
+        .div_euclid(bucket_span(resolution).num_seconds())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedBucket {
+    /// Candles up to the first incomplete one, these never change
+    settled: Vec<Candle>,
+    /// Everything before this is in `settled`
+    settled_until: DateTime<Utc>,
+    /// Candles from `settled_until` on as of `fetched_at`, refetched on the next batch
+    tail: Vec<Candle>,
+    fetched_at: DateTime<Utc>,
+    #[serde(skip)]
+    last_access: DateTime<Utc>,
+}
+
+impl CachedBucket {
+    fn from_candles(
+        key: &BucketKey,
+        mut settled: Vec<Candle>,
+        candles: Vec<Candle>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut tail = vec![];
+        for candle in candles {
+            if tail.is_empty() && candle.complete {
+                settled.push(candle);
+            } else {
+                tail.push(candle);
+            }
+        }
+        let settled_until = match (tail.first(), settled.last()) {
+            (Some(first_incomplete), _) => first_incomplete.start_time,
+            (None, Some(last)) => last.end_time,
+            (None, None) if key.end() + settle_delay() <= now => key.end(),
+            (None, None) => key.start(),
+        };
+        CachedBucket {
+            settled,
+            settled_until,
+            tail,
+            fetched_at: now,
+            last_access: now,
+        }
+    }
+
+    fn is_fresh(
+        &self,
+        key: &BucketKey,
+        latest_batch: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.settled_until >= key.end()
+            || (latest_batch.map_or(true, |b| b <= self.fetched_at)
+                && self.fetched_at + tail_ttl() > now)
+    }
+
+    fn shared_ttl(&self, key: &BucketKey) -> Duration {
+        if self.settled_until >= key.end() {
+            settled_shared_ttl()
+        } else {
+            tail_ttl()
+        }
+    }
+
+    fn candles(&self) -> Vec<Candle> {
+        let mut candles = self.settled.clone();
+        candles.extend(self.tail.iter().cloned());
+        candles
+    }
+}
+
+enum BucketLookup {
+    Hit(Vec<Candle>),
+    /// Needs candles from `fetch_from`, everything before is settled and kept
+    Miss {
+        key: BucketKey,
+        settled: Vec<Candle>,
+        fetch_from: DateTime<Utc>,
+    },
+}
+
+/// A live chart window, e.g. the last 24 hours of 5 minute candles for SOL/USDC.
+#[derive(Clone, Hash, PartialEq, Eq)]
+struct ChartWindow {
+    market_name: String,
+    resolution: Resolution,
+    span_secs: i64,
+}
+
+struct WindowStats {
+    hits: u64,
+    last_access: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    buckets: HashMap<BucketKey, CachedBucket>,
+    windows: HashMap<ChartWindow, WindowStats>,
+    /// When a new candle batch was last seen per market
+    latest_batches: HashMap<String, DateTime<Utc>>,
+}
+
+/// Read-through candle cache in front of `fetch_candles_from`. Candles are cached in fixed
+/// buckets; complete candles are kept for good and only the incomplete tail of a bucket is
+/// refetched once a new batch lands. Also tracks which live chart windows are requested most, so
+/// they can be refreshed before the next request comes in.
+///
+/// With a shared backend, buckets missing from memory are looked up there before going to the
+/// database, and every bucket loaded from the database is written back for other instances.
+#[derive(Default)]
+pub struct CandleCache {
+    state: RwLock<CacheState>,
+    shared: Option<Arc<dyn CacheBackend>>,
+}
+
+impl CandleCache {
+    pub fn with_shared_backend(backend: Arc<dyn CacheBackend>) -> Self {
+        CandleCache {
+            state: RwLock::default(),
+            shared: Some(backend),
+        }
+    }
+
+    pub async fn fetch_candles(
+        &self,
+        pool: &Pool,
+        market_name: &str,
+        resolution: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Candle>> {
+        if to <= from {
+            return Ok(vec![]);
+        }
+        let now = Utc::now();
+        let lookups = self
+            .lookup_buckets(market_name, resolution, from, to, now)
+            .await;
+        let lookups = self.lookup_shared(market_name, lookups, now).await;
+
+        let mut candles = vec![];
+        let mut misses = vec![];
+        for lookup in lookups {
+            match lookup {
+                BucketLookup::Hit(c) => {
+                    candles.extend(self.load_misses(pool, &mut misses, now).await?);
+                    candles.extend(c);
+                }
+                miss => misses.push(miss),
+            }
+        }
+        candles.extend(self.load_misses(pool, &mut misses, now).await?);
+
+        Ok(candles
+            .into_iter()
+            .filter(|c| c.start_time >= from && c.end_time <= to)
+            .collect())
+    }
+
+    async fn lookup_buckets(
+        &self,
+        market_name: &str,
+        resolution: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Vec<BucketLookup> {
+        let mut state = self.state.write().await;
+        let latest_batch = state.latest_batches.get(market_name).copied();
+        let first = bucket_index(from, resolution);
+        let last = bucket_index(to - Duration::seconds(1), resolution);
+
+        (first..=last)
+            .map(|index| {
+                let key = BucketKey {
+                    market_name: market_name.to_string(),
+                    resolution,
+                    index,
+                };
+                match state.buckets.get_mut(&key) {
+                    Some(bucket) if bucket.is_fresh(&key, latest_batch, now) => {
+                        bucket.last_access = now;
+                        BucketLookup::Hit(bucket.candles())
+                    }
+                    Some(bucket) => BucketLookup::Miss {
+                        settled: bucket.settled.clone(),
+                        fetch_from: bucket.settled_until,
+                        key,
+                    },
+                    None => BucketLookup::Miss {
+                        fetch_from: key.start(),
+                        settled: vec![],
+                        key,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Resolves missing buckets from the shared backend when another instance has a fresh copy.
+    async fn lookup_shared(
+        &self,
+        market_name: &str,
+        lookups: Vec<BucketLookup>,
+        now: DateTime<Utc>,
+    ) -> Vec<BucketLookup> {
+        let shared = match &self.shared {
+            Some(shared) => shared,
+            None => return lookups,
+        };
+        let latest_batch = self
+            .state
+            .read()
+            .await
+            .latest_batches
+            .get(market_name)
+            .copied();
+
+        let mut resolved = Vec::with_capacity(lookups.len());
+        for lookup in lookups {
+            let key = match &lookup {
+                BucketLookup::Miss { key, .. } => key,
+                hit => {
+                    resolved.push(hit);
+                    continue;
+                }
+            };
+            let bucket = get_json::<CachedBucket>(shared.as_ref(), &key.shared_key())
+                .await
+                .filter(|b| b.is_fresh(key, latest_batch, now));
+            match bucket {
+                Some(mut bucket) => {
+                    bucket.last_access = now;
+                    let candles = bucket.candles();
+                    self.insert_bucket(key.clone(), bucket).await;
+                    resolved.push(BucketLookup::Hit(candles));
+                }
+                None => resolved.push(lookup),
+            }
+        }
+        resolved
+    }
+
+    async fn insert_bucket(&self, key: BucketKey, bucket: CachedBucket) {
+        let mut state = self.state.write().await;
+        if state.buckets.len() >= MAX_CACHED_BUCKETS && !state.buckets.contains_key(&key) {
+            evict_least_recent(&mut state.buckets);
+        }
+        state.buckets.insert(key, bucket);
+    }
+
+    /// Fetches a run of consecutive missing buckets with a single query and caches them.
+    async fn load_misses(
+        &self,
+        pool: &Pool,
+        misses: &mut Vec<BucketLookup>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let (first_key, fetch_from, end) = match (misses.first(), misses.last()) {
+            (
+                Some(BucketLookup::Miss {
+                    key, fetch_from, ..
+                }),
+                Some(BucketLookup::Miss { key: last_key, .. }),
+            ) => (key.clone(), *fetch_from, last_key.end()),
+            _ => return Ok(vec![]),
+        };
+        let fetched = fetch_candles_from(
+            pool,
+            &first_key.market_name,
+            first_key.resolution,
+            fetch_from,
+            end,
+        )
+        .await?;
+
+        let mut by_bucket: HashMap<i64, Vec<Candle>> = HashMap::new();
+        for candle in fetched {
+            by_bucket
+                .entry(bucket_index(candle.start_time, first_key.resolution))
+                .or_default()
+                .push(candle);
+        }
+
+        let mut candles = vec![];
+        let mut loaded = vec![];
+        for miss in misses.drain(..) {
+            if let BucketLookup::Miss {
+                key,
+                settled,
+                fetch_from,
+            } = miss
+            {
+                // the run was fetched from its first bucket, skip what this one already has
+                let bucket_candles = by_bucket
+                    .remove(&key.index)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|c| c.start_time >= fetch_from)
+                    .collect();
+                let bucket = CachedBucket::from_candles(&key, settled, bucket_candles, now);
+                candles.extend(bucket.candles());
+                loaded.push((key, bucket));
+            }
+        }
+
+        for (key, bucket) in loaded {
+            if let Some(shared) = &self.shared {
+                let ttl = bucket.shared_ttl(&key).to_std()?;
+                set_json(shared.as_ref(), &key.shared_key(), &bucket, ttl).await;
+            }
+            self.insert_bucket(key, bucket).await;
+        }
+        Ok(candles)
+    }
+
+    /// Reloads the buckets holding the latest candles of a market after the worker wrote a batch,
+    /// which also pushes them to the shared backend.
+    pub async fn refresh_latest_buckets(
+        &self,
+        pool: &Pool,
+        market_name: &str,
+    ) -> anyhow::Result<()> {
+        let now = Utc::now();
+        self.record_batch(market_name, now).await;
+        for resolution in Resolution::iter() {
+            let duration = resolution.get_duration();
+            self.fetch_candles(
+                pool,
+                market_name,
+                resolution,
+                now - duration,
+                now + duration,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Counts a client request towards the hot windows, warming requests shouldn't call this.
+    pub async fn record_access(
+        &self,
+        market_name: &str,
+        resolution: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) {
+        let now = Utc::now();
+        // only charts that follow the latest candle are worth refreshing ahead of time
+        if to + resolution.get_duration() < now {
+            return;
+        }
+        let window = ChartWindow {
+            market_name: market_name.to_string(),
+            resolution,
+            span_secs: (to - from).num_seconds(),
+        };
+        let mut state = self.state.write().await;
+        let stats = state.windows.entry(window).or_insert(WindowStats {
+            hits: 0,
+            last_access: now,
+        });
+        stats.hits += 1;
+        stats.last_access = now;
+    }
+
+    /// Marks that a new batch of candles landed for the market, so cached incomplete candles
+    /// fetched before this are refetched.
+    pub async fn record_batch(&self, market_name: &str, time: DateTime<Utc>) {
+        let mut state = self.state.write().await;
+        state.latest_batches.insert(market_name.to_string(), time);
+    }
+
+    /// The most requested live windows for a market as (resolution, span).
+    pub async fn hot_windows(&self, market_name: &str) -> Vec<(Resolution, Duration)> {
+        let now = Utc::now();
+        let mut state = self.state.write().await;
+        state
+            .windows
+            .retain(|_, stats| stats.last_access + hot_window_ttl() >= now);
+
+        let mut windows: Vec<(&ChartWindow, &WindowStats)> = state
+            .windows
+            .iter()
+            .filter(|(w, _)| w.market_name == market_name)
+            .collect();
+        windows.sort_by(|a, b| b.1.hits.cmp(&a.1.hits));
+        windows
+            .into_iter()
+            .take(MAX_HOT_WINDOWS_PER_MARKET)
+            .map(|(w, _)| (w.resolution, Duration::seconds(w.span_secs)))
+            .collect()
+    }
+}
+
+fn evict_least_recent(buckets: &mut HashMap<BucketKey, CachedBucket>) {
+    let oldest = buckets
+        .iter()
+        .min_by_key(|(_, b)| b.last_access)
+        .map(|(k, _)| k.clone());
+    if let Some(oldest) = oldest {
+        buckets.remove(&oldest);
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, TimeZone, Utc};
+
+use super::{candle::Candle, resolution::Resolution};
+
+/// Parses candles from CSV lines of `start_time,open,high,low,close,volume` with optional
+/// `quote_volume` and `trade_count` columns after them. `start_time` is unix seconds or RFC 3339,
+/// and a header line is skipped. Without a quote volume it is estimated from the typical price.
+pub fn parse_candle_csv(
+    contents: &str,
+    market_name: &str,
+    resolution: Resolution,
+) -> anyhow::Result<Vec<Candle>> {
+    let mut candles = vec![];
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        let start_time = match parse_time(fields[0]) {
+            Some(t) => t,
+            None if idx == 0 => continue,
+            None => anyhow::bail!("line {}: invalid start time {}", idx + 1, fields[0]),
+        };
+        if fields.len() < 6 {
+            anyhow::bail!("line {}: expected at least 6 columns", idx + 1);
+        }
+        let number = |i: usize| -> anyhow::Result<f64> {
+            fields[i]
+                .parse::<f64>()
+                .map_err(|_| anyhow::anyhow!("line {}: invalid number {}", idx + 1, fields[i]))
+        };
+        let (open, high, low, close, volume) =
+            (number(1)?, number(2)?, number(3)?, number(4)?, number(5)?);
+        let quote_volume = match fields.get(6) {
+            Some(_) => number(6)?,
+            None => volume * (high + low + close) / 3.0,
+        };
+        let trade_count = match fields.get(7) {
+            Some(f) => f
+                .parse::<i64>()
+                .map_err(|_| anyhow::anyhow!("line {}: invalid trade count {}", idx + 1, f))?,
+            None => 0,
+        };
+
+        candles.push(Candle {
+            market_name: market_name.to_string(),
+            start_time,
+            end_time: resolution.bucket_end(start_time),
+            resolution: resolution.to_string(),
+            open,
+            close,
+            high,
+            low,
+            volume,
+            complete: true,
+            vwap: if volume > 0.0 {
+                quote_volume / volume
+            } else {
+                close
+            },
+            trade_count,
+            quote_volume,
+            // imported candles carry no outlier filtering of their own
+            priced_volume: volume,
+            priced_quote_volume: quote_volume,
+        });
+    }
+    Ok(candles)
+}
+
+pub(crate) fn parse_time(field: &str) -> Option<DateTime<Utc>> {
+    match field.parse::<i64>() {
+        Ok(seconds) => Utc.timestamp_opt(seconds, 0).single(),
+        Err(_) => DateTime::parse_from_rfc3339(field)
+            .ok()
+            .map(|t| t.with_timezone(&Utc)),
+    }
+}
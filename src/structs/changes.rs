@@ -0,0 +1,62 @@
+use serde::Serialize;
+use tokio_postgres::Row;
+
+use super::candle::Candle;
+
+/// A candle as of one of its inserts or updates
+#[derive(Clone, Debug, Serialize)]
+pub struct CandleChange {
+    pub version: i64,
+    pub market_name: String,
+    pub resolution: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub open: f64,
+    pub close: f64,
+    pub high: f64,
+    pub low: f64,
+    pub volume: f64,
+    pub complete: bool,
+    pub vwap: f64,
+    pub trade_count: i64,
+    pub quote_volume: f64,
+}
+
+impl CandleChange {
+    /// The change as a Server-Sent Events message.
+    pub fn to_sse_event(&self) -> String {
+        format!(
+            "event: candle\ndata: {}\n\n",
+            serde_json::to_string(self).unwrap()
+        )
+    }
+
+    pub fn from_row(row: Row) -> Self {
+        let version = row.get(15);
+        let candle = Candle::from_row(row);
+        CandleChange {
+            version,
+            market_name: candle.market_name,
+            resolution: candle.resolution,
+            start_time: candle.start_time.timestamp(),
+            end_time: candle.end_time.timestamp(),
+            open: candle.open,
+            close: candle.close,
+            high: candle.high,
+            low: candle.low,
+            volume: candle.volume,
+            complete: candle.complete,
+            vwap: candle.vwap,
+            trade_count: candle.trade_count,
+            quote_volume: candle.quote_volume,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangesResponse {
+    pub changes: Vec<CandleChange>,
+    /// Pass as `since` to get the next page, unchanged when there was nothing new
+    pub next_cursor: i64,
+    pub has_more: bool,
+}
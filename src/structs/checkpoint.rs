@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use tokio_postgres::Row;
+
+/// Where the minute batching of a market left off, so that a restarted worker resumes there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerCheckpoint {
+    pub market_name: String,
+    /// End of the newest complete minute candle, batching resumes from here
+    pub candles_through: DateTime<Utc>,
+    /// Close of that candle, carried into the empty candles after it
+    pub last_price: f64,
+    pub last_fill_time: Option<DateTime<Utc>>,
+    pub last_fill_slot: Option<i64>,
+}
+
+impl WorkerCheckpoint {
+    pub fn from_row(row: Row) -> Self {
+        WorkerCheckpoint {
+            market_name: row.get(0),
+            candles_through: row.get(1),
+            last_price: row.get(2),
+            last_fill_time: row.get(3),
+            last_fill_slot: row.get(4),
+        }
+    }
+}
@@ -1,15 +1,19 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CoinGeckoOrderBook {
     pub ticker_id: String,
     pub timestamp: String, //as milliseconds
+    /// `[price, size]` pairs
+    #[schema(value_type = Vec<Vec<String>>)]
     pub bids: Vec<(String, String)>,
+    #[schema(value_type = Vec<Vec<String>>)]
     pub asks: Vec<(String, String)>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct CoinGeckoPair {
     pub ticker_id: String,
     pub base: String,
@@ -17,7 +21,7 @@ pub struct CoinGeckoPair {
     pub pool_id: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CoinGeckoTicker {
     pub ticker_id: String,
     pub address: String,
@@ -26,8 +30,16 @@ pub struct CoinGeckoTicker {
     pub last_price: String,
     pub base_volume: String,
     pub target_volume: String,
-    // pub bid: String,
-    // pub ask: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ask: Option<String>,
+    /// USD value of asks within 2% above the mid price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plus_2_percent_depth: Option<String>,
+    /// USD value of bids within 2% below the mid price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minus_2_percent_depth: Option<String>,
     pub high: String,
     pub low: String,
 }
@@ -0,0 +1,63 @@
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{markets::MarketInfo, venue::Venue};
+
+/// A token pair traded on more than one configured market. Its candles merge the candles of
+/// those markets and are stored under a name of their own, e.g. `SOL/USDC@ALL`.
+#[derive(Clone, Debug)]
+pub struct CompositeMarket {
+    /// Stands in for the composite wherever a market is resolved. Its address is made up from the
+    /// name and its book keys are that address, there are no accounts behind it.
+    pub info: MarketInfo,
+    /// Names of the merged markets, as their candles are stored
+    pub market_names: Vec<String>,
+}
+
+/// Composites of every base and quote mint pair of `markets` that more than one market trades, in
+/// the order their first market is configured. Each is named after that first market, without a
+/// venue suffix. A composite keeps the resolutions all of its markets keep.
+pub fn composite_markets(markets: &[MarketInfo]) -> Vec<CompositeMarket> {
+    let mut pairs: Vec<(&str, &str)> = vec![];
+    for market in markets.iter() {
+        let pair = (market.base_mint_key.as_str(), market.quote_mint_key.as_str());
+        if !pairs.contains(&pair) {
+            pairs.push(pair);
+        }
+    }
+
+    pairs
+        .into_iter()
+        .filter_map(|(base_mint, quote_mint)| {
+            let members: Vec<&MarketInfo> = markets
+                .iter()
+                .filter(|m| m.base_mint_key == base_mint && m.quote_mint_key == quote_mint)
+                .collect();
+            if members.len() < 2 {
+                return None;
+            }
+            let first = members[0];
+            let name = Venue::All.qualify(first.name.split('@').next().unwrap_or(&first.name));
+            let address =
+                Pubkey::new_from_array(Sha256::digest(name.as_bytes()).into()).to_string();
+            Some(CompositeMarket {
+                info: MarketInfo {
+                    name,
+                    address: address.clone(),
+                    venue: Venue::All,
+                    bids_key: address.clone(),
+                    asks_key: address.clone(),
+                    event_queue_key: address,
+                    resolutions: first
+                        .resolutions
+                        .iter()
+                        .copied()
+                        .filter(|r| members.iter().all(|m| m.keeps(*r)))
+                        .collect(),
+                    ..first.clone()
+                },
+                market_names: members.iter().map(|m| m.name.clone()).collect(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+use super::candle::Candle;
+
+/// A single bar as reported by the external provider
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExternalCandle {
+    pub start_time: DateTime<Utc>,
+    pub open: f64,
+    pub close: f64,
+    pub high: f64,
+    pub low: f64,
+    pub volume: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CandleDivergence {
+    pub market_name: String,
+    pub resolution: String,
+    pub provider: String,
+    pub start_time: DateTime<Utc>,
+    pub close: f64,
+    pub provider_close: f64,
+    pub volume: f64,
+    pub provider_volume: f64,
+    /// Relative close difference in percent, positive when our close is higher
+    pub close_delta_pct: f64,
+    pub volume_delta_pct: f64,
+}
+
+impl CandleDivergence {
+    pub fn from_candles(provider: &str, candle: &Candle, external: &ExternalCandle) -> Self {
+        CandleDivergence {
+            market_name: candle.market_name.clone(),
+            resolution: candle.resolution.clone(),
+            provider: provider.to_string(),
+            start_time: candle.start_time,
+            close: candle.close,
+            provider_close: external.close,
+            volume: candle.volume,
+            provider_volume: external.volume,
+            close_delta_pct: delta_pct(candle.close, external.close),
+            volume_delta_pct: delta_pct(candle.volume, external.volume),
+        }
+    }
+
+    pub fn from_row(row: Row) -> Self {
+        CandleDivergence {
+            market_name: row.get(0),
+            resolution: row.get(1),
+            provider: row.get(2),
+            start_time: row.get(3),
+            close: row.get(4),
+            provider_close: row.get(5),
+            volume: row.get(6),
+            provider_volume: row.get(7),
+            close_delta_pct: row.get(8),
+            volume_delta_pct: row.get(9),
+        }
+    }
+}
+
+fn delta_pct(ours: f64, theirs: f64) -> f64 {
+    if theirs == 0.0 {
+        if ours == 0.0 {
+            0.0
+        } else {
+            100.0
+        }
+    } else {
+        (ours - theirs) / theirs * 100.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DivergenceSummary {
+    pub market_name: String,
+    pub bars: i64,
+    pub avg_abs_close_delta_pct: f64,
+    pub max_abs_close_delta_pct: f64,
+    pub avg_abs_volume_delta_pct: f64,
+}
+
+impl DivergenceSummary {
+    pub fn from_row(row: Row) -> Self {
+        DivergenceSummary {
+            market_name: row.get(0),
+            bars: row.get(1),
+            avg_abs_close_delta_pct: row.get(2),
+            max_abs_close_delta_pct: row.get(3),
+            avg_abs_volume_delta_pct: row.get(4),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DivergenceResponse {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub summary: Vec<DivergenceSummary>,
+    /// Only populated when a single market is requested
+    pub bars: Vec<CandleDivergence>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BirdeyeOhlcvResponse {
+    pub data: BirdeyeOhlcvData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BirdeyeOhlcvData {
+    pub items: Vec<BirdeyeOhlcvItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BirdeyeOhlcvItem {
+    #[serde(rename = "unixTime")]
+    pub unix_time: i64,
+    pub o: f64,
+    pub h: f64,
+    pub l: f64,
+    pub c: f64,
+    pub v: f64,
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EmbargoConfig {
+    /// Comma separated `market_name:minutes`, e.g. `SOL/USDC:15,RAY/USDC:30`
+    pub embargo_markets: Option<String>,
+}
+
+impl EmbargoConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+/// Markets whose trades and candles are only public after a delay. API keys flagged `realtime`
+/// see them without it.
+#[derive(Clone, Debug, Default)]
+pub struct Embargo {
+    delays: HashMap<String, Duration>,
+}
+
+impl Embargo {
+    pub fn from_config(config: &EmbargoConfig) -> anyhow::Result<Self> {
+        let mut delays = HashMap::new();
+        let markets = config.embargo_markets.as_deref().unwrap_or_default();
+        for entry in markets
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+        {
+            // market names contain slashes but never colons
+            let (market_name, minutes) = entry
+                .rsplit_once(':')
+                .ok_or_else(|| anyhow::anyhow!("expected market_name:minutes, got {}", entry))?;
+            let minutes: i64 = minutes.parse()?;
+            delays.insert(market_name.to_string(), Duration::minutes(minutes));
+        }
+        Ok(Embargo { delays })
+    }
+
+    /// The latest time of the market's data the caller may see, `None` if it isn't embargoed
+    /// for them.
+    pub fn visible_until(&self, market_name: &str, realtime: bool) -> Option<DateTime<Utc>> {
+        if realtime {
+            return None;
+        }
+        // whole minutes, so identical requests within a minute stay identical
+        self.delays.get(market_name).map(|delay| {
+            let until = Utc::now() - *delay;
+            until.duration_trunc(Duration::minutes(1)).unwrap_or(until)
+        })
+    }
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// How up to date the stored data of a market is, as last polled by the server.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MarketFreshness {
+    pub last_fill_at: Option<DateTime<Utc>>,
+    /// Highest version among the market's recent candles, grows with every candle write
+    pub data_version: i64,
+}
+
+impl MarketFreshness {
+    /// Freshness of a response that spans several markets.
+    pub fn combine<'a>(freshness: impl Iterator<Item = &'a MarketFreshness>) -> Self {
+        freshness.fold(MarketFreshness::default(), |acc, f| MarketFreshness {
+            last_fill_at: acc.last_fill_at.max(f.last_fill_at),
+            data_version: acc.data_version.max(f.data_version),
+        })
+    }
+}
+
+/// Response wrapper with the metadata clients need to judge staleness and skip refreshes when
+/// `data_version` hasn't moved.
+#[derive(Clone, Debug, Serialize)]
+pub struct Envelope<T: Serialize> {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub server_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub last_fill_at: Option<DateTime<Utc>>,
+    pub data_version: i64,
+    pub data: T,
+}
+
+impl<T: Serialize> Envelope<T> {
+    pub fn new(freshness: MarketFreshness, data: T) -> Self {
+        Envelope {
+            server_time: Utc::now(),
+            last_fill_at: freshness.last_fill_at,
+            data_version: freshness.data_version,
+            data,
+        }
+    }
+}
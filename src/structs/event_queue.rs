@@ -0,0 +1,109 @@
+use solana_sdk::pubkey::Pubkey;
+
+use super::markets::MarketInfo;
+
+const ACCOUNT_HEAD_PADDING: usize = 5;
+const ACCOUNT_TAIL_PADDING: usize = 7;
+const HEADER_SIZE: usize = 32;
+const EVENT_SIZE: usize = 88;
+
+const EVENT_FLAG_FILL: u8 = 0x01;
+const EVENT_FLAG_BID: u8 = 0x04;
+const EVENT_FLAG_MAKER: u8 = 0x08;
+
+/// A fill event read from an OpenBook (Serum v3) event queue account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuedFill {
+    /// Position in the queue's lifetime sequence, increases by one per event pushed
+    pub seq_num: u64,
+    pub owner: Pubkey,
+    pub bid: bool,
+    pub maker: bool,
+    pub native_qty_paid: u64,
+    pub native_qty_received: u64,
+    pub native_fee_or_rebate: u64,
+}
+
+impl QueuedFill {
+    /// Price before fees in quote tokens per base token, and size in base tokens.
+    pub fn price_and_size(&self, market: &MarketInfo) -> anyhow::Result<(f64, f64)> {
+        let (quote, base) = if self.bid {
+            let quote = if self.maker {
+                self.native_qty_paid + self.native_fee_or_rebate
+            } else {
+                self.native_qty_paid
+                    .saturating_sub(self.native_fee_or_rebate)
+            };
+            (quote, self.native_qty_received)
+        } else {
+            let quote = if self.maker {
+                self.native_qty_received
+                    .saturating_sub(self.native_fee_or_rebate)
+            } else {
+                self.native_qty_received + self.native_fee_or_rebate
+            };
+            (quote, self.native_qty_paid)
+        };
+        market.native_to_ui_price_and_size(quote as u128, base)
+    }
+}
+
+/// The events currently in an event queue account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventQueueSnapshot {
+    /// Sequence number of the oldest event still in the queue
+    pub first_seq_num: u64,
+    /// Sequence number the next pushed event will get
+    pub next_seq_num: u64,
+    /// Fills still in the queue, oldest first
+    pub fills: Vec<QueuedFill>,
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Decodes an event queue account. Returns `None` for data too short to be one.
+pub fn parse_event_queue(data: &[u8]) -> Option<EventQueueSnapshot> {
+    if data.len() < ACCOUNT_HEAD_PADDING + HEADER_SIZE + ACCOUNT_TAIL_PADDING {
+        return None;
+    }
+    let header = &data[ACCOUNT_HEAD_PADDING..];
+    let head = read_u64(header, 8);
+    let count = read_u64(header, 16);
+    let seq_num = read_u64(header, 24);
+
+    let events = &data[ACCOUNT_HEAD_PADDING + HEADER_SIZE..data.len() - ACCOUNT_TAIL_PADDING];
+    let capacity = (events.len() / EVENT_SIZE) as u64;
+    if capacity == 0 || count > capacity {
+        return None;
+    }
+
+    let first_seq_num = seq_num.saturating_sub(count);
+    let fills = (0..count)
+        .filter_map(|i| {
+            let index = ((head + i) % capacity) as usize;
+            let event = &events[index * EVENT_SIZE..(index + 1) * EVENT_SIZE];
+            let flags = event[0];
+            if flags & EVENT_FLAG_FILL == 0 {
+                return None;
+            }
+            let owner: [u8; 32] = event[48..80].try_into().unwrap();
+            Some(QueuedFill {
+                seq_num: first_seq_num + i,
+                owner: Pubkey::new_from_array(owner),
+                bid: flags & EVENT_FLAG_BID != 0,
+                maker: flags & EVENT_FLAG_MAKER != 0,
+                native_qty_received: read_u64(event, 8),
+                native_qty_paid: read_u64(event, 16),
+                native_fee_or_rebate: read_u64(event, 24),
+            })
+        })
+        .collect();
+
+    Some(EventQueueSnapshot {
+        first_seq_num,
+        next_seq_num: seq_num,
+        fills,
+    })
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use super::{candle_import::parse_time, openbook::OpenBookFill};
+
+/// Columns every imported fill needs, named as in `openbook.openbook_fill_events`. `fee` and
+/// `referrer_rebate` are optional.
+const REQUIRED_COLUMNS: [&str; 14] = [
+    "signature",
+    "slot",
+    "block_datetime",
+    "market",
+    "open_orders_owner",
+    "bid",
+    "maker",
+    "native_quantity_paid",
+    "native_quantity_received",
+    "native_fee_or_rebate",
+    "price",
+    "size",
+    "seq_num",
+    "instruction_num",
+];
+
+/// Parses CSV lines of fills whose columns are named by a header line, in any order.
+pub struct FillCsv {
+    columns: HashMap<String, usize>,
+}
+
+impl FillCsv {
+    pub fn from_header(header: &str) -> anyhow::Result<Self> {
+        let columns: HashMap<String, usize> = header
+            .split(',')
+            .enumerate()
+            .map(|(i, name)| (name.trim().to_string(), i))
+            .collect();
+        if let Some(missing) = REQUIRED_COLUMNS.iter().find(|c| !columns.contains_key(**c)) {
+            anyhow::bail!("header has no {} column", missing);
+        }
+        Ok(FillCsv { columns })
+    }
+
+    pub fn parse_line(&self, line: &str) -> anyhow::Result<OpenBookFill> {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        fill_from_fields(|name| {
+            self.columns
+                .get(name)
+                .and_then(|i| fields.get(*i))
+                .map(|f| f.to_string())
+        })
+    }
+}
+
+/// Parses a fill from a JSON object with the same keys as the CSV columns.
+pub fn parse_fill_json(line: &str) -> anyhow::Result<OpenBookFill> {
+    let object: serde_json::Map<String, Value> = serde_json::from_str(line)?;
+    fill_from_fields(|name| match object.get(name) {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(v) => Some(v.to_string()),
+    })
+}
+
+fn fill_from_fields(field: impl Fn(&str) -> Option<String>) -> anyhow::Result<OpenBookFill> {
+    let text = |name: &str| -> anyhow::Result<String> {
+        field(name).ok_or_else(|| anyhow::anyhow!("missing {}", name))
+    };
+    let optional = |name: &str| -> anyhow::Result<Option<f64>> {
+        match field(name) {
+            Some(v) if !v.is_empty() && v != "null" => Ok(Some(parse(name, &v)?)),
+            _ => Ok(None),
+        }
+    };
+    let block_datetime = text("block_datetime")?;
+    Ok(OpenBookFill {
+        signature: text("signature")?,
+        slot: parse("slot", &text("slot")?)?,
+        block_datetime: parse_fill_time(&block_datetime)
+            .ok_or_else(|| anyhow::anyhow!("invalid block_datetime {}", block_datetime))?,
+        market: text("market")?,
+        open_orders_owner: text("open_orders_owner")?,
+        bid: parse_bool("bid", &text("bid")?)?,
+        maker: parse_bool("maker", &text("maker")?)?,
+        native_quantity_paid: parse("native_quantity_paid", &text("native_quantity_paid")?)?,
+        native_quantity_received: parse(
+            "native_quantity_received",
+            &text("native_quantity_received")?,
+        )?,
+        native_fee_or_rebate: parse("native_fee_or_rebate", &text("native_fee_or_rebate")?)?,
+        price: parse("price", &text("price")?)?,
+        size: parse("size", &text("size")?)?,
+        seq_num: parse("seq_num", &text("seq_num")?)?,
+        instruction_num: parse("instruction_num", &text("instruction_num")?)?,
+        fee: optional("fee")?,
+        referrer_rebate: optional("referrer_rebate")?,
+    })
+}
+
+/// Unix seconds, RFC 3339 or Postgres' text output (`2023-03-01 12:00:00.123+00`)
+fn parse_fill_time(value: &str) -> Option<DateTime<Utc>> {
+    parse_time(value).or_else(|| {
+        DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    })
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> anyhow::Result<T> {
+    value
+        .parse::<T>()
+        .map_err(|_| anyhow::anyhow!("invalid {} {}", name, value))
+}
+
+fn parse_bool(name: &str, value: &str) -> anyhow::Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "t" | "1" => Ok(true),
+        "false" | "f" | "0" => Ok(false),
+        _ => anyhow::bail!("invalid {} {}", name, value),
+    }
+}
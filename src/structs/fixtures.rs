@@ -0,0 +1,270 @@
+use chrono::{DateTime, Duration, DurationRound, TimeZone, Utc};
+use serde_derive::Deserialize;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use strum::IntoEnumIterator;
+
+use super::{markets::MarketInfo, openbook::OpenBookFill, resolution::Resolution, venue::Venue};
+
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// Octaves of the price noise, the slowest swings over 2^15 minutes, about three weeks
+const PRICE_OCTAVES: u32 = 16;
+
+/// Distinct fake traders, shared by the fixture markets
+const FIXTURE_TRADERS: u64 = 20;
+
+const TAKER_FEE_RATE: f64 = 0.0004;
+
+/// Trades of one minute get sequence numbers from `minute * FIXTURE_MAX_TRADES_PER_MINUTE`, so
+/// the market, sequence number and maker flag stay unique across minutes
+const FIXTURE_MAX_TRADES_PER_MINUTE: i64 = 1000;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FixtureConfig {
+    #[serde(default = "default_fixture_seed")]
+    pub fixture_seed: u64,
+    /// Days of fills generated, ending at the current minute
+    #[serde(default = "default_fixture_days")]
+    pub fixture_days: i64,
+    /// Roughly the standard deviation of the one minute log return
+    #[serde(default = "default_fixture_volatility")]
+    pub fixture_volatility: f64,
+    /// Average trades per market and minute
+    #[serde(default = "default_fixture_trades_per_minute")]
+    pub fixture_trades_per_minute: f64,
+    /// Unix time the fills are generated relative to instead of the unix epoch, so that runs at
+    /// different times store the same trades at the same distance from it. Only for a fresh
+    /// database, moving it regenerates stored minutes under the sequence numbers of others.
+    pub fixture_epoch: Option<i64>,
+}
+
+fn default_fixture_seed() -> u64 {
+    1
+}
+
+fn default_fixture_days() -> i64 {
+    7
+}
+
+fn default_fixture_volatility() -> f64 {
+    0.001
+}
+
+fn default_fixture_trades_per_minute() -> f64 {
+    4.0
+}
+
+impl FixtureConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+struct FixtureMarket {
+    name: &'static str,
+    /// Price the noise moves around
+    base_price: f64,
+    /// Typical base size of a trade
+    base_size: f64,
+}
+
+const FIXTURE_MARKETS: [FixtureMarket; 2] = [
+    FixtureMarket {
+        name: "ALPHA/USDC",
+        base_price: 25.0,
+        base_size: 10.0,
+    },
+    FixtureMarket {
+        name: "BETA/USDC",
+        base_price: 0.8,
+        base_size: 500.0,
+    },
+];
+
+/// Made up markets quoted in USDC. Their accounts don't exist on chain.
+pub fn fixture_markets() -> Vec<MarketInfo> {
+    FIXTURE_MARKETS
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let key = |account: u64| fake_pubkey(&[i as u64, account]);
+            MarketInfo {
+                name: m.name.to_string(),
+                address: key(0),
+                venue: Venue::OpenBook,
+                base_decimals: 9,
+                quote_decimals: 6,
+                base_mint_key: key(1),
+                quote_mint_key: USDC_MINT.to_string(),
+                bids_key: key(2),
+                asks_key: key(3),
+                event_queue_key: key(4),
+                base_lot_size: 1_000_000,
+                quote_lot_size: 1,
+                resolutions: Resolution::iter().collect(),
+            }
+        })
+        .collect()
+}
+
+/// Maker and taker fills of synthetic trades of a fixture market from `from` until `to`. The
+/// fills of a minute only depend on the seed and the minute since the fixture epoch, so
+/// overlapping ranges generate the same fills and reseeding doesn't duplicate any.
+pub fn generate_fixture_fills(
+    config: &FixtureConfig,
+    market: &MarketInfo,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<OpenBookFill> {
+    let (index, spec) = match FIXTURE_MARKETS
+        .iter()
+        .enumerate()
+        .find(|(_, m)| m.name == market.name)
+    {
+        Some(found) => found,
+        None => return vec![],
+    };
+    let seed = hash(&[config.fixture_seed, index as u64]);
+    let log_price =
+        |minute: i64| spec.base_price.ln() + price_noise(seed, minute, config.fixture_volatility);
+
+    let mut fills = vec![];
+    let epoch_minute = config.fixture_epoch.unwrap_or(0).div_euclid(60);
+    let first_minute = from.timestamp().div_euclid(60) - epoch_minute;
+    let last_minute = to.timestamp().div_euclid(60) - epoch_minute;
+    for minute in first_minute..last_minute {
+        let trades = (config.fixture_trades_per_minute * (1.0 + unit(seed, &[1, minute as u64])))
+            .floor()
+            .max(0.0) as i64;
+        let trades = trades.min(FIXTURE_MAX_TRADES_PER_MINUTE);
+        let (start, end) = (log_price(minute), log_price(minute + 1));
+        for i in 0..trades {
+            let progress = (i as f64 + 0.5) / trades as f64;
+            let jitter =
+                0.2 * config.fixture_volatility * unit(seed, &[2, minute as u64, i as u64]);
+            let price = (start + (end - start) * progress + jitter).exp();
+            let size = spec.base_size * (1.5 * unit(seed, &[3, minute as u64, i as u64])).exp();
+            let taker_bid = unit(seed, &[4, minute as u64, i as u64]) > 0.0;
+            let trade = FixtureTrade {
+                market,
+                time: Utc
+                    .timestamp_opt((epoch_minute + minute) * 60 + (progress * 60.0) as i64, 0)
+                    .unwrap(),
+                seq_num: minute * FIXTURE_MAX_TRADES_PER_MINUTE + i,
+                signature: fake_signature(&[seed, minute as u64, i as u64]),
+                price,
+                size,
+            };
+            // traders are shared between the markets
+            let trader = |part: u64| {
+                let n = hash(&[seed, part, minute as u64, i as u64]) % FIXTURE_TRADERS;
+                fake_pubkey(&[config.fixture_seed, n])
+            };
+            let (maker, taker) = (trader(5), trader(6));
+            fills.push(trade.fill(maker, !taker_bid, true));
+            fills.push(trade.fill(taker, taker_bid, false));
+        }
+    }
+    fills
+}
+
+/// The window fixtures are generated for, ending at the current minute.
+pub fn fixture_range(config: &FixtureConfig) -> (DateTime<Utc>, DateTime<Utc>) {
+    let to = Utc::now().duration_trunc(Duration::minutes(1)).unwrap();
+    (to - Duration::days(config.fixture_days), to)
+}
+
+struct FixtureTrade<'a> {
+    market: &'a MarketInfo,
+    time: DateTime<Utc>,
+    seq_num: i64,
+    signature: String,
+    price: f64,
+    size: f64,
+}
+
+impl FixtureTrade<'_> {
+    fn fill(&self, owner: String, bid: bool, maker: bool) -> OpenBookFill {
+        let base_native = self.size * 10f64.powi(self.market.base_decimals as i32);
+        let quote_native = self.price * self.size * 10f64.powi(self.market.quote_decimals as i32);
+        let (paid, received) = if bid {
+            (quote_native, base_native)
+        } else {
+            (base_native, quote_native)
+        };
+        let fee = if maker {
+            0.0
+        } else {
+            TAKER_FEE_RATE * self.price * self.size
+        };
+        OpenBookFill {
+            signature: self.signature.clone(),
+            // slots are about 400ms apart
+            slot: self.time.timestamp() * 5 / 2,
+            block_datetime: self.time,
+            market: self.market.address.clone(),
+            open_orders_owner: owner,
+            bid,
+            maker,
+            native_quantity_paid: paid,
+            native_quantity_received: received,
+            native_fee_or_rebate: fee * 10f64.powi(self.market.quote_decimals as i32),
+            price: self.price,
+            size: self.size,
+            seq_num: self.seq_num,
+            instruction_num: if maker { 0 } else { 1 },
+            fee: Some(fee),
+            referrer_rebate: None,
+        }
+    }
+}
+
+/// Log price offset at the start of `minute`: octaves of interpolated noise whose amplitude grows
+/// with the square root of their period, which makes it wander like a random walk.
+fn price_noise(seed: u64, minute: i64, volatility: f64) -> f64 {
+    (0..PRICE_OCTAVES)
+        .map(|octave| {
+            let period = 1i64 << octave;
+            let node = minute.div_euclid(period);
+            let progress = minute.rem_euclid(period) as f64 / period as f64;
+            let at = |n: i64| unit(seed, &[7, octave as u64, n as u64]);
+            let value = at(node) + (at(node + 1) - at(node)) * progress;
+            volatility * (period as f64).sqrt() * value
+        })
+        .sum()
+}
+
+/// splitmix64 finalizer
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn hash(parts: &[u64]) -> u64 {
+    parts.iter().fold(0, |h, p| mix(h ^ mix(*p)))
+}
+
+/// Uniform in [-1, 1)
+fn unit(seed: u64, parts: &[u64]) -> f64 {
+    let h = mix(seed ^ hash(parts));
+    (h >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+fn fake_bytes<const N: usize>(parts: &[u64]) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+        let word = hash(&[hash(parts), i as u64]).to_le_bytes();
+        chunk.copy_from_slice(&word[..chunk.len()]);
+    }
+    bytes
+}
+
+fn fake_pubkey(parts: &[u64]) -> String {
+    Pubkey::new_from_array(fake_bytes(parts)).to_string()
+}
+
+fn fake_signature(parts: &[u64]) -> String {
+    Signature::new(&fake_bytes::<64>(parts)).to_string()
+}
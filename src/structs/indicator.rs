@@ -0,0 +1,252 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::candle::Candle;
+
+/// Longest period an indicator accepts, bounds the candles read before a range
+pub const MAX_INDICATOR_PERIOD: usize = 500;
+
+/// A technical indicator computed over candle closes. Implement it and add a case to
+/// `parse_indicator` to serve a new one.
+pub trait Indicator: Send + Sync {
+    /// Names of the series it returns, e.g. `macd`, `signal` and `histogram` for MACD
+    fn outputs(&self) -> &'static [&'static str];
+
+    /// Its parameters in the order they are given
+    fn params(&self) -> Vec<usize>;
+
+    /// Candles before a range that have to be read along for the values at its start to settle
+    fn warmup(&self) -> usize;
+
+    /// One series per output with a value per candle, `None` until enough candles were seen.
+    /// Candles must be in ascending order and without gaps.
+    fn compute(&self, candles: &[Candle]) -> Vec<Vec<Option<f64>>>;
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct IndicatorSeries {
+    pub name: String,
+    /// One value per time, `null` where too few candles came before
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct IndicatorResponse {
+    pub market_name: String,
+    pub resolution: String,
+    pub indicator: String,
+    /// The parameters used, including defaults
+    pub params: Vec<usize>,
+    /// Unix seconds, start of each candle
+    pub time: Vec<i64>,
+    pub series: Vec<IndicatorSeries>,
+}
+
+/// The indicator called `name` with its parameters, or `None` for unknown names and parameters
+/// out of range. Parameters that are left out take their usual defaults.
+pub fn parse_indicator(name: &str, params: &[usize]) -> Option<Box<dyn Indicator>> {
+    let param = |i: usize, default: usize| params.get(i).copied().unwrap_or(default);
+    if params.iter().any(|p| *p == 0 || *p > MAX_INDICATOR_PERIOD) {
+        return None;
+    }
+    let indicator: Box<dyn Indicator> = match (name, params.len()) {
+        ("sma", 0..=1) => Box::new(Sma {
+            period: param(0, 20),
+        }),
+        ("ema", 0..=1) => Box::new(Ema {
+            period: param(0, 20),
+        }),
+        ("rsi", 0..=1) => Box::new(Rsi {
+            period: param(0, 14),
+        }),
+        ("macd", 0..=3) => {
+            let (fast, slow) = (param(0, 12), param(1, 26));
+            if fast >= slow {
+                return None;
+            }
+            Box::new(Macd {
+                fast,
+                slow,
+                signal: param(2, 9),
+            })
+        }
+        _ => return None,
+    };
+    Some(indicator)
+}
+
+/// Simple moving average of the closes
+pub struct Sma {
+    pub period: usize,
+}
+
+impl Indicator for Sma {
+    fn outputs(&self) -> &'static [&'static str] {
+        &["sma"]
+    }
+
+    fn params(&self) -> Vec<usize> {
+        vec![self.period]
+    }
+
+    fn warmup(&self) -> usize {
+        self.period - 1
+    }
+
+    fn compute(&self, candles: &[Candle]) -> Vec<Vec<Option<f64>>> {
+        let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+        vec![sma(&closes, self.period)]
+    }
+}
+
+/// Exponential moving average of the closes, seeded with the simple average of its first period
+pub struct Ema {
+    pub period: usize,
+}
+
+impl Indicator for Ema {
+    fn outputs(&self) -> &'static [&'static str] {
+        &["ema"]
+    }
+
+    fn params(&self) -> Vec<usize> {
+        vec![self.period]
+    }
+
+    fn warmup(&self) -> usize {
+        // the seed's weight has faded below a thousandth by then
+        self.period * 4
+    }
+
+    fn compute(&self, candles: &[Candle]) -> Vec<Vec<Option<f64>>> {
+        let closes: Vec<Option<f64>> = candles.iter().map(|c| Some(c.close)).collect();
+        vec![ema(&closes, self.period)]
+    }
+}
+
+/// Relative strength index with Wilder's smoothing of gains and losses
+pub struct Rsi {
+    pub period: usize,
+}
+
+impl Indicator for Rsi {
+    fn outputs(&self) -> &'static [&'static str] {
+        &["rsi"]
+    }
+
+    fn params(&self) -> Vec<usize> {
+        vec![self.period]
+    }
+
+    fn warmup(&self) -> usize {
+        self.period * 4
+    }
+
+    fn compute(&self, candles: &[Candle]) -> Vec<Vec<Option<f64>>> {
+        let period = self.period as f64;
+        let mut values = vec![None; candles.len()];
+        let (mut gain, mut loss) = (0.0, 0.0);
+        for (i, pair) in candles.windows(2).enumerate() {
+            let i = i + 1;
+            let change = pair[1].close - pair[0].close;
+            let (up, down) = (change.max(0.0), (-change).max(0.0));
+            if i <= self.period {
+                gain += up / period;
+                loss += down / period;
+                if i < self.period {
+                    continue;
+                }
+            } else {
+                gain = (gain * (period - 1.0) + up) / period;
+                loss = (loss * (period - 1.0) + down) / period;
+            }
+            values[i] = Some(if loss == 0.0 {
+                100.0
+            } else {
+                100.0 - 100.0 / (1.0 + gain / loss)
+            });
+        }
+        vec![values]
+    }
+}
+
+/// Moving average convergence divergence: the fast minus the slow EMA of the closes, its EMA as
+/// the signal line and the difference of both as histogram
+pub struct Macd {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl Indicator for Macd {
+    fn outputs(&self) -> &'static [&'static str] {
+        &["macd", "signal", "histogram"]
+    }
+
+    fn params(&self) -> Vec<usize> {
+        vec![self.fast, self.slow, self.signal]
+    }
+
+    fn warmup(&self) -> usize {
+        (self.slow + self.signal) * 4
+    }
+
+    fn compute(&self, candles: &[Candle]) -> Vec<Vec<Option<f64>>> {
+        let closes: Vec<Option<f64>> = candles.iter().map(|c| Some(c.close)).collect();
+        let fast = ema(&closes, self.fast);
+        let slow = ema(&closes, self.slow);
+        let macd: Vec<Option<f64>> = fast
+            .iter()
+            .zip(slow.iter())
+            .map(|(f, s)| Some((*f)? - (*s)?))
+            .collect();
+        let signal = ema(&macd, self.signal);
+        let histogram = macd
+            .iter()
+            .zip(signal.iter())
+            .map(|(m, s)| Some((*m)? - (*s)?))
+            .collect();
+        vec![macd, signal, histogram]
+    }
+}
+
+fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut averages = vec![None; values.len()];
+    let mut sum = 0.0;
+    for (i, value) in values.iter().enumerate() {
+        sum += value;
+        if i >= period {
+            sum -= values[i - period];
+        }
+        if i + 1 >= period {
+            averages[i] = Some(sum / period as f64);
+        }
+    }
+    averages
+}
+
+/// EMA of a series whose leading values may be missing, seeded with the simple average of the
+/// first `period` values it has.
+fn ema(values: &[Option<f64>], period: usize) -> Vec<Option<f64>> {
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut averages = vec![None; values.len()];
+    let mut seen = 0;
+    let mut average = 0.0;
+    for (i, value) in values.iter().enumerate() {
+        let value = match value {
+            Some(value) => *value,
+            None => continue,
+        };
+        seen += 1;
+        if seen <= period {
+            average += value / period as f64;
+            if seen < period {
+                continue;
+            }
+        } else {
+            average += alpha * (value - average);
+        }
+        averages[i] = Some(average);
+    }
+    averages
+}
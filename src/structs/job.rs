@@ -0,0 +1,112 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio_postgres::Row;
+
+/// Rebuilds candles of a market from its fills, see `worker::jobs::rebuild`
+pub const JOB_KIND_REBUILD: &str = "rebuild";
+/// Rebuilds every candle of one or all markets from all their fills, like `backfill-candles`
+pub const JOB_KIND_BACKFILL: &str = "backfill";
+/// One pass of fill retention over one or all markets
+pub const JOB_KIND_PRUNE: &str = "prune";
+/// Rebuilds the days of a market that are missing minute candles
+pub const JOB_KIND_GAP_REPAIR: &str = "gap_repair";
+
+pub const JOB_KINDS: [&str; 4] = [
+    JOB_KIND_REBUILD,
+    JOB_KIND_BACKFILL,
+    JOB_KIND_PRUNE,
+    JOB_KIND_GAP_REPAIR,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobStatus::Queued => write!(f, "queued"),
+            JobStatus::Running => write!(f, "running"),
+            JobStatus::Done => write!(f, "done"),
+            JobStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl JobStatus {
+    pub fn from_str(v: &str) -> Result<Self, ()> {
+        match v {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "done" => Ok(JobStatus::Done),
+            "failed" => Ok(JobStatus::Failed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A job to queue. Which of the parameters a job uses depends on its kind.
+#[derive(Clone, Debug)]
+pub struct NewJob {
+    pub kind: String,
+    pub market_name: Option<String>,
+    pub resolutions: Vec<String>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Higher runs first
+    pub priority: i32,
+    pub max_attempts: i32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub status: JobStatus,
+    pub market_name: Option<String>,
+    pub resolutions: Vec<String>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Candles inserted or changed, once a job that counts them is done
+    pub candles_written: Option<i64>,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub priority: i32,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// A queued job isn't started before this, e.g. while it waits to be retried
+    pub run_after: DateTime<Utc>,
+}
+
+impl Job {
+    pub fn from_row(row: Row) -> Self {
+        let status: String = row.get(2);
+        Job {
+            id: row.get(0),
+            kind: row.get(1),
+            status: JobStatus::from_str(&status).unwrap_or(JobStatus::Failed),
+            market_name: row.get(3),
+            resolutions: row.get(4),
+            start_time: row.get(5),
+            end_time: row.get(6),
+            candles_written: row.get(7),
+            error: row.get(8),
+            created_at: row.get(9),
+            started_at: row.get(10),
+            finished_at: row.get(11),
+            priority: row.get(12),
+            attempts: row.get(13),
+            max_attempts: row.get(14),
+            run_after: row.get(15),
+        }
+    }
+}
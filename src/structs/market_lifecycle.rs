@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+/// When a market was first and last seen trading and up to when its minute candles are complete.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MarketLifecycle {
+    #[serde(skip)]
+    pub market: String,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub first_fill_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub last_fill_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub candles_through: Option<DateTime<Utc>>,
+}
+
+impl MarketLifecycle {
+    pub fn from_row(row: Row) -> Self {
+        MarketLifecycle {
+            market: row.get(0),
+            first_fill_at: row.get(1),
+            last_fill_at: row.get(2),
+            candles_through: row.get(3),
+        }
+    }
+}
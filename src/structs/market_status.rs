@@ -0,0 +1,28 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// How far ingestion and batching of a market have got, for operators.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct MarketStatus {
+    pub market_name: String,
+    pub address: String,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    #[schema(value_type = Option<i64>)]
+    pub last_fill_at: Option<DateTime<Utc>>,
+    /// Newest fill slot within the last day
+    pub last_fill_slot: Option<i64>,
+    /// Slots between the newest fill and the chain tip
+    pub lag_slots: Option<u64>,
+    /// Seconds since the newest fill
+    pub lag_secs: Option<i64>,
+    /// End of the newest complete candle per resolution, unix seconds
+    pub complete_candles_through: BTreeMap<String, i64>,
+    /// Failed batches since the market was first batched
+    pub batch_errors: i64,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    #[schema(value_type = Option<i64>)]
+    pub last_batch_error_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+/// Last price and trailing 24h statistics of a market. Markets without fills in the window report
+/// zero volume and no high, low or change.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MarketSummary {
+    pub market_name: String,
+    pub address: String,
+    pub last_price: Option<f64>,
+    pub base_volume_24h: f64,
+    pub quote_volume_24h: f64,
+    /// `quote_volume_24h` valued at the quote token's current USD price, `None` when the quote
+    /// token has no USD reference
+    pub quote_volume_24h_usd: Option<f64>,
+    pub high_24h: Option<f64>,
+    pub low_24h: Option<f64>,
+    /// Change from the price 24h ago to `last_price` in percent
+    pub change_24h_percent: Option<f64>,
+}
+
+impl MarketSummary {
+    pub fn from_row(row: Row) -> Self {
+        let last_price: Option<f64> = row.get(2);
+        let open_price: Option<f64> = row.get(7);
+        MarketSummary {
+            market_name: row.get(0),
+            address: row.get(1),
+            last_price,
+            base_volume_24h: row.get(3),
+            quote_volume_24h: row.get(4),
+            quote_volume_24h_usd: None,
+            high_24h: row.get(5),
+            low_24h: row.get(6),
+            change_24h_percent: last_price
+                .zip(open_price)
+                .filter(|(_, open)| *open > 0.0)
+                .map(|(last, open)| (last / open - 1.0) * 100.0),
+        }
+    }
+}
@@ -4,30 +4,150 @@ use solana_account_decoder::UiAccountEncoding;
 use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcAccountInfoConfig};
 use solana_sdk::{commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey};
 use spl_token::state::Mint;
-use std::{collections::HashMap, fs::File, str::FromStr};
+use std::{
+    collections::HashMap,
+    fs::File,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use strum::IntoEnumIterator;
 
-use crate::utils::Config;
+use super::{
+    openbook::{native_to_ui, scaled_ratio, MarketState},
+    phoenix::PhoenixMarketParams,
+    resolution::Resolution,
+    venue::Venue,
+};
 
-use super::openbook::MarketState;
+/// USDC and USDT, valued at $1 when normalizing quote amounts
+pub const USD_STABLECOIN_MINTS: [&str; 2] = [
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+];
 
 #[derive(Debug, Clone, Serialize)]
 pub struct MarketInfo {
+    /// Configured name, qualified with the venue for markets outside OpenBook
     pub name: String,
     pub address: String,
+    pub venue: Venue,
     pub base_decimals: u8,
     pub quote_decimals: u8,
     pub base_mint_key: String,
     pub quote_mint_key: String,
     pub bids_key: String,
     pub asks_key: String,
+    pub event_queue_key: String,
     pub base_lot_size: u64,
     pub quote_lot_size: u64,
+    /// Resolutions candles are kept in, always including minutes
+    pub resolutions: Vec<Resolution>,
+}
+
+impl MarketInfo {
+    pub fn keeps(&self, resolution: Resolution) -> bool {
+        self.resolutions.contains(&resolution)
+    }
+}
+
+/// Conversions from the native amounts and lots stored on chain to UI prices and sizes. Fills,
+/// order books and the fill rescaling tool all go through these so they scale the same way.
+impl MarketInfo {
+    /// Scales a ratio of native quote to native base amounts to quote tokens per base token.
+    fn price_exponent(&self) -> i32 {
+        self.base_decimals as i32 - self.quote_decimals as i32
+    }
+
+    /// Price in quote tokens per base token and size in base tokens of a trade of `base_native`
+    /// for `quote_native`.
+    pub fn native_to_ui_price_and_size(
+        &self,
+        quote_native: u128,
+        base_native: u64,
+    ) -> anyhow::Result<(f64, f64)> {
+        Ok((
+            scaled_ratio(quote_native, base_native, self.price_exponent())?,
+            native_to_ui(base_native as u128, self.base_decimals)?,
+        ))
+    }
+
+    /// Converts a price in quote lots per base lot, as order book keys store it.
+    pub fn price_lots_to_ui(&self, price_lots: u128) -> anyhow::Result<f64> {
+        // price_lots * quote_lot_size * 10^base_decimals / (base_lot_size * 10^quote_decimals)
+        let quote_native = price_lots
+            .checked_mul(self.quote_lot_size as u128)
+            .ok_or_else(|| {
+                anyhow::anyhow!("{} price lots of {} overflow", price_lots, self.name)
+            })?;
+        scaled_ratio(quote_native, self.base_lot_size, self.price_exponent())
+    }
+
+    /// Converts a quantity in base lots.
+    pub fn base_lots_to_ui(&self, base_lots: u128) -> anyhow::Result<f64> {
+        let base_native = base_lots
+            .checked_mul(self.base_lot_size as u128)
+            .ok_or_else(|| anyhow::anyhow!("{} base lots of {} overflow", base_lots, self.name))?;
+        native_to_ui(base_native, self.base_decimals)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct MarketConfig {
     pub name: String,
     pub address: String,
+    /// OpenBook when not given
+    #[serde(default)]
+    pub venue: Venue,
+    /// Resolutions to keep candles in, named as the candles endpoint takes them. Every one when
+    /// not given.
+    #[serde(default)]
+    pub resolutions: Option<Vec<String>>,
+}
+
+impl MarketConfig {
+    /// The configured resolutions in order. Every other resolution is built from minute candles,
+    /// weeks and months through the daily ones, so minutes are added whenever another resolution
+    /// is configured. A list without any resolution is rejected.
+    pub fn parse_resolutions(&self) -> anyhow::Result<Vec<Resolution>> {
+        let names = match &self.resolutions {
+            Some(names) => names,
+            None => return Ok(Resolution::iter().collect()),
+        };
+        let mut configured = vec![];
+        for name in names.iter() {
+            let resolution = Resolution::from_str(name.trim()).map_err(|_| {
+                anyhow::anyhow!(
+                    "{} is configured with unknown resolution {}",
+                    self.name,
+                    name
+                )
+            })?;
+            configured.push(resolution);
+        }
+        if configured.is_empty() {
+            anyhow::bail!(
+                "{} is configured without resolutions, leave out resolutions to keep every one",
+                self.name
+            );
+        }
+        if configured.iter().any(|r| *r != Resolution::R1m) {
+            configured.push(Resolution::R1m);
+        }
+        let resolutions: Vec<Resolution> = Resolution::iter()
+            .filter(|r| configured.contains(r))
+            .collect();
+        // weeks and months are added up from the stored daily candles
+        if let Some(calendar) = resolutions.iter().find(|r| r.is_calendar()) {
+            if !resolutions.contains(&Resolution::R1d) {
+                anyhow::bail!(
+                    "{} keeps {} candles, which are built from daily candles, without 1D",
+                    self.name,
+                    calendar
+                );
+            }
+        }
+        Ok(resolutions)
+    }
 }
 
 pub fn load_markets(path: &str) -> Vec<MarketConfig> {
@@ -35,16 +155,88 @@ pub fn load_markets(path: &str) -> Vec<MarketConfig> {
     serde_json::from_reader(reader).unwrap()
 }
 
-pub fn valid_market(market_name: &str, markets: &Vec<MarketInfo>) -> bool {
-    markets.iter().any(|x| x.name == market_name)
+/// Canonical form of a market name for lookups: upper case, with `/` between the tokens.
+/// "SOL/USDC", "sol-usdc" and "SOL_USDC" all become "SOL/USDC".
+pub fn canonical_market_key(name: &str) -> String {
+    name.trim().to_uppercase().replace(['-', '_'], "/")
+}
+
+/// Lookup table from market names and addresses to positions in the market list. Addresses
+/// match exactly, names in their canonical form.
+#[derive(Debug, Clone, Default)]
+pub struct MarketResolver {
+    addresses: HashMap<String, usize>,
+    names: HashMap<String, usize>,
+}
+
+impl MarketResolver {
+    pub fn new(markets: &[MarketInfo]) -> Self {
+        let mut resolver = MarketResolver::default();
+        for (i, market) in markets.iter().enumerate() {
+            resolver.addresses.insert(market.address.clone(), i);
+            resolver.names.insert(canonical_market_key(&market.name), i);
+        }
+        resolver
+    }
+
+    /// The market named or located at `key`.
+    pub fn resolve<'a>(&self, key: &str, markets: &'a [MarketInfo]) -> Option<&'a MarketInfo> {
+        self.addresses
+            .get(key.trim())
+            .or_else(|| self.names.get(&canonical_market_key(key)))
+            .and_then(|&i| markets.get(i))
+    }
+
+    /// The market named or located at `key` on `venue`. Names may leave out the venue
+    /// qualifier, "SOL/USDC" on Phoenix finds "SOL/USDC@phoenix".
+    pub fn resolve_on<'a>(
+        &self,
+        key: &str,
+        venue: Venue,
+        markets: &'a [MarketInfo],
+    ) -> Option<&'a MarketInfo> {
+        self.resolve(key, markets)
+            .filter(|m| m.venue == venue)
+            .or_else(|| {
+                self.resolve(&venue.qualify(key.trim()), markets)
+                    .filter(|m| m.venue == venue)
+            })
+    }
+}
+
+/// The configured markets, replaced as a whole when the configuration is reloaded.
+#[derive(Clone, Debug, Default)]
+pub struct LiveMarkets(Arc<RwLock<Vec<MarketInfo>>>);
+
+impl LiveMarkets {
+    pub fn new(markets: Vec<MarketInfo>) -> Self {
+        LiveMarkets(Arc::new(RwLock::new(markets)))
+    }
+
+    pub fn get(&self) -> Vec<MarketInfo> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, markets: Vec<MarketInfo>) {
+        *self.0.write().unwrap() = markets;
+    }
+
+    pub fn addresses(&self) -> Vec<String> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .map(|m| m.address.clone())
+            .collect()
+    }
 }
 
 pub async fn fetch_market_infos(
-    config: &Config,
+    rpc_url: &str,
     markets: Vec<MarketConfig>,
 ) -> anyhow::Result<Vec<MarketInfo>> {
     let rpc_client =
-        RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::processed());
+        RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::processed());
 
     let rpc_config = RpcAccountInfoConfig {
         encoding: Some(UiAccountEncoding::Base64),
@@ -53,6 +245,18 @@ pub async fn fetch_market_infos(
         min_context_slot: None,
     };
 
+    if let Some(market) = markets.iter().find(|m| m.venue == Venue::All) {
+        anyhow::bail!(
+            "{} can't be configured with venue all, composite markets are derived",
+            market.name
+        );
+    }
+
+    let resolutions = markets
+        .iter()
+        .map(|m| m.parse_resolutions())
+        .collect::<anyhow::Result<Vec<Vec<Resolution>>>>()?;
+
     let market_keys = markets
         .iter()
         .map(|x| Pubkey::from_str(&x.address).unwrap())
@@ -66,9 +270,34 @@ pub async fn fetch_market_infos(
 
     let mut market_infos = market_results
         .iter_mut()
-        .map(|r| {
+        .zip(markets.iter())
+        .zip(resolutions)
+        .map(|((r, config), resolutions)| {
             let get_account_result = r.as_mut().unwrap();
 
+            if config.venue == Venue::Phoenix {
+                let params =
+                    PhoenixMarketParams::from_account_data(&get_account_result.data).unwrap();
+                mint_key_map.insert(params.base_mint, 0);
+                mint_key_map.insert(params.quote_mint, 0);
+                // the book and its events live in the market account itself
+                return MarketInfo {
+                    name: config.venue.qualify(&config.name),
+                    address: config.address.clone(),
+                    venue: config.venue,
+                    base_decimals: 0,
+                    quote_decimals: 0,
+                    base_mint_key: params.base_mint.to_string(),
+                    quote_mint_key: params.quote_mint.to_string(),
+                    bids_key: config.address.clone(),
+                    asks_key: config.address.clone(),
+                    event_queue_key: config.address.clone(),
+                    base_lot_size: params.base_lot_size,
+                    quote_lot_size: params.quote_lot_size,
+                    resolutions,
+                };
+            }
+
             let mut market_bytes: &[u8] = &mut get_account_result.data[5..];
             let raw_market: MarketState =
                 AnchorDeserialize::deserialize(&mut market_bytes).unwrap();
@@ -76,29 +305,26 @@ pub async fn fetch_market_infos(
             let market_address_string = serum_bytes_to_pubkey(raw_market.own_address).to_string();
             let bids_key = serum_bytes_to_pubkey(raw_market.bids);
             let asks_key = serum_bytes_to_pubkey(raw_market.asks);
+            let event_queue_key = serum_bytes_to_pubkey(raw_market.event_q);
             let base_mint_key = serum_bytes_to_pubkey(raw_market.coin_mint);
             let quote_mint_key = serum_bytes_to_pubkey(raw_market.pc_mint);
             mint_key_map.insert(base_mint_key, 0);
             mint_key_map.insert(quote_mint_key, 0);
 
-            let market_name = markets
-                .iter()
-                .find(|x| x.address == market_address_string)
-                .unwrap()
-                .name
-                .clone();
-
             MarketInfo {
-                name: market_name,
+                name: config.name.clone(),
                 address: market_address_string,
+                venue: config.venue,
                 base_decimals: 0,
                 quote_decimals: 0,
                 base_mint_key: base_mint_key.to_string(),
                 quote_mint_key: quote_mint_key.to_string(),
                 bids_key: bids_key.to_string(),
                 asks_key: asks_key.to_string(),
+                event_queue_key: event_queue_key.to_string(),
                 base_lot_size: raw_market.coin_lot_size,
                 quote_lot_size: raw_market.pc_lot_size,
+                resolutions,
             }
         })
         .collect::<Vec<MarketInfo>>();
@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use tokio_postgres::Row;
+
+/// A market's maker fills of one minute summed up by the `openbook.minute_fill_aggregates`
+/// continuous aggregate. Minutes without fills have no row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MinuteAggregate {
+    pub market: String,
+    pub start_time: DateTime<Utc>,
+    /// Price of the first fill in the minute
+    pub open: f64,
+    pub close: f64,
+    pub high: f64,
+    pub low: f64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub trade_count: i64,
+    pub first_fill_time: DateTime<Utc>,
+    pub last_fill_time: DateTime<Utc>,
+    pub last_slot: i64,
+}
+
+impl MinuteAggregate {
+    pub fn from_row(row: Row) -> Self {
+        MinuteAggregate {
+            market: row.get(0),
+            start_time: row.get(1),
+            open: row.get(2),
+            close: row.get(3),
+            high: row.get(4),
+            low: row.get(5),
+            volume: row.get(6),
+            quote_volume: row.get(7),
+            trade_count: row.get(8),
+            first_fill_time: row.get(9),
+            last_fill_time: row.get(10),
+            last_slot: row.get(11),
+        }
+    }
+}
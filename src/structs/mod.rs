@@ -1,8 +1,45 @@
+pub mod api_keys;
+pub mod cache_backend;
 pub mod candle;
+pub mod candle_cache;
+pub mod candle_import;
+pub mod changes;
+pub mod checkpoint;
 pub mod coingecko;
+pub mod composite;
+pub mod divergence;
+pub mod embargo;
+pub mod envelope;
+pub mod event_queue;
+pub mod fill_import;
+pub mod fixtures;
+pub mod indicator;
+pub mod job;
+pub mod market_lifecycle;
+pub mod market_status;
+pub mod market_summary;
 pub mod markets;
+pub mod minute_aggregate;
 pub mod openbook;
+pub mod oracle;
+pub mod orderbook;
+pub mod pattern;
+pub mod phoenix;
+pub mod price_change;
+pub mod privacy;
+pub mod rate;
+pub mod rate_limit;
 pub mod resolution;
+pub mod returns;
+pub mod revision;
+pub mod rolling;
+pub mod session;
 pub mod slab;
+pub mod spread;
+pub mod trade;
 pub mod trader;
 pub mod tradingview;
+pub mod uptime;
+pub mod usd;
+pub mod venue;
+pub mod volume_profile;
@@ -1,6 +1,5 @@
 use anchor_lang::AnchorDeserialize;
 use chrono::{DateTime, Utc};
-use num_traits::Pow;
 use tokio_postgres::Row;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -11,6 +10,8 @@ pub struct PgOpenBookFill {
     pub maker: bool,
     pub price: f64,
     pub size: f64,
+    pub slot: i64,
+    pub seq_num: i64,
 }
 impl PgOpenBookFill {
     pub fn from_row(row: Row) -> Self {
@@ -21,10 +22,77 @@ impl PgOpenBookFill {
             maker: row.get(3),
             price: row.get(4),
             size: row.get(5),
+            slot: row.get(6),
+            seq_num: row.get(7),
         }
     }
 }
 
+/// A fill event as written to `openbook.openbook_fill_events`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenBookFill {
+    pub signature: String,
+    pub slot: i64,
+    pub block_datetime: DateTime<Utc>,
+    pub market: String,
+    pub open_orders_owner: String,
+    pub bid: bool,
+    pub maker: bool,
+    pub native_quantity_paid: f64,
+    pub native_quantity_received: f64,
+    pub native_fee_or_rebate: f64,
+    pub price: f64,
+    pub size: f64,
+    pub seq_num: i64,
+    pub instruction_num: i32,
+    /// Quote tokens, negative for a maker rebate. `None` when the source didn't provide it.
+    pub fee: Option<f64>,
+    /// Quote tokens of a taker fee paid out to the referrer
+    pub referrer_rebate: Option<f64>,
+}
+impl OpenBookFill {
+    pub fn from_row(row: Row) -> Self {
+        OpenBookFill {
+            signature: row.get(0),
+            slot: row.get(1),
+            block_datetime: row.get(2),
+            market: row.get(3),
+            open_orders_owner: row.get(4),
+            bid: row.get(5),
+            maker: row.get(6),
+            native_quantity_paid: row.get(7),
+            native_quantity_received: row.get(8),
+            native_fee_or_rebate: row.get(9),
+            price: row.get(10),
+            size: row.get(11),
+            seq_num: row.get(12),
+            instruction_num: row.get(13),
+            fee: row.get(14),
+            referrer_rebate: row.get(15),
+        }
+    }
+}
+
+/// OpenBook pays referrers a fifth of the taker fee, see `serum_dex::fees::referrer_rebate`
+const REFERRER_REBATE_DIVISOR: u64 = 5;
+
+/// Fee of a fill in quote tokens, negative for a maker rebate, and the part of it paid to the
+/// referrer, derived from the event's native fee or rebate. Scaled by `native_to_ui` like prices
+/// and sizes.
+pub fn fill_fees(
+    native_fee_or_rebate: u64,
+    maker: bool,
+    quote_decimals: u8,
+) -> anyhow::Result<(f64, f64)> {
+    let fee = native_to_ui(native_fee_or_rebate as u128, quote_decimals)?;
+    if maker {
+        Ok((-fee, 0.0))
+    } else {
+        let referrer_rebate = native_fee_or_rebate / REFERRER_REBATE_DIVISOR;
+        Ok((fee, native_to_ui(referrer_rebate as u128, quote_decimals)?))
+    }
+}
+
 #[derive(Copy, Clone, AnchorDeserialize)]
 #[cfg_attr(target_endian = "little", derive(Debug))]
 #[repr(packed)]
@@ -80,6 +148,169 @@ pub struct MarketState {
     pub referrer_rebates_accrued: u64,
 }
 
-pub fn token_factor(decimals: u8) -> f64 {
-    10f64.pow(decimals as f64)
+/// Computes `numerator / denominator * 10^exponent` with integer arithmetic and rounds once, to
+/// the nearest f64. Doing the same in floats rounds at every step, which shows up as noise for
+/// tokens with 0 or very many decimals and for large lot sizes. Fails on a zero denominator and
+/// when the power of ten scales either side out of range.
+pub fn scaled_ratio(numerator: u128, denominator: u64, exponent: i32) -> anyhow::Result<f64> {
+    if denominator == 0 {
+        anyhow::bail!("{} / 0 has no value", numerator);
+    }
+    // 10^exponent = 5^exponent * 2^exponent, only the power of five needs multiplying in
+    let out_of_range = || {
+        anyhow::anyhow!(
+            "{} / {} * 10^{} is out of range",
+            numerator,
+            denominator,
+            exponent
+        )
+    };
+    let five_power = 5u128
+        .checked_pow(exponent.unsigned_abs())
+        .ok_or_else(out_of_range)?;
+    let (numerator, denominator) = if exponent >= 0 {
+        let numerator = numerator.checked_mul(five_power).ok_or_else(out_of_range)?;
+        (numerator, denominator as u128)
+    } else {
+        let denominator = (denominator as u128)
+            .checked_mul(five_power)
+            .ok_or_else(out_of_range)?;
+        (numerator, denominator)
+    };
+    Ok(binary_ratio(numerator, denominator, exponent))
+}
+
+/// `numerator / denominator * 2^exponent` rounded to the nearest f64, ties to even.
+fn binary_ratio(numerator: u128, denominator: u128, mut exponent: i32) -> f64 {
+    if numerator == 0 {
+        return 0.0;
+    }
+    let mut quotient = numerator / denominator;
+    let mut remainder = numerator % denominator;
+    let mut inexact = false;
+    // keep 64 significant bits, the 11 below the 53 an f64 holds decide the rounding
+    if quotient >= 1 << 64 {
+        let shift = 64 - quotient.leading_zeros();
+        inexact = quotient & ((1 << shift) - 1) != 0;
+        quotient >>= shift;
+        exponent += shift as i32;
+    } else {
+        while quotient < 1 << 63 {
+            // remainder < denominator, comparing with the difference can't overflow
+            let gap = denominator - remainder;
+            quotient <<= 1;
+            if remainder >= gap {
+                quotient |= 1;
+                remainder -= gap;
+            } else {
+                remainder <<= 1;
+            }
+            exponent -= 1;
+        }
+    }
+    // sticky bit so a truncated quotient never rounds like an exact tie
+    if inexact || remainder != 0 {
+        quotient |= 1;
+    }
+    quotient as f64 * 2f64.powi(exponent)
+}
+
+/// Converts a native token amount to UI units.
+pub fn native_to_ui(amount: u128, decimals: u8) -> anyhow::Result<f64> {
+    scaled_ratio(amount, 1, -(decimals as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::{markets::MarketInfo, venue::Venue};
+
+    fn market(
+        base_decimals: u8,
+        quote_decimals: u8,
+        base_lot_size: u64,
+        quote_lot_size: u64,
+    ) -> MarketInfo {
+        MarketInfo {
+            name: "TEST/USDC".to_string(),
+            address: String::new(),
+            venue: Venue::OpenBook,
+            base_decimals,
+            quote_decimals,
+            base_mint_key: String::new(),
+            quote_mint_key: String::new(),
+            bids_key: String::new(),
+            asks_key: String::new(),
+            event_queue_key: String::new(),
+            base_lot_size,
+            quote_lot_size,
+            resolutions: vec![],
+        }
+    }
+
+    #[test]
+    fn zero_decimals() {
+        assert_eq!(native_to_ui(12_345, 0).unwrap(), 12_345.0);
+        assert_eq!(native_to_ui(0, 0).unwrap(), 0.0);
+        assert_eq!(scaled_ratio(7, 2, 0).unwrap(), 3.5);
+        // a whole number too large for an f64 rounds like the integer itself
+        assert_eq!(native_to_ui(u128::MAX, 0).unwrap(), u128::MAX as f64);
+    }
+
+    #[test]
+    fn many_decimals() {
+        assert_eq!(native_to_ui(1, 18).unwrap(), 1e-18);
+        assert_eq!(
+            native_to_ui(123_456_789_012_345_678_901, 18).unwrap(),
+            123.456789012345678901
+        );
+        assert_eq!(native_to_ui(1, 24).unwrap(), 1e-24);
+        assert_eq!(
+            native_to_ui(999_999_999_999_999, 15).unwrap(),
+            0.999999999999999
+        );
+        // 5^255 doesn't fit in a u128
+        assert!(native_to_ui(1, 255).is_err());
+    }
+
+    #[test]
+    fn negative_exponents() {
+        assert_eq!(scaled_ratio(1, 1, -3).unwrap(), 0.001);
+        // 25 quote atoms for 10 base atoms, 6 base and 9 quote decimals
+        assert_eq!(scaled_ratio(25, 10, 6 - 9).unwrap(), 0.0025);
+        assert_eq!(scaled_ratio(1, 3, -2).unwrap(), 0.0033333333333333335);
+        assert_eq!(scaled_ratio(5, 1, 3).unwrap(), 5000.0);
+    }
+
+    #[test]
+    fn rounds_once_at_the_last_digit() {
+        // float arithmetic gets these wrong in the last digit
+        assert_eq!(native_to_ui(3, 1).unwrap(), 0.3);
+        assert_ne!(3.0 * 0.1, 0.3);
+        assert_eq!(scaled_ratio(1, 3, 0).unwrap(), 1.0 / 3.0);
+        assert_eq!(scaled_ratio(2, 3, 0).unwrap(), 2.0 / 3.0);
+        // exactly halfway between two f64s rounds to the even one
+        let tie = (1u128 << 53) + 1;
+        assert_eq!(scaled_ratio(tie, 1, 0).unwrap(), (1u64 << 53) as f64);
+        // just above halfway rounds up, the remainder isn't mistaken for a tie
+        assert_eq!(
+            scaled_ratio(tie * 3 + 1, 3, 0).unwrap(),
+            ((1u64 << 53) + 2) as f64
+        );
+    }
+
+    #[test]
+    fn max_size_lots() {
+        let market = market(0, 0, u64::MAX, u64::MAX);
+        let max = u64::MAX as u128;
+        assert_eq!(market.price_lots_to_ui(max).unwrap(), max as f64);
+        assert_eq!(market.base_lots_to_ui(max).unwrap(), (max * max) as f64);
+        assert!(market.price_lots_to_ui(u128::MAX).is_err());
+        assert!(market.base_lots_to_ui(u128::MAX).is_err());
+    }
+
+    #[test]
+    fn zero_denominator() {
+        assert!(scaled_ratio(1, 0, 0).is_err());
+    }
 }
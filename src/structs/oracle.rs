@@ -0,0 +1,164 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+fn default_oracle_poll_secs() -> u64 {
+    60
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OracleConfig {
+    /// Comma separated `symbol:provider:account`, e.g.
+    /// `SOL:pyth:H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG`
+    pub oracle_feeds: Option<String>,
+    #[serde(default = "default_oracle_poll_secs")]
+    pub oracle_poll_secs: u64,
+}
+
+impl OracleConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.oracle_feeds
+            .as_deref()
+            .map_or(false, |feeds| !feeds.trim().is_empty())
+    }
+
+    pub fn feeds(&self) -> anyhow::Result<Vec<OracleFeed>> {
+        self.oracle_feeds
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+            .map(OracleFeed::parse)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OracleProvider {
+    Pyth,
+    Switchboard,
+}
+
+impl OracleProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OracleProvider::Pyth => "pyth",
+            OracleProvider::Switchboard => "switchboard",
+        }
+    }
+
+    /// Price, confidence interval and publish time from the feed's account data.
+    pub fn decode(&self, data: &[u8]) -> Option<(f64, Option<f64>, DateTime<Utc>)> {
+        match self {
+            OracleProvider::Pyth => decode_pyth_price(data),
+            OracleProvider::Switchboard => decode_switchboard_price(data),
+        }
+    }
+}
+
+/// A price account polled for one token's USD price.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OracleFeed {
+    pub symbol: String,
+    pub provider: OracleProvider,
+    pub account: String,
+}
+
+impl OracleFeed {
+    fn parse(entry: &str) -> anyhow::Result<Self> {
+        let parts: Vec<&str> = entry.split(':').collect();
+        let (symbol, provider, account) = match parts[..] {
+            [symbol, provider, account] => (symbol, provider, account),
+            _ => anyhow::bail!("expected symbol:provider:account, got {}", entry),
+        };
+        let provider = match provider {
+            "pyth" => OracleProvider::Pyth,
+            "switchboard" => OracleProvider::Switchboard,
+            _ => anyhow::bail!("unknown oracle provider {}", provider),
+        };
+        Ok(OracleFeed {
+            symbol: symbol.to_uppercase(),
+            provider,
+            account: account.to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OraclePrice {
+    pub symbol: String,
+    pub provider: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+    pub price: f64,
+    pub confidence: Option<f64>,
+}
+
+impl OraclePrice {
+    pub fn from_row(row: Row) -> Self {
+        OraclePrice {
+            symbol: row.get(0),
+            provider: row.get(1),
+            time: row.get(2),
+            price: row.get(3),
+            confidence: row.get(4),
+        }
+    }
+}
+
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_i64(data: &[u8], offset: usize) -> Option<i64> {
+    Some(i64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Aggregate price of a Pyth v2 price account, `None` unless it is currently trading.
+fn decode_pyth_price(data: &[u8]) -> Option<(f64, Option<f64>, DateTime<Utc>)> {
+    if read_u32(data, 0)? != PYTH_MAGIC || read_u32(data, 8)? != PYTH_ACCOUNT_TYPE_PRICE {
+        return None;
+    }
+    if read_u32(data, 224)? != PYTH_STATUS_TRADING {
+        return None;
+    }
+    let exponent = read_u32(data, 20)? as i32;
+    let scale = 10f64.powi(exponent);
+    let price = read_i64(data, 208)? as f64 * scale;
+    let confidence = read_i64(data, 216)? as u64 as f64 * scale;
+    let time = Utc.timestamp_opt(read_i64(data, 96)?, 0).single()?;
+    Some((price, Some(confidence), time))
+}
+
+/// Offset of `latest_confirmed_round.result` in a Switchboard v2 aggregator account
+const SWITCHBOARD_RESULT_OFFSET: usize = 366;
+/// Offset of `latest_confirmed_round.round_open_timestamp`
+const SWITCHBOARD_TIMESTAMP_OFFSET: usize = 358;
+
+/// Latest confirmed result of a Switchboard v2 aggregator account.
+fn decode_switchboard_price(data: &[u8]) -> Option<(f64, Option<f64>, DateTime<Utc>)> {
+    let mantissa = i128::from_le_bytes(
+        data.get(SWITCHBOARD_RESULT_OFFSET..SWITCHBOARD_RESULT_OFFSET + 16)?
+            .try_into()
+            .ok()?,
+    );
+    let scale = read_u32(data, SWITCHBOARD_RESULT_OFFSET + 16)?;
+    let price = mantissa as f64 / 10f64.powi(scale as i32);
+    let time = Utc
+        .timestamp_opt(read_i64(data, SWITCHBOARD_TIMESTAMP_OFFSET)?, 0)
+        .single()?;
+    Some((price, None, time))
+}
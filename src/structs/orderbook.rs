@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+use super::{
+    markets::MarketInfo,
+    usd::{usd_reference, UsdReference},
+};
+
+/// Fraction of the mid price used for the CoinGecko depth figures
+pub const DEPTH_RANGE: f64 = 0.02;
+
+/// Fractions of the mid price the recorded depth snapshots measure liquidity within
+pub const DEPTH_SNAPSHOT_BANDS: [f64; 3] = [0.01, 0.02, 0.05];
+
+/// An order resting on the book with the open orders account that placed it
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestingOrder {
+    pub price: f64,
+    pub quantity: f64,
+    pub owner: String,
+}
+
+/// Top of book and near-mid liquidity for a single market as of `timestamp`
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderBookSnapshot {
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    /// Quote value of resting bids within 2% below the mid price
+    pub bid_depth: f64,
+    /// Quote value of resting asks within 2% above the mid price
+    pub ask_depth: f64,
+    /// Quote value of resting bids within each of `DEPTH_SNAPSHOT_BANDS` below the mid price
+    pub bid_depth_bands: [f64; 3],
+    pub ask_depth_bands: [f64; 3],
+    pub timestamp: DateTime<Utc>,
+}
+
+impl OrderBookSnapshot {
+    /// Builds a snapshot from (price, quantity) levels ordered from the best price outwards.
+    pub fn from_levels(
+        bid_levels: &[(f64, f64)],
+        ask_levels: &[(f64, f64)],
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        let best_bid = bid_levels.first().map(|l| l.0);
+        let best_ask = ask_levels.first().map(|l| l.0);
+        let mid = best_bid.zip(best_ask).map(|(bid, ask)| (bid + ask) / 2.0);
+        let bid_depth_within = |range: f64| match mid {
+            Some(mid) => bid_levels
+                .iter()
+                .take_while(|l| l.0 >= mid * (1.0 - range))
+                .map(|l| l.0 * l.1)
+                .sum::<f64>(),
+            None => 0.0,
+        };
+        let ask_depth_within = |range: f64| match mid {
+            Some(mid) => ask_levels
+                .iter()
+                .take_while(|l| l.0 <= mid * (1.0 + range))
+                .map(|l| l.0 * l.1)
+                .sum::<f64>(),
+            None => 0.0,
+        };
+
+        OrderBookSnapshot {
+            best_bid,
+            best_ask,
+            bid_depth: bid_depth_within(DEPTH_RANGE),
+            ask_depth: ask_depth_within(DEPTH_RANGE),
+            bid_depth_bands: DEPTH_SNAPSHOT_BANDS.map(bid_depth_within),
+            ask_depth_bands: DEPTH_SNAPSHOT_BANDS.map(ask_depth_within),
+            timestamp,
+        }
+    }
+
+    pub fn is_fresh(&self, max_age: Duration) -> bool {
+        Utc::now() - self.timestamp <= max_age
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        match (self.best_bid, self.best_ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            _ => None,
+        }
+    }
+}
+
+/// USD value of one unit of `market`'s quote token. Stablecoin quoted markets are taken at par,
+/// otherwise the mid price of a stablecoin quoted market for the quote token is used.
+pub fn quote_usd_price(
+    market: &MarketInfo,
+    markets: &[MarketInfo],
+    snapshots: &HashMap<String, OrderBookSnapshot>,
+) -> Option<f64> {
+    match usd_reference(market, markets)? {
+        UsdReference::Par => Some(1.0),
+        UsdReference::Market(m) => snapshots.get(&m.address).and_then(|s| s.mid_price()),
+    }
+}
+
+/// Persisted ±2% depth of a market, valued in USD
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DepthStat {
+    pub market_name: String,
+    pub time: DateTime<Utc>,
+    pub mid_price: f64,
+    pub bid_depth_usd: f64,
+    pub ask_depth_usd: f64,
+}
+
+impl DepthStat {
+    pub fn from_row(row: Row) -> Self {
+        DepthStat {
+            market_name: row.get(0),
+            time: row.get(1),
+            mid_price: row.get(2),
+            bid_depth_usd: row.get(3),
+            ask_depth_usd: row.get(4),
+        }
+    }
+}
+
+/// Persisted best prices and depth of a market within each of `DEPTH_SNAPSHOT_BANDS` of the mid
+/// price, in quote tokens
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct DepthSnapshot {
+    pub market_name: String,
+    /// Unix seconds
+    #[serde(with = "chrono::serde::ts_seconds")]
+    #[schema(value_type = i64)]
+    pub time: DateTime<Utc>,
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    pub mid_price: f64,
+    pub bid_depth_1pct: f64,
+    pub ask_depth_1pct: f64,
+    pub bid_depth_2pct: f64,
+    pub ask_depth_2pct: f64,
+    pub bid_depth_5pct: f64,
+    pub ask_depth_5pct: f64,
+}
+
+impl DepthSnapshot {
+    /// `None` for a book without both sides, which has no mid price to measure from.
+    pub fn from_order_book(market_name: &str, snapshot: &OrderBookSnapshot) -> Option<Self> {
+        let [bid_depth_1pct, bid_depth_2pct, bid_depth_5pct] = snapshot.bid_depth_bands;
+        let [ask_depth_1pct, ask_depth_2pct, ask_depth_5pct] = snapshot.ask_depth_bands;
+        Some(DepthSnapshot {
+            market_name: market_name.to_string(),
+            time: snapshot.timestamp,
+            best_bid: snapshot.best_bid,
+            best_ask: snapshot.best_ask,
+            mid_price: snapshot.mid_price()?,
+            bid_depth_1pct,
+            ask_depth_1pct,
+            bid_depth_2pct,
+            ask_depth_2pct,
+            bid_depth_5pct,
+            ask_depth_5pct,
+        })
+    }
+
+    pub fn from_row(row: Row) -> Self {
+        DepthSnapshot {
+            market_name: row.get(0),
+            time: row.get(1),
+            best_bid: row.get(2),
+            best_ask: row.get(3),
+            mid_price: row.get(4),
+            bid_depth_1pct: row.get(5),
+            ask_depth_1pct: row.get(6),
+            bid_depth_2pct: row.get(7),
+            ask_depth_2pct: row.get(8),
+            bid_depth_5pct: row.get(9),
+            ask_depth_5pct: row.get(10),
+        }
+    }
+}
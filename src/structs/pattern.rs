@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::candle::Candle;
+
+/// A body at most this fraction of the candle's range counts as a doji
+const DOJI_BODY_RATIO: f64 = 0.1;
+/// A hammer's lower shadow is at least this many times its body
+const HAMMER_SHADOW_RATIO: f64 = 2.0;
+/// A hammer's upper shadow is at most this fraction of the candle's range
+const HAMMER_UPPER_SHADOW_RATIO: f64 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandlePattern {
+    BullishEngulfing,
+    BearishEngulfing,
+    Doji,
+    Hammer,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PatternDetection {
+    pub market_name: String,
+    pub resolution: String,
+    pub pattern: CandlePattern,
+    /// Start of the candle completing the pattern
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    pub close: f64,
+}
+
+/// Finds the patterns completed by each of the candles, which must be in ascending order. Only
+/// complete candles with trades are considered, a flat empty candle isn't a doji.
+pub fn detect_patterns(candles: &[Candle]) -> Vec<PatternDetection> {
+    let mut detections = vec![];
+    let mut previous: Option<&Candle> = None;
+    for candle in candles.iter().filter(|c| c.complete && c.trade_count > 0) {
+        let mut patterns = vec![];
+        if let Some(prev) = previous {
+            // only compare neighbours, not candles on either side of a gap
+            if prev.end_time == candle.start_time {
+                patterns.extend(engulfing(prev, candle));
+            }
+        }
+        if is_doji(candle) {
+            patterns.push(CandlePattern::Doji);
+        } else if is_hammer(candle) {
+            patterns.push(CandlePattern::Hammer);
+        }
+        detections.extend(patterns.into_iter().map(|pattern| PatternDetection {
+            market_name: candle.market_name.clone(),
+            resolution: candle.resolution.clone(),
+            pattern,
+            start_time: candle.start_time,
+            close: candle.close,
+        }));
+        previous = Some(candle);
+    }
+    detections
+}
+
+fn engulfing(prev: &Candle, candle: &Candle) -> Option<CandlePattern> {
+    let prev_body = (prev.close - prev.open).abs();
+    let body = (candle.close - candle.open).abs();
+    if body <= prev_body {
+        return None;
+    }
+    if prev.close < prev.open
+        && candle.close > candle.open
+        && candle.open <= prev.close
+        && candle.close >= prev.open
+    {
+        Some(CandlePattern::BullishEngulfing)
+    } else if prev.close > prev.open
+        && candle.close < candle.open
+        && candle.open >= prev.close
+        && candle.close <= prev.open
+    {
+        Some(CandlePattern::BearishEngulfing)
+    } else {
+        None
+    }
+}
+
+fn is_doji(candle: &Candle) -> bool {
+    let range = candle.high - candle.low;
+    range > 0.0 && (candle.close - candle.open).abs() <= range * DOJI_BODY_RATIO
+}
+
+fn is_hammer(candle: &Candle) -> bool {
+    let range = candle.high - candle.low;
+    let body = (candle.close - candle.open).abs();
+    let lower_shadow = candle.open.min(candle.close) - candle.low;
+    let upper_shadow = candle.high - candle.open.max(candle.close);
+    range > 0.0
+        && body > 0.0
+        && lower_shadow >= body * HAMMER_SHADOW_RATIO
+        && upper_shadow <= range * HAMMER_UPPER_SHADOW_RATIO
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use borsh::BorshDeserialize;
+use chrono::{TimeZone, Utc};
+use solana_sdk::pubkey::Pubkey;
+
+use super::openbook::{native_to_ui, scaled_ratio, OpenBookFill};
+
+/// Phoenix v1
+pub const PHOENIX_PROGRAM_ID: &str = "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY";
+
+/// Tag of the instruction Phoenix invokes on itself to emit the events of a market instruction
+const LOG_INSTRUCTION_TAG: u8 = 15;
+
+/// The market header ends with padding, the fields read here fit in its first 316 bytes
+const MARKET_HEADER_MIN_LEN: usize = 316;
+
+/// Lot sizes and decimals of a Phoenix market, read from the header at the start of the market
+/// account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhoenixMarketParams {
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_lot_size: u64,
+    pub quote_lot_size: u64,
+    pub tick_size_in_quote_atoms_per_base_unit: u64,
+    /// Base units are this many whole base tokens, prices are quoted per base unit
+    pub raw_base_units_per_base_unit: u32,
+}
+
+impl PhoenixMarketParams {
+    /// Decodes the `MarketHeader` of a market account: discriminant, status and size params, then
+    /// base token params, base lot size, quote token params, quote lot size and tick size, then
+    /// authority, fee recipient, sequence number and successor, then the raw base units per base
+    /// unit.
+    pub fn from_account_data(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < MARKET_HEADER_MIN_LEN {
+            anyhow::bail!("{} bytes are too short for a Phoenix market", data.len());
+        }
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let key_at =
+            |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        Ok(PhoenixMarketParams {
+            base_decimals: u32_at(40) as u8,
+            base_mint: key_at(48),
+            base_lot_size: u64_at(112),
+            quote_decimals: u32_at(120) as u8,
+            quote_mint: key_at(128),
+            quote_lot_size: u64_at(192),
+            tick_size_in_quote_atoms_per_base_unit: u64_at(200),
+            raw_base_units_per_base_unit: u32_at(312).max(1),
+        })
+    }
+
+    /// Quote tokens per base token.
+    pub fn ticks_to_ui_price(&self, price_in_ticks: u64) -> anyhow::Result<f64> {
+        scaled_ratio(
+            price_in_ticks as u128 * self.tick_size_in_quote_atoms_per_base_unit as u128,
+            self.raw_base_units_per_base_unit as u64,
+            -(self.quote_decimals as i32),
+        )
+    }
+
+    pub fn base_lots_to_native(&self, base_lots: u64) -> u128 {
+        base_lots as u128 * self.base_lot_size as u128
+    }
+
+    /// Quote atoms paid for `base_lots` at `price_in_ticks`.
+    pub fn quote_native(&self, price_in_ticks: u64, base_lots: u64) -> f64 {
+        price_in_ticks as f64
+            * self.tick_size_in_quote_atoms_per_base_unit as f64
+            * self.base_lots_to_native(base_lots) as f64
+            / (self.raw_base_units_per_base_unit as f64 * 10f64.powi(self.base_decimals as i32))
+    }
+}
+
+#[derive(BorshDeserialize)]
+struct AuditLogHeader {
+    _instruction: u8,
+    sequence_number: u64,
+    timestamp: i64,
+    slot: u64,
+    market: [u8; 32],
+    signer: [u8; 32],
+    _total_events: u16,
+}
+
+#[derive(BorshDeserialize)]
+struct FillEvent {
+    index: u16,
+    maker_id: [u8; 32],
+    order_sequence_number: u64,
+    price_in_ticks: u64,
+    base_lots_filled: u64,
+    _base_lots_remaining: u64,
+}
+
+/// Events of a Phoenix log instruction, borsh encoded one after the other. Only headers and
+/// fills are used, the other variants are decoded to skip past them.
+#[allow(dead_code)]
+#[derive(BorshDeserialize)]
+enum PhoenixMarketEvent {
+    Uninitialized,
+    Header(AuditLogHeader),
+    Fill(FillEvent),
+    Place {
+        index: u16,
+        order_sequence_number: u64,
+        client_order_id: u128,
+        price_in_ticks: u64,
+        base_lots_placed: u64,
+    },
+    Reduce {
+        index: u16,
+        order_sequence_number: u64,
+        price_in_ticks: u64,
+        base_lots_removed: u64,
+        base_lots_remaining: u64,
+    },
+    Evict {
+        index: u16,
+        maker_id: [u8; 32],
+        order_sequence_number: u64,
+        price_in_ticks: u64,
+        base_lots_evicted: u64,
+    },
+    FillSummary {
+        index: u16,
+        client_order_id: u128,
+        total_base_lots_filled: u64,
+        total_quote_lots_filled: u64,
+        total_fee_in_quote_lots: u64,
+    },
+    Fee {
+        index: u16,
+        fees_collected_in_quote_lots: u64,
+    },
+    TimeInForce {
+        index: u16,
+        order_sequence_number: u64,
+        last_valid_slot: u64,
+        last_valid_unix_timestamp_in_seconds: u64,
+    },
+    ExpiredOrder {
+        index: u16,
+        maker_id: [u8; 32],
+        order_sequence_number: u64,
+        price_in_ticks: u64,
+        base_lots_removed: u64,
+    },
+}
+
+/// Fills in the data of a Phoenix log instruction, a maker and a taker fill per match like the
+/// OpenBook event queue has them. Empty for other instructions and for markets not in `markets`.
+///
+/// The maker is the trader's wallet, Phoenix has no open orders accounts, and the taker is the
+/// signer of the instruction. Taker fees are only reported per order, not per match, so taker
+/// fills have no fee.
+pub fn decode_phoenix_fills(
+    data: &[u8],
+    signature: &str,
+    markets: &HashMap<Pubkey, PhoenixMarketParams>,
+) -> anyhow::Result<Vec<OpenBookFill>> {
+    if data.first() != Some(&LOG_INSTRUCTION_TAG) {
+        return Ok(vec![]);
+    }
+    let mut events = &data[1..];
+    let header = match PhoenixMarketEvent::deserialize(&mut events)? {
+        PhoenixMarketEvent::Header(header) => header,
+        _ => anyhow::bail!("Phoenix log in {} doesn't start with a header", signature),
+    };
+    let market = Pubkey::new_from_array(header.market);
+    let params = match markets.get(&market) {
+        Some(params) => params,
+        None => return Ok(vec![]),
+    };
+    let block_datetime = Utc
+        .timestamp_opt(header.timestamp, 0)
+        .single()
+        .ok_or_else(|| anyhow::anyhow!("invalid timestamp {}", header.timestamp))?;
+
+    let mut fills = vec![];
+    while !events.is_empty() {
+        let fill = match PhoenixMarketEvent::deserialize(&mut events)? {
+            PhoenixMarketEvent::Fill(fill) => fill,
+            _ => continue,
+        };
+        // resting bids have the top bit of their sequence number set
+        let maker_bid = fill.order_sequence_number >> 63 == 1;
+        let base_native = params.base_lots_to_native(fill.base_lots_filled);
+        let quote_native = params.quote_native(fill.price_in_ticks, fill.base_lots_filled);
+        let price = params.ticks_to_ui_price(fill.price_in_ticks)?;
+        let size = native_to_ui(base_native, params.base_decimals)?;
+        // unique per market as long as an instruction emits fewer than 2^16 events
+        let seq_num = ((header.sequence_number as i64) << 16) | fill.index as i64;
+
+        for (owner, bid, maker) in [
+            (fill.maker_id, maker_bid, true),
+            (header.signer, !maker_bid, false),
+        ] {
+            let (paid, received) = if bid {
+                (quote_native, base_native as f64)
+            } else {
+                (base_native as f64, quote_native)
+            };
+            fills.push(OpenBookFill {
+                signature: signature.to_string(),
+                slot: header.slot as i64,
+                block_datetime,
+                market: market.to_string(),
+                open_orders_owner: Pubkey::new_from_array(owner).to_string(),
+                bid,
+                maker,
+                native_quantity_paid: paid,
+                native_quantity_received: received,
+                native_fee_or_rebate: 0.0,
+                price,
+                size,
+                seq_num,
+                instruction_num: 0,
+                // makers pay no fee on Phoenix
+                fee: maker.then_some(0.0),
+                referrer_rebate: None,
+            });
+        }
+    }
+    Ok(fills)
+}
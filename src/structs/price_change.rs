@@ -0,0 +1,45 @@
+use serde::Serialize;
+
+/// Windows the price change is reported over, with their SQL intervals.
+pub const PRICE_CHANGE_WINDOWS: [(&str, &str); 3] =
+    [("1h", "1 hour"), ("24h", "1 day"), ("7d", "7 days")];
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PriceChange {
+    pub window: String,
+    /// Close of the minute candle the window starts at
+    pub open_price: Option<f64>,
+    /// Change from `open_price` to the current price in percent
+    pub change_percent: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PriceChangeResponse {
+    pub market_name: String,
+    pub price: Option<f64>,
+    pub changes: Vec<PriceChange>,
+}
+
+impl PriceChangeResponse {
+    /// `closes` holds the current close followed by the close at the start of each window.
+    pub fn from_closes(market_name: String, closes: &[Option<f64>]) -> Self {
+        let price = closes.first().copied().flatten();
+        let changes = PRICE_CHANGE_WINDOWS
+            .iter()
+            .zip(closes.iter().skip(1))
+            .map(|((window, _), open_price)| PriceChange {
+                window: window.to_string(),
+                open_price: *open_price,
+                change_percent: price
+                    .zip(*open_price)
+                    .filter(|(_, open)| *open > 0.0)
+                    .map(|(price, open)| (price / open - 1.0) * 100.0),
+            })
+            .collect();
+        PriceChangeResponse {
+            market_name,
+            price,
+            changes,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PrivacyConfig {
+    /// Replace trader addresses and transaction signatures with pseudonyms for requests without
+    /// an API key
+    #[serde(default)]
+    pub anonymize_traders: bool,
+    /// Mixed into the pseudonyms so they can't be reversed by hashing known addresses
+    #[serde(default, serialize_with = "crate::utils::settings::redact_str")]
+    pub anonymize_traders_salt: String,
+}
+
+impl PrivacyConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+/// Hex characters of the digest kept in a pseudonym
+const PSEUDONYM_LEN: usize = 16;
+
+/// Turns addresses into stable pseudonyms, so a trader can still be followed across responses
+/// without revealing the wallet.
+#[derive(Clone, Debug, Default)]
+pub struct Anonymizer {
+    enabled: bool,
+    salt: String,
+}
+
+impl Anonymizer {
+    pub fn from_config(config: &PrivacyConfig) -> Self {
+        Anonymizer {
+            enabled: config.anonymize_traders,
+            salt: config.anonymize_traders_salt.clone(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn pseudonym(&self, value: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.salt.as_bytes())
+            .chain_update(value.as_bytes())
+            .finalize();
+        format!("{:x}", digest)[..PSEUDONYM_LEN].to_string()
+    }
+}
@@ -0,0 +1,69 @@
+use serde::Serialize;
+
+use super::markets::{MarketInfo, USD_STABLECOIN_MINTS};
+
+/// Quote mint that rates are routed through when no market trades the pair directly
+pub const ROUTING_MINT: &str = USD_STABLECOIN_MINTS[0];
+
+/// A market used to convert between two mints. When `inverted` the conversion goes from the
+/// market's quote to its base, so the close is inverted.
+#[derive(Clone, Debug)]
+pub struct RateHop {
+    pub market: MarketInfo,
+    pub inverted: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RateLeg {
+    pub market_name: String,
+    pub inverted: bool,
+    pub close: f64,
+    /// Start time of the candle the close was taken from
+    pub candle_time: i64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RateResponse {
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub at: u64,
+    /// Amount of quote per unit of base
+    pub rate: f64,
+    pub route: Vec<RateLeg>,
+}
+
+fn find_hop(from_mint: &str, to_mint: &str, markets: &[MarketInfo]) -> Option<RateHop> {
+    markets.iter().find_map(|m| {
+        if m.base_mint_key == from_mint && m.quote_mint_key == to_mint {
+            Some(RateHop {
+                market: m.clone(),
+                inverted: false,
+            })
+        } else if m.base_mint_key == to_mint && m.quote_mint_key == from_mint {
+            Some(RateHop {
+                market: m.clone(),
+                inverted: true,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// Finds the markets to convert `base_mint` into `quote_mint`, either a direct market or two
+/// markets through USDC. An empty route means the mints are the same.
+pub fn find_rate_route(
+    base_mint: &str,
+    quote_mint: &str,
+    markets: &[MarketInfo],
+) -> Option<Vec<RateHop>> {
+    if base_mint == quote_mint {
+        return Some(vec![]);
+    }
+    if let Some(hop) = find_hop(base_mint, quote_mint, markets) {
+        return Some(vec![hop]);
+    }
+    let first = find_hop(base_mint, ROUTING_MINT, markets)?;
+    let second = find_hop(ROUTING_MINT, quote_mint, markets)?;
+    Some(vec![first, second])
+}
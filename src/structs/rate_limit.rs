@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::api_keys::{hash_api_key, ApiKey};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RateLimitConfig {
+    /// Requests per minute per client IP without an API key, rate limiting is off unless set
+    pub rate_limit_anonymous_per_minute: Option<u32>,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// Holds up to a minute's quota and refills continuously at the per minute rate.
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, per_minute: u32, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_minute as f64 / 60.0).min(per_minute as f64);
+        self.updated_at = now;
+    }
+}
+
+/// Quota state of a client, reported in the `X-RateLimit-*` headers.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the full quota is available again
+    pub reset_secs: u64,
+}
+
+#[derive(Debug)]
+pub enum RateLimitError {
+    UnknownApiKey,
+    Exceeded {
+        status: RateLimitStatus,
+        /// Seconds until the next request would be let through
+        retry_after_secs: u64,
+    },
+}
+
+/// Token bucket rate limiter keyed by API key, or by client IP for anonymous requests. Also
+/// counts the requests of every key until they are flushed to `openbook.api_key_usage`.
+pub struct RateLimiter {
+    /// Replaced when the configuration is reloaded
+    anonymous_per_minute: RwLock<Option<u32>>,
+    /// Active keys by key hash, reloaded from `openbook.api_keys` in the background
+    api_keys: RwLock<HashMap<String, ApiKey>>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    /// Requests per key id since the last flush
+    usage: Mutex<HashMap<i64, i64>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimiter {
+            anonymous_per_minute: RwLock::new(config.rate_limit_anonymous_per_minute),
+            api_keys: RwLock::new(HashMap::new()),
+            buckets: Mutex::new(HashMap::new()),
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Applies a reloaded anonymous limit, clients keep the tokens they have left.
+    pub fn set_anonymous_per_minute(&self, limit: Option<u32>) {
+        *self.anonymous_per_minute.write().unwrap() = limit;
+    }
+
+    pub fn set_api_keys(&self, keys: Vec<ApiKey>) {
+        let keys = keys
+            .into_iter()
+            .filter(|k| k.is_active())
+            .map(|k| (k.key_hash.clone(), k))
+            .collect();
+        *self.api_keys.write().unwrap() = keys;
+    }
+
+    /// Accepts a newly issued key right away instead of on the next reload.
+    pub fn add_api_key(&self, key: ApiKey) {
+        self.api_keys
+            .write()
+            .unwrap()
+            .insert(key.key_hash.clone(), key);
+    }
+
+    /// Stops accepting a key right away instead of on the next reload.
+    pub fn remove_api_key(&self, id: i64) {
+        self.api_keys.write().unwrap().retain(|_, k| k.id != id);
+    }
+
+    /// Request counts per key id since the last call.
+    pub fn take_usage(&self) -> HashMap<i64, i64> {
+        std::mem::take(&mut *self.usage.lock().unwrap())
+    }
+
+    /// Puts back counts that could not be flushed.
+    pub fn restore_usage(&self, usage: HashMap<i64, i64>) {
+        let mut current = self.usage.lock().unwrap();
+        for (id, requests) in usage {
+            *current.entry(id).or_default() += requests;
+        }
+    }
+
+    /// Whether the key is active.
+    pub fn is_known_key(&self, key: &str) -> bool {
+        self.api_keys
+            .read()
+            .unwrap()
+            .contains_key(&hash_api_key(key))
+    }
+
+    /// Whether the key is active and flagged to see embargoed markets without the delay.
+    pub fn is_realtime_key(&self, key: &str) -> bool {
+        self.api_keys
+            .read()
+            .unwrap()
+            .get(&hash_api_key(key))
+            .map_or(false, |k| k.realtime)
+    }
+
+    /// Takes a token from the client's bucket. Requests with an API key are always checked
+    /// against the key's quota; anonymous requests return `None` when no anonymous limit is set.
+    pub fn check(
+        &self,
+        api_key: Option<&str>,
+        client_ip: &str,
+    ) -> Result<Option<RateLimitStatus>, RateLimitError> {
+        let (bucket_key, limit, key_id) = match api_key {
+            Some(key) => {
+                let api_keys = self.api_keys.read().unwrap();
+                let api_key = api_keys
+                    .get(&hash_api_key(key))
+                    .ok_or(RateLimitError::UnknownApiKey)?;
+                (
+                    format!("key:{}", api_key.id),
+                    api_key.requests_per_minute.max(0) as u32,
+                    Some(api_key.id),
+                )
+            }
+            None => match *self.anonymous_per_minute.read().unwrap() {
+                Some(limit) => (format!("ip:{}", client_ip), limit, None),
+                None => return Ok(None),
+            },
+        };
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&bucket_key) {
+            // a bucket untouched for a minute is full again, so forgetting it changes nothing
+            buckets.retain(|_, b| now.duration_since(b.updated_at) < Duration::from_secs(60));
+        }
+        let bucket = buckets.entry(bucket_key).or_insert(TokenBucket {
+            tokens: limit as f64,
+            updated_at: now,
+        });
+        bucket.refill(limit, now);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+            if let Some(id) = key_id {
+                *self.usage.lock().unwrap().entry(id).or_default() += 1;
+            }
+        }
+
+        let status = RateLimitStatus {
+            limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: ((limit as f64 - bucket.tokens) * 60.0 / limit.max(1) as f64).ceil() as u64,
+        };
+        if allowed {
+            Ok(Some(status))
+        } else {
+            Err(RateLimitError::Exceeded {
+                status,
+                retry_after_secs: ((1.0 - bucket.tokens) * 60.0 / limit.max(1) as f64).ceil()
+                    as u64,
+            })
+        }
+    }
+}
@@ -1,8 +1,9 @@
-use chrono::Duration;
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, Utc};
+use serde::{Serialize, Serializer};
 use std::fmt;
 use strum::EnumIter;
 
-#[derive(EnumIter, Copy, Clone, Eq, PartialEq)]
+#[derive(EnumIter, Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Resolution {
     R1m,
     R3m,
@@ -13,6 +14,10 @@ pub enum Resolution {
     R2h,
     R4h,
     R1d,
+    /// Calendar weeks starting on Monday
+    R1w,
+    /// Calendar months
+    R1mo,
 }
 
 pub fn day() -> Duration {
@@ -31,10 +36,18 @@ impl fmt::Display for Resolution {
             Resolution::R2h => write!(f, "2H"),
             Resolution::R4h => write!(f, "4H"),
             Resolution::R1d => write!(f, "1D"),
+            Resolution::R1w => write!(f, "1W"),
+            Resolution::R1mo => write!(f, "1MO"),
         }
     }
 }
 
+impl Serialize for Resolution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl Resolution {
     pub fn get_constituent_resolution(self) -> Resolution {
         match self {
@@ -47,9 +60,13 @@ impl Resolution {
             Resolution::R2h => Resolution::R1h,
             Resolution::R4h => Resolution::R2h,
             Resolution::R1d => Resolution::R4h,
+            Resolution::R1w => Resolution::R1d,
+            Resolution::R1mo => Resolution::R1d,
         }
     }
 
+    /// Length of a bucket, for months the longest one. Use `bucket_start` and `bucket_end` to
+    /// place candles.
     pub fn get_duration(self) -> Duration {
         match self {
             Resolution::R1m => Duration::minutes(1),
@@ -61,9 +78,49 @@ impl Resolution {
             Resolution::R2h => Duration::hours(2),
             Resolution::R4h => Duration::hours(4),
             Resolution::R1d => day(),
+            Resolution::R1w => Duration::weeks(1),
+            Resolution::R1mo => Duration::days(31),
+        }
+    }
+
+    /// Weeks and months, whose buckets follow the calendar instead of dividing a day
+    pub fn is_calendar(self) -> bool {
+        matches!(self, Resolution::R1w | Resolution::R1mo)
+    }
+
+    /// Start of the bucket `time` falls in.
+    pub fn bucket_start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let day_start = time.duration_trunc(day()).unwrap();
+        match self {
+            Resolution::R1w => {
+                day_start - Duration::days(time.weekday().num_days_from_monday() as i64)
+            }
+            Resolution::R1mo => day_start - Duration::days(time.day0() as i64),
+            _ => time.duration_trunc(self.get_duration()).unwrap(),
         }
     }
 
+    /// End of the bucket starting at `start`, which has to be the start of a bucket.
+    pub fn bucket_end(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Resolution::R1mo => {
+                let (year, month) = match start.month() {
+                    12 => (start.year() + 1, 1),
+                    month => (start.year(), month + 1),
+                };
+                DateTime::from_utc(
+                    NaiveDate::from_ymd_opt(year, month, 1)
+                        .unwrap()
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap(),
+                    Utc,
+                )
+            }
+            _ => start + self.get_duration(),
+        }
+    }
+
+    /// Parses the names `Display` writes, and `D` for days as TradingView sends them.
     pub fn from_str(v: &str) -> Result<Self, ()> {
         match v {
             "1M" => Ok(Resolution::R1m),
@@ -74,8 +131,26 @@ impl Resolution {
             "1H" => Ok(Resolution::R1h),
             "2H" => Ok(Resolution::R2h),
             "4H" => Ok(Resolution::R4h),
-            "D" => Ok(Resolution::R1d),
+            "1D" | "D" => Ok(Resolution::R1d),
+            "1W" => Ok(Resolution::R1w),
+            "1MO" => Ok(Resolution::R1mo),
             _ => Err(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn names_round_trip() {
+        for resolution in Resolution::iter() {
+            assert_eq!(
+                Resolution::from_str(&resolution.to_string()),
+                Ok(resolution)
+            );
+        }
+    }
+}
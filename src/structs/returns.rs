@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::candle::Candle;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnKind {
+    /// close / previous close - 1
+    #[default]
+    Simple,
+    /// ln(close / previous close)
+    Log,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CandleReturn {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    pub close: f64,
+    /// `None` when there is no usable previous close: the first candle, a candle after a gap or
+    /// a previous close that isn't a positive number
+    #[serde(rename = "return")]
+    pub value: Option<f64>,
+}
+
+/// Returns of each candle against the close of the candle before it. Candles must be in
+/// ascending order and of one resolution; a return is only computed between adjacent candles, so
+/// missing buckets never turn into one oversized return.
+pub fn compute_returns(candles: &[Candle], kind: ReturnKind) -> Vec<CandleReturn> {
+    let mut previous: Option<&Candle> = None;
+    candles
+        .iter()
+        .map(|candle| {
+            let value = previous
+                .filter(|p| p.end_time == candle.start_time)
+                .map(|p| p.close)
+                .filter(|close| close.is_finite() && *close > 0.0)
+                .map(|previous_close| match kind {
+                    ReturnKind::Simple => candle.close / previous_close - 1.0,
+                    ReturnKind::Log => (candle.close / previous_close).ln(),
+                })
+                .filter(|r| r.is_finite());
+            previous = Some(candle);
+            CandleReturn {
+                start_time: candle.start_time,
+                close: candle.close,
+                value,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+/// OHLCV of a candle as stored before or after a revision
+#[derive(Clone, Debug, Serialize)]
+pub struct RevisedValues {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trade_count: i64,
+}
+
+/// A change to a candle that had already been complete
+#[derive(Clone, Debug, Serialize)]
+pub struct CandleRevision {
+    pub id: i64,
+    pub market_name: String,
+    pub resolution: String,
+    pub start_time: i64,
+    pub old: RevisedValues,
+    pub new: RevisedValues,
+    /// `batch`, `backfill`, `rebuild`, `composite` or `manual`
+    pub reason: String,
+    pub revised_at: i64,
+}
+
+impl CandleRevision {
+    pub fn from_row(row: Row) -> Self {
+        let start_time: DateTime<Utc> = row.get(3);
+        let revised_at: DateTime<Utc> = row.get(17);
+        CandleRevision {
+            id: row.get(0),
+            market_name: row.get(1),
+            resolution: row.get(2),
+            start_time: start_time.timestamp(),
+            old: RevisedValues {
+                open: row.get(4),
+                high: row.get(5),
+                low: row.get(6),
+                close: row.get(7),
+                volume: row.get(8),
+                trade_count: row.get(9),
+            },
+            new: RevisedValues {
+                open: row.get(10),
+                high: row.get(11),
+                low: row.get(12),
+                close: row.get(13),
+                volume: row.get(14),
+                trade_count: row.get(15),
+            },
+            reason: row.get(16),
+            revised_at: revised_at.timestamp(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RevisionsResponse {
+    pub revisions: Vec<CandleRevision>,
+    /// Pass as `since` to get the next page, unchanged when there was nothing new
+    pub next_cursor: i64,
+    pub has_more: bool,
+}
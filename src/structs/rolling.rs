@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+/// One candle over a trailing window, built from the minute candles it covers. The newest minute
+/// may still be in progress.
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct RollingCandle {
+    pub market_name: String,
+    /// Unix seconds, start of the oldest minute in the window
+    #[serde(with = "chrono::serde::ts_seconds")]
+    #[schema(value_type = i64)]
+    pub start_time: DateTime<Utc>,
+    /// Unix seconds, end of the newest minute in the window
+    #[serde(with = "chrono::serde::ts_seconds")]
+    #[schema(value_type = i64)]
+    pub end_time: DateTime<Utc>,
+    /// Price at the start of the window
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub trade_count: i64,
+    /// Change from `open` to `close` in percent, `None` when `open` isn't positive
+    pub change_percent: Option<f64>,
+}
+
+impl RollingCandle {
+    pub fn from_row(market_name: String, row: Row) -> Self {
+        let open: f64 = row.get(0);
+        let close: f64 = row.get(3);
+        RollingCandle {
+            market_name,
+            open,
+            high: row.get(1),
+            low: row.get(2),
+            close,
+            volume: row.get(4),
+            quote_volume: row.get(5),
+            trade_count: row.get(6),
+            start_time: row.get(7),
+            end_time: row.get(8),
+            change_percent: (open > 0.0).then(|| (close / open - 1.0) * 100.0),
+        }
+    }
+}
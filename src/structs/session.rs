@@ -0,0 +1,87 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use serde_derive::Deserialize;
+
+use super::candle::Candle;
+
+fn default_session_start_offset_mins() -> i64 {
+    0
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionConfig {
+    /// Minutes after UTC midnight at which the daily session starts
+    #[serde(default = "default_session_start_offset_mins")]
+    pub session_start_offset_mins: i64,
+}
+
+impl SessionConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    /// Start of the session `at` falls into.
+    pub fn session_start(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let offset = Duration::minutes(self.session_start_offset_mins);
+        let shifted = at - offset;
+        shifted.duration_trunc(Duration::days(1)).unwrap_or(shifted) + offset
+    }
+}
+
+/// Open, high, low and volume of a market since the start of the current session.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionStats {
+    pub market_name: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub session_start: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    /// Close of the newest candle, complete or not
+    pub last: f64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub trade_count: i64,
+}
+
+impl SessionStats {
+    /// Stats of a session whose first minute candle is `first`.
+    pub fn new(session_start: DateTime<Utc>, first: &Candle) -> Self {
+        SessionStats {
+            market_name: first.market_name.clone(),
+            session_start,
+            open: first.open,
+            high: first.high,
+            low: first.low,
+            last: first.close,
+            volume: first.volume,
+            quote_volume: first.quote_volume,
+            trade_count: first.trade_count,
+        }
+    }
+
+    /// Folds the next candle of the session into the stats.
+    pub fn add(&mut self, candle: &Candle) {
+        self.high = self.high.max(candle.high);
+        self.low = self.low.min(candle.low);
+        self.last = candle.close;
+        self.volume += candle.volume;
+        self.quote_volume += candle.quote_volume;
+        self.trade_count += candle.trade_count;
+    }
+
+    /// Folds `candles` into `stats`, starting new stats from the first candle when there are none.
+    pub fn fold(
+        stats: Option<SessionStats>,
+        session_start: DateTime<Utc>,
+        candles: &[Candle],
+    ) -> Option<SessionStats> {
+        candles.iter().fold(stats, |stats, candle| match stats {
+            Some(mut stats) => {
+                stats.add(candle);
+                Some(stats)
+            }
+            None => Some(SessionStats::new(session_start, candle)),
+        })
+    }
+}
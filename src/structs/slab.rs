@@ -3,7 +3,6 @@ use arrayref::array_refs;
 use bytemuck::{cast_mut, cast_ref, cast_slice, Pod, Zeroable};
 use futures::join;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use num_traits::ToPrimitive;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use std::{
     convert::TryFrom,
@@ -12,9 +11,10 @@ use std::{
     str::FromStr,
 };
 
-use crate::structs::openbook::token_factor;
-
-use super::markets::MarketInfo;
+use super::{
+    markets::MarketInfo,
+    orderbook::{OrderBookSnapshot, RestingOrder},
+};
 
 pub type NodeHandle = u32;
 
@@ -101,19 +101,12 @@ impl LeafNode {
         NonZeroU64::new((self.key >> 64) as u64).unwrap()
     }
 
-    pub fn readable_price(&self, market: &MarketInfo) -> f64 {
-        let price_lots = (self.key >> 64) as f64;
-        let base_multiplier = token_factor(market.base_decimals);
-        let quote_multiplier = token_factor(market.quote_decimals);
-        let base_lot_size = market.base_lot_size as f64;
-        let quote_lot_size = market.quote_lot_size as f64;
-        (price_lots * quote_lot_size * base_multiplier) / (base_lot_size * quote_multiplier)
+    pub fn readable_price(&self, market: &MarketInfo) -> anyhow::Result<f64> {
+        market.price_lots_to_ui(self.key >> 64)
     }
 
-    pub fn readable_quantity(&self, market: &MarketInfo) -> f64 {
-        let base_lot_size = market.base_lot_size as f64;
-        let base_multiplier = token_factor(market.base_decimals);
-        self.quantity as f64 * base_lot_size / base_multiplier
+    pub fn readable_quantity(&self, market: &MarketInfo) -> anyhow::Result<f64> {
+        market.base_lots_to_ui(self.quantity as u128)
     }
 
     #[inline]
@@ -389,7 +382,7 @@ impl Slab {
 
     #[inline]
     pub fn find_min(&self) -> Option<&LeafNode> {
-        let handle = self.find_min_max(false).unwrap();
+        let handle = self.find_min_max(false)?;
         match self.get(handle) {
             Some(node) => Some(node.as_leaf().unwrap()),
             None => None,
@@ -398,27 +391,29 @@ impl Slab {
 
     #[inline]
     pub fn find_max(&self) -> Option<&LeafNode> {
-        let handle = self.find_min_max(true).unwrap();
+        let handle = self.find_min_max(true)?;
         match self.get(handle) {
             Some(node) => Some(node.as_leaf().unwrap()),
             None => None,
         }
     }
 
-    pub fn get_best(&self, market: &MarketInfo, bid: bool) -> f64 {
-        let min = if bid {
+    /// Best price on this side of the book, None if the book is empty
+    pub fn get_best(&self, market: &MarketInfo, bid: bool) -> anyhow::Result<Option<f64>> {
+        let best = if bid {
             self.find_max()
         } else {
             self.find_min()
         };
-        min.unwrap().readable_price(market)
+        best.map(|leaf| leaf.readable_price(market)).transpose()
     }
 }
 
+/// Returns the best bid and ask for each market, in the same order as `markets`.
 pub async fn get_best_bids_and_asks(
-    client: RpcClient,
+    client: &RpcClient,
     markets: &Vec<MarketInfo>,
-) -> (Vec<f64>, Vec<f64>) {
+) -> anyhow::Result<(Vec<Option<f64>>, Vec<Option<f64>>)> {
     let bid_keys = markets
         .iter()
         .map(|m| Pubkey::from_str(&m.bids_key).unwrap())
@@ -434,42 +429,140 @@ pub async fn get_best_bids_and_asks(
         client.get_multiple_accounts(&ask_keys)
     );
 
-    let bids = bid_results.unwrap();
-    let asks = ask_results.unwrap();
+    let bids = bid_results?;
+    let asks = ask_results?;
 
     let best_bids = bids
         .into_iter()
         .enumerate()
-        .filter_map(|(index, x)| {
-            if let Some(mut account) = x {
-                let slab = Slab::new(&mut account.data);
-                Some(slab.get_best(&markets[index], true))
-            } else {
-                None
-            }
+        .map(|(index, x)| match x {
+            Some(mut account) => Slab::new(&mut account.data).get_best(&markets[index], true),
+            None => Ok(None),
         })
-        .collect::<Vec<_>>();
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
     let best_asks = asks
         .into_iter()
         .enumerate()
-        .filter_map(|(index, x)| {
-            if let Some(mut account) = x {
-                let slab = Slab::new(&mut account.data);
-                Some(slab.get_best(&markets[index], false))
-            } else {
-                None
-            }
+        .map(|(index, x)| match x {
+            Some(mut account) => Slab::new(&mut account.data).get_best(&markets[index], false),
+            None => Ok(None),
         })
-        .collect::<Vec<_>>();
-    (best_bids, best_asks)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((best_bids, best_asks))
+}
+
+/// Takes a top of book and ±2% depth snapshot of every market, in the same order as `markets`.
+/// Markets whose books could not be loaded are None.
+pub async fn get_orderbook_snapshots(
+    client: &RpcClient,
+    markets: &Vec<MarketInfo>,
+) -> anyhow::Result<Vec<Option<OrderBookSnapshot>>> {
+    let mut snapshots = Vec::with_capacity(markets.len());
+    // getMultipleAccounts accepts at most 100 keys
+    for market_chunk in markets.chunks(50) {
+        let keys = market_chunk
+            .iter()
+            .flat_map(|m| {
+                [
+                    Pubkey::from_str(&m.bids_key).unwrap(),
+                    Pubkey::from_str(&m.asks_key).unwrap(),
+                ]
+            })
+            .collect::<Vec<Pubkey>>();
+        let accounts = client.get_multiple_accounts(&keys).await?;
+        let timestamp = chrono::Utc::now();
+
+        for (index, market) in market_chunk.iter().enumerate() {
+            let (bid_acc, ask_acc) = (&accounts[2 * index], &accounts[2 * index + 1]);
+            let snapshot = match (bid_acc.clone(), ask_acc.clone()) {
+                (Some(mut bid_acc), Some(mut ask_acc)) => {
+                    let bids = Slab::new(&mut bid_acc.data);
+                    let asks = Slab::new(&mut ask_acc.data);
+                    let bid_levels = readable_levels(bids.traverse(true), market)?;
+                    let ask_levels = readable_levels(asks.traverse(false), market)?;
+                    Some(OrderBookSnapshot::from_levels(
+                        &bid_levels,
+                        &ask_levels,
+                        timestamp,
+                    ))
+                }
+                _ => None,
+            };
+            snapshots.push(snapshot);
+        }
+    }
+    Ok(snapshots)
+}
+
+/// Bids and asks of every market with their owners, ordered from the best price outwards. `None`
+/// for markets whose book accounts couldn't be read.
+pub async fn get_resting_orders(
+    client: &RpcClient,
+    markets: &Vec<MarketInfo>,
+) -> anyhow::Result<Vec<Option<(Vec<RestingOrder>, Vec<RestingOrder>)>>> {
+    let mut books = Vec::with_capacity(markets.len());
+    // getMultipleAccounts accepts at most 100 keys
+    for market_chunk in markets.chunks(50) {
+        let keys = market_chunk
+            .iter()
+            .flat_map(|m| {
+                [
+                    Pubkey::from_str(&m.bids_key).unwrap(),
+                    Pubkey::from_str(&m.asks_key).unwrap(),
+                ]
+            })
+            .collect::<Vec<Pubkey>>();
+        let accounts = client.get_multiple_accounts(&keys).await?;
+
+        for (index, market) in market_chunk.iter().enumerate() {
+            let (bid_acc, ask_acc) = (&accounts[2 * index], &accounts[2 * index + 1]);
+            let book = match (bid_acc.clone(), ask_acc.clone()) {
+                (Some(mut bid_acc), Some(mut ask_acc)) => {
+                    let bids = Slab::new(&mut bid_acc.data);
+                    let asks = Slab::new(&mut ask_acc.data);
+                    Some((
+                        resting_orders(bids.traverse(true), market)?,
+                        resting_orders(asks.traverse(false), market)?,
+                    ))
+                }
+                _ => None,
+            };
+            books.push(book);
+        }
+    }
+    Ok(books)
+}
+
+fn resting_orders(
+    leaves: Vec<&LeafNode>,
+    market: &MarketInfo,
+) -> anyhow::Result<Vec<RestingOrder>> {
+    leaves
+        .into_iter()
+        .map(|x| {
+            Ok(RestingOrder {
+                price: x.readable_price(market)?,
+                quantity: x.readable_quantity(market)?,
+                owner: Pubkey::new_from_array(*cast_ref::<[u64; 4], [u8; 32]>(&x.owner()))
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+fn readable_levels(leaves: Vec<&LeafNode>, market: &MarketInfo) -> anyhow::Result<Vec<(f64, f64)>> {
+    leaves
+        .into_iter()
+        .map(|x| Ok((x.readable_price(market)?, x.readable_quantity(market)?)))
+        .collect()
 }
 
 pub async fn get_orderbooks_with_depth(
     client: RpcClient,
     market: &MarketInfo,
     depth: usize,
-) -> (Vec<(String, String)>, Vec<(String, String)>) {
+) -> anyhow::Result<(Vec<(String, String)>, Vec<(String, String)>)> {
     let keys = vec![
         Pubkey::from_str(&market.bids_key).unwrap(),
         Pubkey::from_str(&market.asks_key).unwrap(),
@@ -487,34 +580,30 @@ pub async fn get_orderbooks_with_depth(
 
     let bid_leaves = bids.traverse(true);
     let ask_leaves = asks.traverse(false);
-    let bid_levels = construct_levels(bid_leaves, market, depth);
-    let ask_levels = construct_levels(ask_leaves, market, depth);
+    let bid_levels = construct_levels(bid_leaves, market, depth)?;
+    let ask_levels = construct_levels(ask_leaves, market, depth)?;
 
-    (bid_levels, ask_levels)
+    Ok((bid_levels, ask_levels))
 }
 
 fn construct_levels(
     leaves: Vec<&LeafNode>,
     market: &MarketInfo,
     depth: usize,
-) -> Vec<(String, String)> {
+) -> anyhow::Result<Vec<(String, String)>> {
     let mut levels: Vec<(f64, f64)> = vec![];
     for x in leaves {
         let len = levels.len();
-        if len > 0 && levels[len - 1].0 == x.readable_price(market).to_f64().unwrap() {
-            let q = x.readable_quantity(market).to_f64().unwrap();
-            levels[len - 1].1 += q;
+        if len > 0 && levels[len - 1].0 == x.readable_price(market)? {
+            levels[len - 1].1 += x.readable_quantity(market)?;
         } else if len == depth {
             break;
         } else {
-            levels.push((
-                x.readable_price(market).to_f64().unwrap(),
-                x.readable_quantity(market).to_f64().unwrap(),
-            ));
+            levels.push((x.readable_price(market)?, x.readable_quantity(market)?));
         }
     }
-    levels
+    Ok(levels
         .into_iter()
         .map(|x| (x.0.to_string(), x.1.to_string()))
-        .collect()
+        .collect())
 }
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_derive::Deserialize;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+use super::orderbook::OrderBookSnapshot;
+
+fn default_spread_sample_secs() -> u64 {
+    60
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SpreadConfig {
+    /// Seconds between two spread samples of a market
+    #[serde(default = "default_spread_sample_secs")]
+    pub spread_sample_secs: u64,
+}
+
+impl SpreadConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+/// Top of book of a market at one point in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpreadSample {
+    pub market_name: String,
+    pub time: DateTime<Utc>,
+    pub best_bid: f64,
+    pub best_ask: f64,
+    pub mid_price: f64,
+}
+
+impl SpreadSample {
+    /// `None` for a book without both sides, which has no spread.
+    pub fn from_order_book(market_name: &str, snapshot: &OrderBookSnapshot) -> Option<Self> {
+        Some(SpreadSample {
+            market_name: market_name.to_string(),
+            time: snapshot.timestamp,
+            best_bid: snapshot.best_bid?,
+            best_ask: snapshot.best_ask?,
+            mid_price: snapshot.mid_price()?,
+        })
+    }
+}
+
+/// Spread samples of one bucket, spreads in basis points of the mid price
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct SpreadBucket {
+    /// Unix seconds
+    #[serde(with = "chrono::serde::ts_seconds")]
+    #[schema(value_type = i64)]
+    pub start_time: DateTime<Utc>,
+    pub samples: i64,
+    pub avg_spread_bps: f64,
+    pub median_spread_bps: f64,
+    pub max_spread_bps: f64,
+    /// Average mid price of the samples
+    pub avg_mid_price: f64,
+}
+
+impl SpreadBucket {
+    pub fn from_row(row: Row) -> Self {
+        SpreadBucket {
+            start_time: row.get(0),
+            samples: row.get(1),
+            avg_spread_bps: row.get(2),
+            median_spread_bps: row.get(3),
+            max_spread_bps: row.get(4),
+            avg_mid_price: row.get(5),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct SpreadHistoryResponse {
+    pub market_name: String,
+    pub resolution: String,
+    pub buckets: Vec<SpreadBucket>,
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+use super::openbook::fill_fees;
+
+/// Direction of a trade from the taker's point of view.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+impl fmt::Display for TradeSide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TradeSide::Buy => write!(f, "buy"),
+            TradeSide::Sell => write!(f, "sell"),
+        }
+    }
+}
+
+impl TradeSide {
+    pub fn from_str(v: &str) -> Result<Self, ()> {
+        match v {
+            "buy" => Ok(TradeSide::Buy),
+            "sell" => Ok(TradeSide::Sell),
+            _ => Err(()),
+        }
+    }
+
+    /// Trades are read from the maker fill, whose `bid` is the opposite of the taker's side
+    pub fn maker_bid(self) -> bool {
+        self == TradeSide::Sell
+    }
+
+    /// `bid` of the maker or taker fill of a trade on this side
+    pub fn fill_bid(self, maker: bool) -> bool {
+        if maker {
+            self.maker_bid()
+        } else {
+            !self.maker_bid()
+        }
+    }
+
+    fn from_fill_bid(bid: bool, maker: bool) -> Self {
+        if bid == maker {
+            TradeSide::Sell
+        } else {
+            TradeSide::Buy
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TradeGrouping {
+    Minute,
+}
+
+impl TradeGrouping {
+    pub fn from_str(v: &str) -> Result<Self, ()> {
+        match v {
+            "minute" => Ok(TradeGrouping::Minute),
+            _ => Err(()),
+        }
+    }
+
+    /// Field name for postgres `date_trunc`
+    pub fn date_trunc_field(self) -> &'static str {
+        match self {
+            TradeGrouping::Minute => "minute",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TradeFilter {
+    /// Only trades the taker entered on this side
+    pub side: Option<TradeSide>,
+    /// Only trades of at least this base size
+    pub min_size: f64,
+    /// Read the taker fill of each trade instead of the maker fill
+    pub taker: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct Trade {
+    /// Unix seconds
+    pub time: i64,
+    pub price: f64,
+    pub size: f64,
+    pub side: TradeSide,
+    pub signature: String,
+    /// Quote tokens paid by the fill's owner, negative for a maker rebate
+    pub fee: f64,
+    /// Quote tokens of the taker fee paid out to the referrer
+    pub referrer_rebate: f64,
+}
+
+impl Trade {
+    pub fn from_row(row: Row, quote_decimals: u8) -> anyhow::Result<Self> {
+        let maker: bool = row.get(5);
+        let stored_fees = (
+            row.get::<usize, Option<f64>>(7),
+            row.get::<usize, Option<f64>>(8),
+        );
+        let (fee, referrer_rebate) = match stored_fees {
+            (Some(fee), Some(referrer_rebate)) => (fee, referrer_rebate),
+            // fills stored before fees were recorded only carry the native amount, a whole
+            // number stored as a double
+            (fee, referrer_rebate) => {
+                let native_fee_or_rebate = row.get::<usize, f64>(6) as u64;
+                let (native_fee, native_rebate) =
+                    fill_fees(native_fee_or_rebate, maker, quote_decimals)?;
+                (
+                    fee.unwrap_or(native_fee),
+                    referrer_rebate.unwrap_or(native_rebate),
+                )
+            }
+        };
+        Ok(Trade {
+            time: row.get::<usize, DateTime<Utc>>(0).timestamp(),
+            price: row.get(1),
+            size: row.get(2),
+            side: TradeSide::from_fill_bid(row.get(3), maker),
+            signature: row.get(4),
+            fee,
+            referrer_rebate,
+        })
+    }
+}
+
+/// Trades aggregated over one interval of a `TradeGrouping`.
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct TradeBucket {
+    /// Unix seconds
+    pub start_time: i64,
+    pub trade_count: i64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub vwap: f64,
+    pub high: f64,
+    pub low: f64,
+}
+
+impl TradeBucket {
+    pub fn from_row(row: Row) -> Self {
+        TradeBucket {
+            start_time: row.get::<usize, DateTime<Utc>>(0).timestamp(),
+            trade_count: row.get(1),
+            volume: row.get(2),
+            quote_volume: row.get(3),
+            buy_volume: row.get(4),
+            sell_volume: row.get(5),
+            vwap: row.get(6),
+            high: row.get(7),
+            low: row.get(8),
+        }
+    }
+}
+
+/// Either the trades themselves or, with `group_by`, their buckets
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum TradesResponse {
+    Trades(Vec<Trade>),
+    Buckets(Vec<TradeBucket>),
+}
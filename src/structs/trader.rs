@@ -1,16 +1,19 @@
 use std::fmt;
 
-use num_traits::ToPrimitive;
+use chrono::{DateTime, Datelike, Duration, DurationRound, Utc};
 use serde::Serialize;
 use tokio_postgres::Row;
+use utoipa::ToSchema;
 
-use super::openbook::token_factor;
+use super::{openbook::native_to_ui, resolution::day};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct PgTrader {
     pub open_orders_owner: String,
     pub raw_ask_size: i64,
     pub raw_bid_size: i64,
+    pub raw_maker_size: i64,
+    pub raw_taker_size: i64,
 }
 impl PgTrader {
     pub fn from_row(row: Row) -> Self {
@@ -18,6 +21,44 @@ impl PgTrader {
             open_orders_owner: row.get(0),
             raw_ask_size: row.get(1),
             raw_bid_size: row.get(2),
+            raw_maker_size: row.get(3),
+            raw_taker_size: row.get(4),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TraderRole {
+    Maker,
+    Taker,
+    All,
+}
+impl fmt::Display for TraderRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraderRole::Maker => write!(f, "maker"),
+            TraderRole::Taker => write!(f, "taker"),
+            TraderRole::All => write!(f, "all"),
+        }
+    }
+}
+
+impl TraderRole {
+    pub fn from_str(v: &str) -> Result<Self, ()> {
+        match v {
+            "maker" => Ok(TraderRole::Maker),
+            "taker" => Ok(TraderRole::Taker),
+            "all" => Ok(TraderRole::All),
+            _ => Err(()),
+        }
+    }
+
+    /// Value of the fills `maker` column to filter on, None keeps both sides
+    pub fn maker_filter(self) -> Option<bool> {
+        match self {
+            TraderRole::Maker => Some(true),
+            TraderRole::Taker => Some(false),
+            TraderRole::All => None,
         }
     }
 }
@@ -36,27 +77,114 @@ impl fmt::Display for VolumeType {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+impl VolumeType {
+    pub fn from_str(v: &str) -> Result<Self, ()> {
+        match v {
+            "base" | "Base" => Ok(VolumeType::Base),
+            "quote" | "Quote" => Ok(VolumeType::Quote),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LeaderboardPeriod {
+    Day,
+    Week,
+}
+
+impl fmt::Display for LeaderboardPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeaderboardPeriod::Day => write!(f, "1D"),
+            LeaderboardPeriod::Week => write!(f, "1W"),
+        }
+    }
+}
+
+impl LeaderboardPeriod {
+    pub fn from_str(v: &str) -> Result<Self, ()> {
+        match v {
+            "1D" | "D" | "day" => Ok(LeaderboardPeriod::Day),
+            "1W" | "W" | "week" => Ok(LeaderboardPeriod::Week),
+            _ => Err(()),
+        }
+    }
+
+    pub fn get_duration(self) -> Duration {
+        match self {
+            LeaderboardPeriod::Day => day(),
+            LeaderboardPeriod::Week => Duration::weeks(1),
+        }
+    }
+
+    /// Start of the UTC day, or of the ISO week (Monday), containing `time`.
+    pub fn period_start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let day_start = time.duration_trunc(day()).unwrap();
+        match self {
+            LeaderboardPeriod::Day => day_start,
+            LeaderboardPeriod::Week => {
+                day_start - Duration::days(time.weekday().num_days_from_monday() as i64)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
 pub struct Trader {
     pub pubkey: String,
     pub volume: f64,
+    pub maker_volume: f64,
+    pub taker_volume: f64,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
 pub struct TraderResponse {
     pub start_time: u64,
     pub end_time: u64,
     pub volume_type: String,
+    pub role: String,
     pub traders: Vec<Trader>,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct LeaderboardResponse {
+    pub market_name: String,
+    pub period: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub volume_type: String,
+    pub role: String,
+    pub traders: Vec<Trader>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgLeaderboardEntry {
+    pub open_orders_owner: String,
+    pub volume: f64,
+    pub maker_volume: f64,
+    pub taker_volume: f64,
+}
+impl PgLeaderboardEntry {
+    pub fn from_row(row: Row) -> Self {
+        PgLeaderboardEntry {
+            open_orders_owner: row.get(0),
+            volume: row.get(1),
+            maker_volume: row.get(2),
+            taker_volume: row.get(3),
+        }
+    }
+}
+
 // Note that the Postgres queries only return volumes in base or quote
-pub fn calculate_trader_volume(trader: PgTrader, decimals: u8) -> Trader {
-    let bid_size = (trader.raw_bid_size as f64) / token_factor(decimals);
-    let ask_size = (trader.raw_ask_size as f64) / token_factor(decimals);
+pub fn calculate_trader_volume(trader: PgTrader, decimals: u8) -> anyhow::Result<Trader> {
+    // add in native units so the only rounding happens in the conversion
+    let volume = trader.raw_bid_size.max(0) as u128 + trader.raw_ask_size.max(0) as u128;
 
-    Trader {
+    Ok(Trader {
         pubkey: trader.open_orders_owner,
-        volume: (bid_size + ask_size).to_f64().unwrap(),
-    }
+        volume: native_to_ui(volume, decimals)?,
+        maker_volume: native_to_ui(trader.raw_maker_size.max(0) as u128, decimals)?,
+        taker_volume: native_to_ui(trader.raw_taker_size.max(0) as u128, decimals)?,
+    })
 }
@@ -1,15 +1,17 @@
 use chrono::Utc;
 use num_traits::ToPrimitive;
 use serde::Serialize;
+use utoipa::ToSchema;
 
 use super::candle::Candle;
 
-#[derive(Serialize)]
+/// Candles in TradingView's UDF format, one array per field
+#[derive(Serialize, ToSchema)]
 pub struct TvResponse {
     /// ok, error, no_data
-    #[serde(rename(serialize = "s"))]
+    #[serde(rename = "s")]
     pub status: String,
-    #[serde(rename(serialize = "errmsg"), skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "errmsg", skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
     pub time: Vec<u64>,
     pub close: Vec<f64>,
@@ -17,11 +19,16 @@ pub struct TvResponse {
     pub high: Vec<f64>,
     pub low: Vec<f64>,
     pub volume: Vec<u64>,
+    #[serde(rename = "quoteVolume")]
+    pub quote_volume: Vec<f64>,
+    pub vwap: Vec<f64>,
+    #[serde(rename = "trades")]
+    pub trade_count: Vec<i64>,
+    /// Quote volume in USD, `null` where the quote token has no USD reference
+    #[serde(rename = "volumeUsd", skip_serializing_if = "Option::is_none")]
+    pub volume_usd: Option<Vec<Option<f64>>>,
     /// Only Some if s == no_data
-    #[serde(
-        rename(serialize = "nextTime"),
-        skip_serializing_if = "Option::is_none"
-    )]
+    #[serde(rename = "nextTime", skip_serializing_if = "Option::is_none")]
     pub next_time: Option<u64>,
 }
 
@@ -33,6 +40,9 @@ impl TvResponse {
         let mut low: Vec<f64> = Vec::new();
         let mut high: Vec<f64> = Vec::new();
         let mut volume: Vec<u64> = Vec::new();
+        let mut quote_volume: Vec<f64> = Vec::new();
+        let mut vwap: Vec<f64> = Vec::new();
+        let mut trade_count: Vec<i64> = Vec::new();
 
         for c in candles.into_iter() {
             time.push(chrono::DateTime::<Utc>::timestamp(&c.start_time) as u64);
@@ -41,6 +51,9 @@ impl TvResponse {
             high.push(c.high.to_f64().unwrap());
             low.push(c.low.to_f64().unwrap());
             volume.push(c.volume.to_u64().unwrap());
+            quote_volume.push(c.quote_volume);
+            vwap.push(c.vwap);
+            trade_count.push(c.trade_count);
         }
 
         // Debug checks
@@ -49,6 +62,9 @@ impl TvResponse {
         assert_eq!(open.len(), low.len());
         assert_eq!(low.len(), high.len());
         assert_eq!(volume.len(), time.len());
+        assert_eq!(quote_volume.len(), time.len());
+        assert_eq!(vwap.len(), time.len());
+        assert_eq!(trade_count.len(), time.len());
 
         TvResponse {
             status: "ok".to_owned(),
@@ -59,7 +75,16 @@ impl TvResponse {
             low,
             high,
             volume,
+            quote_volume,
+            vwap,
+            trade_count,
+            volume_usd: None,
             next_time: None,
         }
     }
+
+    pub fn with_volume_usd(mut self, volume_usd: Vec<Option<f64>>) -> Self {
+        self.volume_usd = Some(volume_usd);
+        self
+    }
 }
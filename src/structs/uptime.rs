@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_derive::Deserialize;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+use super::orderbook::RestingOrder;
+
+fn default_uptime_sample_secs() -> u64 {
+    60
+}
+
+fn default_uptime_max_spread_bps() -> f64 {
+    100.0
+}
+
+fn default_uptime_min_quote_size() -> f64 {
+    1000.0
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UptimeConfig {
+    /// Seconds between two looks at the order books
+    #[serde(default = "default_uptime_sample_secs")]
+    pub uptime_sample_secs: u64,
+    /// How far from the mid price, in basis points, resting orders still count
+    #[serde(default = "default_uptime_max_spread_bps")]
+    pub uptime_max_spread_bps: f64,
+    /// Quote value an owner needs resting on each side within the spread to count as quoting
+    #[serde(default = "default_uptime_min_quote_size")]
+    pub uptime_min_quote_size: f64,
+}
+
+impl UptimeConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+/// Open orders accounts quoting both sides of a book: bids and asks within `max_spread_bps` of the
+/// mid price, each worth at least `min_quote_size` in quote tokens. Empty for a book without both
+/// sides.
+pub fn quoting_owners(
+    bids: &[RestingOrder],
+    asks: &[RestingOrder],
+    max_spread_bps: f64,
+    min_quote_size: f64,
+) -> Vec<String> {
+    let (best_bid, best_ask) = match (bids.first(), asks.first()) {
+        (Some(bid), Some(ask)) => (bid.price, ask.price),
+        _ => return vec![],
+    };
+    let mid = (best_bid + best_ask) / 2.0;
+    let range = max_spread_bps / 10_000.0;
+    let bid_sizes = sizes_by_owner(bids, |price| price >= mid * (1.0 - range));
+    let ask_sizes = sizes_by_owner(asks, |price| price <= mid * (1.0 + range));
+
+    let mut owners: Vec<String> = bid_sizes
+        .into_iter()
+        .filter(|(owner, size)| {
+            *size >= min_quote_size
+                && ask_sizes
+                    .get(owner)
+                    .map_or(false, |ask_size| *ask_size >= min_quote_size)
+        })
+        .map(|(owner, _)| owner.to_string())
+        .collect();
+    owners.sort();
+    owners
+}
+
+/// Quote value per owner of the orders from the best price outwards while `within` holds.
+fn sizes_by_owner(orders: &[RestingOrder], within: impl Fn(f64) -> bool) -> HashMap<&str, f64> {
+    let mut sizes: HashMap<&str, f64> = HashMap::new();
+    for order in orders.iter().take_while(|o| within(o.price)) {
+        *sizes.entry(order.owner.as_str()).or_default() += order.price * order.quantity;
+    }
+    sizes
+}
+
+/// Share of the samples of a range in which an open orders account quoted the market
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct MakerUptime {
+    pub open_orders_owner: String,
+    pub quoted_samples: i64,
+    pub samples: i64,
+    /// `quoted_samples / samples`, between 0 and 1
+    pub uptime: f64,
+}
+
+impl MakerUptime {
+    pub fn from_row(row: Row) -> Self {
+        let quoted_samples: i64 = row.get(1);
+        let samples: i64 = row.get(2);
+        MakerUptime {
+            open_orders_owner: row.get(0),
+            quoted_samples,
+            samples,
+            uptime: if samples > 0 {
+                quoted_samples as f64 / samples as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct UptimeResponse {
+    pub market_name: String,
+    /// First UTC day of the range
+    #[schema(value_type = String)]
+    pub from: NaiveDate,
+    /// Last UTC day of the range, inclusive
+    #[schema(value_type = String)]
+    pub to: NaiveDate,
+    /// Highest uptime first
+    pub makers: Vec<MakerUptime>,
+}
@@ -0,0 +1,52 @@
+use super::{
+    candle::Candle,
+    markets::{MarketInfo, USD_STABLECOIN_MINTS},
+};
+
+/// Where the USD value of a market's quote token comes from.
+#[derive(Clone, Copy, Debug)]
+pub enum UsdReference<'a> {
+    /// The quote token is a USD stablecoin, taken at par
+    Par,
+    /// A stablecoin quoted market trading the quote token
+    Market(&'a MarketInfo),
+}
+
+/// USD reference of `market`'s quote token, `None` when no configured market prices it.
+pub fn usd_reference<'a>(
+    market: &MarketInfo,
+    markets: &'a [MarketInfo],
+) -> Option<UsdReference<'a>> {
+    if USD_STABLECOIN_MINTS.contains(&market.quote_mint_key.as_str()) {
+        return Some(UsdReference::Par);
+    }
+    markets
+        .iter()
+        .find(|m| {
+            m.base_mint_key == market.quote_mint_key
+                && USD_STABLECOIN_MINTS.contains(&m.quote_mint_key.as_str())
+        })
+        .map(UsdReference::Market)
+}
+
+/// Quote volume of each candle in USD, valued at the reference market's vwap over the same bucket,
+/// or its last close before it when the bucket had no candle. `reference` is `None` for markets
+/// quoted at par and must be sorted by start time otherwise.
+pub fn usd_volumes(candles: &[Candle], reference: Option<&[Candle]>) -> Vec<Option<f64>> {
+    candles
+        .iter()
+        .map(|c| match reference {
+            None => Some(c.quote_volume),
+            Some(reference) => {
+                let i = reference.partition_point(|r| r.start_time <= c.start_time);
+                let r = reference.get(i.checked_sub(1)?)?;
+                let price = if r.start_time == c.start_time && r.trade_count > 0 {
+                    r.vwap
+                } else {
+                    r.close
+                };
+                Some(c.quote_volume * price)
+            }
+        })
+        .collect()
+}
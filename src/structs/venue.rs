@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+/// The DEX a market trades on.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Venue {
+    #[default]
+    OpenBook,
+    Phoenix,
+    /// Composite markets merging the markets of every venue that trade a token pair
+    All,
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Venue::OpenBook => write!(f, "openbook"),
+            Venue::Phoenix => write!(f, "phoenix"),
+            Venue::All => write!(f, "all"),
+        }
+    }
+}
+
+impl Venue {
+    /// Name a market of this venue goes by in candles and responses. OpenBook markets keep their
+    /// configured name, others get `@<venue>` appended so a pair listed on several venues keeps
+    /// separate candles, and composites `@ALL`.
+    pub fn qualify(&self, name: &str) -> String {
+        match self {
+            Venue::OpenBook => name.to_string(),
+            Venue::All => format!("{}@ALL", name),
+            _ => format!("{}@{}", name, self),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+/// Trades of one `width_bucket` bin, as aggregated by the database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgVolumeBin {
+    /// 1 based
+    pub bin: i32,
+    pub trade_count: i64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    /// Lowest and highest price of the whole range, the same for every bin
+    pub low: f64,
+    pub high: f64,
+}
+
+impl PgVolumeBin {
+    pub fn from_row(row: Row) -> Self {
+        PgVolumeBin {
+            bin: row.get(0),
+            trade_count: row.get(1),
+            volume: row.get(2),
+            quote_volume: row.get(3),
+            buy_volume: row.get(4),
+            sell_volume: row.get(5),
+            low: row.get(6),
+            high: row.get(7),
+        }
+    }
+}
+
+/// Trades whose price fell in `[price_low, price_high)`, the highest level includes its upper
+/// bound.
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct VolumeLevel {
+    pub price_low: f64,
+    pub price_high: f64,
+    pub trade_count: i64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    /// Base volume of trades the taker bought
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct VolumeProfileResponse {
+    pub market_name: String,
+    /// Unix seconds
+    pub from: i64,
+    /// Unix seconds
+    pub to: i64,
+    /// Levels of equal width from the lowest to the highest traded price, ascending. Empty when
+    /// nothing traded in the range.
+    pub levels: Vec<VolumeLevel>,
+}
+
+impl VolumeProfileResponse {
+    /// Spreads the range of the bins over `bins` levels, including the ones nothing traded in.
+    pub fn from_bins(
+        market_name: String,
+        from: i64,
+        to: i64,
+        bins: i32,
+        rows: Vec<PgVolumeBin>,
+    ) -> Self {
+        let (low, high) = match rows.first() {
+            Some(row) => (row.low, row.high),
+            None => {
+                return VolumeProfileResponse {
+                    market_name,
+                    from,
+                    to,
+                    levels: vec![],
+                }
+            }
+        };
+        let width = (high - low) / bins as f64;
+        let mut levels: Vec<VolumeLevel> = (0..bins)
+            .map(|i| VolumeLevel {
+                price_low: low + width * i as f64,
+                price_high: if i == bins - 1 {
+                    high
+                } else {
+                    low + width * (i + 1) as f64
+                },
+                trade_count: 0,
+                volume: 0.0,
+                quote_volume: 0.0,
+                buy_volume: 0.0,
+                sell_volume: 0.0,
+            })
+            .collect();
+        for row in rows {
+            if let Some(level) = levels.get_mut((row.bin - 1) as usize) {
+                level.trade_count = row.trade_count;
+                level.volume = row.volume;
+                level.quote_volume = row.quote_volume;
+                level.buy_volume = row.buy_volume;
+                level.sell_volume = row.sell_volume;
+            }
+        }
+        VolumeProfileResponse {
+            market_name,
+            from,
+            to,
+            levels,
+        }
+    }
+}
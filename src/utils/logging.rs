@@ -0,0 +1,22 @@
+use tracing_subscriber::EnvFilter;
+
+use super::settings::CommonConfig;
+
+/// Installs the global tracing subscriber. `LOG_FORMAT=json` writes one JSON object per line with
+/// the fields of the enclosing spans, anything else the multi-line human readable format.
+/// Verbosity follows `RUST_LOG` and defaults to `info`. Records from the `log` crate are
+/// forwarded, so dependencies that use it still show up.
+pub fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    // settings that can't be read are reported once logging is set up
+    let format = CommonConfig::from_env().ok().and_then(|c| c.log_format);
+    match format.as_deref() {
+        Some("json") => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+        _ => subscriber.pretty().init(),
+    }
+}
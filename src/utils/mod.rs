@@ -1,10 +1,26 @@
+pub mod logging;
+pub mod reload;
+pub mod settings;
+pub mod singleflight;
+
 use anchor_lang::prelude::Pubkey;
 use chrono::{NaiveDateTime, Utc};
 use deadpool_postgres::Pool;
-use serde_derive::Deserialize;
 use solana_sdk::pubkey;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{
+    database::replica::ReadPool,
+    structs::{
+        cache_backend::CacheBackend, candle::Candle, candle_cache::CandleCache,
+        changes::CandleChange, coingecko::CoinGeckoTicker, embargo::Embargo,
+        envelope::MarketFreshness, markets::{MarketInfo, MarketResolver}, orderbook::OrderBookSnapshot,
+        privacy::Anonymizer, rate_limit::RateLimiter, session::SessionStats, venue::Venue,
+    },
+};
 
-use crate::structs::markets::MarketInfo;
+use self::singleflight::SingleFlight;
 
 pub const OPENBOOK_KEY: Pubkey = pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
 
@@ -20,34 +36,62 @@ impl<T, E: std::fmt::Debug> AnyhowWrap for Result<T, E> {
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
-pub struct PgConfig {
-    pub pg: deadpool_postgres::Config,
-    pub pg_max_pool_connections: usize,
-    pub pg_use_ssl: bool,
-    pub pg_ca_cert_path: Option<String>,
-    pub pg_client_key_path: Option<String>,
-}
-
-impl PgConfig {
-    pub fn from_env() -> Result<Self, config::ConfigError> {
-        config::Config::builder()
-            .add_source(config::Environment::default().separator("_"))
-            .add_source(config::Environment::default())
-            .build()?
-            .try_deserialize()
-    }
-}
-
-#[derive(Clone, Debug, Deserialize)]
-pub struct Config {
-    pub rpc_url: String,
-}
-
 pub struct WebContext {
     pub rpc_url: String,
     pub markets: Vec<MarketInfo>,
+    /// Finds `markets` by name or address
+    pub market_resolver: MarketResolver,
+    /// Token pairs merged across their markets, found by name like markets but not listed with
+    /// them where only markets of a venue make sense
+    pub composite_markets: Vec<MarketInfo>,
+    pub composite_resolver: MarketResolver,
+    /// Primary, for the few writes the server makes
     pub pool: Pool,
+    /// Read replica when configured and reachable, the primary otherwise
+    pub read_pool: ReadPool,
+    /// Pool for the admin endpoints, only connected when they are enabled
+    pub admin_pool: Option<Pool>,
+    /// Latest top of book per market address, refreshed in the background
+    pub orderbook_snapshots: RwLock<HashMap<String, OrderBookSnapshot>>,
+    pub candle_cache: CandleCache,
+    /// Short lived response cache, shared between instances when Redis is configured
+    pub cache: Arc<dyn CacheBackend>,
+    pub rate_limiter: RateLimiter,
+    /// Concurrent identical candle queries share one execution
+    pub candle_requests: SingleFlight<Vec<Candle>>,
+    pub ticker_requests: SingleFlight<Vec<CoinGeckoTicker>>,
+    /// Markets per query of the CoinGecko ticker aggregates
+    pub coingecko_max_markets_per_query: usize,
+    /// Longest range of a candle request, in candles of the requested resolution
+    pub max_range_candles: i64,
+    pub embargo: Embargo,
+    /// Last fill time and data version per market name, refreshed in the background
+    pub freshness: RwLock<HashMap<String, MarketFreshness>>,
+    /// Newest candles of a market, published after each of its batches while anyone streams
+    pub candle_updates: broadcast::Sender<CandleChange>,
+    /// Current session stats per market name, refreshed in the background
+    pub session_stats: RwLock<HashMap<String, SessionStats>>,
+    /// Pseudonyms for trader addresses in public responses, a no-op unless privacy mode is on
+    pub anonymizer: Anonymizer,
+}
+
+impl WebContext {
+    /// The market a request refers to, by name in any case or separator style, or by address.
+    pub fn find_market(&self, key: &str) -> Option<&MarketInfo> {
+        self.market_resolver
+            .resolve(key, &self.markets)
+            .or_else(|| self.composite_resolver.resolve(key, &self.composite_markets))
+    }
+
+    /// Like `find_market`, among the markets of one venue.
+    pub fn find_market_on(&self, key: &str, venue: Venue) -> Option<&MarketInfo> {
+        self.market_resolver
+            .resolve_on(key, venue, &self.markets)
+            .or_else(|| {
+                self.composite_resolver
+                    .resolve_on(key, venue, &self.composite_markets)
+            })
+    }
 }
 
 #[allow(deprecated)]
@@ -0,0 +1,122 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{sync::watch, time::sleep};
+use tracing::info;
+
+/// How often a watched file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Tells the tasks that hold configuration to read it again. Cloned into every task that
+/// applies reloads, each clone sees every request made after it was cloned.
+#[derive(Clone)]
+pub struct Reload {
+    receiver: watch::Receiver<u64>,
+}
+
+impl Reload {
+    /// Resolves on the next reload request.
+    pub async fn requested(&mut self) {
+        if self.receiver.changed().await.is_err() {
+            // the listener is gone, no request will follow
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Requests a reload on SIGHUP, and whenever the modification time of `watched` changes.
+pub fn listen_for_reload(watched: Option<PathBuf>) -> Reload {
+    let (sender, receiver) = watch::channel(0);
+    tokio::spawn(async move {
+        let mut hangup = Hangup::listen();
+        let mut modified = watched.as_deref().and_then(modified_at);
+        let mut requests = 0;
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => info!("SIGHUP received, reloading the configuration"),
+                _ = sleep(WATCH_INTERVAL), if watched.is_some() => {
+                    let path = watched.as_deref().unwrap();
+                    let current = modified_at(path);
+                    if current == modified {
+                        continue;
+                    }
+                    modified = current;
+                    info!("{} changed, reloading the configuration", path.display());
+                }
+            }
+            requests += 1;
+            if sender.send(requests).is_err() {
+                return;
+            }
+        }
+    });
+    Reload { receiver }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(unix)]
+struct Hangup(tokio::signal::unix::Signal);
+
+#[cfg(unix)]
+impl Hangup {
+    fn listen() -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Hangup(signal(SignalKind::hangup()).expect("failed to listen for SIGHUP"))
+    }
+
+    async fn recv(&mut self) {
+        self.0.recv().await;
+    }
+}
+
+#[cfg(not(unix))]
+struct Hangup;
+
+#[cfg(not(unix))]
+impl Hangup {
+    fn listen() -> Self {
+        Hangup
+    }
+
+    async fn recv(&mut self) {
+        futures::future::pending::<()>().await;
+    }
+}
+
+/// Sets the variables of the `.env` file in the working directory again, replacing the values
+/// read at startup, so that settings taken from the environment pick up its changes. Variables
+/// the process was started with and the file doesn't set stay as they are.
+pub fn reload_env_file() -> anyhow::Result<()> {
+    let contents = match std::fs::read_to_string(".env") {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for (key, value) in parse_env_file(&contents) {
+        std::env::set_var(key, value);
+    }
+    Ok(())
+}
+
+/// `KEY=value` lines, skipping blank lines and `#` comments. Values may be quoted.
+fn parse_env_file(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("export ").unwrap_or(line).split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let unquoted = ['"', '\'']
+                .iter()
+                .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)));
+            (key.trim(), unquoted.unwrap_or(value))
+        })
+        .collect()
+}
@@ -0,0 +1,532 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use config::{builder::DefaultState, ConfigBuilder, ConfigError, Environment, File};
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+
+use crate::{
+    structs::{
+        embargo::{Embargo, EmbargoConfig},
+        oracle::OracleConfig,
+        privacy::PrivacyConfig,
+        rate_limit::RateLimitConfig,
+        resolution::Resolution,
+        session::SessionConfig,
+        spread::SpreadConfig,
+        uptime::UptimeConfig,
+    },
+    worker::{
+        alerts::AlertConfig,
+        candle_batching::{BatchingConfig, OutlierConfig},
+        cluster::ClusterConfig,
+        comparator::ComparatorConfig,
+        ingestion::{phoenix::PhoenixConfig, websocket::WebsocketConfig, IngestionConfig},
+        jobs::JobConfig,
+        patterns::PatternWebhookConfig,
+        reconciliation::ReconciliationConfig,
+        retention::RetentionConfig,
+    },
+};
+
+/// Names a file of settings that environment variables, and `.env`, override. Its keys are the
+/// variable names in lower case, e.g. `pg_use_ssl = true`, with the connection settings of
+/// `PG_HOST` and friends in a `[pg]` table. TOML by default, the extension picks other formats.
+pub const CONFIG_FILE_VAR: &str = "CONFIG_FILE";
+
+/// Replaces the values of secrets in printed settings
+const REDACTED: &str = "<redacted>";
+
+/// The config file, if any, without the environment on top.
+pub fn builder() -> ConfigBuilder<DefaultState> {
+    let builder = config::Config::builder();
+    match dotenv::var(CONFIG_FILE_VAR).ok().filter(|p| !p.is_empty()) {
+        Some(path) => builder.add_source(File::with_name(&path)),
+        None => builder,
+    }
+}
+
+/// Reads a section of the settings from the environment over the config file.
+pub fn load<T: DeserializeOwned>() -> Result<T, ConfigError> {
+    builder()
+        .add_source(Environment::default())
+        .build()?
+        .try_deserialize()
+}
+
+/// Serializes a secret as a placeholder, so printed settings show whether it is set.
+pub fn redact<S: Serializer>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value.as_deref() {
+        Some(v) if !v.is_empty() => serializer.serialize_some(REDACTED),
+        _ => serializer.serialize_none(),
+    }
+}
+
+/// Like `redact`, for secrets that are empty rather than unset.
+pub fn redact_str<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    redact(&Some(value.to_string()), serializer)
+}
+
+/// Serializes a URL without the credentials or API keys it may carry in its user info, path
+/// or query, so printed settings still show the host.
+pub fn redact_url<S: Serializer>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value.as_deref() {
+        Some(url) if !url.is_empty() => {
+            let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+            let authority = rest.split(['/', '?']).next().unwrap_or_default();
+            let host = authority.rsplit('@').next().unwrap_or_default();
+            let mut redacted = if scheme.is_empty() {
+                host.to_string()
+            } else {
+                format!("{}://{}", scheme, host)
+            };
+            if authority.contains('@') || rest.len() > authority.len() {
+                redacted.push('/');
+                redacted.push_str(REDACTED);
+            }
+            serializer.serialize_some(&redacted)
+        }
+        _ => serializer.serialize_none(),
+    }
+}
+
+fn default_timescale_chunk_interval_days() -> i32 {
+    7
+}
+
+fn default_timescale_compress_after_days() -> i32 {
+    30
+}
+
+fn default_fill_partition_months_ahead() -> u32 {
+    3
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PgConfig {
+    #[serde(serialize_with = "redact_pg")]
+    pub pg: deadpool_postgres::Config,
+    pub pg_max_pool_connections: usize,
+    pub pg_use_ssl: bool,
+    pub pg_ca_cert_path: Option<String>,
+    /// A PKCS#12 archive, or the PEM key of `PG_CLIENT_CERT_PATH`
+    pub pg_client_key_path: Option<String>,
+    /// PEM certificate chain of the client, set it to use a PEM key instead of PKCS#12
+    pub pg_client_cert_path: Option<String>,
+    /// Password of the PKCS#12 archive, `pass` when unset
+    #[serde(default, serialize_with = "redact")]
+    pub pg_client_key_password: Option<String>,
+    /// How often the certificate files are checked for changes, 60 by default, 0 turns it off
+    pub pg_tls_reload_secs: Option<u64>,
+    /// Store candles and fills as TimescaleDB hypertables with native compression
+    #[serde(default)]
+    pub pg_use_timescale: bool,
+    #[serde(default = "default_timescale_chunk_interval_days")]
+    pub pg_timescale_chunk_interval_days: i32,
+    /// Chunks whose data is older than this are compressed
+    #[serde(default = "default_timescale_compress_after_days")]
+    pub pg_timescale_compress_after_days: i32,
+    /// Keep minute fill aggregates in a continuous aggregate maintained by TimescaleDB and build
+    /// minute candles from it rather than from the fills
+    #[serde(default)]
+    pub pg_timescale_minute_aggregate: bool,
+    /// Partition the fills table by month, as an alternative to TimescaleDB
+    #[serde(default)]
+    pub pg_partition_fills: bool,
+    /// Months of fills partitions created ahead of time
+    #[serde(default = "default_fill_partition_months_ahead")]
+    pub pg_fill_partition_months_ahead: u32,
+    /// Create the roles below during setup and connect each binary as the least privileged one
+    /// it needs, instead of as `PG_USER`
+    #[serde(default)]
+    pub pg_use_roles: bool,
+    #[serde(serialize_with = "redact")]
+    pub pg_ingest_writer_password: Option<String>,
+    #[serde(serialize_with = "redact")]
+    pub pg_api_reader_password: Option<String>,
+    #[serde(serialize_with = "redact")]
+    pub pg_admin_password: Option<String>,
+    /// `postgres://host:port/dbname` of a read replica for the server's queries. Credentials are
+    /// the same as on the primary.
+    pub pg_read_url: Option<String>,
+    /// Give up on getting a connection from the pool after this long instead of waiting forever
+    pub pg_pool_wait_timeout_secs: Option<u64>,
+    /// Queries in `database::fetch` slower than this are logged with their parameter count, 0
+    /// turns the log off. 1000 by default.
+    pub pg_slow_query_threshold_ms: Option<u64>,
+    /// Log the parameter values of slow queries too. They include trader wallet addresses, so
+    /// this is for debugging only.
+    #[serde(default)]
+    pub pg_slow_query_log_params: bool,
+}
+
+fn redact_pg<S: Serializer>(
+    pg: &deadpool_postgres::Config,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut pg = pg.clone();
+    if pg.password.is_some() {
+        pg.password = Some(REDACTED.to_string());
+    }
+    if pg.url.is_some() {
+        pg.url = Some(REDACTED.to_string());
+    }
+    pg.serialize(serializer)
+}
+
+impl PgConfig {
+    /// `PG_HOST`, `PG_PORT`, `PG_USER`, `PG_PASSWORD` and `PG_DBNAME` fill the nested `pg`
+    /// connection settings, the rest are read as they are.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        builder()
+            .add_source(Environment::default().separator("_"))
+            .add_source(Environment::default())
+            .build()?
+            .try_deserialize()
+    }
+}
+
+/// Settings read on their own by a few places rather than as part of a feature's section.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CommonConfig {
+    /// Solana RPC node the markets are looked up on and most scrapers poll
+    #[serde(serialize_with = "redact_url")]
+    pub rpc_url: Option<String>,
+    pub server_bind_addr: Option<String>,
+    /// `json` or `pretty`
+    pub log_format: Option<String>,
+    /// `snake` or `camel`
+    pub response_key_case: Option<String>,
+    /// Shared response and candle cache, a per-process one is used when unset
+    #[serde(serialize_with = "redact_url")]
+    pub redis_url: Option<String>,
+}
+
+impl CommonConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        load()
+    }
+}
+
+/// Every setting of the library, as one struct to check up front and to print. Each feature
+/// still reads its own section where it's used, so this is what they will see.
+#[derive(Debug, Serialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub common: CommonConfig,
+    #[serde(flatten)]
+    pub pg: PgConfig,
+    #[serde(flatten)]
+    pub ingestion: IngestionConfig,
+    #[serde(flatten)]
+    pub websocket: WebsocketConfig,
+    #[serde(flatten)]
+    pub phoenix: PhoenixConfig,
+    #[cfg(feature = "geyser")]
+    #[serde(flatten)]
+    pub geyser: crate::worker::ingestion::geyser::GeyserConfig,
+    #[cfg(feature = "kafka")]
+    #[serde(flatten)]
+    pub kafka: crate::worker::ingestion::kafka::KafkaConfig,
+    #[serde(flatten)]
+    pub batching: BatchingConfig,
+    #[serde(flatten)]
+    pub outlier: OutlierConfig,
+    #[serde(flatten)]
+    pub jobs: JobConfig,
+    #[serde(flatten)]
+    pub retention: RetentionConfig,
+    #[serde(flatten)]
+    pub reconciliation: ReconciliationConfig,
+    #[serde(flatten)]
+    pub comparator: ComparatorConfig,
+    #[serde(flatten)]
+    pub alerts: AlertConfig,
+    #[serde(flatten)]
+    pub cluster: ClusterConfig,
+    #[serde(flatten)]
+    pub patterns: PatternWebhookConfig,
+    #[serde(flatten)]
+    pub spread: SpreadConfig,
+    #[serde(flatten)]
+    pub uptime: UptimeConfig,
+    #[serde(flatten)]
+    pub oracle: OracleConfig,
+    #[serde(flatten)]
+    pub session: SessionConfig,
+    #[serde(flatten)]
+    pub privacy: PrivacyConfig,
+    #[serde(flatten)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(flatten)]
+    pub embargo: EmbargoConfig,
+}
+
+impl Config {
+    /// Reads every section and checks them together. A value that can't be read stops at the
+    /// first error, the checks after that report every setting they reject.
+    pub fn load() -> Result<Self, InvalidConfig> {
+        let config = Config {
+            common: CommonConfig::from_env()?,
+            pg: PgConfig::from_env()?,
+            ingestion: IngestionConfig::from_env()?,
+            websocket: WebsocketConfig::from_env()?,
+            phoenix: PhoenixConfig::from_env()?,
+            #[cfg(feature = "geyser")]
+            geyser: crate::worker::ingestion::geyser::GeyserConfig::from_env()?,
+            #[cfg(feature = "kafka")]
+            kafka: crate::worker::ingestion::kafka::KafkaConfig::from_env()?,
+            batching: BatchingConfig::from_env()?,
+            outlier: OutlierConfig::from_env()?,
+            jobs: JobConfig::from_env()?,
+            retention: RetentionConfig::from_env()?,
+            reconciliation: ReconciliationConfig::from_env()?,
+            comparator: ComparatorConfig::from_env()?,
+            alerts: AlertConfig::from_env()?,
+            cluster: ClusterConfig::from_env()?,
+            patterns: PatternWebhookConfig::from_env()?,
+            spread: SpreadConfig::from_env()?,
+            uptime: UptimeConfig::from_env()?,
+            oracle: OracleConfig::from_env()?,
+            session: SessionConfig::from_env()?,
+            privacy: PrivacyConfig::from_env()?,
+            rate_limit: RateLimitConfig::from_env()?,
+            embargo: EmbargoConfig::from_env()?,
+        };
+        let invalid = config.check();
+        if invalid.is_empty() {
+            Ok(config)
+        } else {
+            Err(InvalidConfig(invalid))
+        }
+    }
+
+    fn check(&self) -> Vec<InvalidSetting> {
+        let mut invalid = vec![];
+        let mut reject = |key: &str, reason: String| {
+            invalid.push(InvalidSetting {
+                key: Some(key.to_string()),
+                reason,
+            })
+        };
+
+        let common = &self.common;
+        if let Some(url) = is_set(&common.rpc_url) {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                reject("RPC_URL", "must be an http:// or https:// URL".to_string());
+            }
+        }
+        if let Some(addr) = is_set(&common.server_bind_addr) {
+            let port = addr.rsplit_once(':').map(|(_, port)| port.parse::<u16>());
+            if !matches!(port, Some(Ok(_))) {
+                reject(
+                    "SERVER_BIND_ADDR",
+                    format!("{:?} is not a host and port, e.g. [::]:8080", addr),
+                );
+            }
+        }
+        if let Some(format) = is_set(&common.log_format) {
+            if !["json", "pretty"].contains(&format) {
+                reject(
+                    "LOG_FORMAT",
+                    format!("{:?} is neither json nor pretty", format),
+                );
+            }
+        }
+        if let Some(case) = is_set(&common.response_key_case) {
+            if !["snake", "camel"].contains(&case) {
+                reject(
+                    "RESPONSE_KEY_CASE",
+                    format!("{:?} is neither snake nor camel", case),
+                );
+            }
+        }
+
+        let pg = &self.pg;
+        if pg.pg_max_pool_connections == 0 {
+            reject("PG_MAX_POOL_CONNECTIONS", "must be at least 1".to_string());
+        }
+        if pg.pg_use_ssl {
+            for (key, path, required) in [
+                ("PG_CA_CERT_PATH", &pg.pg_ca_cert_path, true),
+                ("PG_CLIENT_KEY_PATH", &pg.pg_client_key_path, true),
+                ("PG_CLIENT_CERT_PATH", &pg.pg_client_cert_path, false),
+            ] {
+                match is_set(path) {
+                    None if required => reject(key, "required when PG_USE_SSL is true".to_string()),
+                    Some(path) if !Path::new(path).is_file() => {
+                        reject(key, format!("{} is not a readable file", path))
+                    }
+                    _ => {}
+                }
+            }
+            if is_set(&pg.pg_client_cert_path).is_some()
+                && is_set(&pg.pg_client_key_password).is_some()
+            {
+                reject(
+                    "PG_CLIENT_KEY_PASSWORD",
+                    "only applies to PKCS#12, the PEM key of PG_CLIENT_CERT_PATH must be unencrypted"
+                        .to_string(),
+                );
+            }
+        }
+        if pg.pg_use_timescale && pg.pg_partition_fills {
+            reject(
+                "PG_PARTITION_FILLS",
+                "can't be combined with PG_USE_TIMESCALE".to_string(),
+            );
+        }
+        if pg.pg_use_roles {
+            for (key, password) in [
+                ("PG_INGEST_WRITER_PASSWORD", &pg.pg_ingest_writer_password),
+                ("PG_API_READER_PASSWORD", &pg.pg_api_reader_password),
+                ("PG_ADMIN_PASSWORD", &pg.pg_admin_password),
+            ] {
+                if is_set(password).is_none() {
+                    reject(key, "required when PG_USE_ROLES is true".to_string());
+                }
+            }
+        }
+
+        if self.ingestion.commitment_config().is_err() {
+            reject(
+                "COMMITMENT",
+                format!(
+                    "{:?} is not processed, confirmed or finalized",
+                    self.ingestion.commitment
+                ),
+            );
+        }
+        if self.phoenix.phoenix_poll_ms == 0 {
+            reject("PHOENIX_POLL_MS", "must be at least 1".to_string());
+        }
+        #[cfg(feature = "kafka")]
+        {
+            let kafka = &self.kafka;
+            if is_set(&kafka.kafka_brokers).is_some() && is_set(&kafka.kafka_topic).is_none() {
+                reject(
+                    "KAFKA_TOPIC",
+                    "required when KAFKA_BROKERS is set".to_string(),
+                );
+            }
+        }
+
+        if let Some(pct) = self.outlier.candle_outlier_max_deviation_pct {
+            if pct <= 0.0 {
+                reject(
+                    "CANDLE_OUTLIER_MAX_DEVIATION_PCT",
+                    "must be above 0, leave it unset to keep every fill".to_string(),
+                );
+            }
+        }
+        if let Some(days) = self.retention.fill_retention_days {
+            if days <= 0 {
+                reject(
+                    "FILL_RETENTION_DAYS",
+                    "must be at least 1, leave it unset to keep fills forever".to_string(),
+                );
+            }
+        }
+        let alerts = &self.alerts;
+        match (
+            is_set(&alerts.alert_telegram_bot_token),
+            is_set(&alerts.alert_telegram_chat_id),
+        ) {
+            (Some(_), None) => reject(
+                "ALERT_TELEGRAM_CHAT_ID",
+                "required when ALERT_TELEGRAM_BOT_TOKEN is set".to_string(),
+            ),
+            (None, Some(_)) => reject(
+                "ALERT_TELEGRAM_BOT_TOKEN",
+                "required when ALERT_TELEGRAM_CHAT_ID is set".to_string(),
+            ),
+            _ => {}
+        }
+        let resolution = &self.patterns.pattern_webhook_resolution;
+        if Resolution::from_str(resolution).is_err() {
+            reject(
+                "PATTERN_WEBHOOK_RESOLUTION",
+                format!("{:?} is not a resolution", resolution),
+            );
+        }
+
+        if let Err(e) = self.oracle.feeds() {
+            reject("ORACLE_FEEDS", e.to_string());
+        }
+        if let Err(e) = Embargo::from_config(&self.embargo) {
+            reject("EMBARGO_MARKETS", e.to_string());
+        }
+        if self.privacy.anonymize_traders && self.privacy.anonymize_traders_salt.is_empty() {
+            reject(
+                "ANONYMIZE_TRADERS_SALT",
+                "required when ANONYMIZE_TRADERS is true, or pseudonyms can be reversed"
+                    .to_string(),
+            );
+        }
+        if self.rate_limit.rate_limit_anonymous_per_minute == Some(0) {
+            reject(
+                "RATE_LIMIT_ANONYMOUS_PER_MINUTE",
+                "must be at least 1, leave it unset to turn rate limiting off".to_string(),
+            );
+        }
+        invalid
+    }
+}
+
+/// A value that is set, empty ones count as unset like in `.env-example`.
+fn is_set(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+/// A setting that can't be read or is rejected, with the reason.
+#[derive(Debug)]
+pub struct InvalidSetting {
+    /// The environment variable, when the error names one
+    pub key: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: {}", key, self.reason),
+            None => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+impl From<ConfigError> for InvalidSetting {
+    fn from(e: ConfigError) -> Self {
+        let key = match &e {
+            ConfigError::Type { key: Some(key), .. } => Some(key.to_uppercase()),
+            ConfigError::Message(message) => message
+                .strip_prefix("missing field `")
+                .and_then(|rest| rest.split_once('`'))
+                .map(|(field, _)| field.to_uppercase()),
+            _ => None,
+        };
+        InvalidSetting {
+            key,
+            reason: e.to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidConfig(pub Vec<InvalidSetting>);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration")?;
+        for setting in self.0.iter() {
+            write!(f, "\n  {}", setting)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+impl From<ConfigError> for InvalidConfig {
+    fn from(e: ConfigError) -> Self {
+        InvalidConfig(vec![e.into()])
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::sync::watch;
+
+type SharedResult<V> = Option<Result<V, Arc<anyhow::Error>>>;
+
+/// Error handed to every caller of a failed call. Keeps the original error as its source so
+/// callers can still downcast it, e.g. to tell an unreachable database from a failed query.
+#[derive(Debug)]
+struct SharedError(Arc<anyhow::Error>);
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "coalesced call failed")
+    }
+}
+
+impl Error for SharedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&**self.0)
+    }
+}
+
+/// Coalesces concurrent identical requests: while a call for a key is running, further calls
+/// for the same key wait for its result instead of running their own. Nothing is kept once the
+/// call finishes, so results are never staler than the call itself.
+pub struct SingleFlight<V> {
+    calls: Mutex<HashMap<String, (u64, watch::Receiver<SharedResult<V>>)>>,
+    next_id: AtomicU64,
+}
+
+impl<V> Default for SingleFlight<V> {
+    fn default() -> Self {
+        SingleFlight {
+            calls: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Unregisters the leading call when it finishes or is dropped halfway, e.g. because its client
+/// disconnected. Waiting calls then notice the closed channel and run the call themselves.
+struct LeaderGuard<'a, V> {
+    calls: &'a Mutex<HashMap<String, (u64, watch::Receiver<SharedResult<V>>)>>,
+    key: &'a str,
+    id: u64,
+}
+
+impl<V> Drop for LeaderGuard<'_, V> {
+    fn drop(&mut self) {
+        let mut calls = self.calls.lock().unwrap();
+        if matches!(calls.get(self.key), Some((id, _)) if *id == self.id) {
+            calls.remove(self.key);
+        }
+    }
+}
+
+impl<V: Clone> SingleFlight<V> {
+    pub async fn run<F, Fut>(&self, key: &str, call: F) -> anyhow::Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<V>>,
+    {
+        let (sender, waiting) = {
+            let mut calls = self.calls.lock().unwrap();
+            match calls.get(key) {
+                Some((_, receiver)) => (None, Some(receiver.clone())),
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let (sender, receiver) = watch::channel(None);
+                    calls.insert(key.to_string(), (id, receiver));
+                    (Some((sender, id)), None)
+                }
+            }
+        };
+
+        if let Some(mut receiver) = waiting {
+            loop {
+                if let Some(result) = receiver.borrow().clone() {
+                    return result.map_err(|e| SharedError(e).into());
+                }
+                if receiver.changed().await.is_err() {
+                    // the leading call was dropped before it finished
+                    return call().await;
+                }
+            }
+        }
+
+        let (sender, id) = sender.unwrap();
+        let _guard = LeaderGuard {
+            calls: &self.calls,
+            key,
+            id,
+        };
+        let result = call().await.map_err(Arc::new);
+        sender.send(Some(result.clone())).ok();
+        result.map_err(|e| SharedError(e).into())
+    }
+}
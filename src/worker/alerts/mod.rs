@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use deadpool_postgres::Pool;
+use prometheus::{core::Collector, IntCounterVec};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    database::lifecycle::fetch_market_lifecycles,
+    structs::{market_lifecycle::MarketLifecycle, markets::MarketInfo},
+    worker::{
+        cluster::MarketAssignment,
+        metrics::{
+            METRIC_BATCH_ERRORS_TOTAL, METRIC_FILL_INSERT_ERRORS_TOTAL, METRIC_RPC_ERRORS_TOTAL,
+        },
+    },
+};
+
+/// Time between two checks, error counts are compared over the same window
+fn check_interval() -> Duration {
+    Duration::minutes(1)
+}
+
+fn default_alert_candle_staleness_secs() -> u64 {
+    600
+}
+
+fn default_alert_scraper_errors_per_min() -> u64 {
+    20
+}
+
+fn default_alert_write_errors_per_min() -> u64 {
+    5
+}
+
+fn default_alert_repeat_secs() -> u64 {
+    3600
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AlertConfig {
+    /// Receives alerts as JSON with the message in `text`, which Slack incoming webhooks accept
+    #[serde(serialize_with = "crate::utils::settings::redact_url")]
+    pub alert_webhook_url: Option<String>,
+    /// Telegram bot that sends alerts to `alert_telegram_chat_id`
+    #[serde(serialize_with = "crate::utils::settings::redact")]
+    pub alert_telegram_bot_token: Option<String>,
+    pub alert_telegram_chat_id: Option<String>,
+    /// Alert when a market's newest ingested fill is older than this. Unset, as quiet markets
+    /// would keep it firing.
+    pub alert_fill_staleness_secs: Option<u64>,
+    /// Alert when a market's minute candles are complete only up to longer ago than this, 0
+    /// turns it off
+    #[serde(default = "default_alert_candle_staleness_secs")]
+    pub alert_candle_staleness_secs: u64,
+    /// Scraper RPC errors per minute that raise an alert, 0 turns it off
+    #[serde(default = "default_alert_scraper_errors_per_min")]
+    pub alert_scraper_errors_per_min: u64,
+    /// Failed fill writes and candle batches per minute that raise an alert, 0 turns it off
+    #[serde(default = "default_alert_write_errors_per_min")]
+    pub alert_write_errors_per_min: u64,
+    /// Alerts that keep firing are sent again this often
+    #[serde(default = "default_alert_repeat_secs")]
+    pub alert_repeat_secs: u64,
+}
+
+impl AlertConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.alert_webhook_url.is_some() || self.telegram().is_some()
+    }
+
+    fn telegram(&self) -> Option<(&str, &str)> {
+        Some((
+            self.alert_telegram_bot_token.as_deref()?,
+            self.alert_telegram_chat_id.as_deref()?,
+        ))
+    }
+}
+
+/// Checks every minute for stale markets and error spikes and notifies the configured
+/// channels when an alert starts firing, while it keeps firing and once it resolves. Staleness
+/// is only checked for the owned markets, errors are counted per worker.
+pub async fn watch_for_alerts(
+    config: &AlertConfig,
+    pool: &Pool,
+    markets: Vec<MarketInfo>,
+    assignment: MarketAssignment,
+) -> anyhow::Result<()> {
+    let http_client = reqwest::Client::new();
+    let repeat_after = Duration::seconds(config.alert_repeat_secs as i64);
+    info!("Alerting on {} markets", markets.len());
+
+    // alerts firing, when they were last sent and what they said
+    let mut firing: HashMap<String, (DateTime<Utc>, String)> = HashMap::new();
+    let mut last_counts = ErrorCounts::read();
+    loop {
+        sleep(check_interval().to_std()?).await;
+        let now = Utc::now();
+        let counts = ErrorCounts::read();
+        let mut alerts = error_alerts(config, &last_counts, &counts);
+        last_counts = counts;
+        // without the lifecycles, stale markets can't be told from fresh ones and stay as they are
+        let stale = match fetch_market_lifecycles(pool).await {
+            Ok(lifecycles) => Some(staleness_alerts(
+                config,
+                &lifecycles,
+                &markets,
+                &assignment,
+                now,
+            )),
+            Err(e) => {
+                alerts.push((
+                    "database".to_string(),
+                    format!("Failed to read the market lifecycles: {:?}", e),
+                ));
+                None
+            }
+        };
+        let staleness_checked = stale.is_some();
+        alerts.extend(stale.unwrap_or_default());
+
+        for (key, message) in alerts.iter() {
+            let due = match firing.get(key) {
+                Some((sent_at, _)) => now - *sent_at >= repeat_after,
+                None => true,
+            };
+            if due {
+                warn!("Alert {}: {}", key, message);
+                notify(config, &http_client, &format!("Alert: {}", message)).await;
+                firing.insert(key.clone(), (now, message.clone()));
+            }
+        }
+        let resolved: Vec<String> = firing
+            .keys()
+            .filter(|key| !alerts.iter().any(|(k, _)| k == *key))
+            .filter(|key| staleness_checked || !is_staleness_alert(key))
+            .cloned()
+            .collect();
+        for key in resolved {
+            if let Some((_, message)) = firing.remove(&key) {
+                info!("Alert {} resolved", key);
+                notify(config, &http_client, &format!("Resolved: {}", message)).await;
+            }
+        }
+    }
+}
+
+/// Totals of the error counters of this worker
+struct ErrorCounts {
+    scraper: u64,
+    writes: u64,
+}
+
+impl ErrorCounts {
+    fn read() -> Self {
+        ErrorCounts {
+            scraper: counter_total(&METRIC_RPC_ERRORS_TOTAL),
+            writes: METRIC_FILL_INSERT_ERRORS_TOTAL.get()
+                + counter_total(&METRIC_BATCH_ERRORS_TOTAL),
+        }
+    }
+}
+
+fn counter_total(counter: &IntCounterVec) -> u64 {
+    counter
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .map(|metric| metric.get_counter().get_value() as u64)
+        .sum()
+}
+
+fn error_alerts(
+    config: &AlertConfig,
+    last: &ErrorCounts,
+    current: &ErrorCounts,
+) -> Vec<(String, String)> {
+    let mut alerts = vec![];
+    let scraper_errors = current.scraper.saturating_sub(last.scraper);
+    if config.alert_scraper_errors_per_min > 0
+        && scraper_errors >= config.alert_scraper_errors_per_min
+    {
+        alerts.push((
+            "scraper_errors".to_string(),
+            format!("{} scraper RPC errors in the last minute", scraper_errors),
+        ));
+    }
+    let write_errors = current.writes.saturating_sub(last.writes);
+    if config.alert_write_errors_per_min > 0 && write_errors >= config.alert_write_errors_per_min {
+        alerts.push((
+            "write_errors".to_string(),
+            format!(
+                "{} failed fill writes and candle batches in the last minute",
+                write_errors
+            ),
+        ));
+    }
+    alerts
+}
+
+fn is_staleness_alert(key: &str) -> bool {
+    key.starts_with("fills:") || key.starts_with("candles:")
+}
+
+fn staleness_alerts(
+    config: &AlertConfig,
+    lifecycles: &[MarketLifecycle],
+    markets: &[MarketInfo],
+    assignment: &MarketAssignment,
+    now: DateTime<Utc>,
+) -> Vec<(String, String)> {
+    let mut alerts = vec![];
+    for market in markets.iter().filter(|m| assignment.owns(&m.address)) {
+        let lifecycle = match lifecycles.iter().find(|l| l.market == market.address) {
+            Some(lifecycle) => lifecycle,
+            None => continue,
+        };
+        if let (Some(limit), Some(last_fill_at)) =
+            (config.alert_fill_staleness_secs, lifecycle.last_fill_at)
+        {
+            if now - last_fill_at > Duration::seconds(limit as i64) {
+                alerts.push((
+                    format!("fills:{}", market.name),
+                    format!(
+                        "No fills of {} since {}",
+                        market.name,
+                        last_fill_at.to_rfc3339()
+                    ),
+                ));
+            }
+        }
+        if let Some(candles_through) = lifecycle.candles_through {
+            let limit = Duration::seconds(config.alert_candle_staleness_secs as i64);
+            if config.alert_candle_staleness_secs > 0 && now - candles_through > limit {
+                alerts.push((
+                    format!("candles:{}", market.name),
+                    format!(
+                        "Candles of {} are only complete up to {}",
+                        market.name,
+                        candles_through.to_rfc3339()
+                    ),
+                ));
+            }
+        }
+    }
+    alerts
+}
+
+/// Sends `text` to every configured channel, failures are only logged.
+async fn notify(config: &AlertConfig, http_client: &reqwest::Client, text: &str) {
+    if let Some(url) = config.alert_webhook_url.as_deref() {
+        let result = http_client
+            .post(url)
+            .json(&json!({ "text": text }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to post alert to the webhook: {:?}", e);
+        }
+    }
+    if let Some((token, chat_id)) = config.telegram() {
+        let result = http_client
+            .post(format!("https://api.telegram.org/bot{}/sendMessage", token))
+            .json(&json!({ "chat_id": chat_id, "text": text }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to send alert to Telegram: {:?}", e);
+        }
+    }
+}
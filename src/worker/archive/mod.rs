@@ -0,0 +1,335 @@
+use std::{fs::File, sync::Arc};
+
+use arrow::{
+    array::{
+        Array, ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, StringArray,
+        TimestampMicrosecondArray,
+    },
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    record_batch::RecordBatch,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Duration, DurationRound, TimeZone, Utc};
+use deadpool_postgres::Pool;
+use object_store::{aws::AmazonS3Builder, local::LocalFileSystem, path::Path, ObjectStore};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use tracing::info;
+
+use crate::{
+    database::archive::{fetch_archive_candles, fetch_archive_fills},
+    structs::{candle::Candle, markets::MarketInfo, openbook::OpenBookFill},
+};
+
+/// Where archived Parquet files go: `s3://bucket/prefix` or a local directory. S3 credentials
+/// and region are read from the usual `AWS_*` environment variables.
+pub struct ArchiveDestination {
+    store: Box<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl ArchiveDestination {
+    pub fn parse(destination: &str) -> anyhow::Result<Self> {
+        match destination.strip_prefix("s3://") {
+            Some(rest) => {
+                let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+                let store = AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()?;
+                Ok(ArchiveDestination {
+                    store: Box::new(store),
+                    prefix: prefix.trim_end_matches('/').to_string(),
+                })
+            }
+            None => {
+                std::fs::create_dir_all(destination)?;
+                Ok(ArchiveDestination {
+                    store: Box::new(LocalFileSystem::new_with_prefix(destination)?),
+                    prefix: String::new(),
+                })
+            }
+        }
+    }
+
+    fn path(&self, table: &str, market: &str, day: DateTime<Utc>) -> Path {
+        // market names contain slashes, keep them out of the partition path
+        let file = format!(
+            "{}/market={}/date={}/part-0.parquet",
+            table,
+            market.replace('/', "-"),
+            day.format("%Y-%m-%d")
+        );
+        if self.prefix.is_empty() {
+            Path::from(file)
+        } else {
+            Path::from(format!("{}/{}", self.prefix, file))
+        }
+    }
+
+    async fn put(&self, path: &Path, batch: RecordBatch) -> anyhow::Result<()> {
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut buf = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        self.store.put(path, Bytes::from(buf)).await?;
+        Ok(())
+    }
+}
+
+/// Writes one Parquet file per market and UTC day of fills in `[from, to)`, days without fills
+/// are skipped. Returns the number of fills written.
+pub async fn archive_fills(
+    pool: &Pool,
+    destination: &ArchiveDestination,
+    market: &MarketInfo,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> anyhow::Result<usize> {
+    let mut written = 0;
+    let mut day = from.duration_trunc(Duration::days(1))?;
+    while day < to {
+        let fills =
+            fetch_archive_fills(pool, &market.address, day, day + Duration::days(1)).await?;
+        if !fills.is_empty() {
+            let path = destination.path("fills", &market.address, day);
+            destination.put(&path, fills_to_batch(&fills)?).await?;
+            info!("Archived {} fills to {}", fills.len(), path);
+            written += fills.len();
+        }
+        day += Duration::days(1);
+    }
+    Ok(written)
+}
+
+/// Writes one Parquet file per market and UTC day of candles (all resolutions) in `[from, to)`.
+pub async fn archive_candles(
+    pool: &Pool,
+    destination: &ArchiveDestination,
+    market: &MarketInfo,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> anyhow::Result<usize> {
+    let mut written = 0;
+    let mut day = from.duration_trunc(Duration::days(1))?;
+    while day < to {
+        let candles =
+            fetch_archive_candles(pool, &market.name, day, day + Duration::days(1)).await?;
+        if !candles.is_empty() {
+            let path = destination.path("candles", &market.name, day);
+            destination.put(&path, candles_to_batch(&candles)?).await?;
+            info!("Archived {} candles to {}", candles.len(), path);
+            written += candles.len();
+        }
+        day += Duration::days(1);
+    }
+    Ok(written)
+}
+
+/// Reads fills back from a Parquet file written by `archive_fills`, one record batch at a time.
+pub fn read_archived_fills(
+    path: &str,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<OpenBookFill>>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    Ok(reader.flat_map(|batch| {
+        let fills = batch
+            .map_err(anyhow::Error::from)
+            .and_then(|b| batch_to_fills(&b));
+        match fills {
+            Ok(fills) => fills.into_iter().map(Ok).collect::<Vec<_>>(),
+            Err(e) => vec![Err(e)],
+        }
+    }))
+}
+
+fn batch_to_fills(batch: &RecordBatch) -> anyhow::Result<Vec<OpenBookFill>> {
+    fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> anyhow::Result<&'a T> {
+        batch
+            .column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<T>())
+            .ok_or_else(|| anyhow::anyhow!("missing or mistyped column {}", name))
+    }
+    let signature = column::<StringArray>(batch, "signature")?;
+    let slot = column::<Int64Array>(batch, "slot")?;
+    let block_datetime = column::<TimestampMicrosecondArray>(batch, "block_datetime")?;
+    let market = column::<StringArray>(batch, "market")?;
+    let open_orders_owner = column::<StringArray>(batch, "open_orders_owner")?;
+    let bid = column::<BooleanArray>(batch, "bid")?;
+    let maker = column::<BooleanArray>(batch, "maker")?;
+    let native_quantity_paid = column::<Float64Array>(batch, "native_quantity_paid")?;
+    let native_quantity_received = column::<Float64Array>(batch, "native_quantity_received")?;
+    let native_fee_or_rebate = column::<Float64Array>(batch, "native_fee_or_rebate")?;
+    let price = column::<Float64Array>(batch, "price")?;
+    let size = column::<Float64Array>(batch, "size")?;
+    let seq_num = column::<Int64Array>(batch, "seq_num")?;
+    let instruction_num = column::<Int32Array>(batch, "instruction_num")?;
+    let fee = column::<Float64Array>(batch, "fee")?;
+    let referrer_rebate = column::<Float64Array>(batch, "referrer_rebate")?;
+    let optional = |values: &Float64Array, i: usize| (!values.is_null(i)).then(|| values.value(i));
+
+    Ok((0..batch.num_rows())
+        .map(|i| OpenBookFill {
+            signature: signature.value(i).to_string(),
+            slot: slot.value(i),
+            block_datetime: Utc.timestamp_nanos(block_datetime.value(i) * 1000),
+            market: market.value(i).to_string(),
+            open_orders_owner: open_orders_owner.value(i).to_string(),
+            bid: bid.value(i),
+            maker: maker.value(i),
+            native_quantity_paid: native_quantity_paid.value(i),
+            native_quantity_received: native_quantity_received.value(i),
+            native_fee_or_rebate: native_fee_or_rebate.value(i),
+            price: price.value(i),
+            size: size.value(i),
+            seq_num: seq_num.value(i),
+            instruction_num: instruction_num.value(i),
+            fee: optional(fee, i),
+            referrer_rebate: optional(referrer_rebate, i),
+        })
+        .collect())
+}
+
+fn timestamp_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        false,
+    )
+}
+
+fn timestamps(times: impl Iterator<Item = DateTime<Utc>>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from(times.map(|t| t.timestamp_micros()).collect::<Vec<i64>>())
+            .with_timezone_utc(),
+    )
+}
+
+fn fills_to_batch(fills: &[OpenBookFill]) -> anyhow::Result<RecordBatch> {
+    let schema = Schema::new(vec![
+        Field::new("signature", DataType::Utf8, false),
+        Field::new("slot", DataType::Int64, false),
+        timestamp_field("block_datetime"),
+        Field::new("market", DataType::Utf8, false),
+        Field::new("open_orders_owner", DataType::Utf8, false),
+        Field::new("bid", DataType::Boolean, false),
+        Field::new("maker", DataType::Boolean, false),
+        Field::new("native_quantity_paid", DataType::Float64, false),
+        Field::new("native_quantity_received", DataType::Float64, false),
+        Field::new("native_fee_or_rebate", DataType::Float64, false),
+        Field::new("price", DataType::Float64, false),
+        Field::new("size", DataType::Float64, false),
+        Field::new("seq_num", DataType::Int64, false),
+        Field::new("instruction_num", DataType::Int32, false),
+        Field::new("fee", DataType::Float64, true),
+        Field::new("referrer_rebate", DataType::Float64, true),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            fills.iter().map(|f| f.signature.as_str()),
+        )),
+        Arc::new(Int64Array::from_iter_values(fills.iter().map(|f| f.slot))),
+        timestamps(fills.iter().map(|f| f.block_datetime)),
+        Arc::new(StringArray::from_iter_values(
+            fills.iter().map(|f| f.market.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            fills.iter().map(|f| f.open_orders_owner.as_str()),
+        )),
+        Arc::new(BooleanArray::from(
+            fills.iter().map(|f| f.bid).collect::<Vec<bool>>(),
+        )),
+        Arc::new(BooleanArray::from(
+            fills.iter().map(|f| f.maker).collect::<Vec<bool>>(),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            fills.iter().map(|f| f.native_quantity_paid),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            fills.iter().map(|f| f.native_quantity_received),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            fills.iter().map(|f| f.native_fee_or_rebate),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            fills.iter().map(|f| f.price),
+        )),
+        Arc::new(Float64Array::from_iter_values(fills.iter().map(|f| f.size))),
+        Arc::new(Int64Array::from_iter_values(
+            fills.iter().map(|f| f.seq_num),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            fills.iter().map(|f| f.instruction_num),
+        )),
+        Arc::new(Float64Array::from(
+            fills.iter().map(|f| f.fee).collect::<Vec<Option<f64>>>(),
+        )),
+        Arc::new(Float64Array::from(
+            fills
+                .iter()
+                .map(|f| f.referrer_rebate)
+                .collect::<Vec<Option<f64>>>(),
+        )),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+fn candles_to_batch(candles: &[Candle]) -> anyhow::Result<RecordBatch> {
+    let schema = Schema::new(vec![
+        Field::new("market_name", DataType::Utf8, false),
+        timestamp_field("start_time"),
+        timestamp_field("end_time"),
+        Field::new("resolution", DataType::Utf8, false),
+        Field::new("open", DataType::Float64, false),
+        Field::new("close", DataType::Float64, false),
+        Field::new("high", DataType::Float64, false),
+        Field::new("low", DataType::Float64, false),
+        Field::new("volume", DataType::Float64, false),
+        Field::new("complete", DataType::Boolean, false),
+        Field::new("vwap", DataType::Float64, false),
+        Field::new("trade_count", DataType::Int64, false),
+        Field::new("quote_volume", DataType::Float64, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            candles.iter().map(|c| c.market_name.as_str()),
+        )),
+        timestamps(candles.iter().map(|c| c.start_time)),
+        timestamps(candles.iter().map(|c| c.end_time)),
+        Arc::new(StringArray::from_iter_values(
+            candles.iter().map(|c| c.resolution.as_str()),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.open),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.close),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.high),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.low),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.volume),
+        )),
+        Arc::new(BooleanArray::from(
+            candles.iter().map(|c| c.complete).collect::<Vec<bool>>(),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.vwap),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            candles.iter().map(|c| c.trade_count),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c.quote_volume),
+        )),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
@@ -0,0 +1,339 @@
+//! Candle computation without a database: the worker feeds these functions the fills and candles
+//! it reads from Postgres, and anyone with their own fills can call them to get identical candles.
+
+use std::{collections::VecDeque, ops::Range};
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use tracing::debug;
+
+use crate::{
+    structs::{
+        candle::Candle,
+        minute_aggregate::MinuteAggregate,
+        openbook::PgOpenBookFill,
+        resolution::{day, Resolution},
+    },
+    utils::{f64_max, f64_min},
+};
+
+/// Minute candles without a later fill count as complete once they ended this long before `as_of`
+fn completion_delay() -> Duration {
+    Duration::minutes(10)
+}
+
+#[derive(Clone, Debug)]
+pub struct AggregationOptions {
+    pub market_name: String,
+    /// Close of the candle before the range, opens the first candles. Defaults to the price of
+    /// the first fill.
+    pub last_price: Option<f64>,
+    /// The time the candles are computed at, decides which candles are complete. Fills after
+    /// it are aggregated but not yet trusted to complete the candles before them.
+    pub as_of: DateTime<Utc>,
+    /// Leave prices of anomalous fills out of the candles, `None` aggregates every fill as is
+    pub outlier_filter: Option<OutlierFilter>,
+}
+
+/// Treats a fill as an anomalous print when its price is further than `max_deviation` (a
+/// fraction) from the median price of the `window` fills before it.
+#[derive(Clone, Copy, Debug)]
+pub struct OutlierFilter {
+    pub max_deviation: f64,
+    pub window: usize,
+}
+
+/// Fewer prices than this in the window aren't enough to call a fill an outlier
+const MIN_MEDIAN_SAMPLES: usize = 3;
+
+/// Flags the fills, sorted by time, that `filter` considers anomalous. The median runs over all
+/// previous fills, flagged or not, so a lasting move in price is accepted once it makes up half
+/// the window while a single fat-finger print is not. `last_price` seeds the window.
+pub fn flag_anomalous_fills(
+    fills: &[PgOpenBookFill],
+    filter: &OutlierFilter,
+    last_price: Option<f64>,
+) -> Vec<bool> {
+    let mut window: VecDeque<f64> = last_price.into_iter().collect();
+    fills
+        .iter()
+        .map(|fill| {
+            let anomalous = window.len() >= MIN_MEDIAN_SAMPLES.min(filter.window)
+                && median(&window).map_or(false, |median| {
+                    median > 0.0 && (fill.price / median - 1.0).abs() > filter.max_deviation
+                });
+            window.push_back(fill.price);
+            if window.len() > filter.window {
+                window.pop_front();
+            }
+            anomalous
+        })
+        .collect()
+}
+
+fn median(prices: &VecDeque<f64>) -> Option<f64> {
+    let mut sorted: Vec<f64> = prices.iter().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((sorted[mid - 1] + sorted[mid]) / 2.0),
+        _ => Some(sorted[mid]),
+    }
+}
+
+/// Aggregates maker fills, sorted by time, into candles of `resolution` covering `range`. Higher
+/// resolutions are built up through the same chain of constituent resolutions as the worker
+/// uses, e.g. 1H candles from 30M candles from 15M candles and so on.
+pub fn aggregate_fills_to_candles(
+    fills: &[PgOpenBookFill],
+    resolution: Resolution,
+    range: Range<DateTime<Utc>>,
+    options: &AggregationOptions,
+) -> Vec<Candle> {
+    let range = resolution.bucket_start(range.start)..range.end;
+
+    let mut chain = vec![resolution];
+    while *chain.last().unwrap() != Resolution::R1m {
+        chain.push(chain.last().unwrap().get_constituent_resolution());
+    }
+
+    let mut candles = fills_to_minute_candles(fills, range.clone(), options);
+    for resolution in chain.into_iter().rev().skip(1) {
+        candles = combine_candles(&candles, resolution, range.clone());
+    }
+    candles
+}
+
+/// One candle per minute of `range`. Minutes without fills carry the previous close. Anomalous
+/// fills count towards volume and trades but not towards prices or the vwap.
+pub fn fills_to_minute_candles(
+    fills: &[PgOpenBookFill],
+    range: Range<DateTime<Utc>>,
+    options: &AggregationOptions,
+) -> Vec<Candle> {
+    let empty_candle = Candle::create_empty_candle(options.market_name.clone(), Resolution::R1m);
+
+    let minutes = (range.end - range.start).num_minutes().max(0);
+    let mut candles = vec![empty_candle; minutes as usize];
+
+    let anomalous = match &options.outlier_filter {
+        Some(filter) => flag_anomalous_fills(fills, filter, options.last_price),
+        None => vec![false; fills.len()],
+    };
+    let mut fills_iter = fills.iter().zip(anomalous).peekable();
+    let mut start_time = range.start;
+    let mut end_time = start_time + Duration::minutes(1);
+
+    let mut last_price = match options.last_price {
+        Some(p) => p,
+        None => match fills_iter.peek() {
+            Some((first, _)) => first.price,
+            None => return Vec::new(),
+        },
+    };
+
+    for candle in candles.iter_mut() {
+        candle.open = last_price;
+        candle.close = last_price;
+        candle.low = last_price;
+        candle.high = last_price;
+
+        while matches!(fills_iter.peek(), Some((f, _)) if f.time < end_time) {
+            let (fill, anomalous) = fills_iter.next().unwrap();
+
+            candle.volume += fill.size;
+            candle.trade_count += 1;
+            candle.quote_volume += fill.price * fill.size;
+            if anomalous {
+                continue;
+            }
+
+            candle.close = fill.price;
+            candle.low = f64_min(fill.price, candle.low);
+            candle.high = f64_max(fill.price, candle.high);
+            candle.priced_volume += fill.size;
+            candle.priced_quote_volume += fill.price * fill.size;
+
+            last_price = fill.price;
+        }
+
+        candle.vwap = priced_vwap(candle);
+
+        candle.start_time = start_time;
+        candle.end_time = end_time;
+        let next_fill_time = fills_iter.peek().map(|(f, _)| f.time);
+        candle.complete = matches!(next_fill_time, Some(t) if t > end_time && t <= options.as_of)
+            || end_time < options.as_of - completion_delay();
+        start_time = end_time;
+        end_time += Duration::minutes(1);
+    }
+
+    candles
+}
+
+/// The candles `fills_to_minute_candles` builds without an outlier filter, from fills already
+/// summed up per minute by the database. `aggregates` are sorted by time and minute aligned.
+pub fn aggregates_to_minute_candles(
+    aggregates: &[MinuteAggregate],
+    range: Range<DateTime<Utc>>,
+    options: &AggregationOptions,
+) -> Vec<Candle> {
+    let minutes = (range.end - range.start).num_minutes().max(0);
+    let mut aggregates_iter = aggregates
+        .iter()
+        .skip_while(|a| a.start_time < range.start)
+        .peekable();
+
+    let mut last_price = match options.last_price {
+        Some(p) => p,
+        None => match aggregates_iter.peek() {
+            Some(first) => first.open,
+            None => return Vec::new(),
+        },
+    };
+
+    let mut candles = Vec::with_capacity(minutes as usize);
+    let mut start_time = range.start;
+    for _ in 0..minutes {
+        let end_time = start_time + Duration::minutes(1);
+        let mut candle = Candle {
+            start_time,
+            end_time,
+            open: last_price,
+            close: last_price,
+            high: last_price,
+            low: last_price,
+            vwap: last_price,
+            ..Candle::create_empty_candle(options.market_name.clone(), Resolution::R1m)
+        };
+        if let Some(minute) = aggregates_iter.next_if(|a| a.start_time == start_time) {
+            candle.close = minute.close;
+            candle.high = f64_max(candle.high, minute.high);
+            candle.low = f64_min(candle.low, minute.low);
+            candle.volume = minute.volume;
+            candle.trade_count = minute.trade_count;
+            candle.quote_volume = minute.quote_volume;
+            // no outlier filter in this mode, every fill sets prices
+            candle.priced_volume = minute.volume;
+            candle.priced_quote_volume = minute.quote_volume;
+            candle.vwap = priced_vwap(&candle);
+            last_price = minute.close;
+        }
+        let next_fill_time = aggregates_iter.peek().map(|a| a.first_fill_time);
+        candle.complete = matches!(next_fill_time, Some(t) if t > end_time && t <= options.as_of)
+            || end_time < options.as_of - completion_delay();
+        candles.push(candle);
+        start_time = end_time;
+    }
+
+    candles
+}
+
+/// Combines candles of the constituent resolution, sorted by time, into candles of
+/// `target_resolution` starting at `range.start`, one per bucket that ends within the range.
+/// Always returns at least one candle.
+pub fn combine_candles(
+    constituent_candles: &[Candle],
+    target_resolution: Resolution,
+    range: Range<DateTime<Utc>>,
+) -> Vec<Candle> {
+    debug!("combining for target_resolution: {}", target_resolution);
+    if constituent_candles.is_empty() {
+        return Vec::new();
+    }
+
+    let empty_candle = Candle::create_empty_candle(
+        constituent_candles[0].market_name.clone(),
+        target_resolution,
+    );
+    let mut combined_candles = vec![];
+
+    let mut last_close = constituent_candles[0].close;
+    let mut con_iter = constituent_candles.iter().peekable();
+    let mut start_time = range.start;
+    let mut end_time = target_resolution.bucket_end(start_time);
+
+    while combined_candles.is_empty() || end_time <= range.end {
+        let mut candle = empty_candle.clone();
+        candle.open = last_close;
+        candle.low = last_close;
+        candle.close = last_close;
+        candle.high = last_close;
+
+        while matches!(con_iter.peek(), Some(c) if c.end_time <= end_time) {
+            let unit_candle = con_iter.next().unwrap();
+            candle.high = f64_max(candle.high, unit_candle.high);
+            candle.low = f64_min(candle.low, unit_candle.low);
+            candle.close = unit_candle.close;
+            candle.volume += unit_candle.volume;
+            candle.trade_count += unit_candle.trade_count;
+            candle.complete = unit_candle.complete;
+            candle.end_time = unit_candle.end_time;
+            candle.quote_volume += unit_candle.quote_volume;
+            candle.priced_volume += unit_candle.priced_volume;
+            candle.priced_quote_volume += unit_candle.priced_quote_volume;
+        }
+
+        candle.vwap = priced_vwap(&candle);
+
+        candle.start_time = start_time;
+        candle.end_time = end_time;
+
+        start_time = end_time;
+        end_time = target_resolution.bucket_end(start_time);
+
+        last_close = candle.close;
+        combined_candles.push(candle);
+    }
+
+    combined_candles
+}
+
+/// Start of the day `time` falls in, for days that start at midnight of the UTC offset `offset`.
+pub fn session_start(time: DateTime<Utc>, offset: Duration) -> DateTime<Utc> {
+    (time + offset).duration_trunc(day()).unwrap() - offset
+}
+
+/// Combines candles sorted by time into daily candles of days that start at midnight of the UTC
+/// offset `offset`, e.g. nine hours earlier than UTC days for `+09:00`. The duration of the
+/// candles has to divide the offset. Only days with candles are returned.
+pub fn combine_session_candles(candles: &[Candle], offset: Duration) -> Vec<Candle> {
+    let mut sessions: Vec<Candle> = vec![];
+    for candle in candles {
+        let start_time = session_start(candle.start_time, offset);
+        let end_time = start_time + day();
+        match sessions.last_mut() {
+            Some(session) if session.start_time == start_time => {
+                session.high = f64_max(session.high, candle.high);
+                session.low = f64_min(session.low, candle.low);
+                session.close = candle.close;
+                session.volume += candle.volume;
+                session.quote_volume += candle.quote_volume;
+                session.priced_volume += candle.priced_volume;
+                session.priced_quote_volume += candle.priced_quote_volume;
+                session.trade_count += candle.trade_count;
+                session.complete = candle.complete && candle.end_time == end_time;
+            }
+            _ => sessions.push(Candle {
+                start_time,
+                end_time,
+                resolution: Resolution::R1d.to_string(),
+                complete: candle.complete && candle.end_time == end_time,
+                ..candle.clone()
+            }),
+        }
+    }
+    for session in sessions.iter_mut() {
+        session.vwap = priced_vwap(session);
+    }
+    sessions
+}
+
+/// Vwap over the fills that set prices, the close when none did.
+pub fn priced_vwap(candle: &Candle) -> f64 {
+    if candle.priced_volume > 0.0 {
+        candle.priced_quote_volume / candle.priced_volume
+    } else {
+        candle.close
+    }
+}
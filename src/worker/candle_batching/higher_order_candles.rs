@@ -1,151 +1,152 @@
 use chrono::{DateTime, Duration, DurationRound, Utc};
 use deadpool_postgres::Pool;
-use log::debug;
-use std::cmp::{max, min};
-use strum::IntoEnumIterator;
+use std::{cmp::min, collections::HashMap};
 
 use crate::{
     database::{
-        fetch::{
-            fetch_candles_from, fetch_earliest_candles,
-            fetch_latest_finished_candle,
-        },
+        fetch::{fetch_candles_from, fetch_earliest_candles},
         insert::build_candles_upsert_statement,
+        revisions::{revision_transaction, REVISION_REASON_BACKFILL},
     },
     structs::{
         candle::Candle,
+        markets::MarketInfo,
         resolution::{day, Resolution},
     },
     utils::{f64_max, f64_min, AnyhowWrap},
+    worker::candle_batching::aggregate::{combine_candles, priced_vwap},
 };
 
-pub async fn batch_higher_order_candles(
-    pool: &Pool,
-    market_name: &str,
-    resolution: Resolution,
-) -> anyhow::Result<Vec<Candle>> {
-    let latest_candle = fetch_latest_finished_candle(pool, market_name, resolution).await?;
+/// Higher resolution buckets still open after the minute candles batched so far, so that each
+/// batch's minute candles update every resolution at once without reading anything back.
+#[derive(Clone, Debug)]
+pub struct OpenBuckets {
+    /// End of the last complete minute folded in, the next batch has to start here
+    pub through: DateTime<Utc>,
+    /// Resolutions the market keeps, the rest are skipped
+    resolutions: Vec<Resolution>,
+    buckets: HashMap<Resolution, Candle>,
+}
 
-    match latest_candle {
-        Some(candle) => {
-            let start_time = candle.end_time;
-            let end_time = start_time + day();
-            let mut constituent_candles = fetch_candles_from(
-                pool,
-                market_name,
-                resolution.get_constituent_resolution(),
-                start_time,
-                end_time,
-            )
-            .await?;
-            if constituent_candles.is_empty() {
-                return Ok(Vec::new());
-            }
-            let combined_candles =
-                combine_into_higher_order_candles(&mut constituent_candles, resolution, start_time);
-            Ok(combined_candles)
-        }
-        None => {
-            let mut constituent_candles =
-                fetch_earliest_candles(pool, market_name, resolution.get_constituent_resolution())
+impl OpenBuckets {
+    /// Rebuilds the open buckets from the stored minute candles of the day `through` falls in.
+    /// Every other resolution divides a day, except weeks and months, which start from the
+    /// stored daily candles of their earlier days.
+    pub async fn load(
+        pool: &Pool,
+        market_name: &str,
+        resolutions: &[Resolution],
+        through: DateTime<Utc>,
+    ) -> anyhow::Result<Self> {
+        let day_start = through.duration_trunc(day())?;
+        let mut open = OpenBuckets {
+            through: day_start,
+            resolutions: resolutions.to_vec(),
+            buckets: HashMap::new(),
+        };
+        for resolution in resolutions.iter().copied().filter(|r| r.is_calendar()) {
+            let bucket_start = resolution.bucket_start(day_start);
+            let days =
+                fetch_candles_from(pool, market_name, Resolution::R1d, bucket_start, day_start)
                     .await?;
-            if constituent_candles.is_empty() {
-                debug!(
-                    "Batching {}, but no candles found for: {:?}, {}",
-                    resolution,
-                    market_name,
-                    resolution.get_constituent_resolution()
-                );
-                return Ok(Vec::new());
+            if let Some(first) = days.first() {
+                let mut bucket = Candle {
+                    start_time: bucket_start,
+                    end_time: resolution.bucket_end(bucket_start),
+                    open: first.open,
+                    close: first.open,
+                    high: first.open,
+                    low: first.open,
+                    ..Candle::create_empty_candle(market_name.to_string(), resolution)
+                };
+                for day in days.iter() {
+                    add_minute(&mut bucket, day);
+                }
+                open.buckets.insert(resolution, bucket);
             }
-            let start_time = constituent_candles[0].start_time.duration_trunc(day())?;
+        }
+        let minutes =
+            fetch_candles_from(pool, market_name, Resolution::R1m, day_start, through).await?;
+        open.advance(&minutes);
+        open.through = through;
+        Ok(open)
+    }
 
-            if constituent_candles.is_empty() {
-                return Ok(Vec::new());
+    /// Folds minute candles, sorted and without gaps, into every higher resolution and returns
+    /// the candles of each bucket they touched. Only the leading complete minutes are kept, the
+    /// rest may still change and are folded in again by the next batch.
+    pub fn advance(&mut self, minutes: &[Candle]) -> Vec<Candle> {
+        let settled = minutes.iter().take_while(|m| m.complete).count();
+        let mut candles = vec![];
+        for resolution in self.resolutions.iter().copied() {
+            if resolution == Resolution::R1m {
+                continue;
             }
-
-            let combined_candles =
-                combine_into_higher_order_candles(&mut constituent_candles, resolution, start_time);
-
-            Ok(trim_candles(
-                combined_candles,
-                constituent_candles[0].start_time,
-            ))
+            let mut bucket = self.buckets.get(&resolution).cloned();
+            let mut touched = false;
+            for (i, minute) in minutes.iter().enumerate() {
+                let bucket_start = resolution.bucket_start(minute.start_time);
+                if !matches!(&bucket, Some(b) if b.start_time == bucket_start) {
+                    if touched {
+                        candles.extend(bucket.take());
+                    }
+                    bucket = Some(Candle {
+                        start_time: bucket_start,
+                        end_time: resolution.bucket_end(bucket_start),
+                        open: minute.open,
+                        close: minute.open,
+                        high: minute.open,
+                        low: minute.open,
+                        ..Candle::create_empty_candle(minute.market_name.clone(), resolution)
+                    });
+                }
+                let b = bucket.as_mut().unwrap();
+                add_minute(b, minute);
+                touched = true;
+                if i + 1 == settled {
+                    self.buckets.insert(resolution, b.clone());
+                }
+            }
+            if touched {
+                candles.extend(bucket);
+            }
+        }
+        if let Some(last) = minutes[..settled].last() {
+            self.through = last.end_time;
         }
+        candles
     }
 }
 
+fn add_minute(bucket: &mut Candle, minute: &Candle) {
+    bucket.high = f64_max(bucket.high, minute.high);
+    bucket.low = f64_min(bucket.low, minute.low);
+    bucket.close = minute.close;
+    bucket.volume += minute.volume;
+    bucket.trade_count += minute.trade_count;
+    bucket.quote_volume += minute.quote_volume;
+    bucket.priced_volume += minute.priced_volume;
+    bucket.priced_quote_volume += minute.priced_quote_volume;
+    bucket.vwap = priced_vwap(bucket);
+    bucket.complete = minute.complete && minute.end_time == bucket.end_time;
+}
+
 fn combine_into_higher_order_candles(
     constituent_candles: &Vec<Candle>,
     target_resolution: Resolution,
     st: DateTime<Utc>,
 ) -> Vec<Candle> {
-    debug!("combining for target_resolution: {}", target_resolution);
-
-    let duration = target_resolution.get_duration();
-
-    let empty_candle = Candle::create_empty_candle(
-        constituent_candles[0].market_name.clone(),
-        target_resolution,
-    );
+    // up to a day of candles, plus the one in progress
     let now = Utc::now().duration_trunc(Duration::minutes(1)).unwrap();
-    let candle_window = min(now - st, day());
-    let num_candles = max(
-        1,
-        (candle_window.num_minutes() / duration.num_minutes()) as usize + 1,
-    );
-
-    let mut combined_candles = vec![empty_candle; num_candles];
-
-    let mut last_close = constituent_candles[0].close;
-    let mut con_iter = constituent_candles.iter().peekable();
-    let mut start_time = st;
-    let mut end_time = start_time + duration;
-
-    for i in 0..combined_candles.len() {
-        combined_candles[i].open = last_close;
-        combined_candles[i].low = last_close;
-        combined_candles[i].close = last_close;
-        combined_candles[i].high = last_close;
-
-        while matches!(con_iter.peek(), Some(c) if c.end_time <= end_time) {
-            let unit_candle = con_iter.next().unwrap();
-            combined_candles[i].high = f64_max(combined_candles[i].high, unit_candle.high);
-            combined_candles[i].low = f64_min(combined_candles[i].low, unit_candle.low);
-            combined_candles[i].close = unit_candle.close;
-            combined_candles[i].volume += unit_candle.volume;
-            combined_candles[i].complete = unit_candle.complete;
-            combined_candles[i].end_time = unit_candle.end_time;
-        }
-
-        combined_candles[i].start_time = start_time;
-        combined_candles[i].end_time = end_time;
-
-        start_time = end_time;
-        end_time += duration;
-
-        last_close = combined_candles[i].close;
-    }
-
-    combined_candles
-}
-
-fn trim_candles(mut c: Vec<Candle>, start_time: DateTime<Utc>) -> Vec<Candle> {
-    let mut i = 0;
-    while i < c.len() {
-        if c[i].end_time <= start_time {
-            c.remove(i);
-        } else {
-            i += 1;
-        }
-    }
-    c
+    let end = st + min(now - st, day()) + target_resolution.get_duration();
+    combine_candles(constituent_candles, target_resolution, st..end)
 }
 
 pub async fn backfill_batch_higher_order_candles(
     pool: &Pool,
-    market_name: &str,
+    market: &MarketInfo,
 ) -> anyhow::Result<()> {
+    let market_name = market.name.as_str();
     let earliest_candles = fetch_earliest_candles(pool, market_name, Resolution::R1m).await?;
     let mut start_time = earliest_candles[0].start_time.duration_trunc(day())?;
     while start_time < Utc::now() {
@@ -159,8 +160,8 @@ pub async fn backfill_batch_higher_order_candles(
         )
         .await?;
 
-        for resolution in Resolution::iter() {
-            if resolution == Resolution::R1m {
+        for resolution in market.resolutions.iter().copied() {
+            if resolution == Resolution::R1m || resolution.is_calendar() {
                 continue;
             }
             let mut combined_candles =
@@ -168,15 +169,40 @@ pub async fn backfill_batch_higher_order_candles(
             candles.append(&mut combined_candles);
         }
 
-        let upsert_statement = build_candles_upsert_statement(&candles);
-        let client = pool.get().await.unwrap();
-        client
-            .execute(&upsert_statement, &[])
-            .await
-            .map_err_anyhow()?;
+        upsert_backfilled_candles(pool, candles).await?;
         // println!("{:?} {:?} done", market_name, start_time);
         start_time += day();
     }
 
+    // weeks and months span more than a day, they follow once every day is written
+    let first_day = earliest_candles[0].start_time.duration_trunc(day())?;
+    let days =
+        fetch_candles_from(pool, market_name, Resolution::R1d, first_day, Utc::now()).await?;
+    for resolution in market
+        .resolutions
+        .iter()
+        .copied()
+        .filter(|r| r.is_calendar())
+    {
+        let start = resolution.bucket_start(first_day);
+        let end = resolution.bucket_end(resolution.bucket_start(Utc::now()));
+        upsert_backfilled_candles(pool, combine_candles(&days, resolution, start..end)).await?;
+    }
+
+    Ok(())
+}
+
+async fn upsert_backfilled_candles(pool: &Pool, candles: Vec<Candle>) -> anyhow::Result<()> {
+    if candles.is_empty() {
+        return Ok(());
+    }
+    let upsert_statement = build_candles_upsert_statement(&candles);
+    let mut client = pool.get().await.unwrap();
+    let transaction = revision_transaction(&mut client, REVISION_REASON_BACKFILL).await?;
+    transaction
+        .execute(&upsert_statement, &[])
+        .await
+        .map_err_anyhow()?;
+    transaction.commit().await?;
     Ok(())
 }
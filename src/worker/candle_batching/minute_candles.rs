@@ -3,149 +3,335 @@ use std::{cmp::min, collections::HashMap};
 use chrono::{DateTime, Duration, DurationRound, Utc};
 use deadpool_postgres::Pool;
 use itertools::Itertools;
-use log::debug;
+use tracing::{debug, info};
 
 use crate::database::backfill::{
-    fetch_earliest_fill_multiple_markets, fetch_fills_multiple_markets_from,
-    fetch_last_minute_candles,
+    fetch_fill_retention_watermarks, fetch_fills_multiple_markets_from, fetch_last_minute_candles,
 };
 use crate::{
     database::{
-        fetch::{fetch_earliest_fill, fetch_fills_from, fetch_latest_finished_candle},
+        anomalies::flag_anomalous_fills,
+        fetch::{fetch_fills_from, fetch_latest_finished_candle},
         insert::build_candles_upsert_statement,
+        lifecycle::{fetch_first_fill_time, record_fills_seen},
+        minute_aggregate::fetch_minute_aggregates,
+        revisions::{revision_transaction, REVISION_REASON_BACKFILL},
     },
     structs::{
         candle::{Candle},
+        checkpoint::WorkerCheckpoint,
         markets::MarketInfo,
         openbook::PgOpenBookFill,
         resolution::{day, Resolution},
     },
-    utils::{f64_max, f64_min, AnyhowWrap},
+    utils::AnyhowWrap,
+    worker::candle_batching::aggregate::{
+        self, aggregates_to_minute_candles, fills_to_minute_candles, AggregationOptions,
+        OutlierFilter,
+    },
 };
 
-pub async fn batch_1m_candles(pool: &Pool, market: &MarketInfo) -> anyhow::Result<Vec<Candle>> {
+/// Minute candles from the resume point onwards, and the checkpoint to resume from next time.
+pub struct MinuteBatch {
+    pub candles: Vec<Candle>,
+    /// `None` if no candle was completed, the previous checkpoint still holds then
+    pub checkpoint: Option<WorkerCheckpoint>,
+}
+
+/// Fills newer than `finality_lag` are included but don't complete any candle yet.
+pub async fn batch_1m_candles(
+    pool: &Pool,
+    market: &MarketInfo,
+    checkpoint: Option<&WorkerCheckpoint>,
+    finality_lag: Duration,
+    outlier_filter: Option<OutlierFilter>,
+) -> anyhow::Result<MinuteBatch> {
     let market_name = &market.name;
     let market_address = &market.address;
-    let latest_candle = fetch_latest_finished_candle(pool, market_name, Resolution::R1m).await?;
+    let as_of = Utc::now() - finality_lag;
+    // without a checkpoint, e.g. on the first run after upgrading, derive it from the candles
+    let resume_point = match checkpoint {
+        Some(c) => Some((c.candles_through, c.last_price)),
+        None => fetch_latest_finished_candle(pool, market_name, Resolution::R1m)
+            .await?
+            .map(|c| (c.end_time, c.close)),
+    };
 
-    match latest_candle {
-        Some(candle) => {
-            let start_time = candle.end_time;
+    match resume_point {
+        Some((start_time, last_price)) => {
             let end_time = min(
                 start_time + day(),
                 (Utc::now() + Duration::minutes(1)).duration_trunc(Duration::minutes(1))?,
             );
             let mut fills = fetch_fills_from(pool, market_address, start_time, end_time).await?;
+            record_fills(pool, market_address, &fills).await?;
+            record_anomalies(
+                pool,
+                market_address,
+                &fills,
+                outlier_filter,
+                Some(last_price),
+            )
+            .await?;
 
             let candles = combine_fills_into_1m_candles(
                 &mut fills,
                 market,
                 start_time,
                 end_time,
-                Some(candle.close),
+                Some(last_price),
+                as_of,
+                outlier_filter,
+            );
+            let checkpoint = checkpoint_after(
+                market,
+                &candles,
+                |t| last_fill_before(&fills, t),
+                checkpoint,
             );
-            Ok(candles)
+            Ok(MinuteBatch {
+                candles,
+                checkpoint,
+            })
         }
         None => {
-            let earliest_fill = fetch_earliest_fill(pool, market_address).await?;
+            let first_fill_time = fetch_first_fill_time(pool, market_address).await?;
 
-            if earliest_fill.is_none() {
+            if first_fill_time.is_none() {
                 debug!("No fills found for: {:?}", market_name);
-                return Ok(Vec::new());
+                return Ok(MinuteBatch {
+                    candles: Vec::new(),
+                    checkpoint: None,
+                });
             }
 
-            let start_time = earliest_fill
+            let start_time = first_fill_time
                 .unwrap()
-                .time
                 .duration_trunc(Duration::minutes(1))?;
             let end_time = min(
                 start_time + day(),
                 Utc::now().duration_trunc(Duration::minutes(1))?,
             );
             let mut fills = fetch_fills_from(pool, market_address, start_time, end_time).await?;
+            record_fills(pool, market_address, &fills).await?;
+            record_anomalies(pool, market_address, &fills, outlier_filter, None).await?;
             if !fills.is_empty() {
-                let candles =
-                    combine_fills_into_1m_candles(&mut fills, market, start_time, end_time, None);
-                Ok(candles)
+                let candles = combine_fills_into_1m_candles(
+                    &mut fills,
+                    market,
+                    start_time,
+                    end_time,
+                    None,
+                    as_of,
+                    outlier_filter,
+                );
+                let checkpoint =
+                    checkpoint_after(market, &candles, |t| last_fill_before(&fills, t), None);
+                Ok(MinuteBatch {
+                    candles,
+                    checkpoint,
+                })
             } else {
-                Ok(Vec::new())
+                Ok(MinuteBatch {
+                    candles: Vec::new(),
+                    checkpoint: None,
+                })
             }
         }
     }
 }
 
-fn combine_fills_into_1m_candles(
-    fills: &mut Vec<PgOpenBookFill>,
+/// Like `batch_1m_candles`, but from the minute aggregates TimescaleDB maintains over the fills,
+/// so catching up reads one row per minute instead of every fill. The outlier filter doesn't
+/// apply, the aggregates are of every fill.
+pub async fn batch_1m_candles_from_aggregate(
+    pool: &Pool,
     market: &MarketInfo,
-    st: DateTime<Utc>,
-    et: DateTime<Utc>,
-    maybe_last_price: Option<f64>,
-) -> Vec<Candle> {
-    let empty_candle = Candle::create_empty_candle(market.name.clone(), Resolution::R1m);
-
-    let minutes = (et - st).num_minutes();
-    let mut candles = vec![empty_candle; minutes as usize];
-
-    let mut fills_iter = fills.iter_mut().peekable();
-    let mut start_time = st;
-    let mut end_time = start_time + Duration::minutes(1);
-
-    let mut last_price = match maybe_last_price {
-        Some(p) => p,
-        None => {
-            let first = fills_iter.peek().unwrap();
-            first.price
-        }
+    checkpoint: Option<&WorkerCheckpoint>,
+    finality_lag: Duration,
+) -> anyhow::Result<MinuteBatch> {
+    let as_of = Utc::now() - finality_lag;
+    let resume_point = match checkpoint {
+        Some(c) => Some((c.candles_through, c.last_price)),
+        None => fetch_latest_finished_candle(pool, &market.name, Resolution::R1m)
+            .await?
+            .map(|c| (c.end_time, c.close)),
+    };
+    let (start_time, last_price) = match resume_point {
+        Some((start_time, last_price)) => (start_time, Some(last_price)),
+        None => match fetch_first_fill_time(pool, &market.address).await? {
+            Some(t) => (t.duration_trunc(Duration::minutes(1))?, None),
+            None => {
+                debug!("No fills found for: {:?}", market.name);
+                return Ok(MinuteBatch {
+                    candles: Vec::new(),
+                    checkpoint: None,
+                });
+            }
+        },
     };
+    let end_time = min(
+        start_time + day(),
+        (Utc::now() + Duration::minutes(1)).duration_trunc(Duration::minutes(1))?,
+    );
 
-    for i in 0..candles.len() {
-        candles[i].open = last_price;
-        candles[i].close = last_price;
-        candles[i].low = last_price;
-        candles[i].high = last_price;
+    let aggregates = fetch_minute_aggregates(pool, &market.address, start_time, end_time).await?;
+    if let (Some(first), Some(last)) = (aggregates.first(), aggregates.last()) {
+        record_fills_seen(
+            pool,
+            &market.address,
+            first.first_fill_time,
+            last.last_fill_time,
+        )
+        .await?;
+    }
+    let options = AggregationOptions {
+        market_name: market.name.clone(),
+        last_price,
+        as_of,
+        outlier_filter: None,
+    };
+    let candles = aggregates_to_minute_candles(&aggregates, start_time..end_time, &options);
+    let checkpoint = checkpoint_after(
+        market,
+        &candles,
+        |t| {
+            aggregates
+                .iter()
+                .rev()
+                .find(|a| a.start_time < t)
+                .map(|a| (a.last_fill_time, a.last_slot))
+        },
+        checkpoint,
+    );
+    Ok(MinuteBatch {
+        candles,
+        checkpoint,
+    })
+}
 
-        while matches!(fills_iter.peek(), Some(f) if f.time < end_time) {
-            let fill = fills_iter.next().unwrap();
+/// Checkpoint at the newest complete candle, with the time and slot of the last fill that went
+/// into it as found by `last_fill_before`.
+fn checkpoint_after(
+    market: &MarketInfo,
+    candles: &[Candle],
+    last_fill_before: impl Fn(DateTime<Utc>) -> Option<(DateTime<Utc>, i64)>,
+    previous: Option<&WorkerCheckpoint>,
+) -> Option<WorkerCheckpoint> {
+    let newest_complete = candles.iter().rev().find(|c| c.complete)?;
+    let last_fill = last_fill_before(newest_complete.end_time);
+    Some(WorkerCheckpoint {
+        market_name: market.name.clone(),
+        candles_through: newest_complete.end_time,
+        last_price: newest_complete.close,
+        last_fill_time: last_fill
+            .map(|(time, _)| time)
+            .or_else(|| previous.and_then(|p| p.last_fill_time)),
+        last_fill_slot: last_fill
+            .map(|(_, slot)| slot)
+            .or_else(|| previous.and_then(|p| p.last_fill_slot)),
+    })
+}
 
-            candles[i].close = fill.price;
-            candles[i].low = f64_min(fill.price, candles[i].low);
-            candles[i].high = f64_max(fill.price, candles[i].high);
-            candles[i].volume += fill.size;
+fn last_fill_before(fills: &[PgOpenBookFill], time: DateTime<Utc>) -> Option<(DateTime<Utc>, i64)> {
+    fills
+        .iter()
+        .rev()
+        .find(|f| f.time < time)
+        .map(|f| (f.time, f.slot))
+}
 
-            last_price = fill.price;
+async fn record_fills(
+    pool: &Pool,
+    market_address: &str,
+    fills: &[PgOpenBookFill],
+) -> anyhow::Result<()> {
+    match (fills.first(), fills.last()) {
+        (Some(first), Some(last)) => {
+            record_fills_seen(pool, market_address, first.time, last.time).await
         }
+        _ => Ok(()),
+    }
+}
 
-        candles[i].start_time = start_time;
-        candles[i].end_time = end_time;
-        candles[i].complete = matches!(fills_iter.peek(), Some(f) if f.time > end_time)
-            || end_time < Utc::now() - Duration::minutes(10);
-        start_time = end_time;
-        end_time += Duration::minutes(1);
+/// Flags the fills the outlier filter leaves out of the candles, with the same seed price the
+/// candles are built with.
+async fn record_anomalies(
+    pool: &Pool,
+    market_address: &str,
+    fills: &[PgOpenBookFill],
+    outlier_filter: Option<OutlierFilter>,
+    maybe_last_price: Option<f64>,
+) -> anyhow::Result<()> {
+    let filter = match outlier_filter {
+        Some(filter) => filter,
+        None => return Ok(()),
+    };
+    let anomalous = aggregate::flag_anomalous_fills(fills, &filter, maybe_last_price);
+    let seq_nums: Vec<i64> = fills
+        .iter()
+        .zip(anomalous)
+        .filter(|(_, anomalous)| *anomalous)
+        .map(|(f, _)| f.seq_num)
+        .collect();
+    let flagged = flag_anomalous_fills(pool, market_address, &seq_nums).await?;
+    if flagged > 0 {
+        info!("Flagged {} anomalous fills of {}", flagged, market_address);
     }
+    Ok(())
+}
 
-    candles
+fn combine_fills_into_1m_candles(
+    fills: &[PgOpenBookFill],
+    market: &MarketInfo,
+    st: DateTime<Utc>,
+    et: DateTime<Utc>,
+    maybe_last_price: Option<f64>,
+    as_of: DateTime<Utc>,
+    outlier_filter: Option<OutlierFilter>,
+) -> Vec<Candle> {
+    let options = AggregationOptions {
+        market_name: market.name.clone(),
+        last_price: maybe_last_price,
+        as_of,
+        outlier_filter,
+    };
+    fills_to_minute_candles(fills, st..et, &options)
 }
 
 /// Goes from the earliest fill to the most recent. Will mark candles as complete if there are missing gaps of fills between the start and end.
 pub async fn backfill_batch_1m_candles(
     pool: &Pool,
     markets: Vec<MarketInfo>,
+    outlier_filter: Option<OutlierFilter>,
 ) -> anyhow::Result<()> {
     let market_address_strings: Vec<String> = markets.iter().map(|m| m.address.clone()).collect();
     let mut candle_container = HashMap::new();
-    let client = pool.get().await?;
+    let mut client = pool.get().await?;
+
+    // fills before these were pruned, the candles there are kept rather than rebuilt from a partial set
+    let watermarks = fetch_fill_retention_watermarks(&client, &market_address_strings).await?;
+    for (market_address, pruned_before) in watermarks.iter() {
+        info!(
+            "Fills for {} were pruned before {}, skipping earlier candles",
+            market_address, pruned_before
+        );
+    }
 
-    let earliest_fill =
-        fetch_earliest_fill_multiple_markets(&client, &market_address_strings).await?;
-    if earliest_fill.is_none() {
-        println!("No fills found for backfill");
+    let mut earliest_fill_time = None;
+    for market in markets.iter() {
+        if let Some(t) = fetch_first_fill_time(pool, &market.address).await? {
+            earliest_fill_time = Some(earliest_fill_time.map_or(t, |e: DateTime<Utc>| e.min(t)));
+        }
+    }
+    if earliest_fill_time.is_none() {
+        info!("No fills found for backfill");
         return Ok(());
     }
-    println!("Found earliset fill for backfill");
+    info!("Found earliset fill for backfill");
 
-    let mut start_time = earliest_fill
+    let mut start_time = earliest_fill_time
         .unwrap()
-        .time
         .duration_trunc(Duration::minutes(1))?;
     while start_time < Utc::now() {
         let end_time = min(
@@ -162,7 +348,12 @@ pub async fn backfill_batch_1m_candles(
         .await?;
         // println!("{:?} {:?}", start_time, end_time);
         // println!("all fills len : {:?}", all_fills.len());
-        println!("{:?}", all_fills[0]);
+        debug!(
+            "{} fills from {} to {}",
+            all_fills.len(),
+            start_time,
+            end_time
+        );
         // println!("{:?}", all_fills[1]);
         // println!("Fetched multiple fills for backfill");
         let fills_groups = all_fills
@@ -175,15 +366,23 @@ pub async fn backfill_batch_1m_candles(
             .map(|(m, group)| (m, group.collect()))
             .collect();
 
-        println!("fbm len : {:?}", fills_by_market.len());
+        debug!("fbm len : {:?}", fills_by_market.len());
         // sort fills by market, make candles
         for (_, mut fills) in fills_by_market {
             let market = markets
                 .iter()
                 .find(|m| m.address == fills[0].market_key)
                 .unwrap();
-            let minute_candles =
-                combine_fills_into_1m_candles(&mut fills, market, start_time, end_time, None);
+            record_anomalies(pool, &market.address, &fills, outlier_filter, None).await?;
+            let minute_candles = combine_fills_into_1m_candles(
+                &mut fills,
+                market,
+                start_time,
+                end_time,
+                None,
+                Utc::now(),
+                outlier_filter,
+            );
             candle_container.insert(&market.address, minute_candles);
         }
 
@@ -201,28 +400,41 @@ pub async fn backfill_batch_1m_candles(
                     start_time,
                     end_time,
                     Some(last_candle.close),
+                    Utc::now(),
+                    outlier_filter,
                 );
                 *v = empty_candles;
             }
         }
 
         // insert candles in batches
-        for candles in candle_container.values() {
+        for (market_address, candles) in candle_container.iter() {
+            let candles: Vec<Candle> = match watermarks.get(*market_address) {
+                Some(pruned_before) => candles
+                    .iter()
+                    .filter(|c| c.start_time >= *pruned_before)
+                    .cloned()
+                    .collect(),
+                None => candles.clone(),
+            };
             let candle_chunks: Vec<Vec<Candle>> =
                 candles.chunks(1500).map(|chunk| chunk.to_vec()).collect(); // 1440 minutes in a day
             for c in candle_chunks {
                 let upsert_statement = build_candles_upsert_statement(&c);
-                client
+                let transaction =
+                    revision_transaction(&mut client, REVISION_REASON_BACKFILL).await?;
+                transaction
                     .execute(&upsert_statement, &[])
                     .await
                     .map_err_anyhow()?;
+                transaction.commit().await?;
             }
         }
         // reset entries but keep markets we've seen for blank candles
         for (_, v) in candle_container.iter_mut() {
             *v = vec![];
         }
-        println!("day done");
+        info!("Backfilled 1m candles up to {}", end_time);
         start_time += day();
     }
     Ok(())
@@ -1,76 +1,377 @@
+pub mod aggregate;
 pub mod higher_order_candles;
 pub mod minute_candles;
 
+use std::{collections::HashMap, sync::Arc};
+
 use chrono::Duration;
 use deadpool_postgres::Pool;
-use log::{error, warn};
-use strum::IntoEnumIterator;
-use tokio::time::sleep;
+use serde_derive::{Deserialize, Serialize};
+use tokio::{sync::Semaphore, time::sleep};
+use tracing::{error, info, instrument, warn};
 
 use crate::{
-    database::insert::build_candles_upsert_statement,
-    structs::{candle::Candle, markets::MarketInfo, resolution::Resolution},
+    database::{
+        checkpoints::{fetch_worker_checkpoint, save_worker_checkpoint},
+        insert::{CandleColumns, CANDLES_UPSERT_RETURNING_CHANGES},
+        lifecycle::{record_batch_error, record_candles_through},
+        reconciliation::{fetch_first_stale_minute, invalidate_candles_from},
+        revisions::{revision_transaction, REVISION_REASON_BATCH},
+    },
+    structs::{
+        candle::Candle, candle_cache::CandleCache, checkpoint::WorkerCheckpoint,
+        markets::MarketInfo,
+    },
     utils::AnyhowWrap,
-    worker::candle_batching::minute_candles::batch_1m_candles,
+    worker::{
+        candle_batching::minute_candles::{batch_1m_candles, batch_1m_candles_from_aggregate},
+        cluster::MarketAssignment,
+        shutdown::Shutdown,
+    },
+};
+
+use self::{aggregate::OutlierFilter, higher_order_candles::OpenBuckets};
+
+use super::metrics::{
+    METRIC_BATCH_ERRORS_TOTAL, METRIC_CANDLES_TOTAL, METRIC_CANDLE_UPSERTS_TOTAL,
+    METRIC_COMPLETE_CANDLE_MUTATIONS_TOTAL, METRIC_REOPENED_CANDLES_TOTAL,
 };
 
-use self::higher_order_candles::batch_higher_order_candles;
+fn default_candle_outlier_window() -> usize {
+    20
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OutlierConfig {
+    /// Fills further than this many percent from the rolling median price are left out of candle
+    /// prices. Unset builds candles from every fill.
+    pub candle_outlier_max_deviation_pct: Option<f64>,
+    /// Number of previous fills the median is taken over
+    #[serde(default = "default_candle_outlier_window")]
+    pub candle_outlier_window: usize,
+}
 
-use super::metrics::METRIC_CANDLES_TOTAL;
+impl OutlierConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn outlier_filter(&self) -> Option<OutlierFilter> {
+        self.candle_outlier_max_deviation_pct
+            .map(|pct| OutlierFilter {
+                max_deviation: pct / 100.0,
+                window: self.candle_outlier_window.max(1),
+            })
+    }
+}
+
+fn default_batch_max_concurrency() -> usize {
+    16
+}
+
+fn default_batch_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_candle_late_fill_window_secs() -> u64 {
+    3600
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchingConfig {
+    /// Markets whose batches may run at the same time, the rest wait for a free slot
+    #[serde(default = "default_batch_max_concurrency")]
+    pub batch_max_concurrency: usize,
+    /// Longest wait before retrying a market whose batches keep failing
+    #[serde(default = "default_batch_max_backoff_secs")]
+    pub batch_max_backoff_secs: u64,
+    /// How far back complete candles are checked against their fills and reopened when fills
+    /// arrived after they closed, 0 turns the check off
+    #[serde(default = "default_candle_late_fill_window_secs")]
+    pub candle_late_fill_window_secs: u64,
+}
+
+impl BatchingConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn limiter(&self) -> BatchLimiter {
+        BatchLimiter {
+            permits: Arc::new(Semaphore::new(self.batch_max_concurrency.max(1))),
+            max_backoff: Duration::seconds(self.batch_max_backoff_secs as i64),
+        }
+    }
+
+    pub fn late_fill_window(&self) -> Duration {
+        Duration::seconds(self.candle_late_fill_window_secs as i64)
+    }
+}
+
+/// Shared by every market's batching task to bound the database work running at once.
+#[derive(Clone, Debug)]
+pub struct BatchLimiter {
+    permits: Arc<Semaphore>,
+    max_backoff: Duration,
+}
 
-pub async fn batch_for_market(pool: &Pool, market: &MarketInfo) -> anyhow::Result<()> {
+impl BatchLimiter {
+    /// Wait before the next batch of a market that failed `failures` times in a row
+    fn delay(&self, failures: u32) -> Duration {
+        let delay = Duration::milliseconds(BATCH_INTERVAL_MS << failures.min(16));
+        delay.min(
+            self.max_backoff
+                .max(Duration::milliseconds(BATCH_INTERVAL_MS)),
+        )
+    }
+}
+
+/// How each market's fills are turned into candles
+#[derive(Clone, Copy, Debug)]
+pub struct BatchOptions {
+    /// Fills newer than this are included but don't complete any candle yet
+    pub finality_lag: Duration,
+    pub outlier_filter: Option<OutlierFilter>,
+    /// Read minute candles from the TimescaleDB continuous aggregate instead of the fills
+    pub minute_aggregate: bool,
+    /// Complete minute candles this far before the checkpoint are reopened when their fills changed
+    pub late_fill_window: Duration,
+}
+
+/// Time between two batches of a market
+const BATCH_INTERVAL_MS: i64 = 5000;
+
+pub async fn batch_for_market(
+    pool: &Pool,
+    market: &MarketInfo,
+    assignment: &MarketAssignment,
+    shared_cache: Option<Arc<CandleCache>>,
+    options: BatchOptions,
+    limiter: BatchLimiter,
+    mut shutdown: Shutdown,
+) -> anyhow::Result<()> {
+    let mut failures = 0;
     loop {
         let market_clone = market.clone();
+        // rebuilt after a failure, the failed batch may have left them half advanced
+        let mut open_buckets: Option<OpenBuckets> = None;
         loop {
-            sleep(Duration::milliseconds(5000).to_std()?).await;
-            match batch_inner(pool, &market_clone).await {
-                Ok(_) => {}
+            // a batch in flight runs to completion, shutdown only cuts the wait between batches
+            tokio::select! {
+                _ = sleep(limiter.delay(failures).to_std()?) => {}
+                _ = shutdown.requested() => {
+                    info!("Stopped batching {}", market.name);
+                    return Ok(());
+                }
+            }
+            // another replica batches this market
+            if !assignment.owns(&market_clone.address) {
+                open_buckets = None;
+                continue;
+            }
+            let permit = tokio::select! {
+                permit = limiter.permits.acquire() => permit?,
+                _ = shutdown.requested() => {
+                    info!("Stopped batching {}", market.name);
+                    return Ok(());
+                }
+            };
+            let batch = batch_inner(
+                pool,
+                &market_clone,
+                shared_cache.as_deref(),
+                options,
+                &mut open_buckets,
+            );
+            let result = batch.await;
+            drop(permit);
+            match result {
+                Ok(_) => failures = 0,
                 Err(e) => {
+                    failures += 1;
+                    METRIC_BATCH_ERRORS_TOTAL
+                        .with_label_values(&[market_clone.name.as_str()])
+                        .inc();
                     error!(
                         "Batching thread failed for {:?} with error: {:?}",
                         market_clone.name.clone(),
                         e
                     );
+                    if let Err(e) = record_batch_error(pool, &market_clone.address).await {
+                        warn!("Failed to record batch error: {:?}", e);
+                    }
                     break;
                 }
             };
         }
-        warn!("Restarting {:?} batching thread", market.name);
+        warn!(
+            "Restarting {:?} batching thread in {}s",
+            market.name,
+            limiter.delay(failures).num_seconds()
+        );
     }
 }
 
-async fn batch_inner(pool: &Pool, market: &MarketInfo) -> anyhow::Result<()> {
+#[instrument(skip_all, fields(market = %market.name))]
+async fn batch_inner(
+    pool: &Pool,
+    market: &MarketInfo,
+    shared_cache: Option<&CandleCache>,
+    options: BatchOptions,
+    open_buckets: &mut Option<OpenBuckets>,
+) -> anyhow::Result<()> {
     let market_name = &market.name.clone();
-    let candles = batch_1m_candles(pool, market).await?;
+    // read every batch, another replica or fill reconciliation may have moved it
+    let checkpoint = fetch_worker_checkpoint(pool, market_name).await?;
+    if let Some(checkpoint) = checkpoint.as_ref() {
+        if reopen_late_candles(pool, market, checkpoint, options.late_fill_window).await? {
+            // rebuilt from the reopened candle on the next batch
+            *open_buckets = None;
+            return Ok(());
+        }
+    }
+    let batch = if options.minute_aggregate {
+        batch_1m_candles_from_aggregate(pool, market, checkpoint.as_ref(), options.finality_lag)
+            .await?
+    } else {
+        batch_1m_candles(
+            pool,
+            market,
+            checkpoint.as_ref(),
+            options.finality_lag,
+            options.outlier_filter,
+        )
+        .await?
+    };
+    let mut candles = batch.candles;
     if candles.is_empty() {
         return Ok(());
     }
+    let complete_through = candles
+        .iter()
+        .filter(|c| c.complete)
+        .map(|c| c.end_time)
+        .max();
+
+    // the higher resolutions come from the same minute candles, the open buckets only have to be
+    // read back when the batch doesn't continue where the last one left off
+    let batch_start = candles[0].start_time;
+    let mut buckets = match open_buckets.take() {
+        Some(b) if b.through == batch_start => b,
+        _ => OpenBuckets::load(pool, market_name, &market.resolutions, batch_start).await?,
+    };
+    let mut higher_order_candles = buckets.advance(&candles);
+    candles.append(&mut higher_order_candles);
     METRIC_CANDLES_TOTAL
         .with_label_values(&[market.name.as_str()])
-        .inc_by(candles.clone().len() as u64);
+        .inc_by(candles.len() as u64);
     save_candles(pool, candles).await?;
-    for resolution in Resolution::iter() {
-        if resolution == Resolution::R1m {
-            continue;
+    if let Some(through) = complete_through {
+        record_candles_through(pool, &market.address, through).await?;
+    }
+    // only move the checkpoint once everything derived from the batch is saved
+    if let Some(next) = batch.checkpoint {
+        save_worker_checkpoint(pool, &next).await?;
+    }
+    *open_buckets = Some(buckets);
+    // let server instances pick up the new candles without querying the database themselves
+    if let Some(cache) = shared_cache {
+        if let Err(e) = cache.refresh_latest_buckets(pool, market_name).await {
+            warn!(
+                "Failed to refresh shared candle cache for {}: {:?}",
+                market_name, e
+            );
         }
-        let candles = batch_higher_order_candles(pool, market_name, resolution).await?;
-        METRIC_CANDLES_TOTAL
-            .with_label_values(&[market.name.as_str()])
-            .inc_by(candles.clone().len() as u64);
-        save_candles(pool, candles).await?;
     }
     Ok(())
 }
 
+#[instrument(skip_all, fields(count = candles.len()), err)]
 async fn save_candles(pool: &Pool, candles: Vec<Candle>) -> anyhow::Result<()> {
     if candles.is_empty() {
         return Ok(());
     }
-    let upsert_statement = build_candles_upsert_statement(&candles);
-    let client = pool.get().await.unwrap();
-    client
-        .execute(&upsert_statement, &[])
+    let columns = CandleColumns::from_candles(&candles);
+    let mut client = pool.get().await.unwrap();
+    let transaction = revision_transaction(&mut client, REVISION_REASON_BATCH).await?;
+    let upsert_statement = transaction
+        .prepare_cached(CANDLES_UPSERT_RETURNING_CHANGES)
         .await
         .map_err_anyhow()?;
+    let rows = transaction
+        .query(&upsert_statement, &columns.params())
+        .await
+        .map_err_anyhow()?;
+    transaction.commit().await?;
+    record_upsert_metrics(&candles, &rows);
     Ok(())
 }
+
+/// Looks for fills that arrived after the minute candles they fall into were completed, e.g. from
+/// a source that lags behind the others, and reopens every candle from the earliest such minute
+/// so the batcher recomputes them. Fills older than the window are not picked up.
+async fn reopen_late_candles(
+    pool: &Pool,
+    market: &MarketInfo,
+    checkpoint: &WorkerCheckpoint,
+    window: Duration,
+) -> anyhow::Result<bool> {
+    if window <= Duration::zero() {
+        return Ok(false);
+    }
+    let through = checkpoint.candles_through;
+    let stale = fetch_first_stale_minute(
+        pool,
+        &market.name,
+        &market.address,
+        through - window,
+        through,
+    )
+    .await?;
+    match stale {
+        Some(from) => {
+            warn!(
+                "Fills of {} arrived after the candle at {} was complete, reopening",
+                market.name, from
+            );
+            invalidate_candles_from(pool, &market.name, from).await?;
+            METRIC_REOPENED_CANDLES_TOTAL
+                .with_label_values(&[market.name.as_str()])
+                .inc();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Counts inserts, updates and no-op writes per market, and updates of complete candles, which
+/// mean fills turned up or changed after a candle was closed.
+fn record_upsert_metrics(candles: &[Candle], rows: &[tokio_postgres::Row]) {
+    let mut unchanged: HashMap<&str, u64> = HashMap::new();
+    for candle in candles.iter() {
+        *unchanged.entry(candle.market_name.as_str()).or_default() += 1;
+    }
+    for row in rows.iter() {
+        let market_name: String = row.get(0);
+        let resolution: String = row.get(1);
+        let inserted: bool = row.get(2);
+        let was_complete: bool = row.get(3);
+        if let Some(count) = unchanged.get_mut(market_name.as_str()) {
+            *count = count.saturating_sub(1);
+        }
+        let result = if inserted { "inserted" } else { "updated" };
+        METRIC_CANDLE_UPSERTS_TOTAL
+            .with_label_values(&[&market_name, result])
+            .inc();
+        if !inserted && was_complete {
+            METRIC_COMPLETE_CANDLE_MUTATIONS_TOTAL
+                .with_label_values(&[&market_name, &resolution])
+                .inc();
+        }
+    }
+    for (market_name, count) in unchanged {
+        METRIC_CANDLE_UPSERTS_TOTAL
+            .with_label_values(&[market_name, "unchanged"])
+            .inc_by(count);
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::Duration;
+use deadpool_postgres::Pool;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use super::metrics::METRIC_CLUSTER_REPLICAS;
+
+fn default_worker_heartbeat_secs() -> i64 {
+    10
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ClusterConfig {
+    /// When unset every replica works on every market
+    #[serde(default)]
+    pub worker_cluster_enabled: bool,
+    /// Defaults to the hostname and process id
+    pub worker_replica_id: Option<String>,
+    #[serde(default = "default_worker_heartbeat_secs")]
+    pub worker_heartbeat_secs: i64,
+}
+
+impl ClusterConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    fn replica_id(&self) -> String {
+        self.worker_replica_id.clone().unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string());
+            format!("{}-{}", host, std::process::id())
+        })
+    }
+}
+
+/// Which markets this replica is responsible for. Markets are assigned to live replicas with
+/// rendezvous hashing, so when a replica joins or leaves only its share of markets moves.
+#[derive(Clone)]
+pub struct MarketAssignment {
+    replica_id: String,
+    /// None when clustering is disabled
+    replicas: Arc<RwLock<Option<Vec<String>>>>,
+}
+
+impl MarketAssignment {
+    /// An assignment that owns every market, for single replica deployments
+    pub fn everything() -> Self {
+        MarketAssignment {
+            replica_id: String::new(),
+            replicas: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn owns(&self, market_address: &str) -> bool {
+        match self.replicas.read().unwrap().as_ref() {
+            Some(replicas) => {
+                rendezvous_owner(market_address, replicas) == Some(self.replica_id.as_str())
+            }
+            None => true,
+        }
+    }
+
+    fn set_replicas(&self, replicas: Vec<String>) {
+        let mut current = self.replicas.write().unwrap();
+        if current.as_ref() != Some(&replicas) {
+            info!("Live worker replicas: {:?}", replicas);
+            METRIC_CLUSTER_REPLICAS.set(replicas.len() as i64);
+            *current = Some(replicas);
+        }
+    }
+}
+
+/// FNV-1a, stable across builds and platforms unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn rendezvous_owner<'a>(market_address: &str, replicas: &'a [String]) -> Option<&'a str> {
+    replicas
+        .iter()
+        .max_by_key(|r| fnv1a(format!("{}:{}", market_address, r).as_bytes()))
+        .map(|r| r.as_str())
+}
+
+/// Registers this replica and returns its market assignment. When clustering is enabled the
+/// returned assignment is kept up to date by `maintain_membership`.
+pub async fn join_cluster(pool: &Pool, config: &ClusterConfig) -> anyhow::Result<MarketAssignment> {
+    if !config.worker_cluster_enabled {
+        return Ok(MarketAssignment::everything());
+    }
+    let assignment = MarketAssignment {
+        replica_id: config.replica_id(),
+        replicas: Arc::new(RwLock::new(None)),
+    };
+    info!("Joining worker cluster as {}", assignment.replica_id);
+    refresh_membership(pool, config, &assignment).await?;
+    Ok(assignment)
+}
+
+pub async fn maintain_membership(
+    pool: &Pool,
+    config: &ClusterConfig,
+    assignment: MarketAssignment,
+) -> anyhow::Result<()> {
+    loop {
+        sleep(Duration::seconds(config.worker_heartbeat_secs).to_std()?).await;
+        if let Err(e) = refresh_membership(pool, config, &assignment).await {
+            warn!("Failed to refresh worker cluster membership: {:?}", e);
+        }
+    }
+}
+
+async fn refresh_membership(
+    pool: &Pool,
+    config: &ClusterConfig,
+    assignment: &MarketAssignment,
+) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+    // a replica is considered gone after missing three heartbeats
+    let expiry = config.worker_heartbeat_secs * 3;
+
+    client
+        .execute(
+            r#"INSERT INTO openbook.worker_replicas (replica_id, heartbeat_at)
+            VALUES ($1, now())
+            ON CONFLICT (replica_id) DO UPDATE SET heartbeat_at = now()"#,
+            &[&assignment.replica_id],
+        )
+        .await?;
+    client
+        .execute(
+            "DELETE FROM openbook.worker_replicas WHERE heartbeat_at < now() - $1 * interval '1 second'",
+            &[&(expiry as f64)],
+        )
+        .await?;
+    let replicas: Vec<String> = client
+        .query(
+            "SELECT replica_id FROM openbook.worker_replicas ORDER BY replica_id",
+            &[],
+        )
+        .await?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+
+    assignment.set_replicas(replicas);
+    Ok(())
+}
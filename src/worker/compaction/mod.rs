@@ -0,0 +1,167 @@
+use chrono::DurationRound;
+use deadpool_postgres::Pool;
+use itertools::Itertools;
+use tracing::info;
+
+use crate::{
+    database::{
+        compaction::{
+            candle_exists, delete_candles_by_id, fetch_duplicate_candles, fetch_misaligned_candles,
+            PgCandleRow,
+        },
+        fetch::fetch_fills_from,
+        retention::fetch_fill_retention_watermark,
+    },
+    structs::{
+        candle::Candle, markets::MarketInfo, openbook::PgOpenBookFill, resolution::Resolution,
+    },
+    utils::{f64_max, f64_min},
+};
+
+/// Relative tolerance used when comparing stored candles to values recomputed from fills
+const TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Debug, Default)]
+pub struct CompactionReport {
+    pub market_name: String,
+    pub resolution: String,
+    pub duplicate_groups: usize,
+    pub misaligned_rows: usize,
+    pub deleted_rows: u64,
+    /// Conflicts left untouched because no row could be verified against fills
+    pub unverified: usize,
+}
+
+/// OHLCV of a bucket recomputed straight from fills; open depends on the previous bucket so it's not checked
+#[derive(Clone, Debug, PartialEq)]
+struct FillSummary {
+    close: f64,
+    high: f64,
+    low: f64,
+    volume: f64,
+}
+
+/// Finds duplicate and misaligned candle rows and, when `apply` is set, deletes the ones that
+/// can be shown to be redundant. Returns one report per market and resolution with conflicts.
+pub async fn compact_candles(
+    pool: &Pool,
+    markets: &Vec<MarketInfo>,
+    apply: bool,
+) -> anyhow::Result<Vec<CompactionReport>> {
+    let mut reports = vec![];
+    for market in markets.iter() {
+        for resolution in market.resolutions.iter().copied() {
+            let report = compact_market_resolution(pool, market, resolution, apply).await?;
+            if report.duplicate_groups > 0 || report.misaligned_rows > 0 {
+                info!("{:?}", report);
+                reports.push(report);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+async fn compact_market_resolution(
+    pool: &Pool,
+    market: &MarketInfo,
+    resolution: Resolution,
+    apply: bool,
+) -> anyhow::Result<CompactionReport> {
+    let mut report = CompactionReport {
+        market_name: market.name.clone(),
+        resolution: resolution.to_string(),
+        ..Default::default()
+    };
+    let mut to_delete: Vec<i64> = vec![];
+
+    let pruned_before = fetch_fill_retention_watermark(pool, &market.address).await?;
+    let duplicates = fetch_duplicate_candles(pool, &market.name, resolution).await?;
+    let duplicate_groups: Vec<Vec<PgCandleRow>> = duplicates
+        .into_iter()
+        .group_by(|r| r.candle.start_time)
+        .into_iter()
+        .map(|(_, group)| group.collect())
+        .collect();
+    for group in duplicate_groups {
+        report.duplicate_groups += 1;
+
+        let first = &group[0].candle;
+        // fills for this bucket were pruned, an empty set would prove nothing
+        if matches!(pruned_before, Some(t) if first.start_time < t) {
+            report.unverified += 1;
+            continue;
+        }
+        let fills =
+            fetch_fills_from(pool, &market.address, first.start_time, first.end_time).await?;
+        let expected = summarize_fills(&fills);
+
+        // keep the most recent row that agrees with the fills
+        let keep = group
+            .iter()
+            .rev()
+            .find(|r| matches_fills(&r.candle, &expected));
+        match keep {
+            Some(keep) => to_delete.extend(group.iter().filter(|r| r.id != keep.id).map(|r| r.id)),
+            None => report.unverified += 1,
+        }
+    }
+
+    // weeks and months don't have a fixed length to check the alignment against
+    let misaligned = if resolution.is_calendar() {
+        vec![]
+    } else {
+        fetch_misaligned_candles(pool, &market.name, resolution).await?
+    };
+    for row in misaligned.iter() {
+        report.misaligned_rows += 1;
+        let bucket_start = row
+            .candle
+            .start_time
+            .duration_trunc(resolution.get_duration())?;
+        // only drop a stray row if the canonical bucket covering it exists
+        if candle_exists(pool, &market.name, resolution, bucket_start).await? {
+            to_delete.push(row.id);
+        } else {
+            report.unverified += 1;
+        }
+    }
+
+    if apply && !to_delete.is_empty() {
+        report.deleted_rows = delete_candles_by_id(pool, &to_delete).await?;
+    }
+    Ok(report)
+}
+
+fn summarize_fills(fills: &[PgOpenBookFill]) -> Option<FillSummary> {
+    let first = fills.first()?;
+    let mut summary = FillSummary {
+        close: first.price,
+        high: first.price,
+        low: first.price,
+        volume: 0.0,
+    };
+    for fill in fills.iter() {
+        summary.close = fill.price;
+        summary.high = f64_max(summary.high, fill.price);
+        summary.low = f64_min(summary.low, fill.price);
+        summary.volume += fill.size;
+    }
+    Some(summary)
+}
+
+fn matches_fills(candle: &Candle, expected: &Option<FillSummary>) -> bool {
+    match expected {
+        Some(e) => {
+            approx_eq(candle.volume, e.volume)
+                && approx_eq(candle.close, e.close)
+                && approx_eq(candle.high, f64_max(e.high, candle.open))
+                && approx_eq(candle.low, f64_min(e.low, candle.open))
+        }
+        // an empty bucket carries the previous close forward
+        None => candle.volume == 0.0 && candle.open == candle.close,
+    }
+}
+
+fn approx_eq(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TOLERANCE * f64_max(a.abs(), b.abs())
+}
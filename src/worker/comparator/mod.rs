@@ -0,0 +1,179 @@
+use chrono::{Duration, DurationRound, Utc};
+use deadpool_postgres::Pool;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{
+    database::{
+        fetch::fetch_candles_from,
+        insert::{DivergenceColumns, DIVERGENCE_UPSERT},
+    },
+    structs::{
+        divergence::{BirdeyeOhlcvResponse, CandleDivergence, ExternalCandle},
+        markets::MarketInfo,
+        resolution::Resolution,
+    },
+    utils::{to_timestampz, AnyhowWrap},
+};
+
+const PROVIDER: &str = "birdeye";
+/// Bars are compared at this resolution, over the trailing `comparison_window()`
+const COMPARISON_RESOLUTION: Resolution = Resolution::R15m;
+
+fn comparison_window() -> Duration {
+    Duration::hours(2)
+}
+
+fn default_comparator_url() -> String {
+    "https://public-api.birdeye.so/defi/ohlcv/pair".to_string()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ComparatorConfig {
+    /// The comparator is disabled unless an API key is configured
+    #[serde(serialize_with = "crate::utils::settings::redact")]
+    pub comparator_api_key: Option<String>,
+    #[serde(default = "default_comparator_url")]
+    pub comparator_url: String,
+    /// Comma separated market names, all markets are compared if unset
+    pub comparator_markets: Option<String>,
+}
+
+impl ComparatorConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.comparator_api_key.is_some()
+    }
+
+    pub fn selected_markets(&self, markets: &[MarketInfo]) -> Vec<MarketInfo> {
+        match &self.comparator_markets {
+            Some(names) => {
+                let names = names.split(',').map(|n| n.trim()).collect::<Vec<&str>>();
+                markets
+                    .iter()
+                    .filter(|m| names.contains(&m.name.as_str()))
+                    .cloned()
+                    .collect()
+            }
+            None => markets.to_vec(),
+        }
+    }
+}
+
+/// Periodically compares recent candles against an external provider and records per-bar deltas.
+pub async fn run_comparator(
+    pool: &Pool,
+    config: &ComparatorConfig,
+    markets: Vec<MarketInfo>,
+) -> anyhow::Result<()> {
+    let http_client = reqwest::Client::new();
+    info!(
+        "Comparing candles for {} markets against {}",
+        markets.len(),
+        PROVIDER
+    );
+    loop {
+        for market in markets.iter() {
+            if let Err(e) = compare_market(pool, config, &http_client, market).await {
+                error!("Comparator failed for {}: {:?}", market.name, e);
+            }
+        }
+        sleep(COMPARISON_RESOLUTION.get_duration().to_std()?).await;
+    }
+}
+
+async fn compare_market(
+    pool: &Pool,
+    config: &ComparatorConfig,
+    http_client: &reqwest::Client,
+    market: &MarketInfo,
+) -> anyhow::Result<()> {
+    let end_time = Utc::now().duration_trunc(COMPARISON_RESOLUTION.get_duration())?;
+    let start_time = end_time - comparison_window();
+
+    let candles = fetch_candles_from(
+        pool,
+        &market.name,
+        COMPARISON_RESOLUTION,
+        start_time,
+        end_time,
+    )
+    .await?;
+    if candles.is_empty() {
+        return Ok(());
+    }
+
+    let external_candles = fetch_external_candles(
+        config,
+        http_client,
+        &market.address,
+        start_time.timestamp(),
+        end_time.timestamp(),
+    )
+    .await?;
+
+    let divergences = candles
+        .iter()
+        .filter(|c| c.complete)
+        .filter_map(|c| {
+            external_candles
+                .iter()
+                .find(|e| e.start_time == c.start_time)
+                .map(|e| CandleDivergence::from_candles(PROVIDER, c, e))
+        })
+        .collect::<Vec<CandleDivergence>>();
+    if divergences.is_empty() {
+        return Ok(());
+    }
+
+    let columns = DivergenceColumns::from_divergences(&divergences);
+    let client = pool.get().await?;
+    client
+        .execute(DIVERGENCE_UPSERT, &columns.params())
+        .await
+        .map_err_anyhow()?;
+    Ok(())
+}
+
+async fn fetch_external_candles(
+    config: &ComparatorConfig,
+    http_client: &reqwest::Client,
+    market_address: &str,
+    time_from: i64,
+    time_to: i64,
+) -> anyhow::Result<Vec<ExternalCandle>> {
+    let api_key = config.comparator_api_key.clone().unwrap_or_default();
+    let response = http_client
+        .get(&config.comparator_url)
+        .header("X-API-KEY", api_key)
+        .header("x-chain", "solana")
+        .query(&[
+            ("address", market_address.to_string()),
+            ("type", "15m".to_string()),
+            ("time_from", time_from.to_string()),
+            ("time_to", time_to.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<BirdeyeOhlcvResponse>()
+        .await?;
+
+    Ok(response
+        .data
+        .items
+        .into_iter()
+        .map(|i| ExternalCandle {
+            start_time: to_timestampz(i.unix_time as u64),
+            open: i.o,
+            close: i.c,
+            high: i.h,
+            low: i.l,
+            volume: i.v,
+        })
+        .collect())
+}
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use deadpool_postgres::Pool;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{
+    database::composite::merge_composite_candles, structs::composite::CompositeMarket,
+    worker::cluster::MarketAssignment,
+};
+
+/// Composites follow their markets at the pace minute candles are batched
+const MERGE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Keeps the candles of every owned composite market up with the candles of the markets it
+/// merges, every resolution from the same resolution of its markets.
+pub async fn batch_composite_candles(
+    pool: &Pool,
+    composites: Vec<CompositeMarket>,
+    assignment: MarketAssignment,
+) -> anyhow::Result<()> {
+    loop {
+        for composite in composites
+            .iter()
+            .filter(|c| assignment.owns(&c.info.address))
+        {
+            for resolution in composite.info.resolutions.iter().copied() {
+                if let Err(e) = merge_composite_candles(
+                    pool,
+                    &composite.info.name,
+                    &composite.market_names,
+                    resolution,
+                )
+                .await
+                {
+                    warn!(
+                        "Failed to merge {} candles of {}: {:?}",
+                        resolution, composite.info.name, e
+                    );
+                }
+            }
+        }
+        sleep(MERGE_INTERVAL).await;
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use deadpool_postgres::Pool;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{
+    database::insert::{
+        build_depth_snapshots_insert_statement, build_depth_stats_insert_statement,
+    },
+    structs::{
+        markets::{MarketInfo, USD_STABLECOIN_MINTS},
+        orderbook::{quote_usd_price, DepthSnapshot, DepthStat, OrderBookSnapshot},
+        slab::get_orderbook_snapshots,
+    },
+    utils::AnyhowWrap,
+    worker::cluster::MarketAssignment,
+};
+
+/// Records ±2% order book depth in USD and a depth snapshot at 1%, 2% and 5% from the mid price
+/// for every market once a minute.
+pub async fn record_depth_stats(
+    pool: &Pool,
+    rpc_url: String,
+    markets: Vec<MarketInfo>,
+    assignment: MarketAssignment,
+) -> anyhow::Result<()> {
+    let client = RpcClient::new(rpc_url);
+    loop {
+        if let Err(e) = record_depth_stats_inner(pool, &client, &markets, &assignment).await {
+            warn!("Failed to record depth stats: {:?}", e);
+        }
+        sleep(Duration::minutes(1).to_std()?).await;
+    }
+}
+
+async fn record_depth_stats_inner(
+    pool: &Pool,
+    client: &RpcClient,
+    markets: &Vec<MarketInfo>,
+    assignment: &MarketAssignment,
+) -> anyhow::Result<()> {
+    let owned: Vec<&MarketInfo> = markets
+        .iter()
+        .filter(|m| assignment.owns(&m.address))
+        .collect();
+    if owned.is_empty() {
+        return Ok(());
+    }
+    // owned markets plus the USD markets their quote tokens are valued with
+    let needed: Vec<MarketInfo> = markets
+        .iter()
+        .filter(|m| {
+            assignment.owns(&m.address)
+                || (USD_STABLECOIN_MINTS.contains(&m.quote_mint_key.as_str())
+                    && owned.iter().any(|o| o.quote_mint_key == m.base_mint_key))
+        })
+        .cloned()
+        .collect();
+    let snapshots: HashMap<String, OrderBookSnapshot> = needed
+        .iter()
+        .zip(get_orderbook_snapshots(client, &needed).await?)
+        .filter_map(|(m, s)| s.map(|s| (m.address.clone(), s)))
+        .collect();
+
+    let time = Utc::now();
+    let depth_snapshots = owned
+        .iter()
+        .filter_map(|m| DepthSnapshot::from_order_book(&m.name, snapshots.get(&m.address)?))
+        .collect::<Vec<DepthSnapshot>>();
+    let stats = owned
+        .into_iter()
+        .filter_map(|m| {
+            let snapshot = snapshots.get(&m.address)?;
+            let usd_price = quote_usd_price(m, markets, &snapshots)?;
+            Some(DepthStat {
+                market_name: m.name.clone(),
+                time,
+                mid_price: snapshot.mid_price()?,
+                bid_depth_usd: snapshot.bid_depth * usd_price,
+                ask_depth_usd: snapshot.ask_depth * usd_price,
+            })
+        })
+        .collect::<Vec<DepthStat>>();
+    let db_client = pool.get().await?;
+    if !stats.is_empty() {
+        let insert_statement = build_depth_stats_insert_statement(&stats);
+        db_client
+            .execute(&insert_statement, &[])
+            .await
+            .map_err_anyhow()?;
+    }
+    // markets without a USD reference still get a snapshot, it's in quote tokens
+    if !depth_snapshots.is_empty() {
+        let insert_statement = build_depth_snapshots_insert_statement(&depth_snapshots);
+        db_client
+            .execute(&insert_statement, &[])
+            .await
+            .map_err_anyhow()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, pin::Pin, time::Duration as WaitDuration};
+
+use async_trait::async_trait;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde_derive::{Deserialize, Serialize};
+use solana_sdk::{bs58, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::time::{timeout_at, Instant};
+use tracing::info;
+use yellowstone_grpc_client::GeyserGrpcClient;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+    SubscribeRequestFilterAccounts, SubscribeUpdate,
+};
+
+use super::{queue_tracker::EventQueueTracker, TradeSource};
+use crate::structs::{markets::MarketInfo, openbook::OpenBookFill, venue::Venue};
+
+fn default_geyser_batch_timeout_ms() -> u64 {
+    500
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GeyserConfig {
+    /// The geyser source is disabled unless an endpoint is configured
+    #[serde(serialize_with = "crate::utils::settings::redact_url")]
+    pub geyser_grpc_url: Option<String>,
+    #[serde(serialize_with = "crate::utils::settings::redact")]
+    pub geyser_x_token: Option<String>,
+    #[serde(default = "default_geyser_batch_timeout_ms")]
+    pub geyser_batch_timeout_ms: u64,
+}
+
+impl GeyserConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.geyser_grpc_url.is_some()
+    }
+}
+
+fn commitment_level(commitment: CommitmentConfig) -> CommitmentLevel {
+    if commitment.is_finalized() {
+        CommitmentLevel::Finalized
+    } else if commitment.is_confirmed() {
+        CommitmentLevel::Confirmed
+    } else {
+        CommitmentLevel::Processed
+    }
+}
+
+/// Reads fills straight off the markets' event queues, streamed from a Yellowstone gRPC geyser
+/// plugin as the accounts change.
+pub struct GeyserFillSource {
+    // dropping the request sink ends the subscription
+    _requests: Pin<Box<dyn Sink<SubscribeRequest, Error = anyhow::Error> + Send>>,
+    updates: Pin<Box<dyn Stream<Item = anyhow::Result<SubscribeUpdate>> + Send>>,
+    tracker: EventQueueTracker,
+    batch_timeout: WaitDuration,
+}
+
+impl GeyserFillSource {
+    pub async fn connect(
+        config: &GeyserConfig,
+        commitment: CommitmentConfig,
+        markets: &[MarketInfo],
+    ) -> anyhow::Result<Self> {
+        let url = config
+            .geyser_grpc_url
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("GEYSER_GRPC_URL is not set"))?;
+        let commitment = commitment_level(commitment);
+
+        let tracker = EventQueueTracker::new(markets)?;
+
+        let mut client = GeyserGrpcClient::connect(url, config.geyser_x_token.clone(), None)?;
+        let (requests, updates) = client.subscribe().await?;
+        let mut requests = Box::pin(requests.sink_map_err(anyhow::Error::from));
+        let accounts = HashMap::from([(
+            "openbook_event_queues".to_string(),
+            SubscribeRequestFilterAccounts {
+                account: markets.iter().map(|m| m.event_queue_key.clone()).collect(),
+                ..Default::default()
+            },
+        )]);
+        requests
+            .send(SubscribeRequest {
+                accounts,
+                commitment: Some(commitment as i32),
+                ..Default::default()
+            })
+            .await?;
+        info!("Subscribed to {} event queues over geyser", markets.len());
+
+        Ok(GeyserFillSource {
+            _requests: requests,
+            updates: Box::pin(updates.map(|u| u.map_err(anyhow::Error::from))),
+            tracker,
+            batch_timeout: WaitDuration::from_millis(config.geyser_batch_timeout_ms),
+        })
+    }
+}
+
+#[async_trait]
+impl TradeSource for GeyserFillSource {
+    fn venue(&self) -> Venue {
+        Venue::OpenBook
+    }
+
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<OpenBookFill>> {
+        let deadline = Instant::now() + self.batch_timeout;
+        let mut fills = vec![];
+        while let Ok(update) = timeout_at(deadline, self.updates.next()).await {
+            let update = update.ok_or_else(|| anyhow::anyhow!("geyser stream ended"))??;
+            if let Some(UpdateOneof::Account(account)) = update.update_oneof {
+                if let Some(info) = account.account {
+                    if let Ok(key) = Pubkey::try_from(info.pubkey.as_slice()) {
+                        let signature = info.txn_signature.map(|s| bs58::encode(s).into_string());
+                        fills.extend(self.tracker.new_fills(
+                            &key,
+                            account.slot,
+                            &info.data,
+                            signature,
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(fills)
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        self.tracker.commit();
+        Ok(())
+    }
+}
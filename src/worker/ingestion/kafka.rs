@@ -0,0 +1,201 @@
+use std::time::Duration as WaitDuration;
+
+use async_trait::async_trait;
+use rdkafka::{
+    config::ClientConfig,
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    Message, Offset, TopicPartitionList,
+};
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::{timeout, Instant};
+use tracing::warn;
+
+use super::TradeSource;
+use crate::{
+    structs::{openbook::OpenBookFill, venue::Venue},
+    utils::to_timestampz,
+};
+
+fn default_kafka_group_id() -> String {
+    "openbook-candles".to_string()
+}
+
+fn default_kafka_batch_size() -> usize {
+    1000
+}
+
+fn default_kafka_batch_timeout_ms() -> u64 {
+    500
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KafkaConfig {
+    /// The Kafka source is disabled unless brokers are configured
+    pub kafka_brokers: Option<String>,
+    pub kafka_topic: Option<String>,
+    #[serde(default = "default_kafka_group_id")]
+    pub kafka_group_id: String,
+    #[serde(default = "default_kafka_batch_size")]
+    pub kafka_batch_size: usize,
+    #[serde(default = "default_kafka_batch_timeout_ms")]
+    pub kafka_batch_timeout_ms: u64,
+}
+
+impl KafkaConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.kafka_brokers.is_some()
+    }
+}
+
+/// A fill as published on the topic, one JSON object per message.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KafkaFill {
+    pub signature: String,
+    pub slot: i64,
+    /// Unix seconds
+    pub block_time: u64,
+    pub market: String,
+    pub open_orders_owner: String,
+    pub bid: bool,
+    pub maker: bool,
+    pub native_quantity_paid: f64,
+    pub native_quantity_received: f64,
+    pub native_fee_or_rebate: f64,
+    pub price: f64,
+    pub size: f64,
+    pub seq_num: i64,
+    pub instruction_num: i32,
+    /// Quote tokens, negative for maker rebates
+    pub fee: Option<f64>,
+    pub referrer_rebate: Option<f64>,
+}
+
+impl From<KafkaFill> for OpenBookFill {
+    fn from(f: KafkaFill) -> Self {
+        OpenBookFill {
+            signature: f.signature,
+            slot: f.slot,
+            block_datetime: to_timestampz(f.block_time),
+            market: f.market,
+            open_orders_owner: f.open_orders_owner,
+            bid: f.bid,
+            maker: f.maker,
+            native_quantity_paid: f.native_quantity_paid,
+            native_quantity_received: f.native_quantity_received,
+            native_fee_or_rebate: f.native_fee_or_rebate,
+            price: f.price,
+            size: f.size,
+            seq_num: f.seq_num,
+            instruction_num: f.instruction_num,
+            fee: f.fee,
+            referrer_rebate: f.referrer_rebate,
+        }
+    }
+}
+
+/// Consumes pre-parsed fills from a Kafka (or Redpanda) topic. Auto commit is off: offsets are
+/// only committed after the batch they cover has been written to the database.
+pub struct KafkaFillSource {
+    consumer: StreamConsumer,
+    batch_size: usize,
+    batch_timeout: WaitDuration,
+    pending_offsets: TopicPartitionList,
+}
+
+impl KafkaFillSource {
+    pub fn new(config: &KafkaConfig) -> anyhow::Result<Self> {
+        let brokers = config
+            .kafka_brokers
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("KAFKA_BROKERS is not set"))?;
+        let topic = config
+            .kafka_topic
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("KAFKA_TOPIC is not set"))?;
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", &config.kafka_group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+        consumer.subscribe(&[topic])?;
+
+        Ok(KafkaFillSource {
+            consumer,
+            batch_size: config.kafka_batch_size,
+            batch_timeout: WaitDuration::from_millis(config.kafka_batch_timeout_ms),
+            pending_offsets: TopicPartitionList::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl TradeSource for KafkaFillSource {
+    fn venue(&self) -> Venue {
+        Venue::OpenBook
+    }
+
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<OpenBookFill>> {
+        let mut fills = vec![];
+        let deadline = Instant::now() + self.batch_timeout;
+
+        while fills.len() < self.batch_size {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = match timeout(remaining, self.consumer.recv()).await {
+                Ok(message) => message?,
+                Err(_) => break,
+            };
+
+            // the committed offset is the next one to read, so commit past this message
+            let next_offset = Offset::Offset(message.offset() + 1);
+            if self
+                .pending_offsets
+                .find_partition(message.topic(), message.partition())
+                .is_some()
+            {
+                self.pending_offsets.set_partition_offset(
+                    message.topic(),
+                    message.partition(),
+                    next_offset,
+                )?;
+            } else {
+                self.pending_offsets.add_partition_offset(
+                    message.topic(),
+                    message.partition(),
+                    next_offset,
+                )?;
+            }
+
+            let payload = match message.payload() {
+                Some(payload) => payload,
+                None => continue,
+            };
+            match serde_json::from_slice::<KafkaFill>(payload) {
+                Ok(fill) => fills.push(fill.into()),
+                Err(e) => warn!(
+                    "Skipping malformed fill at {}/{}@{}: {:?}",
+                    message.topic(),
+                    message.partition(),
+                    message.offset(),
+                    e
+                ),
+            }
+        }
+        Ok(fills)
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        if self.pending_offsets.count() == 0 {
+            return Ok(());
+        }
+        self.consumer
+            .commit(&self.pending_offsets, CommitMode::Sync)?;
+        self.pending_offsets = TopicPartitionList::new();
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+#[cfg(feature = "geyser")]
+pub mod geyser;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod phoenix;
+pub mod queue_tracker;
+pub mod websocket;
+
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use deadpool_postgres::Pool;
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    database::{
+        fetch::fetch_known_fill_keys, insert::build_fills_insert_statement,
+        lifecycle::record_fills_seen,
+    },
+    structs::{markets::LiveMarkets, openbook::OpenBookFill, venue::Venue},
+    utils::AnyhowWrap,
+    worker::metrics::METRIC_FILL_INSERT_ERRORS_TOTAL,
+};
+
+/// Average slot time, converts a lag in slots to wall clock time
+const SLOT_DURATION_MS: i64 = 400;
+
+fn default_commitment() -> String {
+    "confirmed".to_string()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IngestionConfig {
+    /// `processed`, `confirmed` or `finalized`, what the fill sources subscribe at
+    #[serde(default = "default_commitment")]
+    pub commitment: String,
+    /// Fills this many slots behind the tip or newer don't complete candles yet, so a candle
+    /// isn't closed on a fill that may still be rolled back
+    #[serde(default)]
+    pub finality_lag_slots: u64,
+    /// Candles are only marked complete this many seconds after they end, so fills that reach
+    /// the database late still land in an open candle
+    #[serde(default)]
+    pub candle_finalization_delay_secs: u64,
+}
+
+impl IngestionConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn commitment_config(&self) -> anyhow::Result<CommitmentConfig> {
+        CommitmentConfig::from_str(&self.commitment)
+            .map_err(|_| anyhow::anyhow!("unknown COMMITMENT {}", self.commitment))
+    }
+
+    /// The longer of the slot lag and the finalization delay
+    pub fn finality_lag(&self) -> Duration {
+        Duration::milliseconds(self.finality_lag_slots as i64 * SLOT_DURATION_MS).max(
+            Duration::seconds(self.candle_finalization_delay_secs as i64),
+        )
+    }
+}
+
+/// Somewhere trades come from, for deployments that don't have the fills service writing
+/// `openbook_fill_events` directly. Each source scrapes one venue, its fills are tagged with it.
+#[async_trait]
+pub trait TradeSource: Send {
+    fn venue(&self) -> Venue;
+
+    /// Waits for the next batch of fills, which may be empty if nothing arrived in time.
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<OpenBookFill>>;
+
+    /// Acknowledges the last batch. Only called once the batch has been written, so a crash in
+    /// between replays it instead of losing it.
+    async fn commit(&mut self) -> anyhow::Result<()>;
+}
+
+/// Writes fills for the target markets from `source` into the fills table until the source fails.
+/// The markets are looked up for every batch, so the fills of markets added by a reload are kept
+/// and those of removed ones dropped right away.
+pub async fn ingest_fills(
+    pool: &Pool,
+    source: &mut dyn TradeSource,
+    markets: &LiveMarkets,
+) -> anyhow::Result<()> {
+    loop {
+        let batch = source.next_batch().await?;
+        let market_addresses = markets.addresses();
+        let fills: Vec<OpenBookFill> = batch
+            .into_iter()
+            .filter(|f| market_addresses.contains(&f.market))
+            .collect();
+        let fills = drop_known_fills(pool, fills).await;
+
+        if !fills.is_empty() {
+            // retry until the write lands, the batch must not be acknowledged before that
+            while let Err(e) = insert_fills(pool, &fills, source.venue()).await {
+                warn!("Failed to insert {} fills: {:?}", fills.len(), e);
+                METRIC_FILL_INSERT_ERRORS_TOTAL.inc();
+                sleep(Duration::seconds(1).to_std()?).await;
+            }
+            info!("Ingested {} fills", fills.len());
+            for (market, times) in fills
+                .iter()
+                .filter(|f| f.maker)
+                .map(|f| (f.market.as_str(), f.block_datetime))
+                .into_group_map()
+            {
+                let first = *times.iter().min().unwrap();
+                let last = *times.iter().max().unwrap();
+                if let Err(e) = record_fills_seen(pool, market, first, last).await {
+                    warn!("Failed to record fills seen for {}: {:?}", market, e);
+                }
+            }
+        }
+        source.commit().await?;
+    }
+}
+
+/// Drops fills that repeat within the batch or are already stored, so retried fetches and
+/// overlapping sources don't count a fill twice. If the lookup fails, the unique index on
+/// `(market, seq_num, maker)` still drops stored ones on insert.
+async fn drop_known_fills(pool: &Pool, fills: Vec<OpenBookFill>) -> Vec<OpenBookFill> {
+    let fills: Vec<OpenBookFill> = fills
+        .into_iter()
+        .unique_by(|f| (f.market.clone(), f.seq_num, f.maker))
+        .collect();
+    if fills.is_empty() {
+        return fills;
+    }
+
+    let markets = fills.iter().map(|f| f.market.as_str()).collect();
+    let seq_nums = fills.iter().map(|f| f.seq_num).collect();
+    let known = match fetch_known_fill_keys(pool, &markets, &seq_nums).await {
+        Ok(known) => known,
+        Err(e) => {
+            warn!("Failed to look up already stored fills: {:?}", e);
+            return fills;
+        }
+    };
+    let count = fills.len();
+    let fills: Vec<OpenBookFill> = fills
+        .into_iter()
+        .filter(|f| !known.contains(&(f.market.clone(), f.seq_num, f.maker)))
+        .collect();
+    if fills.len() < count {
+        info!("Dropped {} already stored fills", count - fills.len());
+    }
+    fills
+}
+
+async fn insert_fills(pool: &Pool, fills: &Vec<OpenBookFill>, venue: Venue) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+    let stmt = build_fills_insert_statement(fills, venue);
+    client.execute(&stmt, &[]).await.map_err_anyhow()?;
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use std::{collections::HashMap, str::FromStr, time::Duration as WaitDuration};
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use serde_derive::{Deserialize, Serialize};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+};
+use solana_sdk::{bs58, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{
+    UiInnerInstructions, UiInstruction, UiLoadedAddresses, UiTransactionEncoding,
+};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use super::TradeSource;
+use crate::{
+    structs::{
+        markets::MarketInfo,
+        openbook::OpenBookFill,
+        phoenix::{decode_phoenix_fills, PhoenixMarketParams, PHOENIX_PROGRAM_ID},
+        venue::Venue,
+    },
+    worker::metrics::METRIC_RPC_ERRORS_TOTAL,
+};
+
+/// Most signatures `getSignaturesForAddress` returns per request
+const SIGNATURE_PAGE_SIZE: usize = 1000;
+
+/// Transactions fetched at once
+const TRANSACTION_FETCH_CONCURRENCY: usize = 8;
+
+fn default_phoenix_poll_ms() -> u64 {
+    2000
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PhoenixConfig {
+    /// Milliseconds between two looks for new transactions of the Phoenix markets
+    #[serde(default = "default_phoenix_poll_ms")]
+    pub phoenix_poll_ms: u64,
+}
+
+impl PhoenixConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+}
+
+/// Polls the transactions of Phoenix markets over RPC and decodes the fills from the events
+/// Phoenix logs through its own log instruction. Starts at the newest transaction of each market,
+/// older fills are left to an import.
+pub struct PhoenixFillSource {
+    client: RpcClient,
+    markets: HashMap<Pubkey, PhoenixMarketParams>,
+    poll_interval: WaitDuration,
+    /// Newest transaction read per market
+    cursors: HashMap<Pubkey, Signature>,
+    /// Cursors of the last batch, they replace `cursors` once it's written
+    pending_cursors: HashMap<Pubkey, Signature>,
+}
+
+impl PhoenixFillSource {
+    pub async fn connect(
+        config: &PhoenixConfig,
+        rpc_url: String,
+        commitment: CommitmentConfig,
+        markets: &[MarketInfo],
+    ) -> anyhow::Result<Self> {
+        // transactions can't be fetched at processed
+        let commitment = if commitment.is_at_least_confirmed() {
+            commitment
+        } else {
+            CommitmentConfig::confirmed()
+        };
+        let client = RpcClient::new_with_commitment(rpc_url, commitment);
+        let keys = markets
+            .iter()
+            .filter(|m| m.venue == Venue::Phoenix)
+            .map(|m| m.address.parse::<Pubkey>())
+            .collect::<Result<Vec<Pubkey>, _>>()?;
+
+        let mut params = HashMap::new();
+        for (key, account) in keys.iter().zip(client.get_multiple_accounts(&keys).await?) {
+            let account =
+                account.ok_or_else(|| anyhow::anyhow!("Phoenix market {} not found", key))?;
+            params.insert(*key, PhoenixMarketParams::from_account_data(&account.data)?);
+        }
+        info!("Polling {} Phoenix markets", params.len());
+
+        Ok(PhoenixFillSource {
+            client,
+            markets: params,
+            poll_interval: WaitDuration::from_millis(config.phoenix_poll_ms),
+            cursors: HashMap::new(),
+            pending_cursors: HashMap::new(),
+        })
+    }
+
+    /// Newest transaction of `market` and the successful ones since its cursor, oldest first.
+    /// Without a cursor only the newest transaction is looked up, to start from.
+    async fn new_signatures(
+        &self,
+        market: &Pubkey,
+    ) -> anyhow::Result<(Option<Signature>, Vec<Signature>)> {
+        let until = self.cursors.get(market).cloned();
+        let mut newest = None;
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page = self
+                .client
+                .get_signatures_for_address_with_config(
+                    market,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(if until.is_some() {
+                            SIGNATURE_PAGE_SIZE
+                        } else {
+                            1
+                        }),
+                        commitment: Some(self.client.commitment()),
+                    },
+                )
+                .await
+                .map_err(|e| {
+                    METRIC_RPC_ERRORS_TOTAL
+                        .with_label_values(&["getSignaturesForAddress"])
+                        .inc();
+                    e
+                })?;
+            let page_len = page.len();
+            for status in page {
+                let signature = Signature::from_str(&status.signature)?;
+                newest = newest.or(Some(signature));
+                before = Some(signature);
+                if status.err.is_none() {
+                    signatures.push(signature);
+                }
+            }
+            if until.is_none() || page_len < SIGNATURE_PAGE_SIZE {
+                break;
+            }
+        }
+        signatures.reverse();
+        Ok((newest, signatures))
+    }
+
+    /// Fills of the transactions of `market` since its cursor and the cursor to continue from.
+    async fn market_fills(
+        &self,
+        market: &Pubkey,
+    ) -> anyhow::Result<(Option<Signature>, Vec<OpenBookFill>)> {
+        let (newest, signatures) = self.new_signatures(market).await?;
+        if !self.cursors.contains_key(market) {
+            return Ok((newest, vec![]));
+        }
+        let fetched: Vec<anyhow::Result<Vec<OpenBookFill>>> = stream::iter(signatures)
+            .map(|signature| fetch_transaction_fills(&self.client, &self.markets, signature))
+            .buffered(TRANSACTION_FETCH_CONCURRENCY)
+            .collect()
+            .await;
+        let mut fills = vec![];
+        for result in fetched {
+            fills.extend(result?);
+        }
+        Ok((newest, fills))
+    }
+}
+
+/// Fills decoded from the Phoenix log instructions among the inner instructions of a transaction.
+async fn fetch_transaction_fills(
+    client: &RpcClient,
+    markets: &HashMap<Pubkey, PhoenixMarketParams>,
+    signature: Signature,
+) -> anyhow::Result<Vec<OpenBookFill>> {
+    let transaction = client
+        .get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(client.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await
+        .map_err(|e| {
+            METRIC_RPC_ERRORS_TOTAL
+                .with_label_values(&["getTransaction"])
+                .inc();
+            e
+        })?
+        .transaction;
+    let meta = match transaction.meta {
+        Some(meta) if meta.err.is_none() => meta,
+        _ => return Ok(vec![]),
+    };
+    let decoded = transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| anyhow::anyhow!("undecodable transaction {}", signature))?;
+
+    // inner instructions index the static keys followed by those loaded from lookup tables
+    let mut account_keys = decoded.message.static_account_keys().to_vec();
+    if let Some(loaded) = Option::<UiLoadedAddresses>::from(meta.loaded_addresses) {
+        for key in loaded.writable.iter().chain(loaded.readonly.iter()) {
+            account_keys.push(key.parse()?);
+        }
+    }
+    let program_id = Pubkey::from_str(PHOENIX_PROGRAM_ID)?;
+
+    let mut fills = vec![];
+    let inner_instructions: Vec<UiInnerInstructions> =
+        Option::from(meta.inner_instructions).unwrap_or_default();
+    for instruction in inner_instructions
+        .iter()
+        .flat_map(|i| i.instructions.iter())
+    {
+        let instruction = match instruction {
+            UiInstruction::Compiled(instruction) => instruction,
+            _ => continue,
+        };
+        if account_keys.get(instruction.program_id_index as usize) != Some(&program_id) {
+            continue;
+        }
+        let data = bs58::decode(&instruction.data).into_vec()?;
+        match decode_phoenix_fills(&data, &signature.to_string(), markets) {
+            Ok(decoded) => fills.extend(decoded),
+            Err(e) => warn!(
+                "Skipping undecodable Phoenix events in {}: {:?}",
+                signature, e
+            ),
+        }
+    }
+    Ok(fills)
+}
+
+#[async_trait]
+impl TradeSource for PhoenixFillSource {
+    fn venue(&self) -> Venue {
+        Venue::Phoenix
+    }
+
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<OpenBookFill>> {
+        sleep(self.poll_interval).await;
+        let mut fills = vec![];
+        let market_keys: Vec<Pubkey> = self.markets.keys().cloned().collect();
+        for market in market_keys {
+            // a market that fails keeps its cursor and is read again on the next poll
+            match self.market_fills(&market).await {
+                Ok((newest, market_fills)) => {
+                    fills.extend(market_fills);
+                    if let Some(newest) = newest {
+                        self.pending_cursors.insert(market, newest);
+                    }
+                }
+                Err(e) => warn!("Failed to poll Phoenix market {}: {:?}", market, e),
+            }
+        }
+        Ok(fills)
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        self.cursors.extend(self.pending_cursors.drain());
+        Ok(())
+    }
+}
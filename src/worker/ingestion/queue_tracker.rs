@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+use crate::structs::{
+    event_queue::parse_event_queue,
+    markets::MarketInfo,
+    openbook::{fill_fees, OpenBookFill},
+};
+
+struct QueueState {
+    market: MarketInfo,
+    /// Sequence number of the first event not yet written, unknown until the first update
+    next_seq_num: Option<u64>,
+    /// Where `next_seq_num` moves to once the current batch is committed
+    pending_seq_num: Option<u64>,
+}
+
+/// Turns consecutive snapshots of the markets' event queues into the fills that are new in each.
+/// Events already queued in the first snapshot are skipped, and fills are timestamped on arrival
+/// since account updates carry no block time.
+pub struct EventQueueTracker {
+    queues: HashMap<Pubkey, QueueState>,
+}
+
+impl EventQueueTracker {
+    pub fn new(markets: &[MarketInfo]) -> anyhow::Result<Self> {
+        let mut queues = HashMap::new();
+        for market in markets {
+            queues.insert(
+                market.event_queue_key.parse::<Pubkey>()?,
+                QueueState {
+                    market: market.clone(),
+                    next_seq_num: None,
+                    pending_seq_num: None,
+                },
+            );
+        }
+        Ok(EventQueueTracker { queues })
+    }
+
+    /// Fills of the queue at `key` that weren't in the previous snapshot. `signature` falls back
+    /// to the slot when the update doesn't say which transaction caused it.
+    pub fn new_fills(
+        &mut self,
+        key: &Pubkey,
+        slot: u64,
+        data: &[u8],
+        signature: Option<String>,
+    ) -> Vec<OpenBookFill> {
+        let queue = match self.queues.get_mut(key) {
+            Some(q) => q,
+            None => return vec![],
+        };
+        let snapshot = match parse_event_queue(data) {
+            Some(s) => s,
+            None => {
+                warn!("Failed to decode event queue of {}", queue.market.name);
+                return vec![];
+            }
+        };
+
+        let from = match queue.pending_seq_num.or(queue.next_seq_num) {
+            Some(seq_num) => seq_num,
+            None => snapshot.next_seq_num,
+        };
+        if from < snapshot.first_seq_num {
+            warn!(
+                "Missed {} events of {}, they were consumed before the update arrived",
+                snapshot.first_seq_num - from,
+                queue.market.name
+            );
+        }
+        queue.pending_seq_num = Some(snapshot.next_seq_num);
+
+        let signature = signature.unwrap_or_else(|| format!("slot-{}", slot));
+        let block_datetime = Utc::now();
+        let market = &queue.market;
+        snapshot
+            .fills
+            .into_iter()
+            .filter(|f| f.seq_num >= from)
+            .filter_map(|f| {
+                let scaled = f.price_and_size(market).and_then(|price_and_size| {
+                    let fees = fill_fees(f.native_fee_or_rebate, f.maker, market.quote_decimals)?;
+                    Ok((price_and_size, fees))
+                });
+                let ((price, size), (fee, referrer_rebate)) = match scaled {
+                    Ok(scaled) => scaled,
+                    Err(e) => {
+                        warn!("Skipping fill {} of {}: {:?}", f.seq_num, market.name, e);
+                        return None;
+                    }
+                };
+                Some(OpenBookFill {
+                    signature: signature.clone(),
+                    slot: slot as i64,
+                    block_datetime,
+                    market: market.address.clone(),
+                    open_orders_owner: f.owner.to_string(),
+                    bid: f.bid,
+                    maker: f.maker,
+                    native_quantity_paid: f.native_qty_paid as f64,
+                    native_quantity_received: f.native_qty_received as f64,
+                    native_fee_or_rebate: f.native_fee_or_rebate as f64,
+                    price,
+                    size,
+                    seq_num: f.seq_num as i64,
+                    instruction_num: 0,
+                    fee: Some(fee),
+                    referrer_rebate: Some(referrer_rebate),
+                })
+            })
+            .collect()
+    }
+
+    /// Marks the fills returned since the last commit as written.
+    pub fn commit(&mut self) {
+        for queue in self.queues.values_mut() {
+            if let Some(seq_num) = queue.pending_seq_num.take() {
+                queue.next_seq_num = Some(seq_num);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::Duration as WaitDuration;
+
+use async_trait::async_trait;
+use futures::{stream::select_all, StreamExt};
+use serde_derive::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::{
+    sync::mpsc,
+    time::{timeout_at, Instant},
+};
+use tracing::info;
+
+use super::{queue_tracker::EventQueueTracker, TradeSource};
+use crate::structs::{markets::MarketInfo, openbook::OpenBookFill, venue::Venue};
+
+fn default_event_queue_ws_batch_timeout_ms() -> u64 {
+    500
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebsocketConfig {
+    /// The websocket source is disabled unless an endpoint is configured
+    #[serde(serialize_with = "crate::utils::settings::redact_url")]
+    pub event_queue_ws_url: Option<String>,
+    #[serde(default = "default_event_queue_ws_batch_timeout_ms")]
+    pub event_queue_ws_batch_timeout_ms: u64,
+}
+
+impl WebsocketConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.event_queue_ws_url
+            .as_ref()
+            .map_or(false, |url| !url.is_empty())
+    }
+}
+
+struct QueueUpdate {
+    key: Pubkey,
+    slot: u64,
+    data: Vec<u8>,
+}
+
+/// Reads fills off the markets' event queues through `accountSubscribe` on a Solana pubsub
+/// websocket. Notifications only carry the latest state of a queue, so events that are pushed
+/// and consumed between two of them are missed and logged.
+pub struct WebsocketFillSource {
+    updates: mpsc::Receiver<anyhow::Result<QueueUpdate>>,
+    tracker: EventQueueTracker,
+    batch_timeout: WaitDuration,
+}
+
+impl WebsocketFillSource {
+    pub async fn connect(
+        config: &WebsocketConfig,
+        commitment: CommitmentConfig,
+        markets: &[MarketInfo],
+    ) -> anyhow::Result<Self> {
+        let url = config
+            .event_queue_ws_url
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("EVENT_QUEUE_WS_URL is not set"))?;
+        let tracker = EventQueueTracker::new(markets)?;
+        let keys = markets
+            .iter()
+            .map(|m| m.event_queue_key.parse::<Pubkey>())
+            .collect::<Result<Vec<Pubkey>, _>>()?;
+
+        // the subscription streams borrow the client, so they live in a task of their own
+        let client = PubsubClient::new(&url).await?;
+        let (sender, receiver) = mpsc::channel(1024);
+        tokio::spawn(async move {
+            let result = stream_queue_updates(&client, keys, commitment, &sender).await;
+            let error = result
+                .err()
+                .unwrap_or_else(|| anyhow::anyhow!("event queue websocket stream ended"));
+            sender.send(Err(error)).await.ok();
+        });
+        info!(
+            "Subscribed to {} event queues over websocket",
+            markets.len()
+        );
+
+        Ok(WebsocketFillSource {
+            updates: receiver,
+            tracker,
+            batch_timeout: WaitDuration::from_millis(config.event_queue_ws_batch_timeout_ms),
+        })
+    }
+}
+
+async fn stream_queue_updates(
+    client: &PubsubClient,
+    keys: Vec<Pubkey>,
+    commitment: CommitmentConfig,
+    sender: &mpsc::Sender<anyhow::Result<QueueUpdate>>,
+) -> anyhow::Result<()> {
+    let mut streams = vec![];
+    for key in keys {
+        let (stream, _unsubscribe) = client
+            .account_subscribe(
+                &key,
+                Some(RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(commitment),
+                    ..Default::default()
+                }),
+            )
+            .await?;
+        streams.push(stream.map(move |response| (key, response)));
+    }
+
+    let mut notifications = select_all(streams);
+    while let Some((key, response)) = notifications.next().await {
+        let data = response
+            .value
+            .data
+            .decode()
+            .ok_or_else(|| anyhow::anyhow!("undecodable account data for {}", key))?;
+        let update = QueueUpdate {
+            key,
+            slot: response.context.slot,
+            data,
+        };
+        if sender.send(Ok(update)).await.is_err() {
+            // the source was dropped
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl TradeSource for WebsocketFillSource {
+    fn venue(&self) -> Venue {
+        Venue::OpenBook
+    }
+
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<OpenBookFill>> {
+        let deadline = Instant::now() + self.batch_timeout;
+        let mut fills = vec![];
+        while let Ok(update) = timeout_at(deadline, self.updates.recv()).await {
+            let update =
+                update.ok_or_else(|| anyhow::anyhow!("event queue websocket stream ended"))??;
+            fills.extend(
+                self.tracker
+                    .new_fills(&update.key, update.slot, &update.data, None),
+            );
+        }
+        Ok(fills)
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        self.tracker.commit();
+        Ok(())
+    }
+}
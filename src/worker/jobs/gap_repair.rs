@@ -0,0 +1,57 @@
+use std::ops::Range;
+
+use chrono::{DateTime, DurationRound, Utc};
+use deadpool_postgres::Pool;
+use tracing::info;
+
+use crate::{
+    database::{
+        checkpoints::fetch_worker_checkpoint, rebuild::fetch_days_missing_minute_candles,
+        retention::fetch_fill_retention_watermark,
+    },
+    structs::{markets::MarketInfo, resolution::day},
+    utils::to_timestampz,
+    worker::{
+        candle_batching::aggregate::OutlierFilter,
+        jobs::rebuild::{ceil_day, rebuild_candles},
+    },
+};
+
+/// Rebuilds every resolution the market keeps of the whole UTC days in `range`, the whole history
+/// when `None`, on which the market is missing minute candles. Only days before the newest
+/// complete candle and after pruned fills are looked at.
+/// Returns the number of candles written.
+pub async fn repair_gaps(
+    pool: &Pool,
+    market: &MarketInfo,
+    range: Option<Range<DateTime<Utc>>>,
+    outlier_filter: Option<OutlierFilter>,
+) -> anyhow::Result<u64> {
+    let through = match fetch_worker_checkpoint(pool, &market.name).await? {
+        Some(checkpoint) => checkpoint.candles_through.duration_trunc(day())?,
+        None => return Ok(0),
+    };
+    let (mut start, end) = match range {
+        Some(range) => (ceil_day(range.start)?, range.end.min(through)),
+        None => (to_timestampz(0), through),
+    };
+    // days before pruned fills can't be rebuilt
+    if let Some(pruned_before) = fetch_fill_retention_watermark(pool, &market.address).await? {
+        start = start.max(ceil_day(pruned_before)?);
+    }
+
+    let days = fetch_days_missing_minute_candles(pool, &market.name, start, end).await?;
+    let mut written = 0;
+    for day_start in days.iter() {
+        written += rebuild_candles(
+            pool,
+            market,
+            &market.resolutions,
+            *day_start..*day_start + day(),
+            outlier_filter,
+        )
+        .await?;
+    }
+    info!("Repaired {} days of {}", days.len(), market.name);
+    Ok(written)
+}
@@ -0,0 +1,167 @@
+pub mod gap_repair;
+pub mod rebuild;
+
+use std::time::Duration as WaitDuration;
+
+use deadpool_postgres::Pool;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    database::jobs::{claim_next_job, finish_job, record_job_heartbeat},
+    structs::{
+        job::{Job, JOB_KIND_BACKFILL, JOB_KIND_GAP_REPAIR, JOB_KIND_PRUNE, JOB_KIND_REBUILD},
+        markets::MarketInfo,
+        resolution::Resolution,
+    },
+    worker::{
+        candle_batching::{
+            aggregate::OutlierFilter, higher_order_candles::backfill_batch_higher_order_candles,
+            minute_candles::backfill_batch_1m_candles,
+        },
+        retention::{prune_fills, RetentionConfig},
+    },
+};
+
+use self::{gap_repair::repair_gaps, rebuild::rebuild_candles};
+
+/// Time between two looks for queued jobs
+const JOB_POLL_INTERVAL: WaitDuration = WaitDuration::from_secs(10);
+
+/// Time between two heartbeats of a running job, well within the two minutes after which other
+/// workers take it over
+const JOB_HEARTBEAT_INTERVAL: WaitDuration = WaitDuration::from_secs(30);
+
+fn default_job_retry_delay_secs() -> u64 {
+    60
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JobConfig {
+    /// Wait before the first retry of a failed job, doubled for every further attempt
+    #[serde(default = "default_job_retry_delay_secs")]
+    pub job_retry_delay_secs: u64,
+}
+
+impl JobConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    /// Wait before retrying a job that failed its `attempts`th attempt
+    fn retry_delay_secs(&self, attempts: i32) -> i64 {
+        (self.job_retry_delay_secs as i64) << (attempts - 1).clamp(0, 10)
+    }
+}
+
+/// What jobs need from the worker that runs them
+#[derive(Clone, Debug)]
+pub struct JobContext {
+    pub markets: Vec<MarketInfo>,
+    pub outlier_filter: Option<OutlierFilter>,
+    pub retention: RetentionConfig,
+}
+
+/// Carries out queued jobs one at a time. Every replica runs this, each job is claimed by one.
+/// A job sends heartbeats while it runs, so it's taken over if the worker stops halfway.
+pub async fn run_jobs(pool: &Pool, config: JobConfig, context: JobContext) -> anyhow::Result<()> {
+    loop {
+        let job = match claim_next_job(pool).await {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                sleep(JOB_POLL_INTERVAL).await;
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to claim a job: {:?}", e);
+                sleep(JOB_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        info!(
+            "Running {} job {}, attempt {} of {}",
+            job.kind, job.id, job.attempts, job.max_attempts
+        );
+
+        let run = run_job(pool, &job, &context);
+        tokio::pin!(run);
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result,
+                _ = sleep(JOB_HEARTBEAT_INTERVAL) => {
+                    if let Err(e) = record_job_heartbeat(pool, job.id).await {
+                        warn!("Failed to record heartbeat of job {}: {:?}", job.id, e);
+                    }
+                }
+            }
+        };
+        if let Err(e) = &result {
+            warn!("{} job {} failed: {:?}", job.kind, job.id, e);
+        }
+        let retry_delay = config.retry_delay_secs(job.attempts);
+        if let Err(e) = finish_job(pool, &job, &result, retry_delay).await {
+            warn!("Failed to record the outcome of job {}: {:?}", job.id, e);
+        }
+    }
+}
+
+/// Runs the job and returns the number of candles it wrote, for kinds that count them.
+pub async fn run_job(pool: &Pool, job: &Job, context: &JobContext) -> anyhow::Result<Option<u64>> {
+    // jobs without a market cover every market
+    let markets: Vec<MarketInfo> = match &job.market_name {
+        Some(name) => vec![context
+            .markets
+            .iter()
+            .find(|m| &m.name == name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("{} is not configured on this worker", name))?],
+        None => context.markets.clone(),
+    };
+    let range = match (job.start_time, job.end_time) {
+        (Some(start_time), Some(end_time)) => Some(start_time..end_time),
+        _ => None,
+    };
+
+    match job.kind.as_str() {
+        JOB_KIND_REBUILD => {
+            let (market, range) = match (markets.first(), range) {
+                (Some(market), Some(range)) if job.market_name.is_some() => (market, range),
+                _ => anyhow::bail!("a rebuild needs a market and a time range"),
+            };
+            let resolutions = job
+                .resolutions
+                .iter()
+                .map(|r| {
+                    Resolution::from_str(r).map_err(|_| anyhow::anyhow!("unknown resolution {}", r))
+                })
+                .collect::<anyhow::Result<Vec<Resolution>>>()?
+                .into_iter()
+                // admin requests cover every resolution by default
+                .filter(|r| market.keeps(*r))
+                .collect::<Vec<Resolution>>();
+            let written =
+                rebuild_candles(pool, market, &resolutions, range, context.outlier_filter).await?;
+            Ok(Some(written))
+        }
+        JOB_KIND_GAP_REPAIR => {
+            let mut written = 0;
+            for market in markets.iter() {
+                written += repair_gaps(pool, market, range.clone(), context.outlier_filter).await?;
+            }
+            Ok(Some(written))
+        }
+        JOB_KIND_BACKFILL => {
+            backfill_batch_1m_candles(pool, markets.clone(), context.outlier_filter).await?;
+            for market in markets.iter() {
+                backfill_batch_higher_order_candles(pool, market).await?;
+            }
+            Ok(None)
+        }
+        JOB_KIND_PRUNE => {
+            prune_fills(pool, &context.retention, &markets).await?;
+            Ok(None)
+        }
+        kind => anyhow::bail!("unknown job kind {}", kind),
+    }
+}
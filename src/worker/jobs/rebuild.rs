@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use deadpool_postgres::Pool;
+use tracing::info;
+
+use crate::{
+    database::{
+        checkpoints::fetch_worker_checkpoint,
+        fetch::{fetch_candles_from, fetch_candles_page, fetch_fills_from},
+        rebuild::replace_candles,
+        retention::fetch_fill_retention_watermark,
+    },
+    structs::{
+        candle::{Candle, CandlePage},
+        markets::MarketInfo,
+        resolution::{day, Resolution},
+    },
+    worker::candle_batching::aggregate::{
+        combine_candles, fills_to_minute_candles, AggregationOptions, OutlierFilter,
+    },
+};
+
+/// Deletes and rebuilds the market's candles of `resolutions` over the whole UTC days covering
+/// `range`, one day per transaction. Minute candles are rebuilt from the fills, the others from
+/// the minute candles, which are only read when they aren't rebuilt themselves. Days from the
+/// newest complete candle on are left to the batcher, and minute candles are not rebuilt up to
+/// the day fills were pruned before. Weeks and months are rebuilt afterwards from the daily
+/// candles, each whole bucket touching the range that ends before the newest complete candle.
+/// Returns the number of candles written.
+pub async fn rebuild_candles(
+    pool: &Pool,
+    market: &MarketInfo,
+    resolutions: &[Resolution],
+    range: Range<DateTime<Utc>>,
+    outlier_filter: Option<OutlierFilter>,
+) -> anyhow::Result<u64> {
+    let through = match fetch_worker_checkpoint(pool, &market.name).await? {
+        Some(checkpoint) => checkpoint.candles_through.duration_trunc(day())?,
+        None => anyhow::bail!("{} has no complete candles yet", market.name),
+    };
+    let rebuild_minutes = resolutions.contains(&Resolution::R1m);
+    let mut start = range.start.duration_trunc(day())?;
+    // minute candles from before pruned fills would be rebuilt from the few that are left
+    if rebuild_minutes {
+        if let Some(pruned_before) = fetch_fill_retention_watermark(pool, &market.address).await? {
+            start = start.max(ceil_day(pruned_before)?);
+        }
+    }
+    let end = ceil_day(range.end)?.min(through);
+    if start >= end {
+        anyhow::bail!(
+            "no day to rebuild between the retained fills and the newest complete candle, {}",
+            through
+        );
+    }
+
+    let (calendar, resolutions): (Vec<Resolution>, Vec<Resolution>) =
+        resolutions.iter().copied().partition(|r| r.is_calendar());
+    let resolution_names: Vec<String> = resolutions.iter().map(|r| r.to_string()).collect();
+    let mut last_price = fetch_candles_page(
+        pool,
+        &market.name,
+        Resolution::R1m,
+        start - day(),
+        start,
+        CandlePage {
+            descending: true,
+            offset: 0,
+            limit: Some(1),
+        },
+    )
+    .await?
+    .first()
+    .map(|c| c.close);
+
+    let mut written = 0;
+    let mut day_start = start;
+    while day_start < end && !resolutions.is_empty() {
+        let day_range = day_start..day_start + day();
+        let minutes = if rebuild_minutes {
+            let fills =
+                fetch_fills_from(pool, &market.address, day_range.start, day_range.end).await?;
+            let options = AggregationOptions {
+                market_name: market.name.clone(),
+                last_price,
+                as_of: Utc::now(),
+                outlier_filter,
+            };
+            fills_to_minute_candles(&fills, day_range.clone(), &options)
+        } else {
+            fetch_candles_from(
+                pool,
+                &market.name,
+                Resolution::R1m,
+                day_range.start,
+                day_range.end,
+            )
+            .await?
+        };
+        if let Some(last) = minutes.last() {
+            last_price = Some(last.close);
+        }
+
+        let mut candles: Vec<Candle> = vec![];
+        for resolution in resolutions.iter() {
+            if *resolution == Resolution::R1m {
+                candles.extend(minutes.iter().cloned());
+            } else {
+                candles.append(&mut combine_candles(
+                    &minutes,
+                    *resolution,
+                    day_range.clone(),
+                ));
+            }
+        }
+        written += replace_candles(
+            pool,
+            &market.name,
+            &resolution_names,
+            day_range.clone(),
+            &candles,
+        )
+        .await?;
+        day_start = day_range.end;
+    }
+    for resolution in calendar {
+        written += rebuild_calendar_candles(pool, &market.name, resolution, range.clone(), through)
+            .await?;
+    }
+    info!(
+        "Rebuilt {} candles of {} from {} to {}",
+        written, market.name, start, end
+    );
+    Ok(written)
+}
+
+/// Rebuilds the weeks or months of `resolution` touching `range` that end by `through` from the
+/// stored daily candles.
+async fn rebuild_calendar_candles(
+    pool: &Pool,
+    market_name: &str,
+    resolution: Resolution,
+    range: Range<DateTime<Utc>>,
+    through: DateTime<Utc>,
+) -> anyhow::Result<u64> {
+    let start = resolution.bucket_start(range.start);
+    let mut end = start;
+    while end < range.end && resolution.bucket_end(end) <= through {
+        end = resolution.bucket_end(end);
+    }
+    if start >= end {
+        return Ok(0);
+    }
+    let days = fetch_candles_from(pool, market_name, Resolution::R1d, start, end).await?;
+    let candles = combine_candles(&days, resolution, start..end);
+    replace_candles(
+        pool,
+        market_name,
+        &[resolution.to_string()],
+        start..end,
+        &candles,
+    )
+    .await
+}
+
+/// Start of the first UTC day at or after `time`
+pub(super) fn ceil_day(time: DateTime<Utc>) -> anyhow::Result<DateTime<Utc>> {
+    Ok((time + day() - Duration::nanoseconds(1)).duration_trunc(day())?)
+}
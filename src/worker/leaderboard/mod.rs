@@ -0,0 +1,151 @@
+use chrono::{DateTime, Duration, Utc};
+use deadpool_postgres::Pool;
+use tokio::time::sleep;
+use tracing::error;
+
+use crate::{
+    database::{
+        fetch::{fetch_top_traders_by_base_volume_from, fetch_top_traders_by_quote_volume_from},
+        insert::build_leaderboard_insert_statement,
+    },
+    structs::{
+        markets::MarketInfo,
+        trader::{calculate_trader_volume, LeaderboardPeriod, Trader, TraderRole, VolumeType},
+    },
+    utils::AnyhowWrap,
+};
+
+/// Number of traders kept per materialized leaderboard.
+const LEADERBOARD_SIZE: usize = 1000;
+
+pub async fn materialize_leaderboards(
+    pool: &Pool,
+    markets: &Vec<MarketInfo>,
+) -> anyhow::Result<()> {
+    let interval = Duration::minutes(15);
+    loop {
+        let now = Utc::now();
+        for market in markets.iter() {
+            for period in [LeaderboardPeriod::Day, LeaderboardPeriod::Week] {
+                let current_start = period.period_start(now);
+                if let Err(e) = materialize_period(pool, market, period, current_start).await {
+                    error!(
+                        "Failed to materialize {} leaderboard for {}: {:?}",
+                        period, market.name, e
+                    );
+                }
+                // finalize the previous period once, right after it rolls over
+                if now - current_start < interval * 2 {
+                    let previous_start = current_start - period.get_duration();
+                    if let Err(e) = materialize_period(pool, market, period, previous_start).await {
+                        error!(
+                            "Failed to finalize {} leaderboard for {}: {:?}",
+                            period, market.name, e
+                        );
+                    }
+                }
+            }
+        }
+        sleep(interval.to_std()?).await;
+    }
+}
+
+async fn materialize_period(
+    pool: &Pool,
+    market: &MarketInfo,
+    period: LeaderboardPeriod,
+    period_start: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let period_end = period_start + period.get_duration();
+
+    for role in [TraderRole::All, TraderRole::Maker, TraderRole::Taker] {
+        let base_traders = fetch_top_traders_by_base_volume_from(
+            pool,
+            &market.address,
+            period_start,
+            period_end,
+            role,
+        )
+        .await?
+        .into_iter()
+        .take(LEADERBOARD_SIZE)
+        .map(|t| calculate_trader_volume(t, market.base_decimals))
+        .collect::<anyhow::Result<Vec<Trader>>>()?;
+        save_leaderboard(
+            pool,
+            market,
+            period,
+            period_start,
+            VolumeType::Base,
+            role,
+            base_traders,
+        )
+        .await?;
+
+        let quote_traders = fetch_top_traders_by_quote_volume_from(
+            pool,
+            &market.address,
+            period_start,
+            period_end,
+            role,
+        )
+        .await?
+        .into_iter()
+        .take(LEADERBOARD_SIZE)
+        .map(|t| calculate_trader_volume(t, market.quote_decimals))
+        .collect::<anyhow::Result<Vec<Trader>>>()?;
+        save_leaderboard(
+            pool,
+            market,
+            period,
+            period_start,
+            VolumeType::Quote,
+            role,
+            quote_traders,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn save_leaderboard(
+    pool: &Pool,
+    market: &MarketInfo,
+    period: LeaderboardPeriod,
+    period_start: DateTime<Utc>,
+    volume_type: VolumeType,
+    role: TraderRole,
+    traders: Vec<Trader>,
+) -> anyhow::Result<()> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    transaction
+        .execute(
+            "DELETE FROM openbook.trader_leaderboard WHERE market = $1 AND period = $2 AND period_start = $3 AND volume_type = $4 AND role = $5",
+            &[
+                &market.address,
+                &period.to_string(),
+                &period_start,
+                &volume_type.to_string(),
+                &role.to_string(),
+            ],
+        )
+        .await?;
+    if !traders.is_empty() {
+        let insert_statement = build_leaderboard_insert_statement(
+            &market.address,
+            period,
+            period_start,
+            volume_type,
+            role,
+            &traders,
+        );
+        transaction
+            .execute(&insert_statement, &[])
+            .await
+            .map_err_anyhow()?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
@@ -6,6 +6,8 @@ use prometheus::{
     register_int_gauge_with_registry, IntCounter, IntCounterVec, IntGauge, Registry,
 };
 
+use crate::database::telemetry::register_database_metrics;
+
 lazy_static! {
     static ref METRIC_REGISTRY: Registry =
         Registry::new_custom(Some("openbook_candles_worker".to_string()), None).unwrap();
@@ -30,6 +32,30 @@ lazy_static! {
         METRIC_REGISTRY
     )
     .unwrap();
+    pub static ref METRIC_CANDLE_UPSERTS_TOTAL: IntCounterVec =
+        register_int_counter_vec_with_registry!(
+            "candle_upserts_total",
+            "Candle rows written by the worker, by whether they were inserted, updated or unchanged",
+            &["market", "result"],
+            METRIC_REGISTRY
+        )
+        .unwrap();
+    pub static ref METRIC_COMPLETE_CANDLE_MUTATIONS_TOTAL: IntCounterVec =
+        register_int_counter_vec_with_registry!(
+            "complete_candle_mutations_total",
+            "Candles that changed after they had been marked complete",
+            &["market", "resolution"],
+            METRIC_REGISTRY
+        )
+        .unwrap();
+    pub static ref METRIC_REOPENED_CANDLES_TOTAL: IntCounterVec =
+        register_int_counter_vec_with_registry!(
+            "reopened_candles_total",
+            "Times complete candles were reopened because fills arrived after they closed",
+            &["market"],
+            METRIC_REGISTRY
+        )
+        .unwrap();
     pub static ref METRIC_TRANSACTIONS_TOTAL: IntCounter = register_int_counter_with_registry!(
         "transactions_total",
         "Total number of transaction signatures scraped",
@@ -44,6 +70,21 @@ lazy_static! {
             METRIC_REGISTRY
         )
         .unwrap();
+    pub static ref METRIC_FILL_INSERT_ERRORS_TOTAL: IntCounter =
+        register_int_counter_with_registry!(
+            "fill_insert_errors_total",
+            "Failed attempts to write a batch of ingested fills",
+            METRIC_REGISTRY
+        )
+        .unwrap();
+    pub static ref METRIC_BATCH_ERRORS_TOTAL: IntCounterVec =
+        register_int_counter_vec_with_registry!(
+            "batch_errors_total",
+            "Candle batches that failed",
+            &["market"],
+            METRIC_REGISTRY
+        )
+        .unwrap();
     pub static ref METRIC_DB_POOL_SIZE: IntGauge = register_int_gauge_with_registry!(
         "db_pool_size",
         "Current size of the DB connection pool",
@@ -56,9 +97,16 @@ lazy_static! {
         METRIC_REGISTRY
     )
     .unwrap();
+    pub static ref METRIC_CLUSTER_REPLICAS: IntGauge = register_int_gauge_with_registry!(
+        "cluster_replicas",
+        "Live worker replicas sharing the markets",
+        METRIC_REGISTRY
+    )
+    .unwrap();
 }
 
 pub async fn serve_metrics() -> anyhow::Result<Server> {
+    register_database_metrics(&METRIC_REGISTRY, "")?;
     let metrics = PrometheusMetricsBuilder::new("openbook_candles_worker")
         .registry(METRIC_REGISTRY.clone())
         .exclude("/metrics")
@@ -1,2 +1,21 @@
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod alerts;
 pub mod candle_batching;
+pub mod cluster;
+pub mod compaction;
+pub mod comparator;
+pub mod composite;
+pub mod depth_stats;
+pub mod ingestion;
+pub mod jobs;
+pub mod leaderboard;
 pub mod metrics;
+pub mod oracle;
+pub mod patterns;
+pub mod reconciliation;
+pub mod retention;
+pub mod shutdown;
+pub mod spread_history;
+pub mod uptime;
+pub mod verification;
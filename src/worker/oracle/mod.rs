@@ -0,0 +1,75 @@
+use std::{str::FromStr, time::Duration};
+
+use deadpool_postgres::Pool;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{
+    database::insert::build_oracle_prices_insert_statement,
+    structs::oracle::{OracleConfig, OracleFeed, OraclePrice},
+    utils::AnyhowWrap,
+};
+
+/// Polls every configured oracle feed and stores prices that were published since the last poll.
+pub async fn record_oracle_prices(
+    pool: &Pool,
+    rpc_url: String,
+    config: &OracleConfig,
+) -> anyhow::Result<()> {
+    let feeds = config.feeds()?;
+    let accounts = feeds
+        .iter()
+        .map(|f| Pubkey::from_str(&f.account))
+        .collect::<Result<Vec<Pubkey>, _>>()?;
+    let client = RpcClient::new(rpc_url);
+    loop {
+        if let Err(e) = record_oracle_prices_inner(pool, &client, &feeds, &accounts).await {
+            warn!("Failed to record oracle prices: {:?}", e);
+        }
+        sleep(Duration::from_secs(config.oracle_poll_secs)).await;
+    }
+}
+
+async fn record_oracle_prices_inner(
+    pool: &Pool,
+    client: &RpcClient,
+    feeds: &[OracleFeed],
+    accounts: &[Pubkey],
+) -> anyhow::Result<()> {
+    let results = client.get_multiple_accounts(accounts).await?;
+    let prices = feeds
+        .iter()
+        .zip(results)
+        .filter_map(|(feed, account)| {
+            let decoded = account.and_then(|a| feed.provider.decode(&a.data));
+            if decoded.is_none() {
+                warn!(
+                    "No usable {} price for {}",
+                    feed.provider.as_str(),
+                    feed.symbol
+                );
+            }
+            let (price, confidence, time) = decoded?;
+            Some(OraclePrice {
+                symbol: feed.symbol.clone(),
+                provider: feed.provider.as_str().to_string(),
+                time,
+                price,
+                confidence,
+            })
+        })
+        .collect::<Vec<OraclePrice>>();
+    if prices.is_empty() {
+        return Ok(());
+    }
+
+    let insert_statement = build_oracle_prices_insert_statement(&prices);
+    let db_client = pool.get().await?;
+    db_client
+        .execute(&insert_statement, &[])
+        .await
+        .map_err_anyhow()?;
+    Ok(())
+}
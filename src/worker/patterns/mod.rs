@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{
+    database::fetch::fetch_recent_candles,
+    structs::{
+        markets::MarketInfo,
+        pattern::{detect_patterns, PatternDetection},
+        resolution::Resolution,
+    },
+    worker::cluster::MarketAssignment,
+};
+
+/// Candles fetched per check, enough for a two candle pattern to complete on the newest one
+const CANDLES_PER_CHECK: i64 = 3;
+
+fn default_pattern_webhook_resolution() -> String {
+    "15M".to_string()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PatternWebhookConfig {
+    /// Detections are only posted when a webhook URL is configured
+    #[serde(serialize_with = "crate::utils::settings::redact_url")]
+    pub pattern_webhook_url: Option<String>,
+    #[serde(default = "default_pattern_webhook_resolution")]
+    pub pattern_webhook_resolution: String,
+}
+
+impl PatternWebhookConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.pattern_webhook_url.is_some()
+    }
+}
+
+/// Posts the patterns completed by each new candle of the owned markets to the webhook, as a
+/// JSON array of detections.
+pub async fn post_pattern_detections(
+    config: &PatternWebhookConfig,
+    pool: &Pool,
+    markets: Vec<MarketInfo>,
+    assignment: MarketAssignment,
+) -> anyhow::Result<()> {
+    let resolution = Resolution::from_str(&config.pattern_webhook_resolution)
+        .map_err(|_| anyhow::anyhow!("invalid PATTERN_WEBHOOK_RESOLUTION"))?;
+    let webhook_url = config.pattern_webhook_url.clone().unwrap_or_default();
+    let http_client = reqwest::Client::new();
+    info!(
+        "Posting {} candle patterns for {} markets",
+        resolution,
+        markets.len()
+    );
+
+    // candles that completed before startup aren't news anymore
+    let started_at = Utc::now() - resolution.get_duration();
+    let mut posted_through: HashMap<String, DateTime<Utc>> = HashMap::new();
+    loop {
+        for market in markets.iter().filter(|m| assignment.owns(&m.address)) {
+            let after = *posted_through.get(&market.name).unwrap_or(&started_at);
+            match detect_new_patterns(pool, market, resolution, after).await {
+                Ok(detections) if !detections.is_empty() => {
+                    let result = http_client
+                        .post(&webhook_url)
+                        .json(&detections)
+                        .send()
+                        .await
+                        .and_then(|r| r.error_for_status());
+                    match result {
+                        Ok(_) => {
+                            let newest = detections.iter().map(|d| d.start_time).max().unwrap();
+                            posted_through.insert(market.name.clone(), newest);
+                        }
+                        Err(e) => error!("Failed to post patterns for {}: {:?}", market.name, e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to detect patterns for {}: {:?}", market.name, e),
+            }
+        }
+        sleep(chrono::Duration::minutes(1).to_std()?).await;
+    }
+}
+
+async fn detect_new_patterns(
+    pool: &Pool,
+    market: &MarketInfo,
+    resolution: Resolution,
+    after: DateTime<Utc>,
+) -> anyhow::Result<Vec<PatternDetection>> {
+    let candles = fetch_recent_candles(pool, &market.name, resolution, CANDLES_PER_CHECK).await?;
+    Ok(detect_patterns(&candles)
+        .into_iter()
+        .filter(|d| d.start_time > after)
+        .collect())
+}
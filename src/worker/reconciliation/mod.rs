@@ -0,0 +1,156 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use deadpool_postgres::Pool;
+use serde_derive::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::TransactionConfirmationStatus;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    database::reconciliation::{
+        delete_fills_of_signatures, fetch_fill_signatures_between, invalidate_candles_from,
+    },
+    structs::markets::MarketInfo,
+    worker::cluster::MarketAssignment,
+};
+
+/// Most signatures `getSignatureStatuses` accepts per request
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+fn default_reconcile_lookback_mins() -> i64 {
+    60
+}
+
+fn default_reconcile_interval_secs() -> u64 {
+    30
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReconciliationConfig {
+    /// Fills scraped at `confirmed` can belong to transactions that never finalize
+    #[serde(default)]
+    pub reconcile_fills: bool,
+    /// How far back fills are verified after a restart
+    #[serde(default = "default_reconcile_lookback_mins")]
+    pub reconcile_lookback_mins: i64,
+    #[serde(default = "default_reconcile_interval_secs")]
+    pub reconcile_interval_secs: u64,
+}
+
+impl ReconciliationConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.reconcile_fills
+    }
+}
+
+enum Verdict {
+    Finalized,
+    Pending,
+    /// Failed, or dropped with its fork
+    NotFinalized,
+}
+
+/// Checks the transactions of recently ingested fills against finalized blocks. Fills of
+/// transactions that failed or were dropped are deleted and the candles built from them are
+/// rebuilt by the batcher.
+pub async fn reconcile_fills(
+    pool: &Pool,
+    config: &ReconciliationConfig,
+    rpc_url: String,
+    markets: Vec<MarketInfo>,
+    assignment: MarketAssignment,
+) -> anyhow::Result<()> {
+    let client = RpcClient::new(rpc_url);
+    // fills up to here have been verified by this process
+    let mut verified_through = Utc::now() - Duration::minutes(config.reconcile_lookback_mins);
+    loop {
+        match reconcile_inner(pool, &client, &markets, &assignment, verified_through).await {
+            Ok(through) => verified_through = through,
+            Err(e) => warn!("Failed to reconcile fills: {:?}", e),
+        }
+        sleep(std::time::Duration::from_secs(
+            config.reconcile_interval_secs,
+        ))
+        .await;
+    }
+}
+
+async fn reconcile_inner(
+    pool: &Pool,
+    client: &RpcClient,
+    markets: &[MarketInfo],
+    assignment: &MarketAssignment,
+    verified_through: DateTime<Utc>,
+) -> anyhow::Result<DateTime<Utc>> {
+    let owned: Vec<&str> = markets
+        .iter()
+        .filter(|m| assignment.owns(&m.address))
+        .map(|m| m.address.as_str())
+        .collect();
+    let now = Utc::now();
+    if owned.is_empty() {
+        return Ok(now);
+    }
+
+    let signatures = fetch_fill_signatures_between(pool, &owned, verified_through, now).await?;
+    let mut not_finalized = vec![];
+    let mut through = now;
+    'chunks: for chunk in signatures.chunks(MAX_SIGNATURES_PER_REQUEST) {
+        let parsed = chunk
+            .iter()
+            .map(|(s, _)| Signature::from_str(s))
+            .collect::<Result<Vec<Signature>, _>>()?;
+        // the history search finds transactions that already left the status cache
+        let statuses = client
+            .get_signature_statuses_with_history(&parsed)
+            .await?
+            .value;
+        for ((signature, time), status) in chunk.iter().zip(statuses) {
+            let verdict = match status {
+                None => Verdict::NotFinalized,
+                Some(s) if s.err.is_some() => Verdict::NotFinalized,
+                Some(s) => match s.confirmation_status {
+                    Some(TransactionConfirmationStatus::Finalized) => Verdict::Finalized,
+                    // older nodes only report a confirmation count, `None` once finalized
+                    None if s.confirmations.is_none() => Verdict::Finalized,
+                    _ => Verdict::Pending,
+                },
+            };
+            match verdict {
+                Verdict::Finalized => {}
+                Verdict::NotFinalized => not_finalized.push(signature.clone()),
+                Verdict::Pending => {
+                    // pick up from here next time
+                    through = *time - Duration::microseconds(1);
+                    break 'chunks;
+                }
+            }
+        }
+    }
+
+    if !not_finalized.is_empty() {
+        let affected = delete_fills_of_signatures(pool, &not_finalized).await?;
+        info!(
+            "Deleted fills of {} transactions that did not finalize",
+            not_finalized.len()
+        );
+        let names: HashMap<&str, &str> = markets
+            .iter()
+            .map(|m| (m.address.as_str(), m.name.as_str()))
+            .collect();
+        for (market, earliest) in affected {
+            if let Some(name) = names.get(market.as_str()) {
+                let from = earliest.duration_trunc(Duration::minutes(1))?;
+                invalidate_candles_from(pool, name, from).await?;
+            }
+        }
+    }
+    Ok(through)
+}
@@ -0,0 +1,171 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use deadpool_postgres::Pool;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{
+    database::{
+        jobs::insert_job_unless_pending,
+        retention::{
+            delete_fills_before, fetch_candles_complete_through, record_fill_retention_watermark,
+        },
+    },
+    structs::{
+        job::{NewJob, JOB_KIND_PRUNE},
+        markets::MarketInfo,
+    },
+};
+#[cfg(feature = "archive")]
+use crate::{
+    database::{lifecycle::fetch_first_fill_time, retention::fetch_fill_retention_watermark},
+    worker::archive::{archive_fills, ArchiveDestination},
+};
+
+fn default_fill_retention_batch_size() -> i64 {
+    10_000
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RetentionConfig {
+    /// Pruning is disabled unless a retention window is configured
+    pub fill_retention_days: Option<i64>,
+    /// Fills deleted per statement, keeps locks and WAL bursts small
+    #[serde(default = "default_fill_retention_batch_size")]
+    pub fill_retention_batch_size: i64,
+    /// Fills are written here as Parquet before they're deleted, see `ArchiveDestination`
+    pub fill_archive_destination: Option<String>,
+}
+
+impl RetentionConfig {
+    pub fn from_env() -> Result<Self, config::ConfigError> {
+        crate::utils::settings::load()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.fill_retention_days.is_some()
+    }
+}
+
+/// Queues a pruning pass over every market every hour, unless one is still pending. The passes
+/// run as jobs so they survive restarts and only one replica prunes at a time.
+pub async fn schedule_pruning(pool: &Pool) -> anyhow::Result<()> {
+    let job = NewJob {
+        kind: JOB_KIND_PRUNE.to_string(),
+        market_name: None,
+        resolutions: vec![],
+        start_time: None,
+        end_time: None,
+        priority: -1,
+        max_attempts: 1,
+    };
+    loop {
+        if let Err(e) = insert_job_unless_pending(pool, &job).await {
+            error!("Failed to queue fill pruning: {:?}", e);
+        }
+        sleep(Duration::hours(1).to_std()?).await;
+    }
+}
+
+/// Deletes fills older than the retention window once every candle built from them is complete.
+/// The cutoff is recorded per market first, so backfills know not to rebuild candles before it.
+/// Markets that fail are logged and skipped, the error of the last one is returned.
+pub async fn prune_fills(
+    pool: &Pool,
+    config: &RetentionConfig,
+    markets: &[MarketInfo],
+) -> anyhow::Result<()> {
+    if !config.is_enabled() {
+        anyhow::bail!("FILL_RETENTION_DAYS is not set");
+    }
+    let retention = Duration::days(config.fill_retention_days.unwrap_or_default());
+    let mut result = Ok(());
+    for market in markets.iter() {
+        if let Err(e) = prune_market(pool, config, market, retention).await {
+            error!("Failed to prune fills for {}: {:?}", market.name, e);
+            result = Err(e);
+        }
+    }
+    result
+}
+
+async fn prune_market(
+    pool: &Pool,
+    config: &RetentionConfig,
+    market: &MarketInfo,
+    retention: Duration,
+) -> anyhow::Result<()> {
+    let complete_through =
+        match fetch_candles_complete_through(pool, &market.name, &market.resolutions).await? {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+    let cutoff = std::cmp::min(Utc::now() - retention, complete_through)
+        .duration_trunc(Duration::days(1))?;
+
+    archive_fills_before(pool, config, market, cutoff).await?;
+    record_fill_retention_watermark(pool, &market.address, cutoff).await?;
+
+    let mut deleted = 0;
+    loop {
+        let n = delete_fills_before(
+            pool,
+            &market.address,
+            cutoff,
+            config.fill_retention_batch_size,
+        )
+        .await?;
+        deleted += n;
+        if n < config.fill_retention_batch_size as u64 {
+            break;
+        }
+    }
+    if deleted > 0 {
+        info!(
+            "Pruned {} fills before {} for {}",
+            deleted, cutoff, market.name
+        );
+    }
+    Ok(())
+}
+
+/// Archives the fills that are about to be pruned, from the previous cutoff onwards.
+#[cfg(feature = "archive")]
+async fn archive_fills_before(
+    pool: &Pool,
+    config: &RetentionConfig,
+    market: &MarketInfo,
+    cutoff: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let destination = match &config.fill_archive_destination {
+        Some(d) => ArchiveDestination::parse(d)?,
+        None => return Ok(()),
+    };
+    let from = match fetch_fill_retention_watermark(pool, &market.address).await? {
+        Some(t) => t,
+        None => match fetch_first_fill_time(pool, &market.address).await? {
+            Some(t) => t,
+            None => return Ok(()),
+        },
+    };
+    if from < cutoff {
+        archive_fills(pool, &destination, market, from, cutoff).await?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "archive"))]
+async fn archive_fills_before(
+    _pool: &Pool,
+    config: &RetentionConfig,
+    _market: &MarketInfo,
+    _cutoff: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    // refuse to delete fills that were meant to be archived first
+    if config.fill_archive_destination.is_some() {
+        anyhow::bail!(
+            "FILL_ARCHIVE_DESTINATION is set but the worker was built without the archive feature"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use tokio::sync::{oneshot, watch};
+use tracing::info;
+
+/// Tells long running tasks to stop at their next safe point. Cloned into every task that
+/// should finish its current unit of work before the worker exits.
+#[derive(Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once shutdown was requested, immediately if it already was.
+    pub async fn requested(&mut self) {
+        while !self.is_requested() {
+            if self.receiver.changed().await.is_err() {
+                // the listener is gone without ever firing
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// A shutdown of a single task, requested along with this one or through the returned
+    /// handle, e.g. when its market is removed from the configuration.
+    pub fn stoppable(&self) -> (StopHandle, Shutdown) {
+        let (sender, receiver) = watch::channel(self.is_requested());
+        let (stop, stopped) = oneshot::channel();
+        let mut parent = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = parent.requested() => {}
+                // also when the handle is dropped
+                _ = stopped => {}
+            }
+            sender.send(true).ok();
+        });
+        (StopHandle(stop), Shutdown { receiver })
+    }
+}
+
+/// Stops the task of a `Shutdown::stoppable`, which also happens when the handle is dropped.
+pub struct StopHandle(oneshot::Sender<()>);
+
+impl StopHandle {
+    pub fn stop(self) {
+        self.0.send(()).ok();
+    }
+}
+
+/// Requests a shutdown on SIGTERM or ctrl-c.
+pub fn listen_for_shutdown() -> Shutdown {
+    let (sender, receiver) = watch::channel(false);
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutdown requested, finishing in-flight work");
+        sender.send(true).ok();
+    });
+    Shutdown { receiver }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    tokio::signal::ctrl_c().await.ok();
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use deadpool_postgres::Pool;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{
+    database::insert::build_spread_samples_insert_statement,
+    structs::{
+        markets::MarketInfo,
+        slab::get_orderbook_snapshots,
+        spread::{SpreadConfig, SpreadSample},
+    },
+    utils::AnyhowWrap,
+    worker::cluster::MarketAssignment,
+};
+
+/// Samples the best bid and ask of every owned market every `spread_sample_secs`.
+pub async fn record_spread_samples(
+    pool: &Pool,
+    rpc_url: String,
+    markets: Vec<MarketInfo>,
+    assignment: MarketAssignment,
+    config: &SpreadConfig,
+) -> anyhow::Result<()> {
+    let client = RpcClient::new(rpc_url);
+    loop {
+        if let Err(e) = record_spread_samples_inner(pool, &client, &markets, &assignment).await {
+            warn!("Failed to record spread samples: {:?}", e);
+        }
+        sleep(Duration::from_secs(config.spread_sample_secs)).await;
+    }
+}
+
+async fn record_spread_samples_inner(
+    pool: &Pool,
+    client: &RpcClient,
+    markets: &[MarketInfo],
+    assignment: &MarketAssignment,
+) -> anyhow::Result<()> {
+    let owned: Vec<MarketInfo> = markets
+        .iter()
+        .filter(|m| assignment.owns(&m.address))
+        .cloned()
+        .collect();
+    if owned.is_empty() {
+        return Ok(());
+    }
+    let samples = owned
+        .iter()
+        .zip(get_orderbook_snapshots(client, &owned).await?)
+        .filter_map(|(m, s)| SpreadSample::from_order_book(&m.name, &s?))
+        .collect::<Vec<SpreadSample>>();
+    if samples.is_empty() {
+        return Ok(());
+    }
+
+    let insert_statement = build_spread_samples_insert_statement(&samples);
+    let db_client = pool.get().await?;
+    db_client
+        .execute(&insert_statement, &[])
+        .await
+        .map_err_anyhow()?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use deadpool_postgres::Pool;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{
+    database::uptime::record_uptime_sample,
+    structs::{
+        markets::MarketInfo,
+        slab::get_resting_orders,
+        uptime::{quoting_owners, UptimeConfig},
+    },
+    worker::cluster::MarketAssignment,
+};
+
+/// Looks at the books of every owned market every `uptime_sample_secs` and counts, per UTC day,
+/// the samples each open orders account quoted in.
+pub async fn record_maker_uptime(
+    pool: &Pool,
+    rpc_url: String,
+    markets: Vec<MarketInfo>,
+    assignment: MarketAssignment,
+    config: &UptimeConfig,
+) -> anyhow::Result<()> {
+    let client = RpcClient::new(rpc_url);
+    loop {
+        if let Err(e) =
+            record_maker_uptime_inner(pool, &client, &markets, &assignment, config).await
+        {
+            warn!("Failed to record maker uptime: {:?}", e);
+        }
+        sleep(Duration::from_secs(config.uptime_sample_secs)).await;
+    }
+}
+
+async fn record_maker_uptime_inner(
+    pool: &Pool,
+    client: &RpcClient,
+    markets: &[MarketInfo],
+    assignment: &MarketAssignment,
+    config: &UptimeConfig,
+) -> anyhow::Result<()> {
+    let owned: Vec<MarketInfo> = markets
+        .iter()
+        .filter(|m| assignment.owns(&m.address))
+        .cloned()
+        .collect();
+    if owned.is_empty() {
+        return Ok(());
+    }
+    let day = Utc::now().date_naive();
+    for (market, book) in owned.iter().zip(get_resting_orders(client, &owned).await?) {
+        // a book that couldn't be read isn't a sample, nobody is blamed for it
+        let (bids, asks) = match book {
+            Some(book) => book,
+            None => continue,
+        };
+        let owners = quoting_owners(
+            &bids,
+            &asks,
+            config.uptime_max_spread_bps,
+            config.uptime_min_quote_size,
+        );
+        record_uptime_sample(pool, &market.address, day, &owners).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tracing::info;
+
+use crate::{
+    database::{
+        anomalies::fetch_anomalous_seq_nums, fetch::fetch_fills_from,
+        retention::fetch_fill_retention_watermark, verification::fetch_sampled_candles,
+    },
+    structs::{candle::Candle, markets::MarketInfo, openbook::PgOpenBookFill},
+    utils::{f64_max, f64_min},
+    worker::candle_batching::aggregate::priced_vwap,
+};
+
+/// Relative tolerance used when comparing stored candles to values recomputed from fills
+const TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Debug, Default)]
+pub struct VerificationReport {
+    pub market_name: String,
+    pub resolution: String,
+    pub sampled: usize,
+    /// Sampled candles with at least one field off
+    pub mismatched: usize,
+    /// Sampled candles whose fills were pruned, so there is nothing to compare them to
+    pub unverified: usize,
+    pub mismatches: Vec<CandleMismatch>,
+}
+
+/// A field of a stored candle that disagrees with the value recomputed from its fills
+#[derive(Clone, Debug, PartialEq)]
+pub struct CandleMismatch {
+    pub start_time: DateTime<Utc>,
+    pub field: &'static str,
+    pub stored: f64,
+    pub expected: f64,
+}
+
+/// Recomputes up to `samples` randomly chosen complete candles since `since` per market and
+/// resolution it keeps from their fills and reports every field that doesn't match. Imported
+/// candles have no fills behind them and are never sampled.
+pub async fn verify_candles(
+    pool: &Pool,
+    markets: &Vec<MarketInfo>,
+    since: DateTime<Utc>,
+    samples: i64,
+) -> anyhow::Result<Vec<VerificationReport>> {
+    let mut reports = vec![];
+    for market in markets.iter() {
+        let pruned_before = fetch_fill_retention_watermark(pool, &market.address).await?;
+        for resolution in market.resolutions.iter().copied() {
+            let candles =
+                fetch_sampled_candles(pool, &market.name, resolution, since, samples).await?;
+            let mut report = VerificationReport {
+                market_name: market.name.clone(),
+                resolution: resolution.to_string(),
+                sampled: candles.len(),
+                ..Default::default()
+            };
+            for candle in candles.iter() {
+                // fills for this bucket were pruned, an empty set would prove nothing
+                if matches!(pruned_before, Some(t) if candle.start_time < t) {
+                    report.unverified += 1;
+                    continue;
+                }
+                let fills =
+                    fetch_fills_from(pool, &market.address, candle.start_time, candle.end_time)
+                        .await?;
+                let anomalous = fetch_anomalous_seq_nums(
+                    pool,
+                    &market.address,
+                    candle.start_time,
+                    candle.end_time,
+                )
+                .await?;
+                let expected = recompute_candle(candle, &fills, &anomalous);
+                let mismatches = diff_candles(candle, &expected);
+                if !mismatches.is_empty() {
+                    report.mismatched += 1;
+                    report.mismatches.extend(mismatches);
+                }
+            }
+            info!(
+                "verified {} {}: {} sampled, {} mismatched",
+                report.market_name, report.resolution, report.sampled, report.mismatched
+            );
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
+
+/// Rebuilds a candle from its bucket's fills the way the batcher does, starting from the stored
+/// open since that depends on the previous bucket. Fills flagged anomalous count towards volume
+/// and trades but not towards prices.
+fn recompute_candle(candle: &Candle, fills: &[PgOpenBookFill], anomalous: &[i64]) -> Candle {
+    let mut expected = Candle {
+        close: candle.open,
+        high: candle.open,
+        low: candle.open,
+        volume: 0.0,
+        trade_count: 0,
+        quote_volume: 0.0,
+        priced_volume: 0.0,
+        priced_quote_volume: 0.0,
+        ..candle.clone()
+    };
+    for fill in fills.iter() {
+        expected.volume += fill.size;
+        expected.trade_count += 1;
+        expected.quote_volume += fill.price * fill.size;
+        if anomalous.contains(&fill.seq_num) {
+            continue;
+        }
+        expected.close = fill.price;
+        expected.high = f64_max(expected.high, fill.price);
+        expected.low = f64_min(expected.low, fill.price);
+        expected.priced_volume += fill.size;
+        expected.priced_quote_volume += fill.price * fill.size;
+    }
+    expected.vwap = priced_vwap(&expected);
+    expected
+}
+
+fn diff_candles(stored: &Candle, expected: &Candle) -> Vec<CandleMismatch> {
+    let fields = [
+        ("close", stored.close, expected.close),
+        ("high", stored.high, expected.high),
+        ("low", stored.low, expected.low),
+        ("volume", stored.volume, expected.volume),
+        ("vwap", stored.vwap, expected.vwap),
+        (
+            "trade_count",
+            stored.trade_count as f64,
+            expected.trade_count as f64,
+        ),
+        ("quote_volume", stored.quote_volume, expected.quote_volume),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, stored, expected)| !approx_eq(*stored, *expected))
+        .map(|(field, stored_value, expected_value)| CandleMismatch {
+            start_time: stored.start_time,
+            field,
+            stored: stored_value,
+            expected: expected_value,
+        })
+        .collect()
+}
+
+fn approx_eq(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TOLERANCE * f64_max(a.abs(), b.abs())
+}
@@ -0,0 +1,272 @@
+//! Boots the server on fixture data and compares every public endpoint's JSON with the snapshots
+//! in `tests/snapshots/api`. Needs Docker, run with `cargo test --features integration-tests`. A
+//! missing snapshot fails the test, set `UPDATE_SNAPSHOTS=1` to record them, and to rerecord all
+//! of them after an intended change.
+#![cfg(feature = "integration-tests")]
+
+use std::{
+    fs,
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, DurationRound, Utc};
+use serde_json::{json, Map, Value};
+use testcontainers::clients::Cli;
+
+mod common;
+
+use common::{database_env, postgres_image};
+
+/// Cases over a window ending now rather than the fixed day, only their times and numbers differ
+/// from run to run
+const CLOCK_RELATIVE: [&str; 4] = [
+    "markets_summary",
+    "candles_recent",
+    "traders_top",
+    "coingecko_tickers",
+];
+
+/// Seeding the fixtures and building their candles happens before the server binds
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Stops the server when the test ends, also when it panics.
+struct ServerProcess(Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Relative difference tolerated between numbers, sums in Postgres needn't add up in one order
+const TOLERANCE: f64 = 1e-9;
+
+/// Whether a field holds times, as unix seconds or RFC 3339
+fn is_time_key(key: &str) -> bool {
+    key == "time"
+        || key == "timestamp"
+        || key.ends_with("_time")
+        || key.ends_with("_at")
+        || key.ends_with("_through")
+}
+
+/// The JSON with its times, which move with the clock, replaced by `"<time>"`. Endpoints over a
+/// window ending now see different fills on every run, `pinned: false` also replaces their
+/// numbers, in JSON or as strings, by `"<number>"`.
+fn normalize(value: &Value, pinned: bool, time: bool) -> Value {
+    match value {
+        Value::Number(_) if time => json!("<time>"),
+        Value::Number(_) if !pinned => json!("<number>"),
+        Value::String(s) if time || DateTime::parse_from_rfc3339(s).is_ok() => json!("<time>"),
+        Value::String(s) if !pinned && s.parse::<f64>().is_ok() => json!("<number>"),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| normalize(item, pinned, time))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), normalize(value, pinned, is_time_key(key))))
+                .collect::<Map<_, _>>(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Equal up to `TOLERANCE` in every number.
+fn same(stored: &Value, actual: &Value) -> bool {
+    match (stored, actual) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+            (a - b).abs() <= TOLERANCE * a.abs().max(b.abs())
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).map_or(false, |b| same(a, b)))
+        }
+        _ => stored == actual,
+    }
+}
+
+/// Compares with the stored snapshot, or records it when `UPDATE_SNAPSHOTS=1`. Returns a
+/// description of the difference, or of the snapshot missing.
+fn check_snapshot(name: &str, actual: &Value) -> Option<String> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots/api")
+        .join(format!("{}.json", name));
+    let rendered = serde_json::to_string_pretty(actual).unwrap() + "\n";
+    let update = std::env::var("UPDATE_SNAPSHOTS").map_or(false, |v| v == "1");
+    match fs::read_to_string(&path) {
+        Ok(stored) if !update => {
+            let stored: Value = serde_json::from_str(&stored).unwrap();
+            (!same(&stored, actual)).then(|| {
+                format!(
+                    "{} changed\n--- stored\n{}\n+++ actual\n{}",
+                    name,
+                    serde_json::to_string_pretty(&stored).unwrap(),
+                    rendered
+                )
+            })
+        }
+        Err(_) if !update => Some(format!("{} has no snapshot at {}", name, path.display())),
+        _ => {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, rendered).unwrap();
+            None
+        }
+    }
+}
+
+#[tokio::test]
+async fn endpoints_match_their_snapshots() {
+    let docker = Cli::default();
+    let node = docker.run(postgres_image());
+    let port = free_port();
+    let base_url = format!("http://127.0.0.1:{}", port);
+
+    // a complete day of the fixture history, generated relative to the day before it so that the
+    // same trades fall into the day on every run
+    let to = Utc::now()
+        .duration_trunc(chrono::Duration::hours(1))
+        .unwrap()
+        .timestamp();
+    let epoch = to - 2 * 86400;
+
+    let _server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_openbook-candles"))
+            .args(["server", "--fixtures"])
+            .envs(database_env(node.get_host_port_ipv4(5432)))
+            .env("SERVER_BIND_ADDR", format!("127.0.0.1:{}", port))
+            .env("FIXTURE_DAYS", "2")
+            .env("FIXTURE_EPOCH", epoch.to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let client = reqwest::Client::new();
+    let started = Instant::now();
+    while client
+        .get(format!("{}/api/markets", base_url))
+        .send()
+        .await
+        .is_err()
+    {
+        assert!(
+            started.elapsed() < STARTUP_TIMEOUT,
+            "server didn't start on fixtures"
+        );
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    let from = (to - 86400).to_string();
+    let to = to.to_string();
+    let range = |params: &[(&'static str, &str)]| {
+        let mut params: Vec<(&'static str, String)> = params
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect();
+        params.push(("from", from.clone()));
+        params.push(("to", to.clone()));
+        params
+    };
+
+    let cases: Vec<(&str, &str, Vec<(&str, String)>)> = vec![
+        ("markets", "/api/markets", vec![]),
+        ("markets_summary", "/api/markets/summary", vec![]),
+        (
+            "candles",
+            "/api/candles",
+            range(&[("market_name", "ALPHA/USDC"), ("resolution", "1H")]),
+        ),
+        (
+            "candles_recent",
+            "/api/candles/recent",
+            vec![
+                ("market_name", "ALPHA/USDC".to_string()),
+                ("resolution", "1M".to_string()),
+                ("n", "5".to_string()),
+            ],
+        ),
+        (
+            "candles_batch",
+            "/api/candles/batch",
+            range(&[("pairs", "ALPHA/USDC:1H,BETA/USDC:15M")]),
+        ),
+        (
+            "trades",
+            "/api/trades",
+            range(&[("market_name", "ALPHA/USDC"), ("limit", "5")]),
+        ),
+        (
+            "traders_base_volume",
+            "/api/traders/base-volume",
+            range(&[("market_name", "ALPHA/USDC")]),
+        ),
+        (
+            "traders_quote_volume",
+            "/api/traders/quote-volume",
+            range(&[("market_name", "ALPHA/USDC")]),
+        ),
+        (
+            "traders_top",
+            "/api/traders/top",
+            vec![
+                ("market_name", "ALPHA/USDC".to_string()),
+                ("period", "1D".to_string()),
+                ("volume_type", "base".to_string()),
+            ],
+        ),
+        ("coingecko_pairs", "/api/coingecko/pairs", vec![]),
+        ("coingecko_tickers", "/api/coingecko/tickers", vec![]),
+        (
+            "error_market_not_found",
+            "/api/candles",
+            range(&[("market_name", "NOPE/USDC"), ("resolution", "1H")]),
+        ),
+        (
+            "error_invalid_resolution",
+            "/api/candles",
+            range(&[("market_name", "ALPHA/USDC"), ("resolution", "7M")]),
+        ),
+    ];
+
+    let mut changes = vec![];
+    for (name, path, params) in cases {
+        let response = client
+            .get(format!("{}{}", base_url, path))
+            .query(&params)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        let body: Value = response.json().await.unwrap();
+        let pinned = !CLOCK_RELATIVE.contains(&name);
+        let snapshot = json!({ "status": status, "body": normalize(&body, pinned, false) });
+        changes.extend(check_snapshot(name, &snapshot));
+    }
+    assert!(
+        changes.is_empty(),
+        "{}\nrerun with UPDATE_SNAPSHOTS=1 if the changes are intended",
+        changes.join("\n")
+    );
+}
@@ -0,0 +1,448 @@
+//! Builds candles from fills in a throwaway Postgres and checks them at every resolution. Needs
+//! Docker, run with `cargo test --features integration-tests`.
+#![cfg(feature = "integration-tests")]
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveDate, TimeZone, Utc, Weekday};
+use deadpool_postgres::Pool;
+use openbook_candles::{
+    database::{
+        checkpoints::save_worker_checkpoint,
+        fetch::{fetch_candles_from, fetch_coingecko_24h_volume},
+        fill_import::copy_fills,
+        initialize::{connect_to_database, setup_database},
+        jobs::{claim_next_job, insert_job},
+    },
+    structs::{
+        candle::Candle,
+        checkpoint::WorkerCheckpoint,
+        job::{NewJob, JOB_KIND_REBUILD},
+        markets::MarketInfo,
+        openbook::{OpenBookFill, PgOpenBookFill},
+        resolution::Resolution,
+        venue::Venue,
+    },
+    worker::{
+        candle_batching::{
+            aggregate::{aggregate_fills_to_candles, AggregationOptions, OutlierFilter},
+            higher_order_candles::backfill_batch_higher_order_candles,
+            minute_candles::backfill_batch_1m_candles,
+        },
+        jobs::{run_job, JobContext},
+        retention::RetentionConfig,
+    },
+};
+use solana_sdk::pubkey::Pubkey;
+use strum::IntoEnumIterator;
+use testcontainers::clients::Cli;
+
+mod common;
+
+use common::{database_env, postgres_image};
+
+const EPSILON: f64 = 1e-9;
+
+/// Points `PgConfig` at the container, then connects and migrates.
+async fn database(port: u16) -> Pool {
+    for (key, value) in database_env(port) {
+        std::env::set_var(key, value);
+    }
+    let pool = connect_to_database().await.unwrap();
+    setup_database(&pool).await.unwrap();
+    pool
+}
+
+fn market(name: &str) -> MarketInfo {
+    MarketInfo {
+        name: name.to_string(),
+        address: Pubkey::new_unique().to_string(),
+        venue: Venue::OpenBook,
+        base_decimals: 9,
+        quote_decimals: 6,
+        base_mint_key: Pubkey::new_unique().to_string(),
+        quote_mint_key: Pubkey::new_unique().to_string(),
+        bids_key: Pubkey::new_unique().to_string(),
+        asks_key: Pubkey::new_unique().to_string(),
+        event_queue_key: Pubkey::new_unique().to_string(),
+        base_lot_size: 1_000_000,
+        quote_lot_size: 1,
+        resolutions: Resolution::iter().collect(),
+    }
+}
+
+/// Maker fill, the side candles are built from.
+fn fill(
+    market: &MarketInfo,
+    seq_num: i64,
+    time: DateTime<Utc>,
+    price: f64,
+    size: f64,
+) -> OpenBookFill {
+    OpenBookFill {
+        signature: format!("{}-{}", market.name, seq_num),
+        slot: time.timestamp(),
+        block_datetime: time,
+        market: market.address.clone(),
+        open_orders_owner: Pubkey::new_unique().to_string(),
+        bid: true,
+        maker: true,
+        native_quantity_paid: price * size * 1e6,
+        native_quantity_received: size * 1e9,
+        native_fee_or_rebate: 0.0,
+        price,
+        size,
+        seq_num,
+        instruction_num: 0,
+        fee: Some(0.0),
+        referrer_rebate: None,
+    }
+}
+
+/// Both fills of a trade: the maker's, on the given side, and the taker's on the other side.
+fn trade(
+    market: &MarketInfo,
+    seq_num: i64,
+    time: DateTime<Utc>,
+    price: f64,
+    size: f64,
+    maker_bid: bool,
+) -> [OpenBookFill; 2] {
+    let bid = fill(market, seq_num, time, price, size);
+    let (quote, base) = (bid.native_quantity_paid, bid.native_quantity_received);
+    let ask = OpenBookFill {
+        bid: false,
+        native_quantity_paid: base,
+        native_quantity_received: quote,
+        ..bid.clone()
+    };
+    let (maker, taker) = if maker_bid { (bid, ask) } else { (ask, bid) };
+    let taker = OpenBookFill {
+        signature: format!("{}-taker", taker.signature),
+        open_orders_owner: Pubkey::new_unique().to_string(),
+        maker: false,
+        ..taker
+    };
+    [maker, taker]
+}
+
+/// Most recent complete UTC day on which Europe or the US switched to or from daylight saving.
+fn last_dst_change() -> DateTime<Utc> {
+    let nth_sunday = |year: i32, month: u32, n: u32| {
+        let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+        let offset = (7 + 6 - first.weekday().num_days_from_monday()) % 7;
+        first + Duration::days((offset + 7 * (n - 1)) as i64)
+    };
+    let last_sunday = |year: i32, month: u32| {
+        let last = NaiveDate::from_ymd_opt(year, month + 1, 1).unwrap() - Duration::days(1);
+        last - Duration::days(last.weekday().num_days_from_sunday() as i64)
+    };
+    let today = Utc::now().date_naive();
+    let change = [today.year() - 1, today.year()]
+        .into_iter()
+        .flat_map(|year| {
+            [
+                nth_sunday(year, 3, 2),
+                last_sunday(year, 3),
+                last_sunday(year, 10),
+                nth_sunday(year, 11, 1),
+            ]
+        })
+        .filter(|day| *day < today)
+        .max()
+        .unwrap();
+    assert_eq!(change.weekday(), Weekday::Sun);
+    Utc.from_utc_datetime(&change.and_hms_opt(0, 0, 0).unwrap())
+}
+
+async fn candle_at(
+    pool: &Pool,
+    market: &MarketInfo,
+    resolution: Resolution,
+    time: DateTime<Utc>,
+) -> Candle {
+    let start = resolution.bucket_start(time);
+    let candles = fetch_candles_from(
+        pool,
+        &market.name,
+        resolution,
+        start,
+        resolution.bucket_end(start),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        candles.len(),
+        1,
+        "{} {} candle at {}",
+        market.name,
+        resolution,
+        start
+    );
+    candles.into_iter().next().unwrap()
+}
+
+fn assert_ohlcv(candle: &Candle, ohlcv: (f64, f64, f64, f64, f64)) {
+    let (open, high, low, close, volume) = ohlcv;
+    let context = format!(
+        "{} {} at {}",
+        candle.market_name, candle.resolution, candle.start_time
+    );
+    assert!((candle.open - open).abs() < EPSILON, "open of {}", context);
+    assert!((candle.high - high).abs() < EPSILON, "high of {}", context);
+    assert!((candle.low - low).abs() < EPSILON, "low of {}", context);
+    assert!(
+        (candle.close - close).abs() < EPSILON,
+        "close of {}",
+        context
+    );
+    assert!(
+        (candle.volume - volume).abs() < EPSILON,
+        "volume of {}",
+        context
+    );
+}
+
+/// One container for all cases: each case trades its own market.
+#[tokio::test]
+async fn candles_match_fills_at_every_resolution() {
+    let docker = Cli::default();
+    let node = docker.run(postgres_image());
+    let pool = database(node.get_host_port_ipv4(5432)).await;
+
+    // two days back, so every candle of the day is complete
+    let day = Utc::now().duration_trunc(Duration::days(1)).unwrap() - Duration::days(2);
+    let single = market("SINGLE/USDC");
+    let busy = market("BUSY/USDC");
+    let dst = market("DST/USDC");
+    let dst_day = last_dst_change();
+
+    let at = |hours: i64, minutes: i64, seconds: i64| {
+        day + Duration::hours(hours) + Duration::minutes(minutes) + Duration::seconds(seconds)
+    };
+    let fills = vec![
+        fill(&single, 1, at(10, 0, 30), 10.0, 2.0),
+        fill(&busy, 1, at(12, 0, 5), 100.0, 1.0),
+        fill(&busy, 2, at(12, 0, 20), 105.0, 2.0),
+        fill(&busy, 3, at(12, 0, 40), 95.0, 1.0),
+        fill(&busy, 4, at(12, 0, 55), 101.0, 3.0),
+        // minutes 12:01 to 12:06 are empty
+        fill(&busy, 5, at(12, 7, 10), 110.0, 1.0),
+        fill(&dst, 1, dst_day + Duration::seconds(30), 50.0, 1.0),
+        fill(
+            &dst,
+            2,
+            dst_day + Duration::minutes(23 * 60 + 30),
+            60.0,
+            1.0,
+        ),
+    ];
+    copy_fills(&pool, &fills).await.unwrap();
+
+    let markets = vec![single.clone(), busy.clone(), dst.clone()];
+    backfill_batch_1m_candles(&pool, markets.clone(), None)
+        .await
+        .unwrap();
+    for market in markets.iter() {
+        backfill_batch_higher_order_candles(&pool, market)
+            .await
+            .unwrap();
+    }
+
+    // a single fill is the whole candle, whatever its size
+    for resolution in Resolution::iter() {
+        let candle = candle_at(&pool, &single, resolution, at(10, 0, 30)).await;
+        assert_ohlcv(&candle, (10.0, 10.0, 10.0, 10.0, 2.0));
+        assert_eq!(candle.trade_count, 1);
+    }
+
+    // the first minute holds four fills, the bigger buckets the fifth as well
+    for resolution in Resolution::iter() {
+        let candle = candle_at(&pool, &busy, resolution, at(12, 0, 0)).await;
+        let minutes = resolution.get_duration().num_minutes();
+        if minutes <= 5 {
+            assert_ohlcv(&candle, (100.0, 105.0, 95.0, 101.0, 7.0));
+            assert_eq!(candle.trade_count, 4);
+        } else {
+            assert_ohlcv(&candle, (100.0, 110.0, 95.0, 110.0, 8.0));
+            assert_eq!(candle.trade_count, 5);
+        }
+    }
+
+    // empty minutes carry the previous close without volume
+    for minute in 1..7 {
+        let candle = candle_at(&pool, &busy, Resolution::R1m, at(12, minute, 0)).await;
+        assert_ohlcv(&candle, (101.0, 101.0, 101.0, 101.0, 0.0));
+        assert_eq!(candle.trade_count, 0);
+    }
+    // and a fill after a gap opens its minute at the previous close
+    let candle = candle_at(&pool, &busy, Resolution::R1m, at(12, 7, 0)).await;
+    assert_ohlcv(&candle, (101.0, 110.0, 101.0, 110.0, 1.0));
+    let candle = candle_at(&pool, &busy, Resolution::R5m, at(12, 5, 0)).await;
+    assert_ohlcv(&candle, (101.0, 110.0, 101.0, 110.0, 1.0));
+
+    // candles are in UTC, a day with a daylight saving change still has 24 hours
+    let candle = candle_at(&pool, &dst, Resolution::R1d, dst_day).await;
+    assert_eq!(candle.start_time, dst_day);
+    assert_eq!(candle.end_time, dst_day + Duration::days(1));
+    assert_ohlcv(&candle, (50.0, 60.0, 50.0, 60.0, 2.0));
+    let hours = fetch_candles_from(
+        &pool,
+        &dst.name,
+        Resolution::R1h,
+        dst_day,
+        dst_day + Duration::days(1),
+    )
+    .await
+    .unwrap();
+    assert_eq!(hours.len(), 24);
+    assert_ohlcv(&hours[23], (50.0, 60.0, 50.0, 60.0, 1.0));
+}
+
+/// A fat-finger print in the second minute counts towards volume but sets neither prices nor the
+/// vwap, at 1M and in the 1H candle built from it alike.
+#[test]
+fn outlier_fill_is_left_out_of_prices_and_vwap() {
+    let start = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+    let at = |seq_num: i64, seconds: i64, price: f64, size: f64| PgOpenBookFill {
+        time: start + Duration::seconds(seconds),
+        market_key: "market".to_string(),
+        bid: true,
+        maker: true,
+        price,
+        size,
+        slot: seconds,
+        seq_num,
+    };
+    let fills = [
+        at(1, 0, 10.0, 1.0),
+        at(2, 10, 10.0, 1.0),
+        at(3, 20, 10.0, 1.0),
+        at(4, 60, 10.0, 1.0),
+        at(5, 70, 1000.0, 5.0),
+        at(6, 80, 10.0, 1.0),
+    ];
+    let options = AggregationOptions {
+        market_name: "SOL/USDC".to_string(),
+        last_price: None,
+        as_of: start + Duration::hours(1),
+        outlier_filter: Some(OutlierFilter {
+            max_deviation: 0.5,
+            window: 10,
+        }),
+    };
+    let range = start..start + Duration::hours(1);
+
+    let minutes = aggregate_fills_to_candles(&fills, Resolution::R1m, range.clone(), &options);
+    assert_ohlcv(&minutes[1], (10.0, 10.0, 10.0, 10.0, 7.0));
+    assert!((minutes[1].vwap - 10.0).abs() < EPSILON, "1M vwap");
+    assert!((minutes[1].quote_volume - 5020.0).abs() < EPSILON);
+
+    let hours = aggregate_fills_to_candles(&fills, Resolution::R1h, range, &options);
+    assert_eq!(hours.len(), 1);
+    assert_ohlcv(&hours[0], (10.0, 10.0, 10.0, 10.0, 10.0));
+    assert!((hours[0].vwap - 10.0).abs() < EPSILON, "1H vwap");
+    assert!((hours[0].quote_volume - 5050.0).abs() < EPSILON);
+}
+
+/// Every trade is stored as a maker and a taker fill, the 24 hour volume counts each trade once
+/// whichever side the maker was on.
+#[tokio::test]
+async fn coingecko_volume_counts_each_trade_once() {
+    let docker = Cli::default();
+    let node = docker.run(postgres_image());
+    let pool = database(node.get_host_port_ipv4(5432)).await;
+
+    let traded = market("TRADED/USDC");
+    let quiet = market("QUIET/USDC");
+    let now = Utc::now();
+    let fills: Vec<OpenBookFill> = [
+        trade(&traded, 1, now - Duration::hours(3), 10.0, 2.0, true),
+        trade(&traded, 2, now - Duration::hours(2), 12.0, 1.0, false),
+        trade(&traded, 3, now - Duration::hours(1), 11.0, 0.5, true),
+        // older than a day
+        trade(&traded, 4, now - Duration::hours(30), 9.0, 100.0, false),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    copy_fills(&pool, &fills).await.unwrap();
+
+    let addresses = vec![traded.address.as_str(), quiet.address.as_str()];
+    // one market per query, so both batches are exercised
+    let volumes = fetch_coingecko_24h_volume(&pool, &addresses, 1)
+        .await
+        .unwrap();
+    assert_eq!(volumes.len(), 2);
+
+    let volume = volumes
+        .iter()
+        .find(|v| v.address == traded.address)
+        .unwrap();
+    assert!((volume.base_size - 3.5).abs() < EPSILON, "base volume");
+    assert!((volume.quote_size - 37.5).abs() < EPSILON, "quote volume");
+
+    let volume = volumes.iter().find(|v| v.address == quiet.address).unwrap();
+    assert_eq!((volume.base_size, volume.quote_size), (0.0, 0.0));
+}
+
+/// Rebuild jobs store resolutions by the names `Display` gives them, like the admin endpoint
+/// does when none are requested, and the worker parses them back.
+#[tokio::test]
+async fn rebuild_job_runs_with_the_default_resolutions() {
+    let docker = Cli::default();
+    let node = docker.run(postgres_image());
+    let pool = database(node.get_host_port_ipv4(5432)).await;
+
+    let day = Utc::now().duration_trunc(Duration::days(1)).unwrap() - Duration::days(2);
+    let rebuilt = market("REBUILT/USDC");
+    let fills = vec![
+        fill(&rebuilt, 1, day + Duration::hours(3), 10.0, 2.0),
+        fill(&rebuilt, 2, day + Duration::hours(15), 12.0, 1.0),
+    ];
+    copy_fills(&pool, &fills).await.unwrap();
+    // the batcher is past the day, but built none of its candles
+    save_worker_checkpoint(
+        &pool,
+        &WorkerCheckpoint {
+            market_name: rebuilt.name.clone(),
+            candles_through: Utc::now().duration_trunc(Duration::minutes(1)).unwrap(),
+            last_price: 12.0,
+            last_fill_time: Some(fills[1].block_datetime),
+            last_fill_slot: Some(fills[1].slot),
+        },
+    )
+    .await
+    .unwrap();
+
+    insert_job(
+        &pool,
+        &NewJob {
+            kind: JOB_KIND_REBUILD.to_string(),
+            market_name: Some(rebuilt.name.clone()),
+            resolutions: Resolution::iter().map(|r| r.to_string()).collect(),
+            start_time: Some(day),
+            end_time: Some(day + Duration::days(1)),
+            priority: 0,
+            max_attempts: 1,
+        },
+    )
+    .await
+    .unwrap();
+    let job = claim_next_job(&pool).await.unwrap().unwrap();
+    let context = JobContext {
+        markets: vec![rebuilt.clone()],
+        outlier_filter: None,
+        retention: RetentionConfig {
+            fill_retention_days: None,
+            fill_retention_batch_size: 10_000,
+            fill_archive_destination: None,
+        },
+    };
+    let written = run_job(&pool, &job, &context).await.unwrap();
+    assert!(written.unwrap() > 0);
+
+    let minute = candle_at(&pool, &rebuilt, Resolution::R1m, day + Duration::hours(3)).await;
+    assert_ohlcv(&minute, (10.0, 10.0, 10.0, 10.0, 2.0));
+    let daily = candle_at(&pool, &rebuilt, Resolution::R1d, day).await;
+    assert!((daily.high - 12.0).abs() < EPSILON);
+    assert!((daily.volume - 3.0).abs() < EPSILON);
+}
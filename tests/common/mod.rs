@@ -0,0 +1,25 @@
+//! Postgres in Docker, shared by the integration tests.
+
+use testcontainers::{core::WaitFor, GenericImage};
+
+pub fn postgres_image() -> GenericImage {
+    GenericImage::new("postgres", "15-alpine")
+        .with_env_var("POSTGRES_HOST_AUTH_METHOD", "trust")
+        .with_exposed_port(5432)
+        .with_wait_for(WaitFor::message_on_stderr(
+            "database system is ready to accept connections",
+        ))
+}
+
+/// The variables `PgConfig` reads to reach the container on `port`.
+pub fn database_env(port: u16) -> Vec<(&'static str, String)> {
+    vec![
+        ("PG_HOST", "127.0.0.1".to_string()),
+        ("PG_PORT", port.to_string()),
+        ("PG_USER", "postgres".to_string()),
+        ("PG_PASSWORD", "".to_string()),
+        ("PG_DBNAME", "postgres".to_string()),
+        ("PG_MAX_POOL_CONNECTIONS", "4".to_string()),
+        ("PG_USE_SSL", "false".to_string()),
+    ]
+}